@@ -0,0 +1,204 @@
+//! Token amounts.
+//!
+//! Every balance, amount and fee is an [`Amount`]: an unsigned 128-bit count
+//! of the token's smallest unit. Arithmetic is checked, so an overflow or an
+//! overdraft surfaces as `None` instead of wrapping.
+//!
+//! JSON carries amounts as decimal strings (`"1000"`), since most JSON
+//! parsers lose precision above 2^53. Binary formats such as the bincode
+//! stream read by the SP1 guest carry the raw `u128`.
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(u128);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+    pub const MAX: Amount = Amount(u128::MAX);
+
+    pub const fn new(value: u128) -> Self {
+        Amount(value)
+    }
+
+    pub const fn get(self) -> u128 {
+        self.0
+    }
+
+    pub const fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+
+    pub fn checked_mul(self, factor: u128) -> Option<Amount> {
+        self.0.checked_mul(factor).map(Amount)
+    }
+
+    pub fn saturating_add(self, other: Amount) -> Amount {
+        Amount(self.0.saturating_add(other.0))
+    }
+
+    /// Sum of `amounts`, or `None` if it overflows.
+    pub fn checked_sum<I: IntoIterator<Item = Amount>>(amounts: I) -> Option<Amount> {
+        amounts
+            .into_iter()
+            .try_fold(Amount::ZERO, |total, amount| total.checked_add(amount))
+    }
+
+    /// Big-endian encoding used in signed payloads.
+    pub fn to_be_bytes(self) -> [u8; 16] {
+        self.0.to_be_bytes()
+    }
+}
+
+impl From<u64> for Amount {
+    fn from(value: u64) -> Self {
+        Amount(value as u128)
+    }
+}
+
+impl From<u128> for Amount {
+    fn from(value: u128) -> Self {
+        Amount(value)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Parses a plain decimal integer. Signs, whitespace and fractions are
+/// rejected rather than silently dropped.
+impl std::str::FromStr for Amount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("Invalid amount: {}", s));
+        }
+        s.parse::<u128>()
+            .map(Amount)
+            .map_err(|_| format!("Amount out of range: {}", s))
+    }
+}
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_u128(self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(AmountVisitor)
+        } else {
+            u128::deserialize(deserializer).map(Amount)
+        }
+    }
+}
+
+/// Accepts decimal strings and, for small values, non-negative JSON integers.
+struct AmountVisitor;
+
+impl<'de> de::Visitor<'de> for AmountVisitor {
+    type Value = Amount;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a non-negative integer amount as a decimal string")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Amount, E> {
+        value.parse().map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Amount, E> {
+        Ok(Amount::from(value))
+    }
+
+    fn visit_u128<E: de::Error>(self, value: u128) -> Result<Amount, E> {
+        Ok(Amount(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Amount, E> {
+        u64::try_from(value)
+            .map(Amount::from)
+            .map_err(|_| E::custom(format!("Amount must not be negative: {}", value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("0".parse(), Ok(Amount::ZERO));
+        assert_eq!("1000".parse(), Ok(Amount::new(1000)));
+        assert_eq!("340282366920938463463374607431768211455".parse(), Ok(Amount::MAX));
+
+        assert_eq!(
+            "340282366920938463463374607431768211456".parse::<Amount>(),
+            Err("Amount out of range: 340282366920938463463374607431768211456".to_string())
+        );
+        for input in ["", "-1", "+1", " 1", "1.5", "1e6", "0x10"] {
+            assert_eq!(input.parse::<Amount>(), Err(format!("Invalid amount: {}", input)));
+        }
+    }
+
+    #[test]
+    fn test_json() {
+        // Written as a string so values past 2^53 survive JSON clients
+        assert_eq!(serde_json::to_string(&Amount::MAX).unwrap(), "\"340282366920938463463374607431768211455\"");
+        assert_eq!(serde_json::to_string(&Amount::new(5)).unwrap(), "\"5\"");
+
+        assert_eq!(serde_json::from_str::<Amount>("\"1000\"").unwrap(), Amount::new(1000));
+        assert_eq!(serde_json::from_str::<Amount>("1000").unwrap(), Amount::new(1000));
+        assert_eq!(
+            serde_json::from_str::<Amount>("\"340282366920938463463374607431768211455\"").unwrap(),
+            Amount::MAX
+        );
+
+        let negative = serde_json::from_str::<Amount>("-5").unwrap_err();
+        assert!(negative.to_string().contains("Amount must not be negative: -5"), "{}", negative);
+        for input in ["\"-5\"", "\"1.5\"", "1.5", "\"\"", "null"] {
+            assert!(serde_json::from_str::<Amount>(input).is_err(), "accepted {}", input);
+        }
+    }
+
+    #[test]
+    fn test_binary() {
+        // Binary formats carry the raw u128
+        let amount = Amount::new(u64::MAX as u128 + 1);
+        let bytes = bincode::serialize(&amount).unwrap();
+        assert_eq!(bytes, (u64::MAX as u128 + 1).to_le_bytes());
+        assert_eq!(bincode::deserialize::<Amount>(&bytes).unwrap(), amount);
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        assert_eq!(Amount::MAX.checked_add(Amount::new(1)), None);
+        assert_eq!(Amount::ZERO.checked_sub(Amount::new(1)), None);
+        assert_eq!(Amount::MAX.saturating_add(Amount::new(1)), Amount::MAX);
+        assert_eq!(
+            Amount::checked_sum([Amount::new(1), Amount::new(2), Amount::new(3)]),
+            Some(Amount::new(6))
+        );
+        assert_eq!(Amount::checked_sum([Amount::MAX, Amount::new(1)]), None);
+        assert_eq!(Amount::new(0x0102).to_be_bytes()[14..], [0x01, 0x02]);
+    }
+}
@@ -0,0 +1,416 @@
+//! Inputs and public outputs of the SP1 batch program.
+//!
+//! The prover script writes `chain_id`, the batch time, the issuer key of
+//! each token as `Vec<(TokenId, [u8; 32])>`, the entry count and then each
+//! [`BatchEntry`] to the guest's stdin; the guest commits a [`BatchResult`].
+//! The ledger hands the script a [`BatchInput`] and gets a [`BatchProof`] back.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Amount, MemberSignature, MultisigPolicy, SignablePayload, TokenId};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferProof {
+    pub token_id: TokenId,
+    pub from_addr: [u8; 32],
+    pub to_addr: [u8; 32],
+    pub amount: Amount,
+    pub fee: Amount,
+    pub nonce: i64,
+    /// Unix timestamp (seconds) after which the transfer may not be sequenced
+    pub valid_until: Option<i64>,
+    /// Unix timestamp (seconds) at which the ledger sequenced the transfer;
+    /// `None` if it never did
+    pub sequenced_at: Option<i64>,
+    #[serde(with = "byte_array")]
+    pub signature: [u8; 64],
+    pub public_key: [u8; 32],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MintProof {
+    pub token_id: TokenId,
+    pub to_addr: [u8; 32],
+    pub amount: Amount,
+    pub nonce: i64,
+    #[serde(with = "byte_array")]
+    pub signature: [u8; 64],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BurnProof {
+    pub token_id: TokenId,
+    pub from_addr: [u8; 32],
+    pub amount: Amount,
+    pub nonce: i64,
+    pub redemption_ref: Option<String>,
+    #[serde(with = "byte_array")]
+    pub signature: [u8; 64],
+    pub public_key: [u8; 32],
+}
+
+/// A single-signature payment from one sender to several receivers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchTransferProof {
+    pub token_id: TokenId,
+    pub from_addr: [u8; 32],
+    pub legs: Vec<([u8; 32], Amount)>,
+    pub fee: Amount,
+    pub nonce: i64,
+    #[serde(with = "byte_array")]
+    pub signature: [u8; 64],
+    pub public_key: [u8; 32],
+}
+
+/// A transfer out of `owner_addr`'s allowance, signed by the spender.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferFromProof {
+    pub token_id: TokenId,
+    pub owner_addr: [u8; 32],
+    pub spender_addr: [u8; 32],
+    pub to_addr: [u8; 32],
+    pub amount: Amount,
+    pub fee: Amount,
+    pub nonce: i64,
+    #[serde(with = "byte_array")]
+    pub signature: [u8; 64],
+    pub public_key: [u8; 32],
+}
+
+/// Signed terms of a hash-time-locked escrow; the digest of their lock
+/// payload is the escrow's ID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscrowTerms {
+    pub token_id: TokenId,
+    pub from_addr: [u8; 32],
+    pub to_addr: [u8; 32],
+    pub amount: Amount,
+    pub fee: Amount,
+    pub hashlock: [u8; 32],
+    /// Unix timestamp (seconds) from which the sender may reclaim the funds
+    pub timeout: i64,
+    pub nonce: i64,
+}
+
+impl EscrowTerms {
+    /// Payload the sender signed to lock the funds.
+    pub fn lock_payload(&self, chain_id: u64) -> SignablePayload {
+        SignablePayload::escrow_lock(
+            chain_id,
+            self.from_addr,
+            self.to_addr,
+            self.amount,
+            self.fee,
+            self.hashlock,
+            self.timeout,
+            self.nonce,
+        )
+        .with_token_id(self.token_id)
+    }
+
+    pub fn escrow_id(&self, chain_id: u64) -> [u8; 32] {
+        self.lock_payload(chain_id).digest()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscrowLockProof {
+    pub terms: EscrowTerms,
+    #[serde(with = "byte_array")]
+    pub signature: [u8; 64],
+    pub public_key: [u8; 32],
+}
+
+/// The recipient's claim of an escrow, with the preimage of its hashlock.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscrowClaimProof {
+    pub terms: EscrowTerms,
+    pub preimage: [u8; 32],
+    #[serde(with = "byte_array")]
+    pub signature: [u8; 64],
+    pub public_key: [u8; 32],
+}
+
+/// The sender's reclaim of an escrow after its timeout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscrowRefundProof {
+    pub terms: EscrowTerms,
+    #[serde(with = "byte_array")]
+    pub signature: [u8; 64],
+    pub public_key: [u8; 32],
+}
+
+/// Signed terms of a standing order; the digest of their payload is the
+/// order's ID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StandingOrderTerms {
+    pub token_id: TokenId,
+    pub from_addr: [u8; 32],
+    pub to_addr: [u8; 32],
+    pub amount: Amount,
+    pub fee: Amount,
+    /// Unix timestamp (seconds) of the first execution
+    pub start: i64,
+    /// Seconds between executions; `None` for a one-off transfer
+    pub interval: Option<i64>,
+    pub max_executions: u32,
+    pub nonce: i64,
+}
+
+impl StandingOrderTerms {
+    /// Payload the sender signed to set up the order.
+    pub fn payload(&self, chain_id: u64) -> SignablePayload {
+        SignablePayload::standing_order(
+            chain_id,
+            self.from_addr,
+            self.to_addr,
+            self.amount,
+            self.fee,
+            self.start,
+            self.interval,
+            self.max_executions,
+            self.nonce,
+        )
+        .with_token_id(self.token_id)
+    }
+
+    /// Unix timestamp (seconds) at which execution `index` falls due.
+    pub fn due_at(&self, index: u32) -> Option<i64> {
+        let offset = (index as i64).checked_mul(self.interval.unwrap_or(0))?;
+        self.start.checked_add(offset)
+    }
+}
+
+/// Execution `execution` of a standing order, authorized by the order's signature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledTransferProof {
+    pub terms: StandingOrderTerms,
+    pub execution: u32,
+    #[serde(with = "byte_array")]
+    pub signature: [u8; 64],
+    pub public_key: [u8; 32],
+}
+
+/// A transfer out of a multisig account, signed by enough of the members of
+/// the policy in force when it was accepted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultisigTransferProof {
+    pub token_id: TokenId,
+    pub from_addr: [u8; 32],
+    pub to_addr: [u8; 32],
+    pub amount: Amount,
+    pub fee: Amount,
+    pub nonce: i64,
+    pub valid_until: Option<i64>,
+    pub sequenced_at: Option<i64>,
+    pub policy: MultisigPolicy,
+    pub signatures: Vec<MemberSignature>,
+}
+
+/// An issuer-signed seizure of funds from `from_addr`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClawbackProof {
+    pub token_id: TokenId,
+    pub from_addr: [u8; 32],
+    pub to_addr: [u8; 32],
+    pub amount: Amount,
+    pub nonce: i64,
+    #[serde(with = "byte_array")]
+    pub signature: [u8; 64],
+}
+
+/// One ledger operation in a proof batch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BatchEntry {
+    Transfer(TransferProof),
+    Mint(MintProof),
+    Burn(BurnProof),
+    BatchTransfer(BatchTransferProof),
+    TransferFrom(TransferFromProof),
+    EscrowLock(EscrowLockProof),
+    EscrowClaim(EscrowClaimProof),
+    EscrowRefund(EscrowRefundProof),
+    ScheduledTransfer(ScheduledTransferProof),
+    MultisigTransfer(MultisigTransferProof),
+    Clawback(ClawbackProof),
+}
+
+impl BatchEntry {
+    /// Token the entry moves.
+    pub fn token_id(&self) -> TokenId {
+        match self {
+            BatchEntry::Transfer(proof) => proof.token_id,
+            BatchEntry::Mint(proof) => proof.token_id,
+            BatchEntry::Burn(proof) => proof.token_id,
+            BatchEntry::BatchTransfer(proof) => proof.token_id,
+            BatchEntry::TransferFrom(proof) => proof.token_id,
+            BatchEntry::EscrowLock(proof) => proof.terms.token_id,
+            BatchEntry::EscrowClaim(proof) => proof.terms.token_id,
+            BatchEntry::EscrowRefund(proof) => proof.terms.token_id,
+            BatchEntry::ScheduledTransfer(proof) => proof.terms.token_id,
+            BatchEntry::MultisigTransfer(proof) => proof.token_id,
+            BatchEntry::Clawback(proof) => proof.token_id,
+        }
+    }
+
+    /// Unix timestamp (seconds) at which the ledger sequenced the entry, for
+    /// the entries that carry an expiry.
+    pub fn sequenced_at(&self) -> Option<i64> {
+        match self {
+            BatchEntry::Transfer(proof) => proof.sequenced_at,
+            BatchEntry::MultisigTransfer(proof) => proof.sequenced_at,
+            _ => None,
+        }
+    }
+
+    /// Whether the entry's expiry passed before it was sequenced, or before
+    /// `batch_time` if it never was.
+    pub fn is_expired(&self, chain_id: u64, batch_time: i64) -> bool {
+        let checked_at = self.sequenced_at().unwrap_or(batch_time);
+        self.payload(chain_id).is_expired(checked_at)
+    }
+
+    /// Canonical payload the entry's signature must cover.
+    pub fn payload(&self, chain_id: u64) -> SignablePayload {
+        let payload = match self {
+            BatchEntry::Transfer(proof) => SignablePayload::transfer(
+                chain_id,
+                proof.from_addr,
+                proof.to_addr,
+                proof.amount,
+                proof.fee,
+                proof.nonce,
+            )
+            .with_valid_until(proof.valid_until),
+            BatchEntry::Mint(proof) => {
+                SignablePayload::mint(chain_id, proof.to_addr, proof.amount, proof.nonce)
+            }
+            BatchEntry::Burn(proof) => SignablePayload::burn(
+                chain_id,
+                proof.from_addr,
+                proof.amount,
+                proof.nonce,
+                proof.redemption_ref.clone(),
+            ),
+            BatchEntry::BatchTransfer(proof) => SignablePayload::batch_transfer(
+                chain_id,
+                proof.from_addr,
+                proof.legs.clone(),
+                proof.fee,
+                proof.nonce,
+            ),
+            BatchEntry::TransferFrom(proof) => SignablePayload::transfer_from(
+                chain_id,
+                proof.spender_addr,
+                proof.owner_addr,
+                proof.to_addr,
+                proof.amount,
+                proof.fee,
+                proof.nonce,
+            ),
+            BatchEntry::EscrowLock(proof) => proof.terms.lock_payload(chain_id),
+            BatchEntry::EscrowClaim(proof) => SignablePayload::escrow_claim(
+                chain_id,
+                proof.terms.escrow_id(chain_id),
+                proof.preimage,
+            ),
+            BatchEntry::EscrowRefund(proof) => {
+                SignablePayload::escrow_refund(chain_id, proof.terms.escrow_id(chain_id))
+            }
+            BatchEntry::ScheduledTransfer(proof) => proof.terms.payload(chain_id),
+            BatchEntry::MultisigTransfer(proof) => SignablePayload::transfer(
+                chain_id,
+                proof.from_addr,
+                proof.to_addr,
+                proof.amount,
+                proof.fee,
+                proof.nonce,
+            )
+            .with_valid_until(proof.valid_until),
+            BatchEntry::Clawback(proof) => SignablePayload::clawback(
+                chain_id,
+                proof.from_addr,
+                proof.to_addr,
+                proof.amount,
+                proof.nonce,
+            ),
+        };
+        payload.with_token_id(self.token_id())
+    }
+}
+
+/// Supply change of one token over a batch.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SupplyChange {
+    pub token_id: TokenId,
+    /// Tokens created by mints in this batch
+    pub minted: Amount,
+    /// Tokens destroyed by burns in this batch
+    pub burned: Amount,
+}
+
+/// Public values committed by the batch program.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub chain_id: u64,
+    /// Unix timestamp (seconds) escrow timeouts and standing order due times
+    /// were checked against, and no entry was sequenced after
+    pub batch_time: i64,
+    /// Issuer key each token's mints and clawbacks were checked against
+    pub issuer_keys: Vec<(TokenId, [u8; 32])>,
+    /// Multisig accounts whose transfers the batch contains, each with the
+    /// digest of every policy those transfers were checked against
+    pub multisig_policies: Vec<([u8; 32], [u8; 32])>,
+    /// Accounts that signed entries in the batch, each with every key those
+    /// entries were checked against
+    pub account_keys: Vec<([u8; 32], [u8; 32])>,
+    pub cycles_used: u64,
+    /// Per-token supply changes, ordered by token ID, for every token the batch minted or burned
+    pub supply: Vec<SupplyChange>,
+}
+
+/// Everything the batch program reads, as the ledger hands it to the prover.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchInput {
+    pub chain_id: u64,
+    /// Unix timestamp (seconds) the batch was assembled at
+    pub batch_time: i64,
+    /// Issuer key of each token the batch mints or claws back
+    pub issuer_keys: Vec<(TokenId, [u8; 32])>,
+    /// In the order the ledger applied them
+    pub entries: Vec<BatchEntry>,
+}
+
+/// A verified proof of a batch, with the public values it commits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchProof {
+    pub proof: Vec<u8>,
+    /// Hash of the program's verifying key, `0x`-prefixed hex
+    pub vkey_hash: String,
+    pub result: BatchResult,
+}
+
+/// Exit status of a prover command for a batch the program rejects, as
+/// opposed to one that couldn't be proven for now.
+pub const PROVER_REJECTED_EXIT_CODE: i32 = 2;
+
+pub(crate) mod byte_array {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        bytes: &[u8; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[u8; N], D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        let len = bytes.len();
+        bytes.try_into().map_err(|_| {
+            serde::de::Error::custom(format!("Expected {} bytes but got {}", N, len))
+        })
+    }
+}
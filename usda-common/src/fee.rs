@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::Amount;
+
+/// Denominator for basis point rates (1 bps = 0.01%).
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// How the minimum fee for a transfer is derived from its amount.
+///
+/// Percentage fees round up, so a non-zero rate never yields a zero fee.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeePolicy {
+    /// Same fee for every transfer
+    Flat { fee: Amount },
+    /// Percentage of the amount
+    BasisPoints { bps: u64 },
+    /// Percentage of the amount, clamped to `[min, max]`
+    Bounded { bps: u64, min: Amount, max: Amount },
+}
+
+impl Default for FeePolicy {
+    fn default() -> Self {
+        FeePolicy::Flat { fee: Amount::ZERO }
+    }
+}
+
+impl FeePolicy {
+    /// Minimum fee a transfer of `amount` must pay.
+    pub fn required_fee(&self, amount: Amount) -> Amount {
+        match *self {
+            FeePolicy::Flat { fee } => fee,
+            FeePolicy::BasisPoints { bps } => bps_of(amount, bps),
+            FeePolicy::Bounded { bps, min, max } => bps_of(amount, bps).clamp(min, max),
+        }
+    }
+}
+
+/// `amount * bps / BPS_DENOMINATOR` rounded up, saturating at [`Amount::MAX`].
+fn bps_of(amount: Amount, bps: u64) -> Amount {
+    let denominator = BPS_DENOMINATOR as u128;
+    let (whole, rest) = (amount.get() / denominator, amount.get() % denominator);
+    let remainder = (rest * bps as u128).div_ceil(denominator);
+    Amount::new(whole)
+        .checked_mul(bps as u128)
+        .and_then(|fee| fee.checked_add(Amount::new(remainder)))
+        .unwrap_or(Amount::MAX)
+}
+
+impl fmt::Display for FeePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeePolicy::Flat { fee } => write!(f, "flat:{}", fee),
+            FeePolicy::BasisPoints { bps } => write!(f, "bps:{}", bps),
+            FeePolicy::Bounded { bps, min, max } => write!(f, "bps:{}:{}:{}", bps, min, max),
+        }
+    }
+}
+
+/// Parses `flat:<fee>`, `bps:<bps>` or `bps:<bps>:<min>:<max>`.
+impl std::str::FromStr for FeePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let amount = |part: &str| {
+            part.parse::<Amount>()
+                .map_err(|_| format!("Invalid fee policy value: {}", part))
+        };
+        let bps = |part: &str| {
+            part.parse::<u64>()
+                .map_err(|_| format!("Invalid fee policy value: {}", part))
+        };
+
+        let policy = match parts.as_slice() {
+            ["flat", fee] => FeePolicy::Flat { fee: amount(fee)? },
+            ["bps", rate] => FeePolicy::BasisPoints { bps: bps(rate)? },
+            ["bps", rate, min, max] => FeePolicy::Bounded {
+                bps: bps(rate)?,
+                min: amount(min)?,
+                max: amount(max)?,
+            },
+            _ => return Err(format!("Invalid fee policy: {}", s)),
+        };
+
+        if let FeePolicy::Bounded { min, max, .. } = policy {
+            if min > max {
+                return Err(format!("Invalid fee policy bounds: {} > {}", min, max));
+            }
+        }
+
+        Ok(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_fee() {
+        let flat = FeePolicy::Flat { fee: Amount::new(25) };
+        assert_eq!(flat.required_fee(Amount::ZERO), Amount::new(25));
+        assert_eq!(flat.required_fee(Amount::MAX), Amount::new(25));
+
+        // 30 bps of 10,000 is exactly 30; any remainder rounds up
+        let bps = FeePolicy::BasisPoints { bps: 30 };
+        assert_eq!(bps.required_fee(Amount::new(10_000)), Amount::new(30));
+        assert_eq!(bps.required_fee(Amount::new(10_001)), Amount::new(31));
+        assert_eq!(bps.required_fee(Amount::new(1)), Amount::new(1));
+        assert_eq!(bps.required_fee(Amount::ZERO), Amount::ZERO);
+        assert_eq!(FeePolicy::BasisPoints { bps: 0 }.required_fee(Amount::MAX), Amount::ZERO);
+
+        // No overflow on large amounts; saturates once the fee can't be represented
+        assert_eq!(
+            FeePolicy::BasisPoints { bps: BPS_DENOMINATOR }.required_fee(Amount::MAX),
+            Amount::MAX
+        );
+        assert_eq!(FeePolicy::BasisPoints { bps: 20_000 }.required_fee(Amount::MAX), Amount::MAX);
+        assert_eq!(
+            FeePolicy::BasisPoints { bps: 5_000 }.required_fee(Amount::MAX),
+            Amount::new(u128::MAX / 2 + 1)
+        );
+
+        let bounded = FeePolicy::Bounded {
+            bps: 10,
+            min: Amount::new(5),
+            max: Amount::new(100),
+        };
+        assert_eq!(bounded.required_fee(Amount::new(1)), Amount::new(5));
+        assert_eq!(bounded.required_fee(Amount::new(20_000)), Amount::new(20));
+        assert_eq!(bounded.required_fee(Amount::MAX), Amount::new(100));
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        for policy in [
+            FeePolicy::default(),
+            FeePolicy::Flat { fee: Amount::new(25) },
+            FeePolicy::BasisPoints { bps: 30 },
+            FeePolicy::Bounded {
+                bps: 10,
+                min: Amount::new(5),
+                max: Amount::new(100),
+            },
+        ] {
+            assert_eq!(policy.to_string().parse(), Ok(policy));
+        }
+        assert_eq!(FeePolicy::default().to_string(), "flat:0");
+        assert_eq!(
+            "bps:10:5:100".parse(),
+            Ok(FeePolicy::Bounded {
+                bps: 10,
+                min: Amount::new(5),
+                max: Amount::new(100),
+            })
+        );
+
+        assert_eq!(
+            "bps:10:100:5".parse::<FeePolicy>(),
+            Err("Invalid fee policy bounds: 100 > 5".to_string())
+        );
+        for input in ["", "flat", "flat:-1", "bps:1.5", "bps:10:5", "percent:1", "flat:1:2"] {
+            assert!(input.parse::<FeePolicy>().is_err(), "accepted {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_json() {
+        let policy = FeePolicy::Bounded {
+            bps: 10,
+            min: Amount::new(5),
+            max: Amount::new(100),
+        };
+        let json = serde_json::to_value(policy).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "type": "bounded", "bps": 10, "min": "5", "max": "100" })
+        );
+        assert_eq!(serde_json::from_value::<FeePolicy>(json).unwrap(), policy);
+    }
+}
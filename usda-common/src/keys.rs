@@ -0,0 +1,20 @@
+//! Account addresses and key rotation.
+//!
+//! An account's address is derived from the Ed25519 key it was created with,
+//! so it stays the same when the account rotates to a new key. Accounts
+//! created before addresses were derived have their raw key as their
+//! address, and sign with that key until they rotate.
+
+use sha2::{Digest, Sha256};
+
+/// Prefix of account address digests, keeping them apart from multisig
+/// policy and payload digests.
+const ADDRESS_TAG: &[u8] = b"USDA-ACCOUNT";
+
+/// Address of an account created with `public_key`.
+pub fn account_address(public_key: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(ADDRESS_TAG);
+    hasher.update(public_key);
+    hasher.finalize().into()
+}
@@ -1,36 +1,145 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fmt;
 
+pub mod amount;
+pub mod batch;
+pub mod fee;
+pub mod keys;
+pub mod limits;
+pub mod multisig;
+pub mod signing;
+pub mod token;
+
+pub use amount::Amount;
+pub use fee::FeePolicy;
+pub use keys::account_address;
+pub use limits::{LimitViolation, SpendingLimits, SpendingUsage};
+pub use multisig::{MemberSignature, MultisigPolicy};
+pub use signing::{PayloadBody, PayloadKind, SignablePayload};
+pub use token::{TokenId, TokenMetadata, DEFAULT_TOKEN_ID};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     pub tx_id: String,
+    pub kind: TransactionKind,
+    /// Token the amount and fee are denominated in
+    pub token_id: TokenId,
     #[serde(with = "hex_array_opt")]
     pub from: Option<[u8; 32]>,
-    #[serde(with = "hex_array")]
-    pub to: [u8; 32],
-    pub amount: i64,
-    pub fee: i64,
+    #[serde(with = "hex_array_opt")]
+    pub to: Option<[u8; 32]>,
+    /// Signer of a transfer out of an allowance; `from` is the owner
+    #[serde(with = "hex_array_opt")]
+    pub spender: Option<[u8; 32]>,
+    pub amount: Amount,
+    pub fee: Amount,
     pub nonce: i64,
     #[serde(with = "hex_array")]
     pub signature: [u8; 64],
     pub timestamp: DateTime<Utc>,
     pub status: TransactionStatus,
+    /// Off-ledger redemption reference supplied with a burn
+    pub redemption_ref: Option<String>,
+    /// Shared by every leg of a batch transfer
+    pub group_id: Option<String>,
+    /// Signed expiry of a transfer
+    pub valid_until: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// What a transaction does to supply: transfers move tokens, mints create
+/// them (no sender) and burns destroy them (no receiver).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum TransactionKind {
+    Transfer,
+    Mint,
+    Burn,
+    /// A transfer signed by a spender against the sender's allowance
+    TransferFrom,
+    /// Moves the sender's funds into a hash-time-locked escrow (no receiver)
+    EscrowLock,
+    /// Releases escrowed funds to the recipient (no sender)
+    EscrowClaim,
+    /// Returns escrowed funds to the sender after the timeout (no sender)
+    EscrowRefund,
+    /// One execution of a standing order, signed ahead of time by the sender
+    ScheduledTransfer,
+    /// Transfer out of a multisig account, signed by enough of its members
+    MultisigTransfer,
+    /// Issuer seizure of an account's funds, paid to an address of the
+    /// issuer's choosing; allowed from frozen accounts
+    Clawback,
+}
+
+impl fmt::Display for TransactionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionKind::Transfer => write!(f, "TRANSFER"),
+            TransactionKind::Mint => write!(f, "MINT"),
+            TransactionKind::Burn => write!(f, "BURN"),
+            TransactionKind::TransferFrom => write!(f, "TRANSFER_FROM"),
+            TransactionKind::EscrowLock => write!(f, "ESCROW_LOCK"),
+            TransactionKind::EscrowClaim => write!(f, "ESCROW_CLAIM"),
+            TransactionKind::EscrowRefund => write!(f, "ESCROW_REFUND"),
+            TransactionKind::ScheduledTransfer => write!(f, "SCHEDULED_TRANSFER"),
+            TransactionKind::MultisigTransfer => write!(f, "MULTISIG_TRANSFER"),
+            TransactionKind::Clawback => write!(f, "CLAWBACK"),
+        }
+    }
+}
+
+impl std::str::FromStr for TransactionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "TRANSFER" => Ok(TransactionKind::Transfer),
+            "MINT" => Ok(TransactionKind::Mint),
+            "BURN" => Ok(TransactionKind::Burn),
+            "TRANSFER_FROM" => Ok(TransactionKind::TransferFrom),
+            "ESCROW_LOCK" => Ok(TransactionKind::EscrowLock),
+            "ESCROW_CLAIM" => Ok(TransactionKind::EscrowClaim),
+            "ESCROW_REFUND" => Ok(TransactionKind::EscrowRefund),
+            "SCHEDULED_TRANSFER" => Ok(TransactionKind::ScheduledTransfer),
+            "MULTISIG_TRANSFER" => Ok(TransactionKind::MultisigTransfer),
+            "CLAWBACK" => Ok(TransactionKind::Clawback),
+            _ => Err(format!("Invalid transaction kind: {}", s)),
+        }
+    }
+}
+
+/// Where a transaction is in its lifecycle:
+/// `Received -> Sequenced -> Batched -> Proven -> Finalized`, or `Failed` or
+/// `Cancelled` on the way.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum TransactionStatus {
-    Pending,
+    /// Held until the sender's earlier nonces arrive; not yet applied
+    Received,
+    /// Applied at its nonce; its effects are in the pending balances
+    Sequenced,
+    /// Sealed into a proof batch
+    Batched,
+    /// Its batch was proven; its effects are in the finalized balances
     Proven,
+    /// Its batch's proof was verified
+    Finalized,
+    /// Its batch could not be proven; its effects were rolled back
     Failed,
+    /// Withdrawn or replaced by its sender before being batched
+    Cancelled,
 }
 
 impl fmt::Display for TransactionStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            TransactionStatus::Pending => write!(f, "PENDING"),
+            TransactionStatus::Received => write!(f, "RECEIVED"),
+            TransactionStatus::Sequenced => write!(f, "SEQUENCED"),
+            TransactionStatus::Batched => write!(f, "BATCHED"),
             TransactionStatus::Proven => write!(f, "PROVEN"),
+            TransactionStatus::Finalized => write!(f, "FINALIZED"),
             TransactionStatus::Failed => write!(f, "FAILED"),
+            TransactionStatus::Cancelled => write!(f, "CANCELLED"),
         }
     }
 }
@@ -40,9 +149,13 @@ impl std::str::FromStr for TransactionStatus {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "PENDING" => Ok(TransactionStatus::Pending),
+            "RECEIVED" => Ok(TransactionStatus::Received),
+            "SEQUENCED" => Ok(TransactionStatus::Sequenced),
+            "BATCHED" => Ok(TransactionStatus::Batched),
             "PROVEN" => Ok(TransactionStatus::Proven),
+            "FINALIZED" => Ok(TransactionStatus::Finalized),
             "FAILED" => Ok(TransactionStatus::Failed),
+            "CANCELLED" => Ok(TransactionStatus::Cancelled),
             _ => Err(format!("Invalid transaction status: {}", s)),
         }
     }
@@ -52,12 +165,150 @@ impl std::str::FromStr for TransactionStatus {
 pub struct Account {
     #[serde(with = "hex_array")]
     pub address: [u8; 32],
-    pub balance: i64,
-    pub pending_balance: i64,
+    /// Accounts hold one token each; an address has an account per token it holds
+    pub token_id: TokenId,
+    pub balance: Amount,
+    pub pending_balance: Amount,
     pub nonce: i64,
     pub created_at: DateTime<Utc>,
 }
 
+/// Permission for `spender` to move up to `remaining` of `owner`'s tokens.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Allowance {
+    #[serde(with = "hex_array")]
+    pub owner: [u8; 32],
+    #[serde(with = "hex_array")]
+    pub spender: [u8; 32],
+    pub token_id: TokenId,
+    /// Still spendable, fees included
+    pub remaining: Amount,
+    /// No transfers out of the allowance after this time
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Nonce the spender's next transfer must be signed with
+    pub nonce: i64,
+}
+
+/// Where the funds of a hash-time-locked escrow are.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum EscrowStatus {
+    /// Held until the recipient reveals the preimage or the timeout passes
+    Locked,
+    /// Released to the recipient against the preimage
+    Claimed,
+    /// Returned to the sender after the timeout
+    Refunded,
+}
+
+impl fmt::Display for EscrowStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EscrowStatus::Locked => write!(f, "LOCKED"),
+            EscrowStatus::Claimed => write!(f, "CLAIMED"),
+            EscrowStatus::Refunded => write!(f, "REFUNDED"),
+        }
+    }
+}
+
+impl std::str::FromStr for EscrowStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "LOCKED" => Ok(EscrowStatus::Locked),
+            "CLAIMED" => Ok(EscrowStatus::Claimed),
+            "REFUNDED" => Ok(EscrowStatus::Refunded),
+            _ => Err(format!("Invalid escrow status: {}", s)),
+        }
+    }
+}
+
+/// Funds locked by `sender` that `recipient` can claim by revealing the
+/// SHA-256 preimage of `hashlock` before `timeout`, and that `sender` can
+/// reclaim from then on.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Escrow {
+    /// Transaction ID of the lock
+    pub escrow_id: String,
+    pub token_id: TokenId,
+    #[serde(with = "hex_array")]
+    pub sender: [u8; 32],
+    #[serde(with = "hex_array")]
+    pub recipient: [u8; 32],
+    pub amount: Amount,
+    #[serde(with = "hex_array")]
+    pub hashlock: [u8; 32],
+    pub timeout: DateTime<Utc>,
+    pub status: EscrowStatus,
+    /// Revealed by the claim
+    #[serde(with = "hex_array_opt")]
+    pub preimage: Option<[u8; 32]>,
+}
+
+impl Escrow {
+    /// Hashlock a preimage opens.
+    pub fn hashlock(preimage: &[u8; 32]) -> [u8; 32] {
+        Sha256::digest(preimage).into()
+    }
+}
+
+/// Where a guardian recovery of an account's key is.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum RecoveryStatus {
+    /// Waiting out the recovery delay, during which the owner can veto it
+    Pending,
+    /// Stopped by the owner's current key
+    Vetoed,
+    /// The account's key was replaced
+    Completed,
+    /// The account's key changed before the delay passed
+    Superseded,
+}
+
+impl fmt::Display for RecoveryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecoveryStatus::Pending => write!(f, "PENDING"),
+            RecoveryStatus::Vetoed => write!(f, "VETOED"),
+            RecoveryStatus::Completed => write!(f, "COMPLETED"),
+            RecoveryStatus::Superseded => write!(f, "SUPERSEDED"),
+        }
+    }
+}
+
+impl std::str::FromStr for RecoveryStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "PENDING" => Ok(RecoveryStatus::Pending),
+            "VETOED" => Ok(RecoveryStatus::Vetoed),
+            "COMPLETED" => Ok(RecoveryStatus::Completed),
+            "SUPERSEDED" => Ok(RecoveryStatus::Superseded),
+            _ => Err(format!("Invalid recovery status: {}", s)),
+        }
+    }
+}
+
+/// Request by an account's guardians to replace its key with `new_key` once
+/// `executable_at` has passed, unless the owner vetoes it first.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AccountRecovery {
+    /// Digest of the guardians' signed request
+    pub recovery_id: String,
+    #[serde(with = "hex_array")]
+    pub address: [u8; 32],
+    #[serde(with = "hex_array")]
+    pub new_key: [u8; 32],
+    /// Key version the recovery replaces
+    pub key_version: i64,
+    pub status: RecoveryStatus,
+    pub requested_at: DateTime<Utc>,
+    pub executable_at: DateTime<Utc>,
+    /// When it was vetoed, completed or superseded
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatchProof {
     pub batch_id: String,
@@ -70,11 +321,25 @@ pub struct BatchProof {
 pub enum WebSocketMessage {
     TransactionPreconfirmed(Transaction),
     TransactionProven(Transaction),
+    /// The transaction's proof batch failed and its effects were rolled back
+    TransactionFailed(Transaction),
+    /// A sequenced transaction was cancelled, or replaced by `replaced_by`
+    TransactionCancelled {
+        tx_id: String,
+        replaced_by: Option<String>,
+    },
     BalanceUpdated { 
         #[serde(with = "hex_array")]
         address: [u8; 32], 
-        balance: i64 
+        token_id: TokenId,
+        balance: Amount 
     },
+    /// An allowance was granted, replaced or spent from
+    AllowanceUpdated(Allowance),
+    /// An escrow was locked, claimed or refunded
+    EscrowUpdated(Escrow),
+    /// A guardian recovery was requested, vetoed, completed or superseded
+    RecoveryUpdated(AccountRecovery),
 }
 
 mod hex_array {
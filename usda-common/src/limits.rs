@@ -0,0 +1,132 @@
+//! Per-account spending limits and velocity rules.
+//!
+//! Limits cap what an account may send, e.g. for users who haven't been
+//! verified yet. Each token has default limits, and the token's issuer can
+//! set different ones for individual accounts.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::Amount;
+
+/// Window of the daily amount limit, in seconds.
+pub const DAY_SECS: i64 = 24 * 60 * 60;
+
+/// Window of the hourly transfer count limit, in seconds.
+pub const HOUR_SECS: i64 = 60 * 60;
+
+/// What an account may send; every limit is optional.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SpendingLimits {
+    /// Largest amount a single transfer may move
+    #[serde(default)]
+    pub per_transfer: Option<Amount>,
+    /// Most the account may send in any 24 hours
+    #[serde(default)]
+    pub daily: Option<Amount>,
+    /// Most transfers the account may send in any hour
+    #[serde(default)]
+    pub hourly_transfers: Option<u32>,
+}
+
+/// What an account has sent inside the limit windows.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SpendingUsage {
+    /// Amount sent in the last 24 hours
+    pub sent_today: Amount,
+    /// Transfers sent in the last hour
+    pub transfers_last_hour: u32,
+    /// Seconds until the oldest of those transfers leaves the hour window
+    pub hour_resets_in: Option<i64>,
+}
+
+/// The limit a transfer would break, with what the client can do about it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum LimitViolation {
+    /// The transfer moves more than `limit` on its own
+    PerTransfer { limit: Amount },
+    /// Only `remaining` of the daily `limit` is left
+    Daily { limit: Amount, remaining: Amount },
+    /// `limit` transfers were already sent this hour; one more fits after
+    /// `retry_after` seconds
+    HourlyTransfers { limit: u32, retry_after: i64 },
+}
+
+impl SpendingLimits {
+    pub fn is_unlimited(&self) -> bool {
+        *self == SpendingLimits::default()
+    }
+
+    /// Checks sending `amounts`, one transfer each, on top of `usage`.
+    pub fn check(&self, amounts: &[Amount], usage: &SpendingUsage) -> Result<(), LimitViolation> {
+        if let Some(limit) = self.per_transfer {
+            if amounts.iter().any(|amount| *amount > limit) {
+                return Err(LimitViolation::PerTransfer { limit });
+            }
+        }
+
+        if let Some(limit) = self.daily {
+            let remaining = limit.checked_sub(usage.sent_today).unwrap_or(Amount::ZERO);
+            let total = Amount::checked_sum(amounts.iter().copied()).unwrap_or(Amount::MAX);
+            if total > remaining {
+                return Err(LimitViolation::Daily { limit, remaining });
+            }
+        }
+
+        if let Some(limit) = self.hourly_transfers {
+            let transfers = usage.transfers_last_hour.saturating_add(amounts.len() as u32);
+            if transfers > limit {
+                return Err(LimitViolation::HourlyTransfers {
+                    limit,
+                    retry_after: usage.hour_resets_in.unwrap_or(HOUR_SECS),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for LimitViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitViolation::PerTransfer { limit } => {
+                write!(f, "Transfer exceeds the per-transfer limit of {}", limit)
+            }
+            LimitViolation::Daily { limit, remaining } => write!(
+                f,
+                "Transfer exceeds the daily limit of {}; {} remaining",
+                limit, remaining
+            ),
+            LimitViolation::HourlyTransfers { limit, retry_after } => write!(
+                f,
+                "More than {} transfers per hour; retry in {} seconds",
+                limit, retry_after
+            ),
+        }
+    }
+}
+
+/// Parses comma-separated `per_transfer:<amount>`, `daily:<amount>` and
+/// `hourly_transfers:<count>`, each optional; an empty string is no limits.
+impl std::str::FromStr for SpendingLimits {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut limits = SpendingLimits::default();
+        for part in s.split(',').filter(|part| !part.is_empty()) {
+            let invalid = || format!("Invalid spending limit: {}", part);
+            let (rule, value) = part.split_once(':').ok_or_else(invalid)?;
+            match rule {
+                "per_transfer" => limits.per_transfer = Some(value.parse().map_err(|_| invalid())?),
+                "daily" => limits.daily = Some(value.parse().map_err(|_| invalid())?),
+                "hourly_transfers" => {
+                    limits.hourly_transfers = Some(value.parse().map_err(|_| invalid())?)
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(limits)
+    }
+}
@@ -0,0 +1,209 @@
+//! M-of-N multisig accounts.
+//!
+//! A multisig account is controlled by a [`MultisigPolicy`]: a set of member
+//! Ed25519 keys and the number of them that must sign. The account's address
+//! is the [`MultisigPolicy::digest`] of the policy it was created with, so it
+//! can't collide with a single-key address and nobody holds a key for it.
+//! Members can later replace the policy through an update the current policy
+//! approves; the address stays the same.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::SignablePayload;
+
+/// Largest number of members a policy may have.
+pub const MAX_MEMBERS: usize = 16;
+
+/// Prefix of policy digests, keeping them apart from payload digests.
+const POLICY_TAG: &[u8] = b"USDA-MULTISIG";
+
+/// Members of a multisig account and how many of them must sign.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "PolicyParts")]
+pub struct MultisigPolicy {
+    threshold: u32,
+    /// Sorted, without duplicates
+    members: Vec<[u8; 32]>,
+}
+
+impl MultisigPolicy {
+    /// Policy requiring `threshold` of `members`. Members may be given in any
+    /// order but only once each.
+    pub fn new(threshold: u32, mut members: Vec<[u8; 32]>) -> Result<Self, String> {
+        if members.is_empty() || members.len() > MAX_MEMBERS {
+            return Err(format!("A multisig needs 1 to {} members", MAX_MEMBERS));
+        }
+        members.sort_unstable();
+        if members.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err("Duplicate multisig member".into());
+        }
+        if threshold == 0 || threshold as usize > members.len() {
+            return Err(format!(
+                "Threshold must be between 1 and the number of members ({})",
+                members.len()
+            ));
+        }
+        Ok(MultisigPolicy { threshold, members })
+    }
+
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    pub fn members(&self) -> &[[u8; 32]] {
+        &self.members
+    }
+
+    /// Commitment to the threshold and members; the address of an account
+    /// created with this policy.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(POLICY_TAG);
+        hasher.update(self.threshold.to_be_bytes());
+        hasher.update((self.members.len() as u32).to_be_bytes());
+        for member in &self.members {
+            hasher.update(member);
+        }
+        hasher.finalize().into()
+    }
+
+    /// The signatures that count towards the threshold: valid signatures of
+    /// `payload` by members, the first one of each member.
+    pub fn approvals<'a>(
+        &self,
+        payload: &SignablePayload,
+        signatures: &'a [MemberSignature],
+    ) -> Vec<&'a MemberSignature> {
+        let mut approvals: Vec<&MemberSignature> = Vec::with_capacity(signatures.len());
+        for signature in signatures {
+            if self.members.binary_search(&signature.public_key).is_ok()
+                && !approvals.iter().any(|approval| approval.public_key == signature.public_key)
+                && payload.verify(&signature.public_key, &signature.signature)
+            {
+                approvals.push(signature);
+            }
+        }
+        approvals
+    }
+
+    /// Whether at least `threshold` distinct members validly signed `payload`.
+    pub fn verify(&self, payload: &SignablePayload, signatures: &[MemberSignature]) -> bool {
+        self.approvals(payload, signatures).len() >= self.threshold as usize
+    }
+}
+
+/// Unchecked form of a policy, validated by [`MultisigPolicy::new`] when deserialized.
+#[derive(Deserialize)]
+struct PolicyParts {
+    threshold: u32,
+    members: Vec<[u8; 32]>,
+}
+
+impl TryFrom<PolicyParts> for MultisigPolicy {
+    type Error = String;
+
+    fn try_from(parts: PolicyParts) -> Result<Self, String> {
+        MultisigPolicy::new(parts.threshold, parts.members)
+    }
+}
+
+/// One member's signature over a multisig account's payload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemberSignature {
+    pub public_key: [u8; 32],
+    #[serde(with = "crate::batch::byte_array")]
+    pub signature: [u8; 64],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn public(seed: u8) -> [u8; 32] {
+        key(seed).verifying_key().to_bytes()
+    }
+
+    fn signed(seed: u8, payload: &SignablePayload) -> MemberSignature {
+        MemberSignature {
+            public_key: public(seed),
+            signature: payload.sign(&key(seed)),
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        let policy = MultisigPolicy::new(2, vec![[3; 32], [1; 32], [2; 32]]).unwrap();
+        assert_eq!(policy.threshold(), 2);
+        assert_eq!(policy.members(), [[1; 32], [2; 32], [3; 32]]);
+
+        // Member order doesn't change the policy or its address
+        let reordered = MultisigPolicy::new(2, vec![[2; 32], [3; 32], [1; 32]]).unwrap();
+        assert_eq!(reordered, policy);
+        assert_eq!(reordered.digest(), policy.digest());
+        assert_ne!(MultisigPolicy::new(3, vec![[1; 32], [2; 32], [3; 32]]).unwrap().digest(), policy.digest());
+
+        assert!(MultisigPolicy::new(1, Vec::new()).is_err());
+        let too_many: Vec<[u8; 32]> = (0..=MAX_MEMBERS as u8).map(|seed| [seed; 32]).collect();
+        assert!(MultisigPolicy::new(1, too_many[..MAX_MEMBERS].to_vec()).is_ok());
+        assert!(MultisigPolicy::new(1, too_many).is_err());
+        assert_eq!(
+            MultisigPolicy::new(1, vec![[1; 32], [2; 32], [1; 32]]),
+            Err("Duplicate multisig member".to_string())
+        );
+        assert!(MultisigPolicy::new(0, vec![[1; 32]]).is_err());
+        assert!(MultisigPolicy::new(2, vec![[1; 32]]).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_validates() {
+        let members = vec![[2u8; 32], [1; 32]];
+        let policy: MultisigPolicy = serde_json::from_value(serde_json::json!({
+            "threshold": 1,
+            "members": members,
+        }))
+        .unwrap();
+        assert_eq!(policy.members(), [[1; 32], [2; 32]]);
+
+        let invalid = serde_json::from_value::<MultisigPolicy>(serde_json::json!({
+            "threshold": 3,
+            "members": members,
+        }));
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_approvals() {
+        let policy = MultisigPolicy::new(2, vec![public(1), public(2), public(3)]).unwrap();
+        let payload = SignablePayload::transfer(1, policy.digest(), [9; 32], 100u64.into(), 0u64.into(), 0);
+        let other = SignablePayload::transfer(1, policy.digest(), [9; 32], 101u64.into(), 0u64.into(), 0);
+
+        let first = signed(1, &payload);
+        let signatures = vec![
+            // Not a member
+            signed(4, &payload),
+            first.clone(),
+            // Counted once per member
+            signed(1, &payload),
+            // Signs a different payload
+            signed(2, &other),
+            // Claims another member's key
+            MemberSignature {
+                public_key: public(3),
+                signature: first.signature,
+            },
+        ];
+        assert_eq!(policy.approvals(&payload, &signatures), vec![&first]);
+        assert!(!policy.verify(&payload, &signatures));
+
+        let third = signed(3, &payload);
+        let signatures = [signatures, vec![third.clone()]].concat();
+        assert_eq!(policy.approvals(&payload, &signatures), vec![&first, &third]);
+        assert!(policy.verify(&payload, &signatures));
+    }
+}
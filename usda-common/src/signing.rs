@@ -0,0 +1,1076 @@
+//! Canonical, domain-separated signing payloads.
+//!
+//! The API server, the SP1 guest and clients all build a [`SignablePayload`]
+//! and sign or verify its [`SignablePayload::to_bytes`] encoding, so every
+//! party agrees on exactly which bytes a signature covers.
+//!
+//! Encoding (all integers big-endian, amounts as 16-byte `u128`):
+//!
+//! ```text
+//! DOMAIN_TAG (4) || PAYLOAD_VERSION (1) || chain_id (8) || token_id (4) || kind (1) || fields
+//! ```
+//!
+//! Every payload names the token it moves, so a signature for one token can't
+//! be replayed against another token's balances or nonces.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+use crate::{token::DEFAULT_TOKEN_ID, Amount, MultisigPolicy, SpendingLimits, TokenId};
+
+/// Prefix of every signed message, so USDA signatures can't be confused with
+/// signatures made by the same key for other protocols.
+pub const DOMAIN_TAG: &[u8; 4] = b"USDA";
+
+/// Version of the payload encoding. Bump whenever the layout changes.
+pub const PAYLOAD_VERSION: u8 = 4;
+
+/// Chain ID used when a deployment doesn't configure one.
+pub const DEFAULT_CHAIN_ID: u64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PayloadKind {
+    Transfer = 0x01,
+    Mint = 0x02,
+    Burn = 0x03,
+    BatchTransfer = 0x04,
+    Cancel = 0x05,
+    Approve = 0x06,
+    TransferFrom = 0x07,
+    EscrowLock = 0x08,
+    EscrowClaim = 0x09,
+    EscrowRefund = 0x0a,
+    StandingOrder = 0x0b,
+    CancelStandingOrder = 0x0c,
+    MultisigUpdate = 0x0d,
+    Freeze = 0x0e,
+    Unfreeze = 0x0f,
+    Clawback = 0x10,
+    SetSpendingLimits = 0x11,
+    RotateKey = 0x12,
+    SetGuardians = 0x13,
+    RecoverAccount = 0x14,
+    VetoRecovery = 0x15,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayloadBody {
+    Transfer {
+        from: [u8; 32],
+        to: [u8; 32],
+        amount: Amount,
+        fee: Amount,
+        nonce: i64,
+        /// Unix timestamp (seconds) after which the transfer may not be applied
+        valid_until: Option<i64>,
+    },
+    /// Issuer-signed creation of new tokens; `nonce` is the token's mint nonce.
+    Mint {
+        to: [u8; 32],
+        amount: Amount,
+        nonce: i64,
+    },
+    /// Holder-signed destruction of tokens, optionally tied to an off-ledger redemption.
+    Burn {
+        from: [u8; 32],
+        amount: Amount,
+        nonce: i64,
+        redemption_ref: Option<String>,
+    },
+    /// One debit from `from` paying every `(to, amount)` leg atomically.
+    BatchTransfer {
+        from: [u8; 32],
+        legs: Vec<([u8; 32], Amount)>,
+        fee: Amount,
+        nonce: i64,
+    },
+    /// Withdraws `from`'s still unbatched transfer at `nonce`.
+    Cancel { from: [u8; 32], nonce: i64 },
+    /// Lets `spender` move up to `limit` of `owner`'s tokens until `expiry`
+    /// (Unix seconds), replacing any earlier allowance. Uses `owner`'s nonce.
+    Approve {
+        owner: [u8; 32],
+        spender: [u8; 32],
+        limit: Amount,
+        expiry: Option<i64>,
+        nonce: i64,
+    },
+    /// Spender-signed transfer out of `owner`'s allowance; `nonce` counts the
+    /// allowance's uses.
+    TransferFrom {
+        spender: [u8; 32],
+        owner: [u8; 32],
+        to: [u8; 32],
+        amount: Amount,
+        fee: Amount,
+        nonce: i64,
+    },
+    /// Locks `amount` for `to` until `timeout` (Unix seconds) under the
+    /// SHA-256 `hashlock`. The digest of this payload identifies the escrow.
+    EscrowLock {
+        from: [u8; 32],
+        to: [u8; 32],
+        amount: Amount,
+        fee: Amount,
+        hashlock: [u8; 32],
+        timeout: i64,
+        nonce: i64,
+    },
+    /// Recipient-signed release of escrow `escrow` against its preimage.
+    EscrowClaim {
+        escrow: [u8; 32],
+        preimage: [u8; 32],
+    },
+    /// Sender-signed return of escrow `escrow` after its timeout.
+    EscrowRefund { escrow: [u8; 32] },
+    /// Authorizes up to `max_executions` transfers of `amount`, the first at
+    /// `start` (Unix seconds) and then every `interval` seconds. Uses `from`'s
+    /// nonce; each execution takes the next nonce when it runs.
+    StandingOrder {
+        from: [u8; 32],
+        to: [u8; 32],
+        amount: Amount,
+        fee: Amount,
+        start: i64,
+        interval: Option<i64>,
+        max_executions: u32,
+        nonce: i64,
+    },
+    /// Stops the standing order `order`'s remaining executions.
+    CancelStandingOrder { order: [u8; 32] },
+    /// Replaces the policy of multisig account `account`, which is at
+    /// `version`, with `policy`. Signed by members of the current policy.
+    MultisigUpdate {
+        account: [u8; 32],
+        policy: MultisigPolicy,
+        version: i64,
+    },
+    /// Issuer-signed freeze of `account`, blocking transfers from or to it.
+    /// `nonce` is the token's compliance nonce, shared by freezes, unfreezes
+    /// and clawbacks.
+    Freeze { account: [u8; 32], nonce: i64 },
+    /// Issuer-signed lifting of a freeze on `account`.
+    Unfreeze { account: [u8; 32], nonce: i64 },
+    /// Issuer-signed seizure of `amount` from `from`, paid to `to`. Works on
+    /// frozen accounts and leaves `from`'s nonce alone.
+    Clawback {
+        from: [u8; 32],
+        to: [u8; 32],
+        amount: Amount,
+        nonce: i64,
+    },
+    /// Issuer-signed spending limits for `account`, replacing the token's
+    /// defaults; `None` puts the account back on the defaults. Uses the
+    /// token's compliance nonce.
+    SetSpendingLimits {
+        account: [u8; 32],
+        limits: Option<SpendingLimits>,
+        nonce: i64,
+    },
+    /// Replaces the key of `account`, which is at key `version`, with
+    /// `new_key`. Signed by the current key.
+    RotateKey {
+        account: [u8; 32],
+        new_key: [u8; 32],
+        version: i64,
+    },
+    /// Replaces the guardians of `account`, which have been set `version`
+    /// times, with `guardians`. Signed by the account's current key.
+    SetGuardians {
+        account: [u8; 32],
+        guardians: MultisigPolicy,
+        version: i64,
+    },
+    /// Request to replace the key of `account`, which is at key `version`,
+    /// with `new_key` once the recovery delay has passed. Signed by the
+    /// account's guardians.
+    RecoverAccount {
+        account: [u8; 32],
+        new_key: [u8; 32],
+        version: i64,
+    },
+    /// Stops the pending recovery `recovery`. Signed by the account's
+    /// current key.
+    VetoRecovery { recovery: [u8; 32] },
+}
+
+impl PayloadBody {
+    pub fn kind(&self) -> PayloadKind {
+        match self {
+            PayloadBody::Transfer { .. } => PayloadKind::Transfer,
+            PayloadBody::Mint { .. } => PayloadKind::Mint,
+            PayloadBody::Burn { .. } => PayloadKind::Burn,
+            PayloadBody::BatchTransfer { .. } => PayloadKind::BatchTransfer,
+            PayloadBody::Cancel { .. } => PayloadKind::Cancel,
+            PayloadBody::Approve { .. } => PayloadKind::Approve,
+            PayloadBody::TransferFrom { .. } => PayloadKind::TransferFrom,
+            PayloadBody::EscrowLock { .. } => PayloadKind::EscrowLock,
+            PayloadBody::EscrowClaim { .. } => PayloadKind::EscrowClaim,
+            PayloadBody::EscrowRefund { .. } => PayloadKind::EscrowRefund,
+            PayloadBody::StandingOrder { .. } => PayloadKind::StandingOrder,
+            PayloadBody::CancelStandingOrder { .. } => PayloadKind::CancelStandingOrder,
+            PayloadBody::MultisigUpdate { .. } => PayloadKind::MultisigUpdate,
+            PayloadBody::Freeze { .. } => PayloadKind::Freeze,
+            PayloadBody::Unfreeze { .. } => PayloadKind::Unfreeze,
+            PayloadBody::Clawback { .. } => PayloadKind::Clawback,
+            PayloadBody::SetSpendingLimits { .. } => PayloadKind::SetSpendingLimits,
+            PayloadBody::RotateKey { .. } => PayloadKind::RotateKey,
+            PayloadBody::SetGuardians { .. } => PayloadKind::SetGuardians,
+            PayloadBody::RecoverAccount { .. } => PayloadKind::RecoverAccount,
+            PayloadBody::VetoRecovery { .. } => PayloadKind::VetoRecovery,
+        }
+    }
+
+    fn encode(&self, out: &mut Encoder) {
+        match self {
+            PayloadBody::Transfer {
+                from,
+                to,
+                amount,
+                fee,
+                nonce,
+                valid_until,
+            } => {
+                out.put_bytes(from);
+                out.put_bytes(to);
+                out.put_amount(*amount);
+                out.put_amount(*fee);
+                out.put_i64(*nonce);
+                out.put_opt_i64(*valid_until);
+            }
+            PayloadBody::Mint { to, amount, nonce } => {
+                out.put_bytes(to);
+                out.put_amount(*amount);
+                out.put_i64(*nonce);
+            }
+            PayloadBody::Burn {
+                from,
+                amount,
+                nonce,
+                redemption_ref,
+            } => {
+                out.put_bytes(from);
+                out.put_amount(*amount);
+                out.put_i64(*nonce);
+                out.put_opt_str(redemption_ref.as_deref());
+            }
+            PayloadBody::BatchTransfer {
+                from,
+                legs,
+                fee,
+                nonce,
+            } => {
+                out.put_bytes(from);
+                out.put_u32(legs.len() as u32);
+                for (to, amount) in legs {
+                    out.put_bytes(to);
+                    out.put_amount(*amount);
+                }
+                out.put_amount(*fee);
+                out.put_i64(*nonce);
+            }
+            PayloadBody::Cancel { from, nonce } => {
+                out.put_bytes(from);
+                out.put_i64(*nonce);
+            }
+            PayloadBody::Approve {
+                owner,
+                spender,
+                limit,
+                expiry,
+                nonce,
+            } => {
+                out.put_bytes(owner);
+                out.put_bytes(spender);
+                out.put_amount(*limit);
+                out.put_opt_i64(*expiry);
+                out.put_i64(*nonce);
+            }
+            PayloadBody::TransferFrom {
+                spender,
+                owner,
+                to,
+                amount,
+                fee,
+                nonce,
+            } => {
+                out.put_bytes(spender);
+                out.put_bytes(owner);
+                out.put_bytes(to);
+                out.put_amount(*amount);
+                out.put_amount(*fee);
+                out.put_i64(*nonce);
+            }
+            PayloadBody::EscrowLock {
+                from,
+                to,
+                amount,
+                fee,
+                hashlock,
+                timeout,
+                nonce,
+            } => {
+                out.put_bytes(from);
+                out.put_bytes(to);
+                out.put_amount(*amount);
+                out.put_amount(*fee);
+                out.put_bytes(hashlock);
+                out.put_i64(*timeout);
+                out.put_i64(*nonce);
+            }
+            PayloadBody::EscrowClaim { escrow, preimage } => {
+                out.put_bytes(escrow);
+                out.put_bytes(preimage);
+            }
+            PayloadBody::EscrowRefund { escrow } => {
+                out.put_bytes(escrow);
+            }
+            PayloadBody::StandingOrder {
+                from,
+                to,
+                amount,
+                fee,
+                start,
+                interval,
+                max_executions,
+                nonce,
+            } => {
+                out.put_bytes(from);
+                out.put_bytes(to);
+                out.put_amount(*amount);
+                out.put_amount(*fee);
+                out.put_i64(*start);
+                out.put_opt_i64(*interval);
+                out.put_u32(*max_executions);
+                out.put_i64(*nonce);
+            }
+            PayloadBody::CancelStandingOrder { order } => {
+                out.put_bytes(order);
+            }
+            PayloadBody::MultisigUpdate {
+                account,
+                policy,
+                version,
+            }
+            | PayloadBody::SetGuardians {
+                account,
+                guardians: policy,
+                version,
+            } => {
+                out.put_bytes(account);
+                out.put_u32(policy.threshold());
+                out.put_u32(policy.members().len() as u32);
+                for member in policy.members() {
+                    out.put_bytes(member);
+                }
+                out.put_i64(*version);
+            }
+            PayloadBody::Freeze { account, nonce } | PayloadBody::Unfreeze { account, nonce } => {
+                out.put_bytes(account);
+                out.put_i64(*nonce);
+            }
+            PayloadBody::Clawback {
+                from,
+                to,
+                amount,
+                nonce,
+            } => {
+                out.put_bytes(from);
+                out.put_bytes(to);
+                out.put_amount(*amount);
+                out.put_i64(*nonce);
+            }
+            PayloadBody::SetSpendingLimits {
+                account,
+                limits,
+                nonce,
+            } => {
+                out.put_bytes(account);
+                match limits {
+                    Some(limits) => {
+                        out.put_u8(1);
+                        out.put_opt_amount(limits.per_transfer);
+                        out.put_opt_amount(limits.daily);
+                        out.put_opt_u32(limits.hourly_transfers);
+                    }
+                    None => out.put_u8(0),
+                }
+                out.put_i64(*nonce);
+            }
+            PayloadBody::RotateKey {
+                account,
+                new_key,
+                version,
+            }
+            | PayloadBody::RecoverAccount {
+                account,
+                new_key,
+                version,
+            } => {
+                out.put_bytes(account);
+                out.put_bytes(new_key);
+                out.put_i64(*version);
+            }
+            PayloadBody::VetoRecovery { recovery } => {
+                out.put_bytes(recovery);
+            }
+        }
+    }
+}
+
+/// A message that can be signed by an account key, bound to one deployment
+/// and one token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignablePayload {
+    pub chain_id: u64,
+    pub token_id: TokenId,
+    pub body: PayloadBody,
+}
+
+impl SignablePayload {
+    /// Payload for the default token; see [`SignablePayload::with_token_id`].
+    pub fn new(chain_id: u64, body: PayloadBody) -> Self {
+        Self {
+            chain_id,
+            token_id: DEFAULT_TOKEN_ID,
+            body,
+        }
+    }
+
+    /// Binds the payload to `token_id`.
+    pub fn with_token_id(mut self, token_id: TokenId) -> Self {
+        self.token_id = token_id;
+        self
+    }
+
+    pub fn transfer(
+        chain_id: u64,
+        from: [u8; 32],
+        to: [u8; 32],
+        amount: Amount,
+        fee: Amount,
+        nonce: i64,
+    ) -> Self {
+        Self::new(
+            chain_id,
+            PayloadBody::Transfer {
+                from,
+                to,
+                amount,
+                fee,
+                nonce,
+                valid_until: None,
+            },
+        )
+    }
+
+    /// Sets the expiry of a transfer payload; other kinds never expire.
+    pub fn with_valid_until(mut self, expiry: Option<i64>) -> Self {
+        if let PayloadBody::Transfer { valid_until, .. } = &mut self.body {
+            *valid_until = expiry;
+        }
+        self
+    }
+
+    pub fn valid_until(&self) -> Option<i64> {
+        match self.body {
+            PayloadBody::Transfer { valid_until, .. } => valid_until,
+            _ => None,
+        }
+    }
+
+    /// Whether the payload's expiry lies before `now` (Unix seconds).
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.valid_until().is_some_and(|valid_until| valid_until < now)
+    }
+
+    pub fn mint(chain_id: u64, to: [u8; 32], amount: Amount, nonce: i64) -> Self {
+        Self::new(chain_id, PayloadBody::Mint { to, amount, nonce })
+    }
+
+    pub fn burn(
+        chain_id: u64,
+        from: [u8; 32],
+        amount: Amount,
+        nonce: i64,
+        redemption_ref: Option<String>,
+    ) -> Self {
+        Self::new(
+            chain_id,
+            PayloadBody::Burn {
+                from,
+                amount,
+                nonce,
+                redemption_ref,
+            },
+        )
+    }
+
+    pub fn batch_transfer(
+        chain_id: u64,
+        from: [u8; 32],
+        legs: Vec<([u8; 32], Amount)>,
+        fee: Amount,
+        nonce: i64,
+    ) -> Self {
+        Self::new(
+            chain_id,
+            PayloadBody::BatchTransfer {
+                from,
+                legs,
+                fee,
+                nonce,
+            },
+        )
+    }
+
+    pub fn cancel(chain_id: u64, from: [u8; 32], nonce: i64) -> Self {
+        Self::new(chain_id, PayloadBody::Cancel { from, nonce })
+    }
+
+    pub fn approve(
+        chain_id: u64,
+        owner: [u8; 32],
+        spender: [u8; 32],
+        limit: Amount,
+        expiry: Option<i64>,
+        nonce: i64,
+    ) -> Self {
+        Self::new(
+            chain_id,
+            PayloadBody::Approve {
+                owner,
+                spender,
+                limit,
+                expiry,
+                nonce,
+            },
+        )
+    }
+
+    pub fn transfer_from(
+        chain_id: u64,
+        spender: [u8; 32],
+        owner: [u8; 32],
+        to: [u8; 32],
+        amount: Amount,
+        fee: Amount,
+        nonce: i64,
+    ) -> Self {
+        Self::new(
+            chain_id,
+            PayloadBody::TransferFrom {
+                spender,
+                owner,
+                to,
+                amount,
+                fee,
+                nonce,
+            },
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn escrow_lock(
+        chain_id: u64,
+        from: [u8; 32],
+        to: [u8; 32],
+        amount: Amount,
+        fee: Amount,
+        hashlock: [u8; 32],
+        timeout: i64,
+        nonce: i64,
+    ) -> Self {
+        Self::new(
+            chain_id,
+            PayloadBody::EscrowLock {
+                from,
+                to,
+                amount,
+                fee,
+                hashlock,
+                timeout,
+                nonce,
+            },
+        )
+    }
+
+    pub fn escrow_claim(chain_id: u64, escrow: [u8; 32], preimage: [u8; 32]) -> Self {
+        Self::new(chain_id, PayloadBody::EscrowClaim { escrow, preimage })
+    }
+
+    pub fn escrow_refund(chain_id: u64, escrow: [u8; 32]) -> Self {
+        Self::new(chain_id, PayloadBody::EscrowRefund { escrow })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn standing_order(
+        chain_id: u64,
+        from: [u8; 32],
+        to: [u8; 32],
+        amount: Amount,
+        fee: Amount,
+        start: i64,
+        interval: Option<i64>,
+        max_executions: u32,
+        nonce: i64,
+    ) -> Self {
+        Self::new(
+            chain_id,
+            PayloadBody::StandingOrder {
+                from,
+                to,
+                amount,
+                fee,
+                start,
+                interval,
+                max_executions,
+                nonce,
+            },
+        )
+    }
+
+    pub fn cancel_standing_order(chain_id: u64, order: [u8; 32]) -> Self {
+        Self::new(chain_id, PayloadBody::CancelStandingOrder { order })
+    }
+
+    pub fn multisig_update(chain_id: u64, account: [u8; 32], policy: MultisigPolicy, version: i64) -> Self {
+        Self::new(
+            chain_id,
+            PayloadBody::MultisigUpdate {
+                account,
+                policy,
+                version,
+            },
+        )
+    }
+
+    pub fn freeze(chain_id: u64, account: [u8; 32], nonce: i64) -> Self {
+        Self::new(chain_id, PayloadBody::Freeze { account, nonce })
+    }
+
+    pub fn unfreeze(chain_id: u64, account: [u8; 32], nonce: i64) -> Self {
+        Self::new(chain_id, PayloadBody::Unfreeze { account, nonce })
+    }
+
+    pub fn clawback(chain_id: u64, from: [u8; 32], to: [u8; 32], amount: Amount, nonce: i64) -> Self {
+        Self::new(
+            chain_id,
+            PayloadBody::Clawback {
+                from,
+                to,
+                amount,
+                nonce,
+            },
+        )
+    }
+
+    pub fn set_spending_limits(
+        chain_id: u64,
+        account: [u8; 32],
+        limits: Option<SpendingLimits>,
+        nonce: i64,
+    ) -> Self {
+        Self::new(
+            chain_id,
+            PayloadBody::SetSpendingLimits {
+                account,
+                limits,
+                nonce,
+            },
+        )
+    }
+
+    pub fn rotate_key(chain_id: u64, account: [u8; 32], new_key: [u8; 32], version: i64) -> Self {
+        Self::new(
+            chain_id,
+            PayloadBody::RotateKey {
+                account,
+                new_key,
+                version,
+            },
+        )
+    }
+
+    pub fn set_guardians(chain_id: u64, account: [u8; 32], guardians: MultisigPolicy, version: i64) -> Self {
+        Self::new(
+            chain_id,
+            PayloadBody::SetGuardians {
+                account,
+                guardians,
+                version,
+            },
+        )
+    }
+
+    pub fn recover_account(chain_id: u64, account: [u8; 32], new_key: [u8; 32], version: i64) -> Self {
+        Self::new(
+            chain_id,
+            PayloadBody::RecoverAccount {
+                account,
+                new_key,
+                version,
+            },
+        )
+    }
+
+    pub fn veto_recovery(chain_id: u64, recovery: [u8; 32]) -> Self {
+        Self::new(chain_id, PayloadBody::VetoRecovery { recovery })
+    }
+
+    pub fn kind(&self) -> PayloadKind {
+        self.body.kind()
+    }
+
+    /// Deterministic byte encoding covered by the signature.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Encoder::default();
+        out.put_bytes(DOMAIN_TAG);
+        out.put_u8(PAYLOAD_VERSION);
+        out.put_u64(self.chain_id);
+        out.put_u32(self.token_id);
+        out.put_u8(self.kind() as u8);
+        self.body.encode(&mut out);
+        out.0
+    }
+
+    /// SHA-256 of [`SignablePayload::to_bytes`].
+    pub fn digest(&self) -> [u8; 32] {
+        Sha256::digest(self.to_bytes()).into()
+    }
+
+    /// Transaction ID of the operation this payload authorizes. Clients can
+    /// compute it before submitting; resubmitting the same payload yields the
+    /// same ID.
+    pub fn tx_id(&self) -> String {
+        hex::encode(self.digest())
+    }
+
+    /// Transaction ID of leg `index` of a batch transfer, whose group ID is
+    /// [`SignablePayload::tx_id`], or of execution `index` of a standing order.
+    pub fn leg_tx_id(&self, index: u32) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.digest());
+        hasher.update(index.to_be_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    pub fn sign(&self, signing_key: &SigningKey) -> [u8; 64] {
+        signing_key.sign(&self.to_bytes()).to_bytes()
+    }
+
+    /// Strict Ed25519 verification of `signature` over this payload.
+    pub fn verify(&self, public_key: &[u8; 32], signature: &[u8; 64]) -> bool {
+        let Ok(verifying_key) = VerifyingKey::from_bytes(public_key) else {
+            return false;
+        };
+        verifying_key
+            .verify_strict(&self.to_bytes(), &Signature::from_bytes(signature))
+            .is_ok()
+    }
+}
+
+#[derive(Default)]
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn put_u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn put_u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn put_u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn put_i64(&mut self, value: i64) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn put_amount(&mut self, value: Amount) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn put_bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    /// Presence byte, then the value if present.
+    fn put_opt_i64(&mut self, value: Option<i64>) {
+        match value {
+            Some(value) => {
+                self.put_u8(1);
+                self.put_i64(value);
+            }
+            None => self.put_u8(0),
+        }
+    }
+
+    /// Presence byte, then the value if present.
+    fn put_opt_u32(&mut self, value: Option<u32>) {
+        match value {
+            Some(value) => {
+                self.put_u8(1);
+                self.put_u32(value);
+            }
+            None => self.put_u8(0),
+        }
+    }
+
+    /// Presence byte, then the value if present.
+    fn put_opt_amount(&mut self, value: Option<Amount>) {
+        match value {
+            Some(value) => {
+                self.put_u8(1);
+                self.put_amount(value);
+            }
+            None => self.put_u8(0),
+        }
+    }
+
+    /// Presence byte, then a u32 length prefix and the UTF-8 bytes.
+    fn put_opt_str(&mut self, value: Option<&str>) {
+        match value {
+            Some(value) => {
+                self.put_u8(1);
+                self.put_u32(value.len() as u32);
+                self.put_bytes(value.as_bytes());
+            }
+            None => self.put_u8(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Header of a version 4 payload for chain 1 and the default token.
+    const HEADER: &str = "55534441 04 0000000000000001 00000000";
+
+    /// Hex of `parts`, which may be spaced for readability.
+    fn hex_of(parts: &[&str]) -> String {
+        parts.concat().replace(' ', "")
+    }
+
+    fn amount(hex: &str) -> String {
+        format!("{:0>32}", hex)
+    }
+
+    #[test]
+    fn test_kind_bytes() {
+        let a = [0xaa; 32];
+        let policy = MultisigPolicy::new(1, vec![a]).unwrap();
+        let kinds = [
+            (SignablePayload::transfer(1, a, a, Amount::ZERO, Amount::ZERO, 0), 0x01),
+            (SignablePayload::mint(1, a, Amount::ZERO, 0), 0x02),
+            (SignablePayload::burn(1, a, Amount::ZERO, 0, None), 0x03),
+            (SignablePayload::batch_transfer(1, a, Vec::new(), Amount::ZERO, 0), 0x04),
+            (SignablePayload::cancel(1, a, 0), 0x05),
+            (SignablePayload::approve(1, a, a, Amount::ZERO, None, 0), 0x06),
+            (SignablePayload::transfer_from(1, a, a, a, Amount::ZERO, Amount::ZERO, 0), 0x07),
+            (SignablePayload::escrow_lock(1, a, a, Amount::ZERO, Amount::ZERO, a, 0, 0), 0x08),
+            (SignablePayload::escrow_claim(1, a, a), 0x09),
+            (SignablePayload::escrow_refund(1, a), 0x0a),
+            (SignablePayload::standing_order(1, a, a, Amount::ZERO, Amount::ZERO, 0, None, 1, 0), 0x0b),
+            (SignablePayload::cancel_standing_order(1, a), 0x0c),
+            (SignablePayload::multisig_update(1, a, policy.clone(), 0), 0x0d),
+            (SignablePayload::freeze(1, a, 0), 0x0e),
+            (SignablePayload::unfreeze(1, a, 0), 0x0f),
+            (SignablePayload::clawback(1, a, a, Amount::ZERO, 0), 0x10),
+            (SignablePayload::set_spending_limits(1, a, None, 0), 0x11),
+            (SignablePayload::rotate_key(1, a, a, 0), 0x12),
+            (SignablePayload::set_guardians(1, a, policy, 0), 0x13),
+            (SignablePayload::recover_account(1, a, a, 0), 0x14),
+            (SignablePayload::veto_recovery(1, a), 0x15),
+        ];
+        for (payload, kind) in kinds {
+            assert_eq!(payload.kind() as u8, kind, "{:?}", payload.kind());
+            assert_eq!(payload.to_bytes()[17], kind, "{:?}", payload.kind());
+        }
+    }
+
+    #[test]
+    fn test_transfer_encoding() {
+        let (from, to) = ("aa".repeat(32), "bb".repeat(32));
+        let payload = SignablePayload::transfer(1, [0xaa; 32], [0xbb; 32], Amount::new(1000), Amount::new(5), 7);
+        assert_eq!(
+            hex::encode(payload.to_bytes()),
+            hex_of(&[
+                HEADER,
+                "01",
+                &from,
+                &to,
+                &amount("03e8"),
+                &amount("05"),
+                "0000000000000007",
+                "00",
+            ])
+        );
+        assert_eq!(
+            payload.tx_id(),
+            "f95f30452d02a860ce03c5b7c20f40f67a0907c5e60e1d74804d993a63526287"
+        );
+
+        // Expiry, token and chain are all covered
+        let bound = payload
+            .clone()
+            .with_valid_until(Some(1_700_000_000))
+            .with_token_id(3);
+        let bound = SignablePayload { chain_id: 0x0102, ..bound };
+        assert_eq!(
+            hex::encode(bound.to_bytes()),
+            hex_of(&[
+                "55534441 04 0000000000000102 00000003",
+                "01",
+                &from,
+                &to,
+                &amount("03e8"),
+                &amount("05"),
+                "0000000000000007",
+                "01 000000006553f100",
+            ])
+        );
+        assert_eq!(bound.valid_until(), Some(1_700_000_000));
+        assert!(!bound.is_expired(1_700_000_000));
+        assert!(bound.is_expired(1_700_000_001));
+        assert!(!payload.is_expired(i64::MAX));
+    }
+
+    #[test]
+    fn test_variable_length_encodings() {
+        let (a, b, c) = ("aa".repeat(32), "bb".repeat(32), "cc".repeat(32));
+
+        let burn = SignablePayload::burn(1, [0xaa; 32], Amount::new(1), 2, Some("wire-1".into()));
+        assert_eq!(
+            hex::encode(burn.to_bytes()),
+            hex_of(&[
+                HEADER,
+                "03",
+                &a,
+                &amount("01"),
+                "0000000000000002",
+                "01 00000006 776972652d31",
+            ])
+        );
+
+        let batch = SignablePayload::batch_transfer(
+            1,
+            [0xaa; 32],
+            vec![([0xbb; 32], Amount::new(1)), ([0xcc; 32], Amount::new(2))],
+            Amount::new(3),
+            4,
+        );
+        assert_eq!(
+            hex::encode(batch.to_bytes()),
+            hex_of(&[
+                HEADER,
+                "04",
+                &a,
+                "00000002",
+                &b,
+                &amount("01"),
+                &c,
+                &amount("02"),
+                &amount("03"),
+                "0000000000000004",
+            ])
+        );
+
+        // Members are encoded sorted, whatever order the policy was given in
+        let policy = MultisigPolicy::new(2, vec![[0xcc; 32], [0xbb; 32]]).unwrap();
+        let update = SignablePayload::multisig_update(1, [0xaa; 32], policy, 5);
+        assert_eq!(
+            hex::encode(update.to_bytes()),
+            hex_of(&[HEADER, "0d", &a, "00000002 00000002", &b, &c, "0000000000000005"])
+        );
+
+        let limits = SpendingLimits {
+            per_transfer: Some(Amount::new(10)),
+            daily: None,
+            hourly_transfers: Some(3),
+        };
+        let set = SignablePayload::set_spending_limits(1, [0xaa; 32], Some(limits), 6);
+        assert_eq!(
+            hex::encode(set.to_bytes()),
+            hex_of(&[
+                HEADER,
+                "11",
+                &a,
+                "01",
+                "01",
+                &amount("0a"),
+                "00",
+                "01 00000003",
+                "0000000000000006",
+            ])
+        );
+        let reset = SignablePayload::set_spending_limits(1, [0xaa; 32], None, 6);
+        assert_eq!(
+            hex::encode(reset.to_bytes()),
+            hex_of(&[HEADER, "11", &a, "00", "0000000000000006"])
+        );
+
+        let order = SignablePayload::standing_order(
+            1,
+            [0xaa; 32],
+            [0xbb; 32],
+            Amount::new(1),
+            Amount::ZERO,
+            -1,
+            Some(60),
+            12,
+            8,
+        );
+        assert_eq!(
+            hex::encode(order.to_bytes()),
+            hex_of(&[
+                HEADER,
+                "0b",
+                &a,
+                &b,
+                &amount("01"),
+                &amount("00"),
+                "ffffffffffffffff",
+                "01 000000000000003c",
+                "0000000c",
+                "0000000000000008",
+            ])
+        );
+    }
+
+    #[test]
+    fn test_leg_tx_ids() {
+        let payload = SignablePayload::cancel(1, [0xaa; 32], 0);
+        let mut leg = payload.digest().to_vec();
+        leg.extend_from_slice(&[0, 0, 0, 1]);
+        assert_eq!(payload.leg_tx_id(1), hex::encode(Sha256::digest(leg)));
+        assert_ne!(payload.leg_tx_id(0), payload.leg_tx_id(1));
+        assert_ne!(payload.leg_tx_id(0), payload.tx_id());
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let public_key = key.verifying_key().to_bytes();
+        let payload = SignablePayload::transfer(1, public_key, [0xbb; 32], Amount::new(1000), Amount::ZERO, 0);
+        let signature = payload.sign(&key);
+        assert!(payload.verify(&public_key, &signature));
+
+        // The signature is bound to the chain, the token and every field
+        let other_chain = SignablePayload { chain_id: 2, ..payload.clone() };
+        assert!(!other_chain.verify(&public_key, &signature));
+        assert!(!payload.clone().with_token_id(1).verify(&public_key, &signature));
+        let other_nonce = SignablePayload::transfer(1, public_key, [0xbb; 32], Amount::new(1000), Amount::ZERO, 1);
+        assert!(!other_nonce.verify(&public_key, &signature));
+
+        let mut tampered = signature;
+        tampered[0] ^= 1;
+        assert!(!payload.verify(&public_key, &tampered));
+        assert!(!payload.verify(&[0xbb; 32], &signature));
+    }
+}
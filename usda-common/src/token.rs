@@ -0,0 +1,172 @@
+//! Token metadata and conversion between base units and display strings.
+//!
+//! Ledger amounts are integer counts of base units; `decimals` says how many
+//! of those make up one token, e.g. with 6 decimals `12345678` base units are
+//! `"12.345678 USDA"`. Conversions are exact: formatting never rounds, and
+//! parsing rejects digits beyond `decimals` instead of rounding them away.
+//!
+//! One ledger can carry several tokens, each identified by a [`TokenId`] and
+//! minted by its own issuer. Token [`DEFAULT_TOKEN_ID`] is the deployment's
+//! original asset and is assumed wherever a token isn't named.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Amount;
+
+/// Identifies a token on the ledger.
+pub type TokenId = u32;
+
+/// Token of requests and payloads that don't name one.
+pub const DEFAULT_TOKEN_ID: TokenId = 0;
+
+/// Largest supported `decimals`; one token (10^38 base units) still fits in an [`Amount`].
+pub const MAX_DECIMALS: u8 = 38;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    pub name: String,
+    pub symbol: String,
+    /// Number of base units per token, as a power of ten
+    pub decimals: u8,
+}
+
+impl Default for TokenMetadata {
+    fn default() -> Self {
+        TokenMetadata {
+            name: "Unified Succinct Digital Asset".into(),
+            symbol: "USDA".into(),
+            decimals: 6,
+        }
+    }
+}
+
+impl TokenMetadata {
+    /// `amount` in tokens followed by the symbol, e.g. `"12.345678 USDA"`.
+    pub fn format(&self, amount: Amount) -> String {
+        format!("{} {}", format_units(amount, self.decimals), self.symbol)
+    }
+
+    /// Parses a token amount such as `"12.345678"` or `"12.345678 USDA"` into
+    /// base units. A symbol, if given, must be this token's.
+    pub fn parse(&self, s: &str) -> Result<Amount, String> {
+        let value = match s.split_once(' ') {
+            Some((value, symbol)) if symbol == self.symbol => value,
+            Some((_, symbol)) => return Err(format!("Unknown token symbol: {}", symbol)),
+            None => s,
+        };
+        parse_units(value, self.decimals)
+    }
+}
+
+/// Formats `amount` base units as a decimal number of tokens. Trailing
+/// fractional zeros are dropped, so whole amounts have no decimal point.
+pub fn format_units(amount: Amount, decimals: u8) -> String {
+    let digits = amount.get().to_string();
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return digits;
+    }
+
+    let padded = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = padded.split_at(padded.len() - decimals);
+    match fraction.trim_end_matches('0') {
+        "" => whole.to_string(),
+        fraction => format!("{}.{}", whole, fraction),
+    }
+}
+
+/// Parses a decimal number of tokens into base units.
+///
+/// Only plain digits with an optional fractional part are accepted (`"12"`,
+/// `"12.5"`); signs, exponents and bare points (`".5"`, `"12."`) are not.
+/// More fractional digits than `decimals` is an error, never rounded.
+pub fn parse_units(s: &str, decimals: u8) -> Result<Amount, String> {
+    if decimals > MAX_DECIMALS {
+        return Err(format!("Unsupported number of decimals: {}", decimals));
+    }
+    let invalid = || format!("Invalid token amount: {}", s);
+    let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+
+    let (whole, fraction) = match s.split_once('.') {
+        Some((whole, fraction)) if is_digits(fraction) => (whole, fraction),
+        Some(_) => return Err(invalid()),
+        None => (s, ""),
+    };
+    if !is_digits(whole) {
+        return Err(invalid());
+    }
+    if fraction.len() > decimals as usize {
+        return Err(format!(
+            "Token amount {} has more than {} decimal places",
+            s, decimals
+        ));
+    }
+
+    let overflow = || format!("Token amount out of range: {}", s);
+    let scale = 10u128.pow(decimals as u32);
+    let whole = whole.parse::<u128>().map_err(|_| overflow())?;
+    let fraction = format!("{:0<width$}", fraction, width = decimals as usize);
+    let fraction = if fraction.is_empty() {
+        0
+    } else {
+        fraction.parse::<u128>().map_err(|_| invalid())?
+    };
+
+    Amount::new(whole)
+        .checked_mul(scale)
+        .and_then(|units| units.checked_add(Amount::new(fraction)))
+        .ok_or_else(overflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_units() {
+        assert_eq!(format_units(Amount::new(12_345_678), 6), "12.345678");
+        assert_eq!(format_units(Amount::new(12_300_000), 6), "12.3");
+        assert_eq!(format_units(Amount::new(12_000_000), 6), "12");
+        assert_eq!(format_units(Amount::new(5), 6), "0.000005");
+        assert_eq!(format_units(Amount::ZERO, 6), "0");
+        assert_eq!(format_units(Amount::new(42), 0), "42");
+        assert_eq!(format_units(Amount::MAX, 38), "3.40282366920938463463374607431768211455");
+
+        let token = TokenMetadata::default();
+        assert_eq!(token.format(Amount::new(12_345_678)), "12.345678 USDA");
+    }
+
+    #[test]
+    fn test_parse_units() {
+        assert_eq!(parse_units("12.345678", 6), Ok(Amount::new(12_345_678)));
+        assert_eq!(parse_units("12.3", 6), Ok(Amount::new(12_300_000)));
+        assert_eq!(parse_units("12", 6), Ok(Amount::new(12_000_000)));
+        assert_eq!(parse_units("0.000005", 6), Ok(Amount::new(5)));
+        assert_eq!(parse_units("007.50", 2), Ok(Amount::new(750)));
+
+        // Excess precision is rejected rather than rounded
+        assert!(parse_units("0.0000001", 6).is_err());
+        assert!(parse_units("1.5", 0).is_err());
+
+        // Only plain decimal notation
+        for input in ["", ".5", "5.", "-1", "+1", "1e6", "1,5", " 1", "1.2.3"] {
+            assert!(parse_units(input, 6).is_err(), "accepted {:?}", input);
+        }
+
+        // Out of range
+        assert!(parse_units("340282366920938463463374607431768211455", 1).is_err());
+        assert_eq!(parse_units("1", MAX_DECIMALS), Ok(Amount::new(10u128.pow(38))));
+        assert!(parse_units("1", MAX_DECIMALS + 1).is_err());
+
+        let token = TokenMetadata::default();
+        assert_eq!(token.parse("12.345678 USDA"), Ok(Amount::new(12_345_678)));
+        assert_eq!(token.parse("12.345678"), Ok(Amount::new(12_345_678)));
+        assert!(token.parse("12.345678 BTC").is_err());
+
+        // Formatting and parsing round-trip
+        for units in [0, 1, 999_999, 1_000_000, 123_456_789_012] {
+            let amount = Amount::new(units);
+            assert_eq!(token.parse(&token.format(amount)), Ok(amount));
+        }
+    }
+}
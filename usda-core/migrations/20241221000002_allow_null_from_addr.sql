@@ -0,0 +1,2 @@
+-- Mints, and later escrow releases, are recorded without a sender
+ALTER TABLE transactions ALTER COLUMN from_addr DROP NOT NULL;
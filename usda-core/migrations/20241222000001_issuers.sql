@@ -0,0 +1,6 @@
+-- Track each issuer's mint nonce so signed mints can't be replayed
+CREATE TABLE issuers (
+    public_key BYTEA PRIMARY KEY,
+    mint_nonce BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,6 @@
+-- Record what each transaction does to supply; burns have no receiver
+ALTER TABLE transactions ADD COLUMN kind TEXT NOT NULL DEFAULT 'TRANSFER';
+ALTER TABLE transactions ADD COLUMN redemption_ref TEXT;
+ALTER TABLE transactions ALTER COLUMN to_addr DROP NOT NULL;
+
+UPDATE transactions SET kind = 'MINT' WHERE from_addr IS NULL;
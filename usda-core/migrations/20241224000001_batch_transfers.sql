@@ -0,0 +1,5 @@
+-- Link the legs of a multi-recipient batch transfer
+ALTER TABLE transactions ADD COLUMN group_id TEXT;
+ALTER TABLE transactions ADD COLUMN leg_index INTEGER;
+
+CREATE INDEX idx_transactions_group_id ON transactions(group_id);
@@ -0,0 +1,2 @@
+-- Expiry signed into a transfer, kept for auditing
+ALTER TABLE transactions ADD COLUMN valid_until TIMESTAMPTZ;
@@ -0,0 +1,16 @@
+-- Transfers received ahead of their sender's nonce, applied once the gap fills
+CREATE TABLE queued_transfers (
+    from_addr BYTEA NOT NULL,
+    nonce BIGINT NOT NULL,
+    tx_id TEXT NOT NULL UNIQUE,
+    to_addr BYTEA NOT NULL,
+    amount BIGINT NOT NULL,
+    fee BIGINT NOT NULL,
+    signature BYTEA NOT NULL,
+    valid_until TIMESTAMP WITH TIME ZONE,
+    queued_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    PRIMARY KEY (from_addr, nonce)
+);
+
+CREATE INDEX idx_queued_transfers_expires_at ON queued_transfers(expires_at);
@@ -0,0 +1,6 @@
+-- Audit trail for cancelled and replaced-by-fee transfers
+ALTER TABLE transactions ADD COLUMN cancelled_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE transactions ADD COLUMN cancel_signature BYTEA;
+ALTER TABLE transactions ADD COLUMN replaced_by TEXT;
+
+CREATE INDEX idx_transactions_from_nonce ON transactions(from_addr, nonce);
@@ -0,0 +1,9 @@
+-- Handlers now only move pending_balance; balance follows once a batch is proven.
+-- Start the preconfirmed view from the balances applied so far.
+UPDATE accounts SET pending_balance = balance;
+
+-- Transactions still pending at this point are already reflected in balance, so
+-- keep them out of future batches to avoid settling them twice.
+UPDATE transactions SET batch_id = 'pre-settlement' WHERE status = 'PENDING' AND batch_id IS NULL;
+
+CREATE INDEX idx_transactions_status ON transactions(status);
@@ -0,0 +1,19 @@
+-- Amounts, fees and balances are unsigned 128-bit integers (usda_common::Amount).
+-- NUMERIC(39, 0) holds every u128; the checks keep negative values out.
+ALTER TABLE accounts
+    ALTER COLUMN balance TYPE NUMERIC(39, 0),
+    ALTER COLUMN pending_balance TYPE NUMERIC(39, 0),
+    ADD CONSTRAINT accounts_balance_non_negative CHECK (balance >= 0),
+    ADD CONSTRAINT accounts_pending_balance_non_negative CHECK (pending_balance >= 0);
+
+ALTER TABLE transactions
+    ALTER COLUMN amount TYPE NUMERIC(39, 0),
+    ALTER COLUMN fee TYPE NUMERIC(39, 0),
+    ADD CONSTRAINT transactions_amount_non_negative CHECK (amount >= 0),
+    ADD CONSTRAINT transactions_fee_non_negative CHECK (fee >= 0);
+
+ALTER TABLE queued_transfers
+    ALTER COLUMN amount TYPE NUMERIC(39, 0),
+    ALTER COLUMN fee TYPE NUMERIC(39, 0),
+    ADD CONSTRAINT queued_transfers_amount_non_negative CHECK (amount >= 0),
+    ADD CONSTRAINT queued_transfers_fee_non_negative CHECK (fee >= 0);
@@ -0,0 +1,43 @@
+-- Several tokens share one ledger. Balances, nonces, transactions and queued
+-- transfers are scoped to a token; existing rows belong to token 0, the
+-- deployment's original asset.
+ALTER TABLE transactions DROP CONSTRAINT transactions_from_addr_fkey;
+ALTER TABLE transactions DROP CONSTRAINT transactions_to_addr_fkey;
+
+ALTER TABLE accounts ADD COLUMN token_id BIGINT NOT NULL DEFAULT 0 CHECK (token_id >= 0);
+ALTER TABLE accounts DROP CONSTRAINT accounts_pkey;
+ALTER TABLE accounts ADD PRIMARY KEY (address, token_id);
+
+ALTER TABLE transactions ADD COLUMN token_id BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE transactions ADD CONSTRAINT transactions_from_addr_fkey
+    FOREIGN KEY (from_addr, token_id) REFERENCES accounts(address, token_id);
+ALTER TABLE transactions ADD CONSTRAINT transactions_to_addr_fkey
+    FOREIGN KEY (to_addr, token_id) REFERENCES accounts(address, token_id);
+
+DROP INDEX idx_transactions_from_nonce;
+CREATE INDEX idx_transactions_from_nonce ON transactions(from_addr, token_id, nonce);
+
+ALTER TABLE queued_transfers ADD COLUMN token_id BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE queued_transfers DROP CONSTRAINT queued_transfers_pkey;
+ALTER TABLE queued_transfers ADD PRIMARY KEY (from_addr, token_id, nonce);
+
+-- Mint nonce and supply of each token. Mint nonces move here from the
+-- per-key issuers table, since each token now has its own issuer.
+CREATE TABLE tokens (
+    token_id BIGINT PRIMARY KEY CHECK (token_id >= 0),
+    mint_nonce BIGINT NOT NULL DEFAULT 0,
+    minted NUMERIC(39, 0) NOT NULL DEFAULT 0 CHECK (minted >= 0),
+    burned NUMERIC(39, 0) NOT NULL DEFAULT 0 CHECK (burned >= 0),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO tokens (token_id, mint_nonce, minted, burned)
+SELECT
+    0,
+    COALESCE((SELECT MAX(mint_nonce) FROM issuers), 0),
+    COALESCE((SELECT SUM(amount) FROM transactions
+              WHERE kind = 'MINT' AND status NOT IN ('FAILED', 'CANCELLED')), 0),
+    COALESCE((SELECT SUM(amount) FROM transactions
+              WHERE kind = 'BURN' AND status NOT IN ('FAILED', 'CANCELLED')), 0);
+
+DROP TABLE issuers;
@@ -0,0 +1,21 @@
+-- Owner-signed permissions for a spender to transfer the owner's tokens.
+-- `nonce` counts the spender's transfers out of the allowance and survives
+-- re-approval; `approval_id` identifies the approval currently in force.
+CREATE TABLE allowances (
+    owner BYTEA NOT NULL,
+    spender BYTEA NOT NULL,
+    token_id BIGINT NOT NULL,
+    remaining NUMERIC(39, 0) NOT NULL CHECK (remaining >= 0),
+    expires_at TIMESTAMP WITH TIME ZONE,
+    nonce BIGINT NOT NULL DEFAULT 0,
+    approval_id TEXT NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (owner, spender, token_id),
+    FOREIGN KEY (owner, token_id) REFERENCES accounts(address, token_id)
+);
+
+CREATE INDEX idx_allowances_spender ON allowances(spender, token_id);
+
+-- Spender of a transfer out of an allowance, and the approval it drew on
+ALTER TABLE transactions ADD COLUMN spender BYTEA;
+ALTER TABLE transactions ADD COLUMN approval_id TEXT;
@@ -0,0 +1,25 @@
+-- Hash-time-locked escrows. Locked funds are out of the sender's balance
+-- until the recipient claims them with the preimage of `hashlock` before
+-- `timeout`, or the sender reclaims them from `timeout` on. The lock's signed
+-- terms are kept so claims and refunds can be proven against them.
+CREATE TABLE escrows (
+    escrow_id TEXT PRIMARY KEY,
+    token_id BIGINT NOT NULL,
+    sender BYTEA NOT NULL,
+    recipient BYTEA NOT NULL,
+    amount NUMERIC(39, 0) NOT NULL CHECK (amount > 0),
+    fee NUMERIC(39, 0) NOT NULL CHECK (fee >= 0),
+    hashlock BYTEA NOT NULL CHECK (length(hashlock) = 32),
+    timeout TIMESTAMP WITH TIME ZONE NOT NULL,
+    nonce BIGINT NOT NULL,
+    status TEXT NOT NULL,
+    preimage BYTEA,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    settled_at TIMESTAMP WITH TIME ZONE,
+    FOREIGN KEY (sender, token_id) REFERENCES accounts(address, token_id)
+);
+
+CREATE INDEX idx_escrows_recipient ON escrows(recipient, token_id);
+
+-- Escrow a lock, claim or refund belongs to
+ALTER TABLE transactions ADD COLUMN escrow_id TEXT;
@@ -0,0 +1,41 @@
+-- Standing orders: transfers the sender signs once and the scheduler
+-- executes when they fall due, at `start_at` and then every `interval_secs`,
+-- at most `max_executions` times. Creating an order uses the sender's nonce;
+-- each execution takes the next nonce when it runs. The signed terms are
+-- kept so executions can be proven against them.
+CREATE TABLE standing_orders (
+    order_id TEXT PRIMARY KEY,
+    token_id BIGINT NOT NULL,
+    from_addr BYTEA NOT NULL,
+    to_addr BYTEA NOT NULL,
+    amount NUMERIC(39, 0) NOT NULL CHECK (amount > 0),
+    fee NUMERIC(39, 0) NOT NULL CHECK (fee >= 0),
+    start_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    interval_secs BIGINT CHECK (interval_secs > 0),
+    max_executions INTEGER NOT NULL CHECK (max_executions > 0),
+    executions INTEGER NOT NULL DEFAULT 0,
+    -- When the next execution falls due; NULL once the order has finished
+    next_run_at TIMESTAMP WITH TIME ZONE,
+    nonce BIGINT NOT NULL,
+    signature BYTEA NOT NULL,
+    status TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (from_addr, token_id) REFERENCES accounts(address, token_id)
+);
+
+CREATE INDEX idx_standing_orders_due ON standing_orders(next_run_at) WHERE status = 'ACTIVE';
+
+-- One row per attempted execution. `tx_id` is NULL when the execution
+-- couldn't be applied, with the reason in `error`.
+CREATE TABLE standing_order_executions (
+    order_id TEXT NOT NULL REFERENCES standing_orders(order_id),
+    execution INTEGER NOT NULL,
+    due_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    executed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    tx_id TEXT,
+    error TEXT,
+    PRIMARY KEY (order_id, execution)
+);
+
+-- Standing order a scheduled transfer executes
+ALTER TABLE transactions ADD COLUMN order_id TEXT;
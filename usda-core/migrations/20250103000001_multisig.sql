@@ -0,0 +1,35 @@
+-- M-of-N multisig accounts. The address is the digest of the policy the
+-- account was created with; updates approved by the policy in force replace
+-- it and bump `version`. Transfers lock this row so they are checked against
+-- the policy that is current when they are applied.
+CREATE TABLE multisig_accounts (
+    address BYTEA PRIMARY KEY,
+    threshold INTEGER NOT NULL CHECK (threshold > 0),
+    members BYTEA[] NOT NULL,
+    version BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Every policy version an account has had, so transfers can be proven
+-- against the policy they were approved under
+CREATE TABLE multisig_policies (
+    address BYTEA NOT NULL REFERENCES multisig_accounts(address),
+    version BIGINT NOT NULL,
+    threshold INTEGER NOT NULL CHECK (threshold > 0),
+    members BYTEA[] NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (address, version)
+);
+
+-- Member signatures of multisig transfers; the transaction's `signature`
+-- holds the first of them
+CREATE TABLE multisig_signatures (
+    tx_id TEXT NOT NULL REFERENCES transactions(tx_id),
+    member BYTEA NOT NULL,
+    signature BYTEA NOT NULL,
+    PRIMARY KEY (tx_id, member)
+);
+
+-- Policy version a multisig transfer was approved under
+ALTER TABLE transactions ADD COLUMN multisig_version BIGINT;
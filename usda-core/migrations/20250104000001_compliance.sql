@@ -0,0 +1,28 @@
+-- Issuer compliance actions: freezes, unfreezes and clawbacks. They share
+-- one nonce per token, separate from the mint nonce, so signed actions
+-- can't be replayed.
+ALTER TABLE tokens ADD COLUMN compliance_nonce BIGINT NOT NULL DEFAULT 0;
+
+-- Addresses frozen in a token; transfers from or to them are rejected.
+-- No account row is needed, so an address can be frozen before it receives
+-- anything.
+CREATE TABLE frozen_accounts (
+    address BYTEA NOT NULL,
+    token_id BIGINT NOT NULL,
+    frozen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (address, token_id)
+);
+
+-- Every freeze and unfreeze with the issuer's signature. Clawbacks are
+-- recorded as transactions instead.
+CREATE TABLE freeze_actions (
+    token_id BIGINT NOT NULL,
+    nonce BIGINT NOT NULL,
+    address BYTEA NOT NULL,
+    frozen BOOLEAN NOT NULL,
+    signature BYTEA NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (token_id, nonce)
+);
+
+CREATE INDEX idx_freeze_actions_address ON freeze_actions(address, token_id);
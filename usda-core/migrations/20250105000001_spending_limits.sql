@@ -0,0 +1,19 @@
+-- Spending limits set by a token's issuer for individual accounts. A NULL
+-- limit is no limit. Accounts without a row, or whose limits were reset,
+-- use the token's default limits; the row keeps the last issuer action so
+-- it can't be replayed.
+CREATE TABLE spending_limits (
+    address BYTEA NOT NULL,
+    token_id BIGINT NOT NULL,
+    uses_defaults BOOLEAN NOT NULL DEFAULT FALSE,
+    per_transfer NUMERIC(39, 0) CHECK (per_transfer >= 0),
+    daily NUMERIC(39, 0) CHECK (daily >= 0),
+    hourly_transfers INTEGER CHECK (hourly_transfers >= 0),
+    nonce BIGINT NOT NULL,
+    signature BYTEA NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (address, token_id)
+);
+
+-- Limit checks add up what an account sent over the last day
+CREATE INDEX idx_transactions_from_timestamp ON transactions(from_addr, token_id, timestamp);
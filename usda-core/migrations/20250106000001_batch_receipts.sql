@@ -0,0 +1,5 @@
+-- When a batch's proof was accepted, and the hash of the verifying key of the
+-- program it proves, for transaction receipts
+ALTER TABLE proof_batches
+    ADD COLUMN proven_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN vkey_hash TEXT;
@@ -0,0 +1,28 @@
+-- Transaction lifecycle: RECEIVED -> SEQUENCED -> BATCHED -> PROVEN -> FINALIZED,
+-- or FAILED / CANCELLED with a reason. The status column was free text; it now
+-- uses the transaction_status enum, which nothing used so far.
+DROP TYPE transaction_status;
+CREATE TYPE transaction_status AS ENUM (
+    'RECEIVED',  -- held until the sender's earlier nonces arrive
+    'SEQUENCED', -- applied at its nonce; in the pending balances
+    'BATCHED',   -- sealed into a proof batch
+    'PROVEN',    -- batch proven; in the settled balances
+    'FINALIZED', -- batch proof verified
+    'FAILED',
+    'CANCELLED'
+);
+
+ALTER TABLE transactions
+    ALTER COLUMN status TYPE transaction_status USING (
+        CASE
+            -- Set aside as already settled by the two-phase settlement migration
+            WHEN status = 'PENDING' AND batch_id = 'pre-settlement' THEN 'FINALIZED'
+            WHEN status = 'PENDING' AND batch_id IS NULL THEN 'SEQUENCED'
+            WHEN status = 'PENDING' THEN 'BATCHED'
+            ELSE status
+        END
+    )::transaction_status,
+    ADD COLUMN status_reason TEXT;
+
+-- Proven batches are finalized once their proof is verified
+ALTER TABLE proof_batches ADD COLUMN finalized_at TIMESTAMP WITH TIME ZONE;
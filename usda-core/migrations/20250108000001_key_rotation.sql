@@ -0,0 +1,25 @@
+-- Current key of each account whose address doesn't sign for itself. New
+-- accounts get an address derived from the key they are created with
+-- (usda_common::account_address); older accounts, whose address is their
+-- raw key, get a row when they first rotate. A rotation signed by the
+-- current key replaces it and bumps `version`. Requests lock this row so
+-- they are checked against the key that is current when they are applied.
+CREATE TABLE account_keys (
+    address BYTEA PRIMARY KEY,
+    public_key BYTEA NOT NULL,
+    version BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Every key an account has had, so entries can be proven against the key
+-- they were signed with. `signature` is the rotation that installed the
+-- key, by the key before it; NULL for the key the account started with.
+CREATE TABLE account_key_history (
+    address BYTEA NOT NULL REFERENCES account_keys(address),
+    version BIGINT NOT NULL,
+    public_key BYTEA NOT NULL,
+    signature BYTEA,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (address, version)
+);
@@ -0,0 +1,44 @@
+-- Guardians of an account: keys of which `threshold` must co-sign a request
+-- to replace the account's key. The owner sets and replaces them with the
+-- account's current key; `version` counts how often they have been set.
+CREATE TABLE account_guardians (
+    address BYTEA PRIMARY KEY,
+    threshold INTEGER NOT NULL CHECK (threshold > 0),
+    guardians BYTEA[] NOT NULL,
+    version BIGINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Guardian requests to replace an account's key. A request waits until
+-- `executable_at`, during which the current key can veto it, and is then
+-- completed unless the key changed in the meantime. Rows are never deleted.
+CREATE TABLE account_recoveries (
+    recovery_id TEXT PRIMARY KEY,
+    address BYTEA NOT NULL,
+    new_key BYTEA NOT NULL,
+    -- Key version being replaced
+    key_version BIGINT NOT NULL,
+    -- Guardians the request was approved by
+    guardian_version BIGINT NOT NULL,
+    status TEXT NOT NULL,
+    requested_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    executable_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    resolved_at TIMESTAMP WITH TIME ZONE,
+    -- Owner's signature of the veto, if vetoed
+    veto_signature BYTEA
+);
+
+CREATE INDEX idx_account_recoveries_due ON account_recoveries(executable_at) WHERE status = 'PENDING';
+CREATE INDEX idx_account_recoveries_address ON account_recoveries(address);
+
+-- Guardian signatures approving a recovery
+CREATE TABLE account_recovery_signatures (
+    recovery_id TEXT NOT NULL REFERENCES account_recoveries(recovery_id),
+    guardian BYTEA NOT NULL,
+    signature BYTEA NOT NULL,
+    PRIMARY KEY (recovery_id, guardian)
+);
+
+-- Recovery that installed a key; such keys have no rotation `signature`
+ALTER TABLE account_key_history ADD COLUMN recovery_id TEXT REFERENCES account_recoveries(recovery_id);
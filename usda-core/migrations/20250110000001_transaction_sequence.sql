@@ -0,0 +1,25 @@
+-- Order in which transactions were applied, for proof batches. `timestamp`
+-- is when the applying database transaction started, so a spend can carry
+-- an earlier timestamp than the credit that funded it. A sequence value is
+-- drawn when the record is inserted, after the sender's row lock is taken,
+-- so anything a transaction depends on has a lower `seq`.
+ALTER TABLE transactions ADD COLUMN seq BIGINT;
+
+CREATE SEQUENCE transactions_seq_seq OWNED BY transactions.seq;
+
+-- Existing transactions keep the order they were batched in so far
+UPDATE transactions
+SET seq = ordered.seq
+FROM (
+    SELECT tx_id, ROW_NUMBER() OVER (ORDER BY timestamp, from_addr, nonce, leg_index) AS seq
+    FROM transactions
+) ordered
+WHERE transactions.tx_id = ordered.tx_id;
+
+SELECT setval('transactions_seq_seq', COALESCE(MAX(seq), 0) + 1, false) FROM transactions;
+
+ALTER TABLE transactions
+    ALTER COLUMN seq SET DEFAULT nextval('transactions_seq_seq'),
+    ALTER COLUMN seq SET NOT NULL;
+
+CREATE UNIQUE INDEX idx_transactions_seq ON transactions(seq);
@@ -0,0 +1,4 @@
+-- Account credited with the transaction's fee when it was sequenced, NULL
+-- when it paid none. Settlement and rollback move the fee in or out of this
+-- account, whichever treasury is configured by then.
+ALTER TABLE transactions ADD COLUMN fee_to BYTEA;
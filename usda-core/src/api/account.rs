@@ -1,28 +1,52 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     Json,
 };
+use ed25519_dalek::VerifyingKey;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use crate::{error::AppError, state::AppState};
-use usda_common::{Account, Transaction, TransactionStatus};
+use super::token::TokenQuery;
+use crate::{
+    api::transaction::{decode_address, decode_signature, verify_payload},
+    error::AppError,
+    lifecycle::from_status_column,
+    numeric::{from_numeric, from_token_column, to_token_column},
+    scheduler::{ORDER_ACTIVE, ORDER_CANCELLED},
+    state::AppState,
+};
+use usda_common::{Account, Allowance, Amount, SignablePayload, TokenId, Transaction, DEFAULT_TOKEN_ID};
 
 #[derive(Deserialize)]
 pub struct CreateAccountRequest {
-    pub public_key: [u8; 32], // 32-byte public key
+    pub public_key: [u8; 32], // 32-byte public key; the address is derived from it
+    #[serde(default)]
+    pub token_id: TokenId, // token the account holds
 }
 
-#[derive(Serialize)]
-pub struct CreateAccountResponse {
-    pub address: [u8; 32], // 32-byte address
+/// Replaces an account's key; signed by the current key.
+#[derive(Debug, Deserialize)]
+pub struct RotateKeyRequest {
+    pub address: String,
+    pub new_public_key: String, // hex encoded
+    pub version: i64,           // key version being replaced
+    pub signature: String,
+}
+
+/// The key an account currently signs with.
+#[derive(Debug, Serialize)]
+pub struct AccountKey {
+    pub address: String,
+    pub public_key: String,
+    /// Number of times the key has been rotated
+    pub version: i64,
 }
 
 pub async fn create(
     State(state): State<Arc<AppState>>,
     Json(req): Json<CreateAccountRequest>,
 ) -> Result<Json<Account>, AppError> {
-    let account = state.create_account(req.public_key).await?;
+    let account = state.create_account(req.public_key, req.token_id).await?;
 
     Ok(Json(account))
 }
@@ -30,39 +54,54 @@ pub async fn create(
 pub async fn get_balance(
     State(state): State<Arc<AppState>>,
     Path(address): Path<[u8; 32]>,
+    Query(query): Query<TokenQuery>,
 ) -> Result<Json<BalanceResponse>, AppError> {
+    let token_id = query.token_id.unwrap_or(DEFAULT_TOKEN_ID);
+    state.token(token_id)?;
     let account = state
-        .get_account(&address)
+        .get_account(&address, token_id)
         .await?
         .ok_or_else(|| AppError::NotFound("Account not found".into()))?;
 
     Ok(Json(BalanceResponse {
-        balance: account.balance as i64,
-        pending_balance: account.pending_balance as i64,
+        token_id,
+        balance: account.balance,
+        pending_balance: account.pending_balance,
     }))
 }
 
+/// Transactions `address` sent, received or spent an allowance on, in every
+/// token or only in `?token_id=`.
 pub async fn get_transactions(
     State(state): State<Arc<AppState>>,
     Path(address): Path<[u8; 32]>,
+    Query(query): Query<TokenQuery>,
 ) -> Result<Json<Vec<Transaction>>, AppError> {
     let rows = sqlx::query!(
         r#"
         SELECT 
             tx_id,
+            kind,
+            token_id,
             from_addr as "from_addr?: Vec<u8>",
-            to_addr as "to_addr!: Vec<u8>", 
-            amount as "amount!: i64", 
-            fee as "fee!: i64", 
+            to_addr as "to_addr?: Vec<u8>", 
+            spender,
+            amount, 
+            fee, 
             nonce as "nonce!: i64", 
             signature as "signature!: Vec<u8>", 
             timestamp as "timestamp!", 
-            status as "status!"
+            status::TEXT as "status!",
+            redemption_ref,
+            group_id,
+            valid_until
         FROM transactions 
-        WHERE from_addr = $1 OR to_addr = $1
+        WHERE (from_addr = $1 OR to_addr = $1 OR spender = $1)
+          AND ($2::BIGINT IS NULL OR token_id = $2)
         ORDER BY timestamp DESC
         "#,
-        &address[..]
+        &address[..],
+        query.token_id.map(to_token_column)
     )
     .fetch_all(&state.db)
     .await
@@ -70,29 +109,321 @@ pub async fn get_transactions(
 
     let transactions = rows
         .into_iter()
-        .map(|row| Transaction {
-            tx_id: row.tx_id,
-            from: row.from_addr.map(|addr| addr.try_into().unwrap()),
-            to: row.to_addr[..].try_into().unwrap(),
-            amount: row.amount,
-            fee: row.fee,
-            nonce: row.nonce,
-            signature: row.signature[..].try_into().unwrap(),
-            timestamp: row.timestamp,
-            status: match row.status.as_str() {
-                "pending" => TransactionStatus::Pending,
-                "proven" => TransactionStatus::Proven,
-                "failed" => TransactionStatus::Failed,
-                _ => TransactionStatus::Failed,
-            },
+        .map(|row| {
+            let corrupt = || AppError::DatabaseError(format!("Corrupt transaction {}", row.tx_id));
+            Ok(Transaction {
+                kind: row.kind.parse().map_err(|_| corrupt())?,
+                token_id: from_token_column(row.token_id)?,
+                from: row.from_addr.map(|addr| addr.try_into()).transpose().map_err(|_| corrupt())?,
+                to: row.to_addr.map(|addr| addr.try_into()).transpose().map_err(|_| corrupt())?,
+                spender: row.spender.map(|addr| addr.try_into()).transpose().map_err(|_| corrupt())?,
+                amount: from_numeric(row.amount)?,
+                fee: from_numeric(row.fee)?,
+                nonce: row.nonce,
+                signature: row.signature[..].try_into().map_err(|_| corrupt())?,
+                timestamp: row.timestamp,
+                status: from_status_column(&row.status)?,
+                redemption_ref: row.redemption_ref,
+                group_id: row.group_id,
+                valid_until: row.valid_until,
+                tx_id: row.tx_id,
+            })
         })
-        .collect();
+        .collect::<Result<Vec<_>, AppError>>()?;
 
     Ok(Json(transactions))
 }
 
+/// Allowances `address` granted or may spend from, in every token or only in
+/// `?token_id=`.
+pub async fn get_allowances(
+    State(state): State<Arc<AppState>>,
+    Path(address): Path<[u8; 32]>,
+    Query(query): Query<TokenQuery>,
+) -> Result<Json<Vec<Allowance>>, AppError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT owner, spender, token_id, remaining, expires_at, nonce
+        FROM allowances
+        WHERE (owner = $1 OR spender = $1)
+          AND ($2::BIGINT IS NULL OR token_id = $2)
+        ORDER BY updated_at DESC
+        "#,
+        &address[..],
+        query.token_id.map(to_token_column)
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let allowances = rows
+        .into_iter()
+        .map(|row| {
+            let corrupt = || AppError::DatabaseError("Corrupt allowance".into());
+            Ok(Allowance {
+                owner: row.owner.try_into().map_err(|_| corrupt())?,
+                spender: row.spender.try_into().map_err(|_| corrupt())?,
+                token_id: from_token_column(row.token_id)?,
+                remaining: from_numeric(row.remaining)?,
+                expires_at: row.expires_at,
+                nonce: row.nonce,
+            })
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    Ok(Json(allowances))
+}
+
+pub async fn get_key(
+    State(state): State<Arc<AppState>>,
+    Path(address): Path<String>,
+) -> Result<Json<AccountKey>, AppError> {
+    let address = decode_address(&address, "account")?;
+    let row = sqlx::query!(
+        "SELECT public_key, version FROM account_keys WHERE address = $1",
+        address.as_slice()
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let (public_key, version) = match row {
+        Some(row) => (key_from_column(row.public_key)?, row.version),
+        None if account_exists(&state.db, &address).await? => (address, 0),
+        None => return Err(AppError::NotFound("Account not found".into())),
+    };
+    Ok(Json(key_response(address, public_key, version)))
+}
+
+/// Replaces an account's key with `new_public_key`. The current key must
+/// sign the rotation; the address stays the same.
+pub async fn rotate_key(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<RotateKeyRequest>,
+) -> Result<Json<AccountKey>, AppError> {
+    let address = decode_address(&req.address, "account")?;
+    let new_key = decode_address(&req.new_public_key, "public key")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+    if VerifyingKey::from_bytes(&new_key).is_err() {
+        return Err(AppError::InvalidInput("Invalid public key".into()));
+    }
+    let payload = SignablePayload::rotate_key(state.chain_id, address, new_key, req.version);
+
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    if !account_exists(&mut *tx, &address).await? {
+        return Err(AppError::NotFound("Account not found".into()));
+    }
+    let (current_key, current_version) = lock_key(&mut tx, &address).await?;
+
+    if current_version != req.version {
+        // Resubmitting an accepted rotation returns the key as it is now
+        let accepted = req.version < current_version
+            && sqlx::query!(
+                r#"
+                SELECT version
+                FROM account_key_history
+                WHERE address = $1 AND version = $2 AND public_key = $3 AND signature = $4
+                "#,
+                address.as_slice(),
+                req.version + 1,
+                new_key.as_slice(),
+                signature_bytes.as_slice()
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?
+            .is_some();
+        if accepted {
+            return Ok(Json(key_response(address, current_key, current_version)));
+        }
+        return Err(AppError::InvalidInput(format!(
+            "Invalid key version. Expected {}, got {}",
+            current_version, req.version
+        )));
+    }
+    verify_payload(&payload, &current_key, &signature_bytes)?;
+    if new_key == current_key {
+        return Err(AppError::InvalidInput("Account already uses this key".into()));
+    }
+
+    let version = req.version + 1;
+    replace_key(&mut tx, &address, &new_key, version, Some(&signature_bytes), None).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(key_response(address, new_key, version)))
+}
+
+/// Current key and key version of the account at `address`, locked against
+/// requests checked against the key until `tx` ends. Accounts whose address
+/// is their key are registered first.
+pub(crate) async fn lock_key(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    address: &[u8; 32],
+) -> Result<([u8; 32], i64), AppError> {
+    let registered = sqlx::query!(
+        r#"
+        INSERT INTO account_keys (address, public_key)
+        VALUES ($1, $1)
+        ON CONFLICT (address) DO NOTHING
+        "#,
+        address.as_slice()
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if registered.rows_affected() > 0 {
+        record_key(tx, address, address, 0, None, None).await?;
+    }
+
+    let current = sqlx::query!(
+        "SELECT public_key, version FROM account_keys WHERE address = $1 FOR UPDATE",
+        address.as_slice()
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok((key_from_column(current.public_key)?, current.version))
+}
+
+/// Installs `new_key` as key `version` of the account at `address`, locked
+/// by [`lock_key`]. The key is installed by a rotation `signature` of the
+/// old key or by the guardian recovery `recovery_id`. Signatures by the old
+/// key stop counting, so the account's queued transfers are dropped and its
+/// standing orders cancelled.
+pub(crate) async fn replace_key(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    address: &[u8; 32],
+    new_key: &[u8; 32],
+    version: i64,
+    signature: Option<&[u8; 64]>,
+    recovery_id: Option<&str>,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        UPDATE account_keys
+        SET public_key = $1, version = $2, updated_at = NOW()
+        WHERE address = $3
+        "#,
+        new_key.as_slice(),
+        version,
+        address.as_slice()
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    record_key(tx, address, new_key, version, signature, recovery_id).await?;
+
+    // Transfers waiting for earlier nonces and standing orders were signed
+    // by the old key
+    sqlx::query!("DELETE FROM queued_transfers WHERE from_addr = $1", address.as_slice())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    sqlx::query!(
+        r#"
+        UPDATE standing_orders
+        SET status = $1, next_run_at = NULL
+        WHERE from_addr = $2 AND status = $3
+        "#,
+        ORDER_CANCELLED,
+        address.as_slice(),
+        ORDER_ACTIVE
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+/// Key the account at `address` currently signs with: its registered key, or
+/// the address itself for accounts whose address is their key. Inside a
+/// transaction the key stays locked against rotation until it ends.
+pub(crate) async fn account_key<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    address: &[u8; 32],
+) -> Result<[u8; 32], AppError> {
+    let row = sqlx::query!(
+        "SELECT public_key FROM account_keys WHERE address = $1 FOR SHARE",
+        address.as_slice()
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    match row {
+        Some(row) => key_from_column(row.public_key),
+        None => Ok(*address),
+    }
+}
+
+/// Checks `signature` over `payload` against the current key of the account
+/// at `address`.
+pub(crate) async fn verify_account_signature<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    payload: &SignablePayload,
+    address: &[u8; 32],
+    signature: &[u8; 64],
+) -> Result<(), AppError> {
+    let public_key = account_key(executor, address).await?;
+    verify_payload(payload, &public_key, signature)
+}
+
+pub(crate) async fn account_exists<'e>(executor: impl sqlx::PgExecutor<'e>, address: &[u8; 32]) -> Result<bool, AppError> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM accounts WHERE address = $1) AS "exists!""#,
+        address.as_slice()
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))
+}
+
+async fn record_key(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    address: &[u8; 32],
+    public_key: &[u8; 32],
+    version: i64,
+    signature: Option<&[u8; 64]>,
+    recovery_id: Option<&str>,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO account_key_history (address, version, public_key, signature, recovery_id)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        address.as_slice(),
+        version,
+        public_key.as_slice(),
+        signature.map(|signature| signature.as_slice()),
+        recovery_id
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+fn key_from_column(public_key: Vec<u8>) -> Result<[u8; 32], AppError> {
+    public_key
+        .try_into()
+        .map_err(|_| AppError::DatabaseError("Corrupt account key".into()))
+}
+
+fn key_response(address: [u8; 32], public_key: [u8; 32], version: i64) -> AccountKey {
+    AccountKey {
+        address: hex::encode(address),
+        public_key: hex::encode(public_key),
+        version,
+    }
+}
+
 #[derive(Serialize)]
 pub struct BalanceResponse {
-    pub balance: i64,
-    pub pending_balance: i64,
+    pub token_id: TokenId,
+    pub balance: Amount,
+    pub pending_balance: Amount,
 }
@@ -0,0 +1,635 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use usda_common::{
+    limits::{DAY_SECS, HOUR_SECS},
+    Amount, SignablePayload, SpendingLimits, SpendingUsage, TokenId, Transaction, TransactionKind,
+    TransactionStatus, DEFAULT_TOKEN_ID,
+};
+
+use super::token::TokenQuery;
+use crate::{
+    api::transaction::{
+        broadcast_preconfirmed, credit_account, debit_account, decode_address, decode_signature,
+        existing_transaction, lock_account, verify_payload, TransactionResponse,
+    },
+    error::AppError,
+    numeric::{from_numeric, to_numeric, to_token_column},
+    state::AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct FreezeRequest {
+    #[serde(default)]
+    pub token_id: TokenId,
+    pub address: String,   // hex encoded address
+    pub nonce: i64,        // token compliance nonce
+    pub signature: String, // hex encoded issuer signature
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClawbackRequest {
+    #[serde(default)]
+    pub token_id: TokenId,
+    pub from: String, // hex encoded address the funds are seized from
+    pub to: String,   // hex encoded address they are paid to
+    pub amount: Amount,
+    pub nonce: i64,        // token compliance nonce
+    pub signature: String, // hex encoded issuer signature
+}
+
+/// Sets an account's spending limits; `limits: null` puts the account back
+/// on the token's default limits.
+#[derive(Debug, Deserialize)]
+pub struct SpendingLimitsRequest {
+    #[serde(default)]
+    pub token_id: TokenId,
+    pub address: String, // hex encoded address
+    pub limits: Option<SpendingLimits>,
+    pub nonce: i64,        // token compliance nonce
+    pub signature: String, // hex encoded issuer signature
+}
+
+/// Limits that apply to an address in a token, and what it has sent inside
+/// their windows.
+#[derive(Debug, Serialize)]
+pub struct SpendingLimitsStatus {
+    pub token_id: TokenId,
+    pub address: String,
+    pub limits: SpendingLimits,
+    /// Whether `limits` are the token's defaults rather than the account's own
+    pub uses_defaults: bool,
+    pub usage: SpendingUsage,
+}
+
+/// Whether an address is frozen in a token.
+#[derive(Debug, Serialize)]
+pub struct FreezeStatus {
+    pub token_id: TokenId,
+    pub address: String,
+    pub frozen: bool,
+    pub frozen_at: Option<DateTime<Utc>>,
+}
+
+/// Freezes an address in a token: transfers from or to it are rejected with
+/// [`AppError::AccountFrozen`] until the issuer unfreezes it. Freezing a
+/// frozen address keeps it frozen but still uses the nonce.
+pub async fn freeze(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<FreezeRequest>,
+) -> Result<Json<FreezeStatus>, AppError> {
+    set_frozen(&state, req, true).await.map(Json)
+}
+
+/// Lifts an issuer's freeze on an address.
+pub async fn unfreeze(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<FreezeRequest>,
+) -> Result<Json<FreezeStatus>, AppError> {
+    set_frozen(&state, req, false).await.map(Json)
+}
+
+/// Freeze status of `address` in `?token_id=`, the default token otherwise.
+pub async fn get_status(
+    State(state): State<Arc<AppState>>,
+    Path(address): Path<String>,
+    Query(query): Query<TokenQuery>,
+) -> Result<Json<FreezeStatus>, AppError> {
+    let token_id = query.token_id.unwrap_or(DEFAULT_TOKEN_ID);
+    state.token(token_id)?;
+    let address = decode_address(&address, "address")?;
+    Ok(Json(freeze_status(&state.db, &address, token_id).await?))
+}
+
+/// Seizes funds from an account, frozen or not, and pays them to an address
+/// of the issuer's choosing. The account's nonce is left alone, so its
+/// signed but unapplied transfers stay valid.
+pub async fn clawback(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ClawbackRequest>,
+) -> Result<Json<TransactionResponse>, AppError> {
+    if req.amount.is_zero() {
+        return Err(AppError::InvalidInput("Clawback amount must be positive".into()));
+    }
+
+    state.token(req.token_id)?;
+    let issuer_bytes = issuer_bytes(&state, req.token_id)?;
+    let from_bytes = decode_address(&req.from, "from")?;
+    let to_bytes = decode_address(&req.to, "to")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+    if from_bytes == to_bytes {
+        return Err(AppError::InvalidInput("Clawback must pay another address".into()));
+    }
+
+    // Verify issuer signature
+    let payload = SignablePayload::clawback(state.chain_id, from_bytes, to_bytes, req.amount, req.nonce)
+        .with_token_id(req.token_id);
+    verify_payload(&payload, &issuer_bytes, &signature_bytes)?;
+
+    // Resubmitting an accepted clawback returns the original record
+    let tx_id = payload.tx_id();
+    if let Some(existing) = existing_transaction(&state.db, &tx_id).await? {
+        return Ok(Json(existing));
+    }
+
+    // Start a transaction for atomicity
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let nonce = lock_compliance_nonce(&mut tx, req.token_id).await?;
+    if nonce != req.nonce {
+        if let Some(existing) = existing_transaction(&mut *tx, &tx_id).await? {
+            return Ok(Json(existing));
+        }
+        return Err(AppError::InvalidNonce);
+    }
+
+    // Seized funds can't be paid into a frozen account
+    check_not_frozen(&mut *tx, req.token_id, &[to_bytes]).await?;
+
+    // Serializes with the account's own transfers
+    lock_account(&mut tx, &from_bytes, req.token_id).await?;
+
+    // Move the funds
+    debit_account(&mut tx, &from_bytes, req.token_id, req.amount).await?;
+    credit_account(&mut tx, &to_bytes, req.token_id, req.amount).await?;
+    advance_compliance_nonce(&mut tx, req.token_id).await?;
+
+    let record = sqlx::query!(
+        r#"
+        INSERT INTO transactions (tx_id, kind, token_id, from_addr, to_addr, amount, fee, nonce, signature, timestamp, status)
+        VALUES ($1, $2, $3, $4, $5, $6, 0, $7, $8, NOW(), $9::TEXT::transaction_status)
+        RETURNING timestamp
+        "#,
+        tx_id,
+        TransactionKind::Clawback.to_string(),
+        to_token_column(req.token_id),
+        from_bytes.as_slice(),
+        to_bytes.as_slice(),
+        to_numeric(req.amount),
+        req.nonce,
+        signature_bytes.as_slice(),
+        TransactionStatus::Sequenced.to_string()
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Commit transaction
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    broadcast_preconfirmed(
+        &state,
+        Transaction {
+            tx_id: tx_id.clone(),
+            kind: TransactionKind::Clawback,
+            token_id: req.token_id,
+            from: Some(from_bytes),
+            to: Some(to_bytes),
+            spender: None,
+            amount: req.amount,
+            fee: Amount::ZERO,
+            nonce: req.nonce,
+            signature: signature_bytes,
+            timestamp: record.timestamp,
+            status: TransactionStatus::Sequenced,
+            redemption_ref: None,
+            group_id: None,
+            valid_until: None,
+        },
+    );
+
+    Ok(Json(TransactionResponse {
+        tx_id,
+        status: TransactionStatus::Sequenced.to_string(),
+    }))
+}
+
+/// Sets or resets an account's spending limits. Resubmitting the request
+/// returns the account's current limits without using another nonce.
+pub async fn set_limits(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SpendingLimitsRequest>,
+) -> Result<Json<SpendingLimitsStatus>, AppError> {
+    state.token(req.token_id)?;
+    let issuer_bytes = issuer_bytes(&state, req.token_id)?;
+    let address = decode_address(&req.address, "address")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+
+    // Verify issuer signature
+    let payload = SignablePayload::set_spending_limits(state.chain_id, address, req.limits, req.nonce)
+        .with_token_id(req.token_id);
+    verify_payload(&payload, &issuer_bytes, &signature_bytes)?;
+
+    // Start a transaction for atomicity
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let nonce = lock_compliance_nonce(&mut tx, req.token_id).await?;
+    if nonce != req.nonce {
+        // Only the latest action on an address can be resubmitted; an older
+        // one would look like it undid the newer limits
+        let replayed = sqlx::query_scalar!(
+            r#"
+            SELECT address FROM spending_limits
+            WHERE address = $1 AND token_id = $2 AND nonce = $3 AND signature = $4
+            "#,
+            address.as_slice(),
+            to_token_column(req.token_id),
+            req.nonce,
+            signature_bytes.as_slice()
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        if replayed.is_some() {
+            return limits_status(&state, &mut tx, &address, req.token_id).await.map(Json);
+        }
+        return Err(AppError::InvalidNonce);
+    }
+
+    let limits = req.limits.unwrap_or_default();
+    sqlx::query!(
+        r#"
+        INSERT INTO spending_limits (address, token_id, uses_defaults, per_transfer, daily, hourly_transfers, nonce, signature)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (address, token_id) DO UPDATE
+        SET uses_defaults = EXCLUDED.uses_defaults,
+            per_transfer = EXCLUDED.per_transfer,
+            daily = EXCLUDED.daily,
+            hourly_transfers = EXCLUDED.hourly_transfers,
+            nonce = EXCLUDED.nonce,
+            signature = EXCLUDED.signature,
+            updated_at = NOW()
+        "#,
+        address.as_slice(),
+        to_token_column(req.token_id),
+        req.limits.is_none(),
+        limits.per_transfer.map(to_numeric),
+        limits.daily.map(to_numeric),
+        limits.hourly_transfers.map(|count| count as i32),
+        req.nonce,
+        signature_bytes.as_slice()
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    advance_compliance_nonce(&mut tx, req.token_id).await?;
+
+    let status = limits_status(&state, &mut tx, &address, req.token_id).await?;
+
+    // Commit transaction
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(status))
+}
+
+/// Spending limits of `address` in `?token_id=`, the default token otherwise.
+pub async fn get_limits(
+    State(state): State<Arc<AppState>>,
+    Path(address): Path<String>,
+    Query(query): Query<TokenQuery>,
+) -> Result<Json<SpendingLimitsStatus>, AppError> {
+    let token_id = query.token_id.unwrap_or(DEFAULT_TOKEN_ID);
+    state.token(token_id)?;
+    let address = decode_address(&address, "address")?;
+
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let status = limits_status(&state, &mut tx, &address, token_id).await?;
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(status))
+}
+
+/// Rejects sending `amounts` from `from`, one transfer each, with
+/// [`AppError::SpendingLimitExceeded`] if it would break the sender's limits.
+/// Call it with the sender's account row locked, so concurrent transfers
+/// can't both fit in what's left.
+pub(crate) async fn check_spending_limits(
+    state: &AppState,
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    from: &[u8; 32],
+    token_id: TokenId,
+    amounts: &[Amount],
+) -> Result<(), AppError> {
+    let (limits, _) = effective_limits(state, tx, from, token_id).await?;
+    if limits.is_unlimited() {
+        return Ok(());
+    }
+
+    let usage = spending_usage(tx, from, token_id).await?;
+    limits
+        .check(amounts, &usage)
+        .map_err(AppError::SpendingLimitExceeded)
+}
+
+/// Rejects the operation with [`AppError::AccountFrozen`] if any of
+/// `addresses` is frozen in `token_id`.
+pub(crate) async fn check_not_frozen<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    token_id: TokenId,
+    addresses: &[[u8; 32]],
+) -> Result<(), AppError> {
+    let addresses: Vec<&[u8]> = addresses.iter().map(|address| address.as_slice()).collect();
+    let frozen = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM frozen_accounts WHERE token_id = $1 AND address = ANY($2)
+        ) AS "frozen!"
+        "#,
+        to_token_column(token_id),
+        &addresses as &[&[u8]]
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    if frozen {
+        return Err(AppError::AccountFrozen);
+    }
+    Ok(())
+}
+
+async fn set_frozen(state: &AppState, req: FreezeRequest, frozen: bool) -> Result<FreezeStatus, AppError> {
+    state.token(req.token_id)?;
+    let issuer_bytes = issuer_bytes(state, req.token_id)?;
+    let address = decode_address(&req.address, "address")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+
+    // Verify issuer signature
+    let payload = if frozen {
+        SignablePayload::freeze(state.chain_id, address, req.nonce)
+    } else {
+        SignablePayload::unfreeze(state.chain_id, address, req.nonce)
+    };
+    verify_payload(&payload.with_token_id(req.token_id), &issuer_bytes, &signature_bytes)?;
+
+    // Start a transaction for atomicity
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let nonce = lock_compliance_nonce(&mut tx, req.token_id).await?;
+    if nonce != req.nonce {
+        // Resubmitting an accepted action returns the address's current status
+        let replayed = sqlx::query_scalar!(
+            "SELECT address FROM freeze_actions WHERE token_id = $1 AND nonce = $2 AND signature = $3",
+            to_token_column(req.token_id),
+            req.nonce,
+            signature_bytes.as_slice()
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        if replayed.is_some() {
+            return freeze_status(&mut *tx, &address, req.token_id).await;
+        }
+        return Err(AppError::InvalidNonce);
+    }
+
+    // Waits for the address's transfers in flight, which hold its account row
+    sqlx::query!(
+        "SELECT nonce FROM accounts WHERE address = $1 AND token_id = $2 FOR UPDATE",
+        address.as_slice(),
+        to_token_column(req.token_id)
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    if frozen {
+        sqlx::query!(
+            r#"
+            INSERT INTO frozen_accounts (address, token_id)
+            VALUES ($1, $2)
+            ON CONFLICT (address, token_id) DO NOTHING
+            "#,
+            address.as_slice(),
+            to_token_column(req.token_id)
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    } else {
+        sqlx::query!(
+            "DELETE FROM frozen_accounts WHERE address = $1 AND token_id = $2",
+            address.as_slice(),
+            to_token_column(req.token_id)
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO freeze_actions (token_id, nonce, address, frozen, signature)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        to_token_column(req.token_id),
+        req.nonce,
+        address.as_slice(),
+        frozen,
+        signature_bytes.as_slice()
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    advance_compliance_nonce(&mut tx, req.token_id).await?;
+
+    let status = freeze_status(&mut *tx, &address, req.token_id).await?;
+
+    // Commit transaction
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(status)
+}
+
+/// Compliance actions are signed by the token's issuer, like mints.
+fn issuer_bytes(state: &AppState, token_id: TokenId) -> Result<[u8; 32], AppError> {
+    state
+        .issuer_key(token_id)
+        .map(|key| key.to_bytes())
+        .ok_or_else(|| {
+            AppError::InvalidInput(format!(
+                "Compliance actions are disabled: no issuer key configured for token {}",
+                token_id
+            ))
+        })
+}
+
+/// Locks and returns the token's compliance nonce.
+async fn lock_compliance_nonce(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    token_id: TokenId,
+) -> Result<i64, AppError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO tokens (token_id)
+        VALUES ($1)
+        ON CONFLICT (token_id) DO NOTHING
+        "#,
+        to_token_column(token_id)
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let token = sqlx::query!(
+        "SELECT compliance_nonce FROM tokens WHERE token_id = $1 FOR UPDATE",
+        to_token_column(token_id)
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(token.compliance_nonce)
+}
+
+async fn advance_compliance_nonce(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    token_id: TokenId,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        "UPDATE tokens SET compliance_nonce = compliance_nonce + 1 WHERE token_id = $1",
+        to_token_column(token_id)
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+async fn limits_status(
+    state: &AppState,
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    address: &[u8; 32],
+    token_id: TokenId,
+) -> Result<SpendingLimitsStatus, AppError> {
+    let (limits, uses_defaults) = effective_limits(state, tx, address, token_id).await?;
+    let usage = spending_usage(tx, address, token_id).await?;
+
+    Ok(SpendingLimitsStatus {
+        token_id,
+        address: hex::encode(address),
+        limits,
+        uses_defaults,
+        usage,
+    })
+}
+
+/// The account's own limits if the issuer set them, the token's defaults
+/// otherwise; the flag is whether they are the defaults.
+async fn effective_limits(
+    state: &AppState,
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    address: &[u8; 32],
+    token_id: TokenId,
+) -> Result<(SpendingLimits, bool), AppError> {
+    let row = sqlx::query!(
+        r#"
+        SELECT per_transfer, daily, hourly_transfers
+        FROM spending_limits
+        WHERE address = $1 AND token_id = $2 AND NOT uses_defaults
+        "#,
+        address.as_slice(),
+        to_token_column(token_id)
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let Some(row) = row else {
+        return Ok((state.default_limits(token_id), true));
+    };
+
+    let limits = SpendingLimits {
+        per_transfer: row.per_transfer.map(from_numeric).transpose()?,
+        daily: row.daily.map(from_numeric).transpose()?,
+        hourly_transfers: row
+            .hourly_transfers
+            .map(|count| u32::try_from(count).map_err(|_| AppError::DatabaseError("Corrupt spending limit".into())))
+            .transpose()?,
+    };
+    Ok((limits, false))
+}
+
+/// What `address` sent over the last day and hour. Transfers waiting in the
+/// nonce queue haven't been applied yet and don't count.
+async fn spending_usage(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    address: &[u8; 32],
+    token_id: TokenId,
+) -> Result<SpendingUsage, AppError> {
+    // Everything that spends the account's funds, except the issuer's clawbacks
+    let kinds = [
+        TransactionKind::Transfer,
+        TransactionKind::ScheduledTransfer,
+        TransactionKind::MultisigTransfer,
+        TransactionKind::TransferFrom,
+        TransactionKind::EscrowLock,
+        TransactionKind::Burn,
+    ]
+    .map(|kind| kind.to_string());
+    let undone = [TransactionStatus::Failed, TransactionStatus::Cancelled].map(|status| status.to_string());
+
+    let usage = sqlx::query!(
+        r#"
+        SELECT
+            COALESCE(SUM(amount), 0) AS "sent_today!",
+            COUNT(*) FILTER (WHERE timestamp > NOW() - make_interval(secs => $5)) AS "transfers_last_hour!",
+            CEIL(EXTRACT(EPOCH FROM
+                MIN(timestamp) FILTER (WHERE timestamp > NOW() - make_interval(secs => $5))
+                    + make_interval(secs => $5) - NOW()
+            ))::BIGINT AS hour_resets_in
+        FROM transactions
+        WHERE from_addr = $1 AND token_id = $2
+          AND kind = ANY($3) AND status <> ALL($4::TEXT[]::transaction_status[])
+          AND timestamp > NOW() - make_interval(secs => $6)
+        "#,
+        address.as_slice(),
+        to_token_column(token_id),
+        &kinds,
+        &undone,
+        HOUR_SECS as f64,
+        DAY_SECS as f64
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(SpendingUsage {
+        sent_today: from_numeric(usage.sent_today)?,
+        transfers_last_hour: u32::try_from(usage.transfers_last_hour).unwrap_or(u32::MAX),
+        hour_resets_in: usage.hour_resets_in,
+    })
+}
+
+async fn freeze_status<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    address: &[u8; 32],
+    token_id: TokenId,
+) -> Result<FreezeStatus, AppError> {
+    let frozen_at = sqlx::query_scalar!(
+        "SELECT frozen_at FROM frozen_accounts WHERE address = $1 AND token_id = $2",
+        address.as_slice(),
+        to_token_column(token_id)
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(FreezeStatus {
+        token_id,
+        address: hex::encode(address),
+        frozen: frozen_at.is_some(),
+        frozen_at,
+    })
+}
@@ -1,2 +1,7 @@
 pub mod account;
+pub mod compliance;
+pub mod multisig;
+pub mod recovery;
+pub mod schedule;
+pub mod token;
 pub mod transaction;
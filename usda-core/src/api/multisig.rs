@@ -0,0 +1,241 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use usda_common::{MemberSignature, MultisigPolicy, SignablePayload};
+
+use crate::{
+    api::transaction::{decode_address, decode_signature},
+    error::AppError,
+    state::AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateMultisigRequest {
+    pub threshold: u32,
+    pub members: Vec<String>, // hex encoded member public keys
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MemberSignatureInput {
+    pub public_key: String,
+    pub signature: String,
+}
+
+/// Replaces a multisig account's policy; signed by members of the current one.
+#[derive(Debug, Deserialize)]
+pub struct UpdateMultisigRequest {
+    pub address: String,
+    pub threshold: u32,
+    pub members: Vec<String>,
+    pub version: i64, // version being replaced
+    pub signatures: Vec<MemberSignatureInput>,
+}
+
+/// A multisig account and its current policy.
+#[derive(Debug, Serialize)]
+pub struct MultisigAccount {
+    pub address: String,
+    pub threshold: u32,
+    pub members: Vec<String>,
+    /// Number of times the policy has been replaced
+    pub version: i64,
+}
+
+/// Registers the multisig account of a policy. The address is derived from
+/// the policy, so creating it needs no signature; creating it again returns
+/// the account as it is now.
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateMultisigRequest>,
+) -> Result<Json<MultisigAccount>, AppError> {
+    let policy = decode_policy(req.threshold, &req.members)?;
+    let address = policy.digest();
+
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let created = sqlx::query!(
+        r#"
+        INSERT INTO multisig_accounts (address, threshold, members)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (address) DO NOTHING
+        "#,
+        address.as_slice(),
+        policy.threshold() as i32,
+        &member_column(&policy)
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    if created.rows_affected() > 0 {
+        record_version(&mut tx, &address, &policy, 0).await?;
+    }
+
+    let (policy, version) = current_policy(&mut *tx, &address)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Multisig account not found".into()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(account_response(address, &policy, version)))
+}
+
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    Path(address): Path<String>,
+) -> Result<Json<MultisigAccount>, AppError> {
+    let address = decode_address(&address, "multisig")?;
+    let (policy, version) = current_policy(&state.db, &address)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Multisig account not found".into()))?;
+    Ok(Json(account_response(address, &policy, version)))
+}
+
+/// Replaces a multisig account's members and threshold. The current policy
+/// must approve the change; the address stays the same.
+pub async fn update(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<UpdateMultisigRequest>,
+) -> Result<Json<MultisigAccount>, AppError> {
+    let address = decode_address(&req.address, "multisig")?;
+    let policy = decode_policy(req.threshold, &req.members)?;
+    let signatures = decode_member_signatures(&req.signatures)?;
+    let payload = SignablePayload::multisig_update(state.chain_id, address, policy.clone(), req.version);
+
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Waits for transfers being checked against the current policy
+    let current = sqlx::query!(
+        "SELECT threshold, members, version FROM multisig_accounts WHERE address = $1 FOR UPDATE",
+        address.as_slice()
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound("Multisig account not found".into()))?;
+    let current_policy = policy_from_columns(current.threshold, current.members)?;
+
+    if current.version != req.version {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid policy version. Expected {}, got {}",
+            current.version, req.version
+        )));
+    }
+    if !current_policy.verify(&payload, &signatures) {
+        return Err(AppError::InvalidSignature);
+    }
+
+    let version = req.version + 1;
+    sqlx::query!(
+        r#"
+        UPDATE multisig_accounts
+        SET threshold = $1, members = $2, version = $3, updated_at = NOW()
+        WHERE address = $4
+        "#,
+        policy.threshold() as i32,
+        &member_column(&policy),
+        version,
+        address.as_slice()
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    record_version(&mut tx, &address, &policy, version).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(account_response(address, &policy, version)))
+}
+
+/// Current policy and version of the multisig account at `address`. Inside a
+/// transaction the account stays locked against updates until it ends.
+pub(crate) async fn current_policy<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    address: &[u8; 32],
+) -> Result<Option<(MultisigPolicy, i64)>, AppError> {
+    let Some(row) = sqlx::query!(
+        "SELECT threshold, members, version FROM multisig_accounts WHERE address = $1 FOR SHARE",
+        address.as_slice()
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some((policy_from_columns(row.threshold, row.members)?, row.version)))
+}
+
+pub(crate) fn decode_member_signatures(
+    signatures: &[MemberSignatureInput],
+) -> Result<Vec<MemberSignature>, AppError> {
+    signatures
+        .iter()
+        .map(|signature| {
+            Ok(MemberSignature {
+                public_key: decode_address(&signature.public_key, "member")?,
+                signature: decode_signature(&signature.signature)?,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn decode_policy(threshold: u32, members: &[String]) -> Result<MultisigPolicy, AppError> {
+    let members = members
+        .iter()
+        .map(|member| decode_address(member, "member"))
+        .collect::<Result<Vec<_>, _>>()?;
+    MultisigPolicy::new(threshold, members).map_err(AppError::InvalidInput)
+}
+
+async fn record_version(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    address: &[u8; 32],
+    policy: &MultisigPolicy,
+    version: i64,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO multisig_policies (address, version, threshold, members)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        address.as_slice(),
+        version,
+        policy.threshold() as i32,
+        &member_column(policy)
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+fn member_column(policy: &MultisigPolicy) -> Vec<Vec<u8>> {
+    policy.members().iter().map(|member| member.to_vec()).collect()
+}
+
+pub(crate) fn policy_from_columns(threshold: i32, members: Vec<Vec<u8>>) -> Result<MultisigPolicy, AppError> {
+    let corrupt = || AppError::DatabaseError("Corrupt multisig policy".into());
+    let members = members
+        .into_iter()
+        .map(|member| member.try_into().map_err(|_| corrupt()))
+        .collect::<Result<Vec<[u8; 32]>, _>>()?;
+    MultisigPolicy::new(u32::try_from(threshold).map_err(|_| corrupt())?, members).map_err(|_| corrupt())
+}
+
+fn account_response(address: [u8; 32], policy: &MultisigPolicy, version: i64) -> MultisigAccount {
+    MultisigAccount {
+        address: hex::encode(address),
+        threshold: policy.threshold(),
+        members: policy.members().iter().map(hex::encode).collect(),
+        version,
+    }
+}
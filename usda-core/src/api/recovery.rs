@@ -0,0 +1,427 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use usda_common::{AccountRecovery, MultisigPolicy, RecoveryStatus, SignablePayload, WebSocketMessage};
+
+use crate::{
+    api::account::{account_exists, verify_account_signature},
+    api::multisig::{decode_member_signatures, decode_policy, MemberSignatureInput},
+    api::transaction::{decode_address, decode_hash, decode_signature},
+    error::AppError,
+    state::AppState,
+};
+
+/// Sets or replaces an account's guardians; signed by the account's key.
+#[derive(Debug, Deserialize)]
+pub struct SetGuardiansRequest {
+    pub address: String,
+    pub threshold: u32,
+    pub guardians: Vec<String>, // hex encoded guardian public keys
+    pub version: i64,           // guardian version being replaced; 0 for the first
+    pub signature: String,
+}
+
+/// Asks for an account's key to be replaced; signed by its guardians.
+#[derive(Debug, Deserialize)]
+pub struct RecoveryRequest {
+    pub address: String,
+    pub new_public_key: String, // hex encoded
+    pub version: i64,           // key version being replaced
+    pub signatures: Vec<MemberSignatureInput>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VetoRecoveryRequest {
+    pub recovery_id: String,
+    pub signature: String, // by the account's current key
+}
+
+/// An account's guardians and how many of them must sign a recovery.
+#[derive(Debug, Serialize)]
+pub struct Guardians {
+    pub address: String,
+    pub threshold: u32,
+    pub guardians: Vec<String>,
+    /// Number of times the guardians have been set
+    pub version: i64,
+}
+
+/// Sets the guardians that can recover an account. Recoveries already
+/// requested stand; the owner can veto them.
+pub async fn set_guardians(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SetGuardiansRequest>,
+) -> Result<Json<Guardians>, AppError> {
+    let address = decode_address(&req.address, "account")?;
+    let guardians = decode_policy(req.threshold, &req.guardians)?;
+    let signature_bytes = decode_signature(&req.signature)?;
+    let payload = SignablePayload::set_guardians(state.chain_id, address, guardians.clone(), req.version);
+
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    if !account_exists(&mut *tx, &address).await? {
+        return Err(AppError::NotFound("Account not found".into()));
+    }
+    verify_account_signature(&mut *tx, &payload, &address, &signature_bytes).await?;
+
+    let current_version = sqlx::query_scalar!(
+        "SELECT version FROM account_guardians WHERE address = $1 FOR UPDATE",
+        address.as_slice()
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .unwrap_or(0);
+    if current_version != req.version {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid guardian version. Expected {}, got {}",
+            current_version, req.version
+        )));
+    }
+
+    let version = req.version + 1;
+    sqlx::query!(
+        r#"
+        INSERT INTO account_guardians (address, threshold, guardians, version)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (address) DO UPDATE
+        SET threshold = EXCLUDED.threshold,
+            guardians = EXCLUDED.guardians,
+            version = EXCLUDED.version,
+            updated_at = NOW()
+        "#,
+        address.as_slice(),
+        guardians.threshold() as i32,
+        &guardian_column(&guardians),
+        version
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(guardians_response(address, &guardians, version)))
+}
+
+pub async fn get_guardians(
+    State(state): State<Arc<AppState>>,
+    Path(address): Path<String>,
+) -> Result<Json<Guardians>, AppError> {
+    let address = decode_address(&address, "account")?;
+    let (guardians, version) = current_guardians(&state.db, &address)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Account has no guardians".into()))?;
+    Ok(Json(guardians_response(address, &guardians, version)))
+}
+
+/// Records the guardians' request to replace an account's key. The key is
+/// replaced once the recovery delay has passed, unless the owner vetoes the
+/// request or changes the key first.
+pub async fn initiate(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<RecoveryRequest>,
+) -> Result<Json<AccountRecovery>, AppError> {
+    let address = decode_address(&req.address, "account")?;
+    let new_key = decode_address(&req.new_public_key, "public key")?;
+    if VerifyingKey::from_bytes(&new_key).is_err() {
+        return Err(AppError::InvalidInput("Invalid public key".into()));
+    }
+    let signatures = decode_member_signatures(&req.signatures)?;
+    let payload = SignablePayload::recover_account(state.chain_id, address, new_key, req.version);
+    let recovery_id = payload.tx_id();
+
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Waits for guardian changes
+    let (guardians, guardian_version) = current_guardians(&mut *tx, &address)
+        .await?
+        .ok_or_else(|| AppError::InvalidInput("Account has no guardians".into()))?;
+    let approvals = guardians.approvals(&payload, &signatures);
+    if approvals.len() < guardians.threshold() as usize {
+        return Err(AppError::InvalidSignature);
+    }
+
+    // Resubmitting a recorded request returns it as it is now
+    if let Some(existing) = load_recovery(&mut *tx, &recovery_id).await? {
+        return Ok(Json(existing));
+    }
+
+    let current = sqlx::query!(
+        "SELECT public_key, version FROM account_keys WHERE address = $1 FOR SHARE",
+        address.as_slice()
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    // Accounts whose address is their key are at version 0
+    let (current_key, key_version) = match current {
+        Some(row) => (row.public_key, row.version),
+        None => (address.to_vec(), 0),
+    };
+    if key_version != req.version {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid key version. Expected {}, got {}",
+            key_version, req.version
+        )));
+    }
+    if current_key == new_key {
+        return Err(AppError::InvalidInput("Account already uses this key".into()));
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO account_recoveries (recovery_id, address, new_key, key_version, guardian_version, status, executable_at)
+        VALUES ($1, $2, $3, $4, $5, $6, NOW() + make_interval(secs => $7))
+        "#,
+        recovery_id,
+        address.as_slice(),
+        new_key.as_slice(),
+        req.version,
+        guardian_version,
+        RecoveryStatus::Pending.to_string(),
+        state.recovery_delay.as_secs_f64()
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    for approval in approvals {
+        sqlx::query!(
+            r#"
+            INSERT INTO account_recovery_signatures (recovery_id, guardian, signature)
+            VALUES ($1, $2, $3)
+            "#,
+            recovery_id,
+            approval.public_key.as_slice(),
+            approval.signature.as_slice()
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    }
+
+    let recovery = load_recovery(&mut *tx, &recovery_id)
+        .await?
+        .ok_or_else(|| AppError::DatabaseError("Recovery missing after insert".into()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    broadcast_recovery(&state, recovery.clone());
+    Ok(Json(recovery))
+}
+
+/// Stops a pending recovery. Signed by the account's current key, before the
+/// recovery delay has passed.
+pub async fn veto(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<VetoRecoveryRequest>,
+) -> Result<Json<AccountRecovery>, AppError> {
+    let recovery = decode_hash(&req.recovery_id, "recovery_id")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+    let payload = SignablePayload::veto_recovery(state.chain_id, recovery);
+    let recovery_id = hex::encode(recovery);
+
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Waits for the recovery being completed
+    let pending = sqlx::query!(
+        r#"
+        SELECT address, status, NOW() >= executable_at AS "due!"
+        FROM account_recoveries
+        WHERE recovery_id = $1
+        FOR UPDATE
+        "#,
+        recovery_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound("Recovery not found".into()))?;
+    let address: [u8; 32] = pending
+        .address
+        .try_into()
+        .map_err(|_| AppError::DatabaseError(format!("Corrupt recovery {}", recovery_id)))?;
+    verify_account_signature(&mut *tx, &payload, &address, &signature_bytes).await?;
+
+    if pending.status != RecoveryStatus::Pending.to_string() {
+        // Resubmitting an accepted veto returns the recovery as it is now
+        if pending.status == RecoveryStatus::Vetoed.to_string() {
+            if let Some(existing) = load_recovery(&mut *tx, &recovery_id).await? {
+                return Ok(Json(existing));
+            }
+        }
+        return Err(AppError::InvalidInput(format!(
+            "Recovery is already {}",
+            pending.status
+        )));
+    }
+    if pending.due {
+        return Err(AppError::InvalidInput(
+            "Recovery delay has passed and it can no longer be vetoed".into(),
+        ));
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE account_recoveries
+        SET status = $1, resolved_at = NOW(), veto_signature = $2
+        WHERE recovery_id = $3
+        "#,
+        RecoveryStatus::Vetoed.to_string(),
+        signature_bytes.as_slice(),
+        recovery_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let recovery = load_recovery(&mut *tx, &recovery_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Recovery not found".into()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    broadcast_recovery(&state, recovery.clone());
+    Ok(Json(recovery))
+}
+
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    Path(recovery_id): Path<String>,
+) -> Result<Json<AccountRecovery>, AppError> {
+    load_recovery(&state.db, &recovery_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound("Recovery not found".into()))
+}
+
+/// Every recovery requested for `address`, newest first.
+pub async fn get_recoveries(
+    State(state): State<Arc<AppState>>,
+    Path(address): Path<String>,
+) -> Result<Json<Vec<AccountRecovery>>, AppError> {
+    let address = decode_address(&address, "account")?;
+    let rows = sqlx::query_as!(
+        RecoveryRow,
+        r#"
+        SELECT recovery_id, address, new_key, key_version, status, requested_at, executable_at, resolved_at
+        FROM account_recoveries
+        WHERE address = $1
+        ORDER BY requested_at DESC
+        "#,
+        address.as_slice()
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    rows.into_iter()
+        .map(RecoveryRow::into_recovery)
+        .collect::<Result<Vec<_>, _>>()
+        .map(Json)
+}
+
+/// Guardians of the account at `address` and their version. Inside a
+/// transaction they stay locked against changes until it ends.
+async fn current_guardians<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    address: &[u8; 32],
+) -> Result<Option<(MultisigPolicy, i64)>, AppError> {
+    let Some(row) = sqlx::query!(
+        "SELECT threshold, guardians, version FROM account_guardians WHERE address = $1 FOR SHARE",
+        address.as_slice()
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    else {
+        return Ok(None);
+    };
+
+    let corrupt = || AppError::DatabaseError("Corrupt account guardians".into());
+    let guardians = row
+        .guardians
+        .into_iter()
+        .map(|guardian| guardian.try_into().map_err(|_| corrupt()))
+        .collect::<Result<Vec<[u8; 32]>, _>>()?;
+    let guardians = MultisigPolicy::new(u32::try_from(row.threshold).map_err(|_| corrupt())?, guardians)
+        .map_err(|_| corrupt())?;
+    Ok(Some((guardians, row.version)))
+}
+
+pub(crate) async fn load_recovery<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    recovery_id: &str,
+) -> Result<Option<AccountRecovery>, AppError> {
+    sqlx::query_as!(
+        RecoveryRow,
+        r#"
+        SELECT recovery_id, address, new_key, key_version, status, requested_at, executable_at, resolved_at
+        FROM account_recoveries
+        WHERE recovery_id = $1
+        "#,
+        recovery_id
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .map(RecoveryRow::into_recovery)
+    .transpose()
+}
+
+pub(crate) fn broadcast_recovery(state: &AppState, recovery: AccountRecovery) {
+    // Sending only fails when nobody is subscribed
+    let _ = state.ws_tx.send(WebSocketMessage::RecoveryUpdated(recovery));
+}
+
+struct RecoveryRow {
+    recovery_id: String,
+    address: Vec<u8>,
+    new_key: Vec<u8>,
+    key_version: i64,
+    status: String,
+    requested_at: DateTime<Utc>,
+    executable_at: DateTime<Utc>,
+    resolved_at: Option<DateTime<Utc>>,
+}
+
+impl RecoveryRow {
+    fn into_recovery(self) -> Result<AccountRecovery, AppError> {
+        let corrupt = || AppError::DatabaseError(format!("Corrupt recovery {}", self.recovery_id));
+        Ok(AccountRecovery {
+            address: self.address.as_slice().try_into().map_err(|_| corrupt())?,
+            new_key: self.new_key.as_slice().try_into().map_err(|_| corrupt())?,
+            key_version: self.key_version,
+            status: self.status.parse().map_err(|_| corrupt())?,
+            requested_at: self.requested_at,
+            executable_at: self.executable_at,
+            resolved_at: self.resolved_at,
+            recovery_id: self.recovery_id,
+        })
+    }
+}
+
+fn guardian_column(guardians: &MultisigPolicy) -> Vec<Vec<u8>> {
+    guardians.members().iter().map(|guardian| guardian.to_vec()).collect()
+}
+
+fn guardians_response(address: [u8; 32], guardians: &MultisigPolicy, version: i64) -> Guardians {
+    Guardians {
+        address: hex::encode(address),
+        threshold: guardians.threshold(),
+        guardians: guardians.members().iter().map(hex::encode).collect(),
+        version,
+    }
+}
@@ -0,0 +1,349 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use usda_common::{batch::StandingOrderTerms, Amount, SignablePayload, TokenId};
+
+use crate::{
+    api::account::verify_account_signature,
+    api::transaction::{
+        broadcast_preconfirmed, check_fee, decode_address, decode_hash, decode_signature,
+        drain_queued_transfers, lock_account,
+    },
+    error::AppError,
+    numeric::{from_numeric, from_token_column, to_numeric, to_token_column},
+    scheduler::{ORDER_ACTIVE, ORDER_CANCELLED},
+    state::AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct StandingOrderRequest {
+    #[serde(default)]
+    pub token_id: TokenId,
+    pub from: String,
+    pub to: String,
+    /// Moved by each execution
+    pub amount: Amount,
+    /// Paid by each execution
+    pub fee: Amount,
+    pub start: i64, // Unix timestamp (seconds) of the first execution
+    #[serde(default)]
+    pub interval: Option<i64>, // seconds between executions; absent for a one-off transfer
+    pub max_executions: u32,
+    pub nonce: i64,
+    pub signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelStandingOrderRequest {
+    pub order_id: String,
+    pub signature: String, // by the order's sender
+}
+
+/// A standing order and how far it has run.
+#[derive(Debug, Serialize)]
+pub struct StandingOrder {
+    pub order_id: String,
+    pub token_id: TokenId,
+    pub from: String, // hex encoded address
+    pub to: String,   // hex encoded address
+    pub amount: Amount,
+    pub fee: Amount,
+    pub start_at: DateTime<Utc>,
+    pub interval_secs: Option<i64>,
+    pub max_executions: u32,
+    /// Executions attempted so far
+    pub executions: u32,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub status: String,
+}
+
+/// One attempted execution of a standing order.
+#[derive(Debug, Serialize)]
+pub struct StandingOrderExecution {
+    pub execution: u32,
+    pub due_at: DateTime<Utc>,
+    pub executed_at: DateTime<Utc>,
+    /// Transfer the execution made; `None` if it couldn't be applied
+    pub tx_id: Option<String>,
+    /// Status of that transfer
+    pub status: Option<String>,
+    /// Why the execution couldn't be applied
+    pub error: Option<String>,
+}
+
+/// Sets up a standing order. Creating it uses the sender's nonce; the
+/// scheduler then executes it as it falls due.
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<StandingOrderRequest>,
+) -> Result<Json<StandingOrder>, AppError> {
+    state.token(req.token_id)?;
+    let from_bytes = decode_address(&req.from, "from")?;
+    let to_bytes = decode_address(&req.to, "to")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+
+    if req.amount.is_zero() {
+        return Err(AppError::InvalidInput("Transfer amount must be positive".into()));
+    }
+    req.amount.checked_add(req.fee).ok_or(AppError::AmountOverflow)?;
+    if req.max_executions == 0 {
+        return Err(AppError::InvalidInput("max_executions must be positive".into()));
+    }
+    let max_executions = i32::try_from(req.max_executions)
+        .map_err(|_| AppError::InvalidInput("max_executions is too large".into()))?;
+    match req.interval {
+        Some(interval) if interval <= 0 => {
+            return Err(AppError::InvalidInput("interval must be positive".into()));
+        }
+        None if req.max_executions > 1 => {
+            return Err(AppError::InvalidInput(
+                "An interval is required for more than one execution".into(),
+            ));
+        }
+        _ => {}
+    }
+    let start_at = DateTime::from_timestamp(req.start, 0)
+        .ok_or_else(|| AppError::InvalidInput("Invalid start timestamp".into()))?;
+
+    // Each execution is checked against the fee policy in force when it runs
+    check_fee(&state, state.fee_policy.required_fee(req.amount), req.fee)?;
+
+    // Verify sender signature
+    let terms = StandingOrderTerms {
+        token_id: req.token_id,
+        from_addr: from_bytes,
+        to_addr: to_bytes,
+        amount: req.amount,
+        fee: req.fee,
+        start: req.start,
+        interval: req.interval,
+        max_executions: req.max_executions,
+        nonce: req.nonce,
+    };
+    let payload = terms.payload(state.chain_id);
+    verify_account_signature(&state.db, &payload, &from_bytes, &signature_bytes).await?;
+    let order_id = payload.tx_id();
+
+    // The whole schedule has to be representable up front
+    terms
+        .due_at(req.max_executions - 1)
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .ok_or_else(|| AppError::InvalidInput("Schedule runs past the supported range".into()))?;
+
+    // Resubmitting an accepted order returns it
+    if let Some(existing) = load_order(&state.db, &order_id).await? {
+        return Ok(Json(existing));
+    }
+
+    // Start a transaction for atomicity
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let nonce = lock_account(&mut tx, &from_bytes, req.token_id).await?;
+    if nonce != req.nonce {
+        // A concurrent submission of the same order may have won the lock
+        if let Some(existing) = load_order(&mut *tx, &order_id).await? {
+            return Ok(Json(existing));
+        }
+        return Err(AppError::InvalidNonce);
+    }
+
+    sqlx::query!(
+        "UPDATE accounts SET nonce = nonce + 1 WHERE address = $1 AND token_id = $2",
+        from_bytes.as_slice(),
+        to_token_column(req.token_id)
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO standing_orders (order_id, token_id, from_addr, to_addr, amount, fee, start_at, interval_secs, max_executions, next_run_at, nonce, signature, status)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $7, $10, $11, $12)
+        "#,
+        order_id,
+        to_token_column(req.token_id),
+        from_bytes.as_slice(),
+        to_bytes.as_slice(),
+        to_numeric(req.amount),
+        to_numeric(req.fee),
+        start_at,
+        req.interval,
+        max_executions,
+        req.nonce,
+        signature_bytes.as_slice(),
+        ORDER_ACTIVE
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Transfers queued behind this order's nonce can now be applied
+    let drained =
+        drain_queued_transfers(&mut tx, &state, &from_bytes, req.token_id, req.nonce + 1).await?;
+
+    // Commit transaction
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    for transaction in drained {
+        broadcast_preconfirmed(&state, transaction);
+    }
+
+    Ok(Json(StandingOrder {
+        order_id,
+        token_id: req.token_id,
+        from: hex::encode(from_bytes),
+        to: hex::encode(to_bytes),
+        amount: req.amount,
+        fee: req.fee,
+        start_at,
+        interval_secs: req.interval,
+        max_executions: req.max_executions,
+        executions: 0,
+        next_run_at: Some(start_at),
+        status: ORDER_ACTIVE.to_string(),
+    }))
+}
+
+/// Stops a standing order's remaining executions. Executions already made
+/// stand; cancelling a finished order returns it unchanged.
+pub async fn cancel(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CancelStandingOrderRequest>,
+) -> Result<Json<StandingOrder>, AppError> {
+    let order_bytes = decode_hash(&req.order_id, "order_id")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Waits for an execution in progress
+    let order = sqlx::query!(
+        "SELECT token_id, from_addr FROM standing_orders WHERE order_id = $1 FOR UPDATE",
+        req.order_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound("Standing order not found".into()))?;
+
+    let from: [u8; 32] = order
+        .from_addr
+        .try_into()
+        .map_err(|_| AppError::DatabaseError("Corrupt standing order".into()))?;
+    let payload = SignablePayload::cancel_standing_order(state.chain_id, order_bytes)
+        .with_token_id(from_token_column(order.token_id)?);
+    verify_account_signature(&mut *tx, &payload, &from, &signature_bytes).await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE standing_orders
+        SET status = $1, next_run_at = NULL
+        WHERE order_id = $2 AND status = $3
+        "#,
+        ORDER_CANCELLED,
+        req.order_id,
+        ORDER_ACTIVE
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let order = load_order(&mut *tx, &req.order_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Standing order not found".into()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(order))
+}
+
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    Path(order_id): Path<String>,
+) -> Result<Json<StandingOrder>, AppError> {
+    load_order(&state.db, &order_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound("Standing order not found".into()))
+}
+
+/// Execution history of a standing order, oldest first.
+pub async fn executions(
+    State(state): State<Arc<AppState>>,
+    Path(order_id): Path<String>,
+) -> Result<Json<Vec<StandingOrderExecution>>, AppError> {
+    if load_order(&state.db, &order_id).await?.is_none() {
+        return Err(AppError::NotFound("Standing order not found".into()));
+    }
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT e.execution, e.due_at, e.executed_at, e.tx_id, t.status::TEXT AS "status?", e.error
+        FROM standing_order_executions e
+        LEFT JOIN transactions t ON t.tx_id = e.tx_id
+        WHERE e.order_id = $1
+        ORDER BY e.execution
+        "#,
+        order_id
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(
+        rows.into_iter()
+            .map(|row| StandingOrderExecution {
+                execution: row.execution as u32,
+                due_at: row.due_at,
+                executed_at: row.executed_at,
+                tx_id: row.tx_id,
+                status: row.status,
+                error: row.error,
+            })
+            .collect(),
+    ))
+}
+
+async fn load_order<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    order_id: &str,
+) -> Result<Option<StandingOrder>, AppError> {
+    let Some(row) = sqlx::query!(
+        r#"
+        SELECT order_id, token_id, from_addr, to_addr, amount, fee, start_at, interval_secs,
+               max_executions, executions, next_run_at, status
+        FROM standing_orders
+        WHERE order_id = $1
+        "#,
+        order_id
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(StandingOrder {
+        order_id: row.order_id,
+        token_id: from_token_column(row.token_id)?,
+        from: hex::encode(row.from_addr),
+        to: hex::encode(row.to_addr),
+        amount: from_numeric(row.amount)?,
+        fee: from_numeric(row.fee)?,
+        start_at: row.start_at,
+        interval_secs: row.interval_secs,
+        max_executions: row.max_executions as u32,
+        executions: row.executions as u32,
+        next_run_at: row.next_run_at,
+        status: row.status,
+    }))
+}
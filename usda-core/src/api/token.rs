@@ -0,0 +1,78 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use usda_common::{Amount, TokenId, TokenMetadata, DEFAULT_TOKEN_ID};
+
+use crate::{
+    error::AppError,
+    numeric::{from_numeric, from_token_column},
+    state::AppState,
+};
+
+/// `?token_id=` selecting one token; the default token when absent.
+#[derive(Debug, Default, Deserialize)]
+pub struct TokenQuery {
+    pub token_id: Option<TokenId>,
+}
+
+/// A token the ledger accepts, with its issuer and supply.
+#[derive(Debug, Serialize)]
+pub struct TokenInfo {
+    pub token_id: TokenId,
+    #[serde(flatten)]
+    pub metadata: TokenMetadata,
+    pub issuer: Option<String>, // hex encoded issuer key
+    pub minted: Amount,
+    pub burned: Amount,
+    /// Outstanding supply, `minted - burned`
+    pub supply: Amount,
+}
+
+/// Name, symbol and decimals clients need to display amounts.
+pub async fn metadata(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TokenQuery>,
+) -> Result<Json<TokenMetadata>, AppError> {
+    let token = state.token(query.token_id.unwrap_or(DEFAULT_TOKEN_ID))?;
+    Ok(Json(token.clone()))
+}
+
+/// Every token on the ledger. Supply counts accepted mints and burns,
+/// including those whose batch isn't proven yet.
+pub async fn list(State(state): State<Arc<AppState>>) -> Result<Json<Vec<TokenInfo>>, AppError> {
+    let rows = sqlx::query!("SELECT token_id, minted, burned FROM tokens")
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let mut supply = HashMap::with_capacity(rows.len());
+    for row in rows {
+        supply.insert(
+            from_token_column(row.token_id)?,
+            (from_numeric(row.minted)?, from_numeric(row.burned)?),
+        );
+    }
+
+    let tokens = state
+        .tokens()
+        .map(|(token_id, metadata)| {
+            let (minted, burned) = supply.get(&token_id).copied().unwrap_or_default();
+            Ok(TokenInfo {
+                token_id,
+                metadata: metadata.clone(),
+                issuer: state.issuer_key(token_id).map(|key| hex::encode(key.to_bytes())),
+                minted,
+                burned,
+                supply: minted.checked_sub(burned).ok_or_else(|| {
+                    AppError::DatabaseError(format!("Token {} burned more than it minted", token_id))
+                })?,
+            })
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    Ok(Json(tokens))
+}
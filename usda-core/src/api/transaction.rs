@@ -1,153 +1,2637 @@
-use axum::{extract::State, Json};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use usda_common::TransactionStatus;
-use uuid::Uuid;
+use usda_common::{
+    Allowance, Amount, Escrow, EscrowStatus, FeePolicy, SignablePayload, TokenId, TokenMetadata, Transaction, TransactionKind,
+    TransactionStatus, WebSocketMessage,
+};
 
-use crate::{error::AppError, state::AppState};
+use crate::{
+    api::account::verify_account_signature,
+    api::compliance::{check_not_frozen, check_spending_limits},
+    api::multisig::{current_policy, decode_member_signatures, MemberSignatureInput},
+    error::AppError,
+    lifecycle::{self, from_status_column},
+    numeric::{from_numeric, from_token_column, to_numeric, to_token_column},
+    state::AppState,
+};
+
+/// Upper bound on the number of legs in one batch transfer
+pub const MAX_BATCH_LEGS: usize = 256;
+
+/// An amount in a request, either in base units (`1000` or `"1000"`) or in
+/// tokens, tagged as such: `{"tokens": "0.001"}` or `{"tokens": "1 USDA"}`.
+/// A bare string is always base units, so `"12"` can't be mistaken for 12 tokens.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(
+    untagged,
+    expecting = r#"an amount in base units, or in tokens as {"tokens": "<amount>"}"#
+)]
+pub enum AmountInput {
+    Units(Amount),
+    Tokens { tokens: String },
+}
+
+impl AmountInput {
+    /// Amount in base units. Signatures always cover base units.
+    pub fn resolve(&self, token: &TokenMetadata) -> Result<Amount, AppError> {
+        match self {
+            AmountInput::Units(amount) => Ok(*amount),
+            AmountInput::Tokens { tokens } => token.parse(tokens).map_err(AppError::InvalidInput),
+        }
+    }
+}
+
+impl From<Amount> for AmountInput {
+    fn from(amount: Amount) -> Self {
+        AmountInput::Units(amount)
+    }
+}
 
 #[derive(Debug, Deserialize)]
 pub struct TransferRequest {
+    #[serde(default)]
+    pub token_id: TokenId, // token to move; the default token when omitted
     pub from: Option<String>,    // hex encoded address
     pub to: String,      // hex encoded address
-    pub amount: i64,
-    pub fee: i64,
+    pub amount: AmountInput,
+    pub fee: AmountInput,
+    pub nonce: i64,
+    pub signature: String, // hex encoded signature
+    #[serde(default)]
+    pub valid_until: Option<i64>, // Unix timestamp (seconds)
+}
+
+/// Transfer out of a multisig account, signed by enough of its members.
+#[derive(Debug, Deserialize)]
+pub struct MultisigTransferRequest {
+    #[serde(default)]
+    pub token_id: TokenId,
+    pub from: String, // hex encoded multisig address
+    pub to: String,
+    pub amount: AmountInput,
+    pub fee: AmountInput,
+    pub nonce: i64,
+    pub signatures: Vec<MemberSignatureInput>,
+    #[serde(default)]
+    pub valid_until: Option<i64>,
+}
+
+/// A transfer to preview. The fee defaults to the required fee and the nonce
+/// to the sender's next one; the signature is only checked if given.
+#[derive(Debug, Deserialize)]
+pub struct SimulateTransferRequest {
+    #[serde(default)]
+    pub token_id: TokenId,
+    pub from: String, // hex encoded address
+    pub to: String,   // hex encoded address
+    pub amount: AmountInput,
+    #[serde(default)]
+    pub fee: Option<AmountInput>,
+    #[serde(default)]
+    pub nonce: Option<i64>,
+    #[serde(default)]
+    pub signature: Option<String>,
+    #[serde(default)]
+    pub valid_until: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MintRequest {
+    #[serde(default)]
+    pub token_id: TokenId, // token to mint
+    pub to: String,        // hex encoded address
+    pub amount: Amount,
+    pub nonce: i64,        // issuer mint nonce
+    pub signature: String, // hex encoded issuer signature
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BurnRequest {
+    #[serde(default)]
+    pub token_id: TokenId, // token to burn
+    pub from: String,      // hex encoded address
+    pub amount: Amount,
+    pub nonce: i64,
+    pub redemption_ref: Option<String>, // off-ledger redemption reference
+    pub signature: String, // hex encoded signature
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferLeg {
+    pub to: String, // hex encoded address
+    pub amount: Amount,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchTransferRequest {
+    #[serde(default)]
+    pub token_id: TokenId, // token every leg moves
+    pub from: String,      // hex encoded address
+    pub legs: Vec<TransferLeg>,
+    pub fee: Amount,       // total fee for the whole batch
+    pub nonce: i64,
+    pub signature: String, // hex encoded signature
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelRequest {
+    #[serde(default)]
+    pub token_id: TokenId, // token of the transfer to cancel
+    pub from: String,      // hex encoded address
+    pub nonce: i64,        // nonce of the transfer to cancel
+    pub signature: String, // hex encoded signature
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApproveRequest {
+    #[serde(default)]
+    pub token_id: TokenId, // token of the allowance
+    pub owner: String,     // hex encoded address
+    pub spender: String,   // hex encoded address
+    pub limit: Amount,     // total the spender may move, fees included
+    #[serde(default)]
+    pub expiry: Option<i64>, // Unix timestamp (seconds)
+    pub nonce: i64,        // owner's account nonce
+    pub signature: String, // hex encoded owner signature
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferFromRequest {
+    #[serde(default)]
+    pub token_id: TokenId, // token to move
+    pub spender: String,   // hex encoded address
+    pub owner: String,     // hex encoded address
+    pub to: String,        // hex encoded address
+    pub amount: AmountInput,
+    pub fee: AmountInput,
+    pub nonce: i64,        // allowance nonce
+    pub signature: String, // hex encoded spender signature
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EscrowLockRequest {
+    #[serde(default)]
+    pub token_id: TokenId, // token to lock
+    pub from: String,      // hex encoded address
+    pub to: String,        // hex encoded recipient address
+    pub amount: Amount,
+    pub fee: Amount,
+    pub hashlock: String,  // hex encoded SHA-256 of the preimage
+    pub timeout: i64,      // Unix timestamp (seconds)
     pub nonce: i64,
     pub signature: String, // hex encoded signature
 }
 
+#[derive(Debug, Deserialize)]
+pub struct EscrowClaimRequest {
+    pub escrow_id: String,
+    pub preimage: String,  // hex encoded, 32 bytes
+    pub signature: String, // hex encoded recipient signature
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EscrowRefundRequest {
+    pub escrow_id: String,
+    pub signature: String, // hex encoded sender signature
+}
+
 #[derive(Serialize)]
 pub struct TransactionResponse {
     pub tx_id: String,
     pub status: String,
 }
 
+/// What submitting a transfer would do, in base units.
+#[derive(Serialize)]
+pub struct TransferPreview {
+    pub tx_id: String,
+    pub signing_payload: String, // hex encoded bytes the sender signs
+    pub status: String,          // status the submission would return
+    pub amount: Amount,
+    pub fee: Amount,
+    pub required_fee: Amount,
+    pub nonce: i64,
+    pub expected_nonce: i64,     // sender's next nonce
+    pub sender_balance: Amount,  // pending balances after the submission
+    pub receiver_balance: Amount,
+    pub released: Vec<String>,   // queued transfers the submission would apply
+}
+
+/// A transaction and how far it is from final.
+#[derive(Serialize)]
+pub struct TransactionLookup {
+    pub transaction: Transaction,
+    pub receipt: TransactionReceipt,
+}
+
+#[derive(Serialize)]
+pub struct TransactionReceipt {
+    pub status: TransactionStatus,
+    pub batch_id: Option<String>, // proof batch the transaction was sealed into
+    pub proven_at: Option<DateTime<Utc>>,
+    pub finalized_at: Option<DateTime<Utc>>,
+    pub vkey_hash: Option<String>, // verifying-key hash of the batch's proof
+    pub reason: Option<String>,    // why the transaction was cancelled or failed
+}
+
+#[derive(Serialize)]
+pub struct BatchTransferResponse {
+    pub group_id: String,
+    pub tx_ids: Vec<String>, // one per leg, in request order
+    pub status: String,
+}
+
+#[derive(Serialize)]
+pub struct FeePolicyResponse {
+    pub policy: FeePolicy,
+    pub treasury: Option<String>, // hex encoded address
+}
+
 pub async fn transfer(
     State(state): State<Arc<AppState>>,
     Json(req): Json<TransferRequest>,
 ) -> Result<Json<TransactionResponse>, AppError> {
-    // Validate amount
-    if req.amount <= 0 {
-        return Err(AppError::InvalidInput("Transfer amount must be positive".into()));
+    let entry = verify_transfer_request(&state, &req).await?;
+
+    // Resubmitting an accepted transaction returns the original record
+    if let Some(existing) = existing_transaction(&state.db, &entry.tx_id).await? {
+        return Ok(Json(existing));
     }
 
+    check_transfer_admission(&state, &entry)?;
+
     // Start a transaction for atomicity
     let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-    // Get sender account if this is not a mint operation
-    if let Some(from) = &req.from {
-        let from_bytes = hex::decode(from)
-            .map_err(|_| AppError::InvalidInput("Invalid from address".into()))?;
-        if from_bytes.len() != 32 {
-            return Err(AppError::InvalidInput("Invalid from address length".into()));
-        }
+    let applied = match submit_transfer(&mut tx, &state, &entry).await? {
+        Submission::Existing(existing) => return Ok(Json(existing)),
+        Submission::Queued => None,
+        Submission::Applied(applied) => Some(applied),
+    };
 
-        let sender = sqlx::query!(
-            r#"
-            SELECT balance, nonce
-            FROM accounts
-            WHERE address = $1
-            FOR UPDATE
-            "#,
-            from_bytes.as_slice()
-        )
-        .fetch_optional(&mut *tx)
+    // Commit transaction
+    tx.commit()
         .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?
-        .ok_or_else(|| AppError::NotFound("Sender account not found".into()))?;
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-        // Verify nonce
-        if sender.nonce != req.nonce {
-            return Err(AppError::InvalidInput(format!(
-                "Invalid nonce. Expected {}, got {}",
-                sender.nonce, req.nonce
-            )));
+    // The transfer itself, then any queued behind it
+    let status = match applied {
+        Some(applied) => {
+            for transaction in applied {
+                broadcast_preconfirmed(&state, transaction);
+            }
+            TransactionStatus::Sequenced
         }
+        None => TransactionStatus::Received,
+    };
 
-        // Verify signature
-        let signature_bytes = hex::decode(&req.signature)
-            .map_err(|_| AppError::InvalidInput("Invalid signature".into()))?;
-        if signature_bytes.len() != 64 {
-            return Err(AppError::InvalidInput("Invalid signature length".into()));
-        }
+    Ok(Json(TransactionResponse {
+        tx_id: entry.tx_id,
+        status: status.to_string(),
+    }))
+}
 
-        let to_bytes = hex::decode(&req.to)
-            .map_err(|_| AppError::InvalidInput("Invalid to address".into()))?;
-        if to_bytes.len() != 32 {
-            return Err(AppError::InvalidInput("Invalid to address length".into()));
-        }
+/// Looks up a transaction by ID, including transfers still waiting in the
+/// nonce queue, with its receipt.
+pub async fn get_transaction(
+    State(state): State<Arc<AppState>>,
+    Path(tx_id): Path<String>,
+) -> Result<Json<TransactionLookup>, AppError> {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            t.tx_id,
+            t.kind,
+            t.token_id,
+            t.from_addr as "from_addr?: Vec<u8>",
+            t.to_addr as "to_addr?: Vec<u8>",
+            t.spender,
+            t.amount,
+            t.fee,
+            t.nonce,
+            t.signature,
+            t.timestamp,
+            t.status::TEXT as "status!",
+            t.status_reason,
+            t.redemption_ref,
+            t.group_id,
+            t.valid_until,
+            t.batch_id,
+            b.proven_at as "proven_at?",
+            b.finalized_at as "finalized_at?",
+            b.vkey_hash as "vkey_hash?"
+        FROM transactions t
+        LEFT JOIN proof_batches b ON b.batch_id = t.batch_id
+        WHERE t.tx_id = $1
+        "#,
+        tx_id
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let Some(row) = row else {
+        return queued_transaction(&state, &tx_id).await.map(Json);
+    };
+
+    let corrupt = || AppError::DatabaseError(format!("Corrupt transaction {}", row.tx_id));
+    let status = from_status_column(&row.status)?;
+    let transaction = Transaction {
+        kind: row.kind.parse().map_err(|_| corrupt())?,
+        token_id: from_token_column(row.token_id)?,
+        from: row.from_addr.map(|addr| addr.try_into()).transpose().map_err(|_| corrupt())?,
+        to: row.to_addr.map(|addr| addr.try_into()).transpose().map_err(|_| corrupt())?,
+        spender: row.spender.map(|addr| addr.try_into()).transpose().map_err(|_| corrupt())?,
+        amount: from_numeric(row.amount)?,
+        fee: from_numeric(row.fee)?,
+        nonce: row.nonce,
+        signature: row.signature[..].try_into().map_err(|_| corrupt())?,
+        timestamp: row.timestamp,
+        status,
+        redemption_ref: row.redemption_ref,
+        group_id: row.group_id,
+        valid_until: row.valid_until,
+        tx_id: row.tx_id,
+    };
+
+    Ok(Json(TransactionLookup {
+        transaction,
+        receipt: TransactionReceipt {
+            status,
+            batch_id: row.batch_id,
+            proven_at: row.proven_at,
+            finalized_at: row.finalized_at,
+            vkey_hash: row.vkey_hash,
+            reason: row.status_reason,
+        },
+    }))
+}
+
+/// Previews a transfer without submitting it: runs the checks and balance
+/// updates of [`transfer`] in a database transaction that is always rolled
+/// back. Fails with the error the submission would fail with.
+pub async fn simulate(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SimulateTransferRequest>,
+) -> Result<Json<TransferPreview>, AppError> {
+    let token = state.token(req.token_id)?;
+    let amount = req.amount.resolve(token)?;
+    let required_fee = state.fee_policy.required_fee(amount);
+    let fee = match &req.fee {
+        Some(fee) => fee.resolve(token)?,
+        None => required_fee,
+    };
+    let request = |nonce| TransferRequest {
+        token_id: req.token_id,
+        from: Some(req.from.clone()),
+        to: req.to.clone(),
+        amount: amount.into(),
+        fee: fee.into(),
+        nonce,
+        signature: String::new(),
+        valid_until: req.valid_until,
+    };
+
+    // The request is checked in the order `transfer` checks it, before the
+    // sender's account is looked up
+    let (mut entry, mut payload) = decode_transfer_request(&state, &request(req.nonce.unwrap_or_default()))?;
+    if req.nonce.is_none() {
+        // An omitted nonce is the sender's next one
+        let next_nonce = state
+            .get_account(&entry.from, req.token_id)
+            .await?
+            .map_or(0, |account| account.nonce);
+        (entry, payload) = decode_transfer_request(&state, &request(next_nonce))?;
+    }
+    if let Some(signature) = &req.signature {
+        entry.signature = decode_signature(signature)?;
+        verify_account_signature(&state.db, &payload, &entry.from, &entry.signature).await?;
+    }
+
+    // Never committed; dropping it rolls it back on every return path
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let existing = existing_transaction(&mut *tx, &entry.tx_id).await?;
+    if existing.is_none() {
+        check_transfer_admission(&state, &entry)?;
+    }
+    let expected_nonce = lock_account(&mut tx, &entry.from, entry.token_id).await?;
+
+    let mut released = Vec::new();
+    let status = match existing {
+        // Resubmitting an accepted transaction returns the original record
+        Some(existing) => existing.status,
+        None => match submit_transfer(&mut tx, &state, &entry).await? {
+            Submission::Existing(existing) => existing.status,
+            Submission::Queued => TransactionStatus::Received.to_string(),
+            Submission::Applied(applied) => {
+                released = applied.into_iter().skip(1).map(|transaction| transaction.tx_id).collect();
+                TransactionStatus::Sequenced.to_string()
+            }
+        },
+    };
+
+    let preview = TransferPreview {
+        tx_id: entry.tx_id.clone(),
+        signing_payload: hex::encode(payload.to_bytes()),
+        status,
+        amount,
+        fee,
+        required_fee,
+        nonce: entry.nonce,
+        expected_nonce,
+        sender_balance: pending_balance(&mut tx, &entry.from, entry.token_id).await?,
+        receiver_balance: pending_balance(&mut tx, &entry.to, entry.token_id).await?,
+        released,
+    };
+
+    tx.rollback()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Json(preview))
+}
+
+/// Transfer out of a multisig account. Members sign the same payload as for
+/// a single-key transfer, and at least the threshold of the account's current
+/// policy must have signed. Unlike single-key transfers, multisig transfers
+/// aren't queued: the nonce must be the account's next one.
+pub async fn multisig_transfer(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<MultisigTransferRequest>,
+) -> Result<Json<TransactionResponse>, AppError> {
+    let token = state.token(req.token_id)?;
+    let amount = req.amount.resolve(token)?;
+    let fee = req.fee.resolve(token)?;
+    if amount.is_zero() {
+        return Err(AppError::InvalidInput("Transfer amount must be positive".into()));
+    }
+
+    let from_bytes = decode_address(&req.from, "from")?;
+    let to_bytes = decode_address(&req.to, "to")?;
+    let signatures = decode_member_signatures(&req.signatures)?;
+    let payload = SignablePayload::transfer(state.chain_id, from_bytes, to_bytes, amount, fee, req.nonce)
+        .with_valid_until(req.valid_until)
+        .with_token_id(req.token_id);
+    let valid_until = req
+        .valid_until
+        .map(|secs| {
+            DateTime::from_timestamp(secs, 0)
+                .ok_or_else(|| AppError::InvalidInput("Invalid valid_until timestamp".into()))
+        })
+        .transpose()?;
+
+    // Resubmitting an accepted transaction returns the original record
+    let tx_id = payload.tx_id();
+    if let Some(existing) = existing_transaction(&state.db, &tx_id).await? {
+        return Ok(Json(existing));
+    }
+
+    // Start a transaction for atomicity
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Held until commit, so the policy can't change before the transfer is applied
+    let (policy, version) = current_policy(&mut *tx, &from_bytes)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Multisig account not found".into()))?;
+    let approvals = policy.approvals(&payload, &signatures);
+    if approvals.len() < policy.threshold() as usize {
+        return Err(AppError::InvalidSignature);
+    }
+
+    let entry = TransferEntry {
+        tx_id,
+        token_id: req.token_id,
+        from: from_bytes,
+        to: to_bytes,
+        amount,
+        fee,
+        nonce: req.nonce,
+        signature: approvals[0].signature,
+        valid_until,
+        order_id: None,
+        multisig_version: Some(version),
+    };
+    check_transfer_admission(&state, &entry)?;
 
-        // Check sufficient balance
-        if sender.balance < req.amount + req.fee {
-            return Err(AppError::InsufficientBalance);
+    let nonce = lock_account(&mut tx, &from_bytes, req.token_id).await?;
+    if req.nonce != nonce {
+        // A concurrent submission of the same transaction may have won the lock
+        if let Some(existing) = existing_transaction(&mut *tx, &entry.tx_id).await? {
+            return Ok(Json(existing));
         }
+        return Err(AppError::InvalidNonce);
+    }
 
-        // Update sender's balance and nonce
+    let transaction = apply_transfer(&mut tx, &state, &entry).await?;
+    for approval in &approvals {
         sqlx::query!(
-            r#"
-            UPDATE accounts
-            SET balance = balance - $1,
-                nonce = nonce + 1
-            WHERE address = $2
-            "#,
-            req.amount + req.fee,
-            from_bytes.as_slice()
+            "INSERT INTO multisig_signatures (tx_id, member, signature) VALUES ($1, $2, $3)",
+            entry.tx_id,
+            approval.public_key.as_slice(),
+            approval.signature.as_slice()
         )
         .execute(&mut *tx)
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
     }
 
-    // Update receiver's balance
-    let to_bytes = hex::decode(&req.to)
-        .map_err(|_| AppError::InvalidInput("Invalid to address".into()))?;
-    if to_bytes.len() != 32 {
-        return Err(AppError::InvalidInput("Invalid to address length".into()));
+    // The nonce gap may now be filled for transfers queued behind this one
+    let drained =
+        drain_queued_transfers(&mut tx, &state, &from_bytes, req.token_id, req.nonce + 1).await?;
+
+    // Commit transaction
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    broadcast_preconfirmed(&state, transaction);
+    for transaction in drained {
+        broadcast_preconfirmed(&state, transaction);
     }
 
+    Ok(Json(TransactionResponse {
+        tx_id: entry.tx_id,
+        status: TransactionStatus::Sequenced.to_string(),
+    }))
+}
+
+/// Withdraws a transfer that hasn't been picked up by a proof batch yet and
+/// returns its funds to the sender. The nonce stays used.
+pub async fn cancel(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CancelRequest>,
+) -> Result<Json<TransactionResponse>, AppError> {
+    let from_bytes = decode_address(&req.from, "from")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+
+    state.token(req.token_id)?;
+
+    // Verify signature
+    let payload = SignablePayload::cancel(state.chain_id, from_bytes, req.nonce)
+        .with_token_id(req.token_id);
+    verify_account_signature(&state.db, &payload, &from_bytes, &signature_bytes).await?;
+
+    // Start a transaction for atomicity
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    lock_account(&mut tx, &from_bytes, req.token_id).await?;
+
+    let Some(target) = live_transfer(&mut tx, &from_bytes, req.token_id, req.nonce).await? else {
+        // Resubmitting an accepted cancellation returns the cancelled record
+        let cancelled = sqlx::query!(
+            r#"
+            SELECT tx_id
+            FROM transactions
+            WHERE from_addr = $1 AND token_id = $2 AND nonce = $3 AND cancel_signature = $4
+            "#,
+            from_bytes.as_slice(),
+            to_token_column(req.token_id),
+            req.nonce,
+            signature_bytes.as_slice()
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("No transfer to cancel at this nonce".into()))?;
+
+        return Ok(Json(TransactionResponse {
+            tx_id: cancelled.tx_id,
+            status: TransactionStatus::Cancelled.to_string(),
+        }));
+    };
+
+    revert_transfer(&mut tx, &from_bytes, &target).await?;
+
+    let target_ids = std::slice::from_ref(&target.tx_id);
+    lifecycle::transition(&mut tx, target_ids, TransactionStatus::Cancelled, Some("Cancelled by sender")).await?;
     sqlx::query!(
         r#"
-        INSERT INTO accounts (address, balance, nonce)
-        VALUES ($1, $2, 0)
-        ON CONFLICT (address) DO UPDATE
-        SET balance = accounts.balance + $2
+        UPDATE transactions
+        SET cancelled_at = NOW(),
+            cancel_signature = $1
+        WHERE tx_id = $2
         "#,
-        to_bytes.as_slice(),
-        req.amount
+        signature_bytes.as_slice(),
+        target.tx_id
     )
     .execute(&mut *tx)
     .await
     .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-    // Create transaction record
-    let tx_id = Uuid::new_v4().to_string();
-    let from_addr = req.from.as_ref().map(|f| hex::decode(f).unwrap());
-    
+    // Commit transaction
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    broadcast_cancelled(&state, &target.tx_id, None);
+
+    Ok(Json(TransactionResponse {
+        tx_id: target.tx_id,
+        status: TransactionStatus::Cancelled.to_string(),
+    }))
+}
+
+/// Replace-by-fee: swaps a not yet batched transfer for a new one signed with
+/// the same nonce and a strictly higher fee.
+pub async fn replace(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<TransferRequest>,
+) -> Result<Json<TransactionResponse>, AppError> {
+    let entry = verify_transfer_request(&state, &req).await?;
+
+    // Resubmitting an accepted replacement returns the original record
+    if let Some(existing) = existing_transaction(&state.db, &entry.tx_id).await? {
+        return Ok(Json(existing));
+    }
+
+    check_transfer_admission(&state, &entry)?;
+
+    // Start a transaction for atomicity
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    lock_account(&mut tx, &entry.from, entry.token_id).await?;
+
+    let target = live_transfer(&mut tx, &entry.from, entry.token_id, entry.nonce)
+        .await?
+        .ok_or_else(|| AppError::NotFound("No transfer to replace at this nonce".into()))?;
+
+    // The replacement must pay more than the transfer it displaces
+    if entry.fee <= target.fee {
+        return Err(AppError::InsufficientFee {
+            required: target
+                .fee
+                .checked_add(Amount::new(1))
+                .ok_or(AppError::AmountOverflow)?,
+        });
+    }
+
+    // Cancelled first, so it no longer counts against the sender's limits
+    revert_transfer(&mut tx, &entry.from, &target).await?;
+    let reason = format!("Replaced by {}", entry.tx_id);
+    let target_ids = std::slice::from_ref(&target.tx_id);
+    lifecycle::transition(&mut tx, target_ids, TransactionStatus::Cancelled, Some(&reason)).await?;
     sqlx::query!(
         r#"
-        INSERT INTO transactions (tx_id, from_addr, to_addr, amount, fee, nonce, signature, timestamp, status)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, NOW(), $8)
+        UPDATE transactions
+        SET cancelled_at = NOW(),
+            replaced_by = $1
+        WHERE tx_id = $2
         "#,
-        tx_id,
-        from_addr.as_deref(),
-        to_bytes.as_slice(),
-        req.amount,
+        entry.tx_id,
+        target.tx_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let transaction = apply_transfer(&mut tx, &state, &entry).await?;
+
+    // Commit transaction
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    broadcast_cancelled(&state, &target.tx_id, Some(entry.tx_id.clone()));
+    broadcast_preconfirmed(&state, transaction);
+
+    Ok(Json(TransactionResponse {
+        tx_id: entry.tx_id,
+        status: TransactionStatus::Sequenced.to_string(),
+    }))
+}
+
+pub async fn batch_transfer(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<BatchTransferRequest>,
+) -> Result<Json<BatchTransferResponse>, AppError> {
+    if req.legs.is_empty() {
+        return Err(AppError::InvalidInput("Batch transfer needs at least one leg".into()));
+    }
+    if req.legs.len() > MAX_BATCH_LEGS {
+        return Err(AppError::InvalidInput(format!(
+            "Batch transfer has more than {} legs",
+            MAX_BATCH_LEGS
+        )));
+    }
+
+    state.token(req.token_id)?;
+    let from_bytes = decode_address(&req.from, "from")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+
+    let mut legs = Vec::with_capacity(req.legs.len());
+    let mut total_amount = Amount::ZERO;
+    let mut required_fee = Amount::ZERO;
+    for leg in &req.legs {
+        if leg.amount.is_zero() {
+            return Err(AppError::InvalidInput("Transfer amount must be positive".into()));
+        }
+        legs.push((decode_address(&leg.to, "to")?, leg.amount));
+        total_amount = total_amount
+            .checked_add(leg.amount)
+            .ok_or(AppError::AmountOverflow)?;
+        required_fee = required_fee.saturating_add(state.fee_policy.required_fee(leg.amount));
+    }
+
+    // Verify signature over the whole batch
+    let payload = SignablePayload::batch_transfer(
+        state.chain_id,
+        from_bytes,
+        legs.clone(),
         req.fee,
         req.nonce,
-        hex::decode(&req.signature).unwrap_or_default(),
-        TransactionStatus::Pending.to_string()
+    )
+    .with_token_id(req.token_id);
+    verify_account_signature(&state.db, &payload, &from_bytes, &signature_bytes).await?;
+
+    // Resubmitting an accepted batch returns the original records
+    let group_id = payload.tx_id();
+    if let Some(existing) = existing_batch(&state.db, &group_id).await? {
+        return Ok(Json(existing));
+    }
+
+    // The fee policy applies to every leg as if it were its own transfer
+    let treasury = check_fee(&state, required_fee, req.fee)?;
+    let total_debit = total_amount
+        .checked_add(req.fee)
+        .ok_or(AppError::AmountOverflow)?;
+
+    // Start a transaction so every leg commits or none does
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let sender = sqlx::query!(
+        r#"
+        SELECT pending_balance, nonce
+        FROM accounts
+        WHERE address = $1 AND token_id = $2
+        FOR UPDATE
+        "#,
+        from_bytes.as_slice(),
+        to_token_column(req.token_id)
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound("Sender account not found".into()))?;
+
+    // Verify nonce
+    if sender.nonce != req.nonce {
+        // A concurrent submission of the same batch may have won the lock
+        if let Some(existing) = existing_batch(&mut *tx, &group_id).await? {
+            return Ok(Json(existing));
+        }
+        return Err(AppError::InvalidNonce);
+    }
+
+    let mut parties = vec![from_bytes];
+    parties.extend(legs.iter().map(|(to_bytes, _)| *to_bytes));
+    check_not_frozen(&mut *tx, req.token_id, &parties).await?;
+    let amounts: Vec<Amount> = legs.iter().map(|(_, amount)| *amount).collect();
+    check_spending_limits(&state, &mut tx, &from_bytes, req.token_id, &amounts).await?;
+
+    // Check sufficient balance
+    if from_numeric(sender.pending_balance)? < total_debit {
+        return Err(AppError::InsufficientBalance);
+    }
+
+    // Debit the sender once for all legs
+    sqlx::query!(
+        r#"
+        UPDATE accounts
+        SET pending_balance = pending_balance - $1,
+            nonce = nonce + 1
+        WHERE address = $2 AND token_id = $3
+        "#,
+        to_numeric(total_debit),
+        from_bytes.as_slice(),
+        to_token_column(req.token_id)
     )
     .execute(&mut *tx)
     .await
     .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
+    if let Some(treasury) = treasury {
+        credit_account(&mut tx, &treasury, req.token_id, req.fee).await?;
+    }
+
+    // One record per leg; the batch fee is recorded on the first leg
+    let mut transactions = Vec::with_capacity(legs.len());
+    for (leg_index, (to_bytes, amount)) in legs.iter().enumerate() {
+        credit_account(&mut tx, to_bytes, req.token_id, *amount).await?;
+
+        let tx_id = payload.leg_tx_id(leg_index as u32);
+        let (fee, fee_to) = if leg_index == 0 { (req.fee, treasury) } else { (Amount::ZERO, None) };
+
+        let record = sqlx::query!(
+            r#"
+            INSERT INTO transactions (tx_id, kind, token_id, from_addr, to_addr, amount, fee, fee_to, nonce, signature, timestamp, status, group_id, leg_index)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, NOW(), $11::TEXT::transaction_status, $12, $13)
+            RETURNING timestamp
+            "#,
+            tx_id,
+            TransactionKind::Transfer.to_string(),
+            to_token_column(req.token_id),
+            from_bytes.as_slice(),
+            to_bytes.as_slice(),
+            to_numeric(*amount),
+            to_numeric(fee),
+            fee_to.as_ref().map(|fee_to| fee_to.as_slice()),
+            req.nonce,
+            signature_bytes.as_slice(),
+            TransactionStatus::Sequenced.to_string(),
+            group_id,
+            leg_index as i32
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        transactions.push(Transaction {
+            tx_id,
+            kind: TransactionKind::Transfer,
+            token_id: req.token_id,
+            from: Some(from_bytes),
+            to: Some(*to_bytes),
+            spender: None,
+            amount: *amount,
+            fee,
+            nonce: req.nonce,
+            signature: signature_bytes,
+            timestamp: record.timestamp,
+            status: TransactionStatus::Sequenced,
+            redemption_ref: None,
+            group_id: Some(group_id.clone()),
+            valid_until: None,
+        });
+    }
+
+    // Transfers queued behind this batch's nonce can now be applied
+    let drained =
+        drain_queued_transfers(&mut tx, &state, &from_bytes, req.token_id, req.nonce + 1).await?;
+
     // Commit transaction
     tx.commit()
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-    Ok(Json(TransactionResponse {
-        tx_id,
-        status: TransactionStatus::Pending.to_string(),
+    let tx_ids = transactions.iter().map(|t| t.tx_id.clone()).collect();
+    for transaction in transactions.into_iter().chain(drained) {
+        broadcast_preconfirmed(&state, transaction);
+    }
+
+    Ok(Json(BatchTransferResponse {
+        group_id,
+        tx_ids,
+        status: TransactionStatus::Sequenced.to_string(),
     }))
 }
+
+pub async fn mint(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<MintRequest>,
+) -> Result<Json<TransactionResponse>, AppError> {
+    // Validate amount
+    if req.amount.is_zero() {
+        return Err(AppError::InvalidInput("Mint amount must be positive".into()));
+    }
+
+    // Each token is minted only by its own issuer
+    state.token(req.token_id)?;
+    let issuer_key = state.issuer_key(req.token_id).ok_or_else(|| {
+        AppError::InvalidInput(format!(
+            "Minting is disabled: no issuer key configured for token {}",
+            req.token_id
+        ))
+    })?;
+    let issuer_bytes = issuer_key.to_bytes();
+    let to_bytes = decode_address(&req.to, "to")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+
+    // Verify issuer signature
+    let payload = SignablePayload::mint(state.chain_id, to_bytes, req.amount, req.nonce)
+        .with_token_id(req.token_id);
+    verify_payload(&payload, &issuer_bytes, &signature_bytes)?;
+
+    // Resubmitting an accepted mint returns the original record
+    let tx_id = payload.tx_id();
+    if let Some(existing) = existing_transaction(&state.db, &tx_id).await? {
+        return Ok(Json(existing));
+    }
+
+    // Start a transaction for atomicity
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Lock the token's mint nonce
+    sqlx::query!(
+        r#"
+        INSERT INTO tokens (token_id)
+        VALUES ($1)
+        ON CONFLICT (token_id) DO NOTHING
+        "#,
+        to_token_column(req.token_id)
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let token = sqlx::query!(
+        r#"
+        SELECT mint_nonce
+        FROM tokens
+        WHERE token_id = $1
+        FOR UPDATE
+        "#,
+        to_token_column(req.token_id)
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Reject replayed or out-of-order mints
+    if token.mint_nonce != req.nonce {
+        if let Some(existing) = existing_transaction(&mut *tx, &tx_id).await? {
+            return Ok(Json(existing));
+        }
+        return Err(AppError::InvalidNonce);
+    }
+
+    // Advance the mint nonce and account for the new supply
+    sqlx::query!(
+        r#"
+        UPDATE tokens
+        SET mint_nonce = mint_nonce + 1,
+            minted = minted + $2
+        WHERE token_id = $1
+        "#,
+        to_token_column(req.token_id),
+        to_numeric(req.amount)
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Credit the receiver
+    credit_account(&mut tx, &to_bytes, req.token_id, req.amount).await?;
+
+    // Mints are recorded without a sender
+    let record = sqlx::query!(
+        r#"
+        INSERT INTO transactions (tx_id, kind, token_id, from_addr, to_addr, amount, fee, nonce, signature, timestamp, status)
+        VALUES ($1, $2, $3, NULL, $4, $5, 0, $6, $7, NOW(), $8::TEXT::transaction_status)
+        RETURNING timestamp
+        "#,
+        tx_id,
+        TransactionKind::Mint.to_string(),
+        to_token_column(req.token_id),
+        to_bytes.as_slice(),
+        to_numeric(req.amount),
+        req.nonce,
+        signature_bytes.as_slice(),
+        TransactionStatus::Sequenced.to_string()
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Commit transaction
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    broadcast_preconfirmed(
+        &state,
+        Transaction {
+            tx_id: tx_id.clone(),
+            kind: TransactionKind::Mint,
+            token_id: req.token_id,
+            from: None,
+            to: Some(to_bytes),
+            spender: None,
+            amount: req.amount,
+            fee: Amount::ZERO,
+            nonce: req.nonce,
+            signature: signature_bytes,
+            timestamp: record.timestamp,
+            status: TransactionStatus::Sequenced,
+            redemption_ref: None,
+            group_id: None,
+            valid_until: None,
+        },
+    );
+
+    Ok(Json(TransactionResponse {
+        tx_id,
+        status: TransactionStatus::Sequenced.to_string(),
+    }))
+}
+
+pub async fn burn(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<BurnRequest>,
+) -> Result<Json<TransactionResponse>, AppError> {
+    // Validate amount
+    if req.amount.is_zero() {
+        return Err(AppError::InvalidInput("Burn amount must be positive".into()));
+    }
+
+    state.token(req.token_id)?;
+    let from_bytes = decode_address(&req.from, "from")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+
+    // Verify signature
+    let payload = SignablePayload::burn(
+        state.chain_id,
+        from_bytes,
+        req.amount,
+        req.nonce,
+        req.redemption_ref.clone(),
+    )
+    .with_token_id(req.token_id);
+    verify_account_signature(&state.db, &payload, &from_bytes, &signature_bytes).await?;
+
+    // Resubmitting an accepted burn returns the original record
+    let tx_id = payload.tx_id();
+    if let Some(existing) = existing_transaction(&state.db, &tx_id).await? {
+        return Ok(Json(existing));
+    }
+
+    // Start a transaction for atomicity
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let holder = sqlx::query!(
+        r#"
+        SELECT pending_balance, nonce
+        FROM accounts
+        WHERE address = $1 AND token_id = $2
+        FOR UPDATE
+        "#,
+        from_bytes.as_slice(),
+        to_token_column(req.token_id)
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound("Account not found".into()))?;
+
+    // Burns share the account's nonce sequence with transfers
+    if holder.nonce != req.nonce {
+        if let Some(existing) = existing_transaction(&mut *tx, &tx_id).await? {
+            return Ok(Json(existing));
+        }
+        return Err(AppError::InvalidNonce);
+    }
+
+    check_not_frozen(&mut *tx, req.token_id, &[from_bytes]).await?;
+    // A redemption pays out off the ledger, so it counts like any other spend
+    check_spending_limits(&state, &mut tx, &from_bytes, req.token_id, &[req.amount]).await?;
+
+    // Check sufficient balance
+    if from_numeric(holder.pending_balance)? < req.amount {
+        return Err(AppError::InsufficientBalance);
+    }
+
+    // Destroy the tokens
+    sqlx::query!(
+        r#"
+        UPDATE accounts
+        SET pending_balance = pending_balance - $1,
+            nonce = nonce + 1
+        WHERE address = $2 AND token_id = $3
+        "#,
+        to_numeric(req.amount),
+        from_bytes.as_slice(),
+        to_token_column(req.token_id)
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO tokens (token_id, burned)
+        VALUES ($1, $2)
+        ON CONFLICT (token_id) DO UPDATE
+        SET burned = tokens.burned + $2
+        "#,
+        to_token_column(req.token_id),
+        to_numeric(req.amount)
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Burns are recorded without a receiver
+    let record = sqlx::query!(
+        r#"
+        INSERT INTO transactions (tx_id, kind, token_id, from_addr, to_addr, amount, fee, nonce, signature, timestamp, status, redemption_ref)
+        VALUES ($1, $2, $3, $4, NULL, $5, 0, $6, $7, NOW(), $8::TEXT::transaction_status, $9)
+        RETURNING timestamp
+        "#,
+        tx_id,
+        TransactionKind::Burn.to_string(),
+        to_token_column(req.token_id),
+        from_bytes.as_slice(),
+        to_numeric(req.amount),
+        req.nonce,
+        signature_bytes.as_slice(),
+        TransactionStatus::Sequenced.to_string(),
+        req.redemption_ref
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Transfers queued behind this burn's nonce can now be applied
+    let drained =
+        drain_queued_transfers(&mut tx, &state, &from_bytes, req.token_id, req.nonce + 1).await?;
+
+    // Commit transaction
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    broadcast_preconfirmed(
+        &state,
+        Transaction {
+            tx_id: tx_id.clone(),
+            kind: TransactionKind::Burn,
+            token_id: req.token_id,
+            from: Some(from_bytes),
+            to: None,
+            spender: None,
+            amount: req.amount,
+            fee: Amount::ZERO,
+            nonce: req.nonce,
+            signature: signature_bytes,
+            timestamp: record.timestamp,
+            status: TransactionStatus::Sequenced,
+            redemption_ref: req.redemption_ref,
+            group_id: None,
+            valid_until: None,
+        },
+    );
+    for transaction in drained {
+        broadcast_preconfirmed(&state, transaction);
+    }
+
+    Ok(Json(TransactionResponse {
+        tx_id,
+        status: TransactionStatus::Sequenced.to_string(),
+    }))
+}
+
+/// Lets a spender move up to `limit` of the owner's tokens with
+/// [`transfer_from`], replacing any earlier allowance; a zero limit revokes
+/// it. Approvals use the owner's account nonce.
+pub async fn approve(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ApproveRequest>,
+) -> Result<Json<Allowance>, AppError> {
+    state.token(req.token_id)?;
+    let owner_bytes = decode_address(&req.owner, "owner")?;
+    let spender_bytes = decode_address(&req.spender, "spender")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+
+    if owner_bytes == spender_bytes {
+        return Err(AppError::InvalidInput("Owner can't approve itself as spender".into()));
+    }
+
+    let expires_at = req
+        .expiry
+        .map(|secs| {
+            DateTime::from_timestamp(secs, 0)
+                .ok_or_else(|| AppError::InvalidInput("Invalid expiry timestamp".into()))
+        })
+        .transpose()?;
+    if expires_at.is_some_and(|expires_at| expires_at < Utc::now()) {
+        return Err(AppError::AllowanceExpired);
+    }
+
+    // Verify owner signature
+    let payload = SignablePayload::approve(
+        state.chain_id,
+        owner_bytes,
+        spender_bytes,
+        req.limit,
+        req.expiry,
+        req.nonce,
+    )
+    .with_token_id(req.token_id);
+    verify_account_signature(&state.db, &payload, &owner_bytes, &signature_bytes).await?;
+    let approval_id = payload.tx_id();
+
+    // Start a transaction for atomicity
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let owner = sqlx::query!(
+        r#"
+        SELECT nonce
+        FROM accounts
+        WHERE address = $1 AND token_id = $2
+        FOR UPDATE
+        "#,
+        owner_bytes.as_slice(),
+        to_token_column(req.token_id)
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound("Owner account not found".into()))?;
+
+    if owner.nonce != req.nonce {
+        // Resubmitting the approval in force returns the allowance
+        let current = sqlx::query!(
+            r#"
+            SELECT remaining, expires_at, nonce
+            FROM allowances
+            WHERE owner = $1 AND spender = $2 AND token_id = $3 AND approval_id = $4
+            "#,
+            owner_bytes.as_slice(),
+            spender_bytes.as_slice(),
+            to_token_column(req.token_id),
+            approval_id
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        return match current {
+            Some(row) => Ok(Json(Allowance {
+                owner: owner_bytes,
+                spender: spender_bytes,
+                token_id: req.token_id,
+                remaining: from_numeric(row.remaining)?,
+                expires_at: row.expires_at,
+                nonce: row.nonce,
+            })),
+            None => Err(AppError::InvalidNonce),
+        };
+    }
+
+    sqlx::query!(
+        "UPDATE accounts SET nonce = nonce + 1 WHERE address = $1 AND token_id = $2",
+        owner_bytes.as_slice(),
+        to_token_column(req.token_id)
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // The spender's nonce carries over, so old transfer signatures stay spent
+    let allowance = sqlx::query!(
+        r#"
+        INSERT INTO allowances (owner, spender, token_id, remaining, expires_at, approval_id)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (owner, spender, token_id) DO UPDATE
+        SET remaining = $4,
+            expires_at = $5,
+            approval_id = $6,
+            updated_at = NOW()
+        RETURNING nonce
+        "#,
+        owner_bytes.as_slice(),
+        spender_bytes.as_slice(),
+        to_token_column(req.token_id),
+        to_numeric(req.limit),
+        expires_at,
+        approval_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Transfers queued behind this approval's nonce can now be applied
+    let drained =
+        drain_queued_transfers(&mut tx, &state, &owner_bytes, req.token_id, req.nonce + 1).await?;
+
+    // Commit transaction
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let allowance = Allowance {
+        owner: owner_bytes,
+        spender: spender_bytes,
+        token_id: req.token_id,
+        remaining: req.limit,
+        expires_at,
+        nonce: allowance.nonce,
+    };
+    broadcast_allowance(&state, allowance.clone());
+    for transaction in drained {
+        broadcast_preconfirmed(&state, transaction);
+    }
+
+    Ok(Json(allowance))
+}
+
+/// Transfer out of the owner's allowance, signed by the spender. Amount and
+/// fee both count against the allowance and come out of the owner's balance.
+pub async fn transfer_from(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<TransferFromRequest>,
+) -> Result<Json<TransactionResponse>, AppError> {
+    let token = state.token(req.token_id)?;
+    let amount = req.amount.resolve(token)?;
+    let fee = req.fee.resolve(token)?;
+
+    // Validate amount
+    if amount.is_zero() {
+        return Err(AppError::InvalidInput("Transfer amount must be positive".into()));
+    }
+
+    let spender_bytes = decode_address(&req.spender, "spender")?;
+    let owner_bytes = decode_address(&req.owner, "owner")?;
+    let to_bytes = decode_address(&req.to, "to")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+
+    // Verify spender signature
+    let payload = SignablePayload::transfer_from(
+        state.chain_id,
+        spender_bytes,
+        owner_bytes,
+        to_bytes,
+        amount,
+        fee,
+        req.nonce,
+    )
+    .with_token_id(req.token_id);
+    verify_account_signature(&state.db, &payload, &spender_bytes, &signature_bytes).await?;
+
+    // Resubmitting an accepted transfer returns the original record
+    let tx_id = payload.tx_id();
+    if let Some(existing) = existing_transaction(&state.db, &tx_id).await? {
+        return Ok(Json(existing));
+    }
+
+    let treasury = check_fee(&state, state.fee_policy.required_fee(amount), fee)?;
+    let debit = amount.checked_add(fee).ok_or(AppError::AmountOverflow)?;
+
+    // Start a transaction for atomicity
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // The owner's row lock orders this against the owner's own transfers
+    lock_account(&mut tx, &owner_bytes, req.token_id).await?;
+    check_not_frozen(&mut *tx, req.token_id, &[owner_bytes, spender_bytes, to_bytes]).await?;
+
+    let allowance = sqlx::query!(
+        r#"
+        SELECT remaining, expires_at, nonce, approval_id
+        FROM allowances
+        WHERE owner = $1 AND spender = $2 AND token_id = $3
+        FOR UPDATE
+        "#,
+        owner_bytes.as_slice(),
+        spender_bytes.as_slice(),
+        to_token_column(req.token_id)
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or(AppError::InsufficientAllowance)?;
+
+    // Spender signatures are single use
+    if allowance.nonce != req.nonce {
+        // A concurrent submission of the same transfer may have won the lock
+        if let Some(existing) = existing_transaction(&mut *tx, &tx_id).await? {
+            return Ok(Json(existing));
+        }
+        return Err(AppError::InvalidNonce);
+    }
+
+    if allowance.expires_at.is_some_and(|expires_at| expires_at < Utc::now()) {
+        return Err(AppError::AllowanceExpired);
+    }
+    if from_numeric(allowance.remaining)? < debit {
+        return Err(AppError::InsufficientAllowance);
+    }
+    // The owner's limits apply to what spenders move on their behalf
+    check_spending_limits(&state, &mut tx, &owner_bytes, req.token_id, &[amount]).await?;
+
+    // Move the funds
+    debit_account(&mut tx, &owner_bytes, req.token_id, debit).await?;
+    credit_account(&mut tx, &to_bytes, req.token_id, amount).await?;
+    if let Some(treasury) = treasury {
+        credit_account(&mut tx, &treasury, req.token_id, fee).await?;
+    }
+
+    let spent = sqlx::query!(
+        r#"
+        UPDATE allowances
+        SET remaining = remaining - $1,
+            nonce = nonce + 1,
+            updated_at = NOW()
+        WHERE owner = $2 AND spender = $3 AND token_id = $4
+        RETURNING remaining, nonce
+        "#,
+        to_numeric(debit),
+        owner_bytes.as_slice(),
+        spender_bytes.as_slice(),
+        to_token_column(req.token_id)
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let updated = Allowance {
+        owner: owner_bytes,
+        spender: spender_bytes,
+        token_id: req.token_id,
+        remaining: from_numeric(spent.remaining)?,
+        expires_at: allowance.expires_at,
+        nonce: spent.nonce,
+    };
+
+    // The owner is recorded as the sender
+    let record = sqlx::query!(
+        r#"
+        INSERT INTO transactions (tx_id, kind, token_id, from_addr, to_addr, spender, approval_id, amount, fee, fee_to, nonce, signature, timestamp, status)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, NOW(), $13::TEXT::transaction_status)
+        RETURNING timestamp
+        "#,
+        tx_id,
+        TransactionKind::TransferFrom.to_string(),
+        to_token_column(req.token_id),
+        owner_bytes.as_slice(),
+        to_bytes.as_slice(),
+        spender_bytes.as_slice(),
+        allowance.approval_id,
+        to_numeric(amount),
+        to_numeric(fee),
+        treasury.as_ref().map(|treasury| treasury.as_slice()),
+        req.nonce,
+        signature_bytes.as_slice(),
+        TransactionStatus::Sequenced.to_string()
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Commit transaction
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    broadcast_preconfirmed(
+        &state,
+        Transaction {
+            tx_id: tx_id.clone(),
+            kind: TransactionKind::TransferFrom,
+            token_id: req.token_id,
+            from: Some(owner_bytes),
+            to: Some(to_bytes),
+            spender: Some(spender_bytes),
+            amount,
+            fee,
+            nonce: req.nonce,
+            signature: signature_bytes,
+            timestamp: record.timestamp,
+            status: TransactionStatus::Sequenced,
+            redemption_ref: None,
+            group_id: None,
+            valid_until: None,
+        },
+    );
+    broadcast_allowance(&state, updated);
+
+    Ok(Json(TransactionResponse {
+        tx_id,
+        status: TransactionStatus::Sequenced.to_string(),
+    }))
+}
+
+/// Moves funds from the sender's balance into a hash-time-locked escrow for
+/// the recipient. The lock's transaction ID is the escrow ID.
+pub async fn escrow_lock(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<EscrowLockRequest>,
+) -> Result<Json<TransactionResponse>, AppError> {
+    // Validate amount
+    if req.amount.is_zero() {
+        return Err(AppError::InvalidInput("Escrow amount must be positive".into()));
+    }
+
+    state.token(req.token_id)?;
+    let from_bytes = decode_address(&req.from, "from")?;
+    let to_bytes = decode_address(&req.to, "to")?;
+    let hashlock = decode_hash(&req.hashlock, "hashlock")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+
+    let timeout = DateTime::from_timestamp(req.timeout, 0)
+        .ok_or_else(|| AppError::InvalidInput("Invalid timeout timestamp".into()))?;
+    if timeout <= Utc::now() {
+        return Err(AppError::InvalidInput("Escrow timeout must be in the future".into()));
+    }
+
+    // Verify signature
+    let payload = SignablePayload::escrow_lock(
+        state.chain_id,
+        from_bytes,
+        to_bytes,
+        req.amount,
+        req.fee,
+        hashlock,
+        req.timeout,
+        req.nonce,
+    )
+    .with_token_id(req.token_id);
+    verify_account_signature(&state.db, &payload, &from_bytes, &signature_bytes).await?;
+
+    // Resubmitting an accepted lock returns the original record
+    let escrow_id = payload.tx_id();
+    if let Some(existing) = existing_transaction(&state.db, &escrow_id).await? {
+        return Ok(Json(existing));
+    }
+
+    let treasury = check_fee(&state, state.fee_policy.required_fee(req.amount), req.fee)?;
+    let debit = req.amount.checked_add(req.fee).ok_or(AppError::AmountOverflow)?;
+
+    // Start a transaction for atomicity
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let sender = sqlx::query!(
+        r#"
+        SELECT pending_balance, nonce
+        FROM accounts
+        WHERE address = $1 AND token_id = $2
+        FOR UPDATE
+        "#,
+        from_bytes.as_slice(),
+        to_token_column(req.token_id)
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound("Sender account not found".into()))?;
+
+    // Locks share the account's nonce sequence with transfers
+    if sender.nonce != req.nonce {
+        if let Some(existing) = existing_transaction(&mut *tx, &escrow_id).await? {
+            return Ok(Json(existing));
+        }
+        return Err(AppError::InvalidNonce);
+    }
+
+    check_not_frozen(&mut *tx, req.token_id, &[from_bytes, to_bytes]).await?;
+    check_spending_limits(&state, &mut tx, &from_bytes, req.token_id, &[req.amount]).await?;
+
+    // Check sufficient balance
+    if from_numeric(sender.pending_balance)? < debit {
+        return Err(AppError::InsufficientBalance);
+    }
+
+    // The locked amount leaves the sender's balance; the fee goes to the treasury now
+    sqlx::query!(
+        r#"
+        UPDATE accounts
+        SET pending_balance = pending_balance - $1,
+            nonce = nonce + 1
+        WHERE address = $2 AND token_id = $3
+        "#,
+        to_numeric(debit),
+        from_bytes.as_slice(),
+        to_token_column(req.token_id)
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    if let Some(treasury) = treasury {
+        credit_account(&mut tx, &treasury, req.token_id, req.fee).await?;
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO escrows (escrow_id, token_id, sender, recipient, amount, fee, hashlock, timeout, nonce, status)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#,
+        escrow_id,
+        to_token_column(req.token_id),
+        from_bytes.as_slice(),
+        to_bytes.as_slice(),
+        to_numeric(req.amount),
+        to_numeric(req.fee),
+        hashlock.as_slice(),
+        timeout,
+        req.nonce,
+        EscrowStatus::Locked.to_string()
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Locks are recorded without a receiver; the funds are held by the escrow
+    let record = sqlx::query!(
+        r#"
+        INSERT INTO transactions (tx_id, kind, token_id, from_addr, to_addr, amount, fee, fee_to, nonce, signature, timestamp, status, escrow_id)
+        VALUES ($1, $2, $3, $4, NULL, $5, $6, $7, $8, $9, NOW(), $10::TEXT::transaction_status, $1)
+        RETURNING timestamp
+        "#,
+        escrow_id,
+        TransactionKind::EscrowLock.to_string(),
+        to_token_column(req.token_id),
+        from_bytes.as_slice(),
+        to_numeric(req.amount),
+        to_numeric(req.fee),
+        treasury.as_ref().map(|treasury| treasury.as_slice()),
+        req.nonce,
+        signature_bytes.as_slice(),
+        TransactionStatus::Sequenced.to_string()
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Transfers queued behind this lock's nonce can now be applied
+    let drained =
+        drain_queued_transfers(&mut tx, &state, &from_bytes, req.token_id, req.nonce + 1).await?;
+
+    // Commit transaction
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    broadcast_preconfirmed(
+        &state,
+        Transaction {
+            tx_id: escrow_id.clone(),
+            kind: TransactionKind::EscrowLock,
+            token_id: req.token_id,
+            from: Some(from_bytes),
+            to: None,
+            spender: None,
+            amount: req.amount,
+            fee: req.fee,
+            nonce: req.nonce,
+            signature: signature_bytes,
+            timestamp: record.timestamp,
+            status: TransactionStatus::Sequenced,
+            redemption_ref: None,
+            group_id: None,
+            valid_until: None,
+        },
+    );
+    broadcast_escrow(
+        &state,
+        Escrow {
+            escrow_id: escrow_id.clone(),
+            token_id: req.token_id,
+            sender: from_bytes,
+            recipient: to_bytes,
+            amount: req.amount,
+            hashlock,
+            timeout,
+            status: EscrowStatus::Locked,
+            preimage: None,
+        },
+    );
+    for transaction in drained {
+        broadcast_preconfirmed(&state, transaction);
+    }
+
+    Ok(Json(TransactionResponse {
+        tx_id: escrow_id,
+        status: TransactionStatus::Sequenced.to_string(),
+    }))
+}
+
+/// Releases a locked escrow to its recipient, who reveals the preimage of the
+/// hashlock before the timeout.
+pub async fn escrow_claim(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<EscrowClaimRequest>,
+) -> Result<Json<TransactionResponse>, AppError> {
+    let escrow = decode_hash(&req.escrow_id, "escrow_id")?;
+    let preimage = decode_hash(&req.preimage, "preimage")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+
+    settle_escrow(&state, escrow, EscrowStatus::Claimed, Some(preimage), signature_bytes)
+        .await
+        .map(Json)
+}
+
+/// Returns a locked escrow to its sender once the timeout has passed.
+pub async fn escrow_refund(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<EscrowRefundRequest>,
+) -> Result<Json<TransactionResponse>, AppError> {
+    let escrow = decode_hash(&req.escrow_id, "escrow_id")?;
+    let signature_bytes = decode_signature(&req.signature)?;
+
+    settle_escrow(&state, escrow, EscrowStatus::Refunded, None, signature_bytes)
+        .await
+        .map(Json)
+}
+
+pub async fn get_escrow(
+    State(state): State<Arc<AppState>>,
+    Path(escrow_id): Path<String>,
+) -> Result<Json<Escrow>, AppError> {
+    state
+        .get_escrow(&escrow_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound("Escrow not found".into()))
+}
+
+/// Claims (`outcome` [`EscrowStatus::Claimed`], with the preimage) or refunds
+/// a locked escrow, crediting the recipient or the sender respectively.
+async fn settle_escrow(
+    state: &AppState,
+    escrow: [u8; 32],
+    outcome: EscrowStatus,
+    preimage: Option<[u8; 32]>,
+    signature_bytes: [u8; 64],
+) -> Result<TransactionResponse, AppError> {
+    let escrow_id = hex::encode(escrow);
+
+    // Start a transaction for atomicity
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let locked = sqlx::query!(
+        r#"
+        SELECT token_id, sender, recipient, amount, hashlock, timeout, status, NOW() >= timeout AS "timed_out!"
+        FROM escrows
+        WHERE escrow_id = $1
+        FOR UPDATE
+        "#,
+        escrow_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound("Escrow not found".into()))?;
+
+    let corrupt = || AppError::DatabaseError(format!("Corrupt escrow {}", escrow_id));
+    let token_id = from_token_column(locked.token_id)?;
+    let sender: [u8; 32] = locked.sender.try_into().map_err(|_| corrupt())?;
+    let recipient: [u8; 32] = locked.recipient.try_into().map_err(|_| corrupt())?;
+    let hashlock: [u8; 32] = locked.hashlock.try_into().map_err(|_| corrupt())?;
+
+    // The recipient signs claims and the sender signs refunds
+    let (payload, signer, kind) = match preimage {
+        Some(preimage) => (
+            SignablePayload::escrow_claim(state.chain_id, escrow, preimage),
+            recipient,
+            TransactionKind::EscrowClaim,
+        ),
+        None => (
+            SignablePayload::escrow_refund(state.chain_id, escrow),
+            sender,
+            TransactionKind::EscrowRefund,
+        ),
+    };
+    let payload = payload.with_token_id(token_id);
+    verify_account_signature(&mut *tx, &payload, &signer, &signature_bytes).await?;
+
+    // Resubmitting an accepted claim or refund returns the original record
+    let tx_id = payload.tx_id();
+    if let Some(existing) = existing_transaction(&mut *tx, &tx_id).await? {
+        return Ok(existing);
+    }
+
+    if locked.status != EscrowStatus::Locked.to_string() {
+        return Err(AppError::InvalidInput(format!(
+            "Escrow is already {}",
+            locked.status
+        )));
+    }
+    if let Some(preimage) = preimage {
+        if locked.timed_out {
+            return Err(AppError::InvalidInput(
+                "Escrow has timed out and can only be refunded".into(),
+            ));
+        }
+        if Escrow::hashlock(&preimage) != hashlock {
+            return Err(AppError::InvalidPreimage);
+        }
+    } else if !locked.timed_out {
+        return Err(AppError::InvalidInput(
+            "Escrow can't be refunded before its timeout".into(),
+        ));
+    }
+
+    let amount = from_numeric(locked.amount)?;
+    let payee = if preimage.is_some() { recipient } else { sender };
+    check_not_frozen(&mut *tx, token_id, &[payee]).await?;
+    credit_account(&mut tx, &payee, token_id, amount).await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE escrows
+        SET status = $1,
+            preimage = $2,
+            settled_at = NOW()
+        WHERE escrow_id = $3
+        "#,
+        outcome.to_string(),
+        preimage.as_ref().map(|preimage| preimage.as_slice()),
+        escrow_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Releases are recorded without a sender; the funds come out of the escrow
+    let record = sqlx::query!(
+        r#"
+        INSERT INTO transactions (tx_id, kind, token_id, from_addr, to_addr, amount, fee, nonce, signature, timestamp, status, escrow_id)
+        VALUES ($1, $2, $3, NULL, $4, $5, 0, 0, $6, NOW(), $7::TEXT::transaction_status, $8)
+        RETURNING timestamp
+        "#,
+        tx_id,
+        kind.to_string(),
+        to_token_column(token_id),
+        payee.as_slice(),
+        to_numeric(amount),
+        signature_bytes.as_slice(),
+        TransactionStatus::Sequenced.to_string(),
+        escrow_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Commit transaction
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    broadcast_preconfirmed(
+        state,
+        Transaction {
+            tx_id: tx_id.clone(),
+            kind,
+            token_id,
+            from: None,
+            to: Some(payee),
+            spender: None,
+            amount,
+            fee: Amount::ZERO,
+            nonce: 0,
+            signature: signature_bytes,
+            timestamp: record.timestamp,
+            status: TransactionStatus::Sequenced,
+            redemption_ref: None,
+            group_id: None,
+            valid_until: None,
+        },
+    );
+    broadcast_escrow(
+        state,
+        Escrow {
+            escrow_id,
+            token_id,
+            sender,
+            recipient,
+            amount,
+            hashlock,
+            timeout: locked.timeout,
+            status: outcome,
+            preimage,
+        },
+    );
+
+    Ok(TransactionResponse {
+        tx_id,
+        status: TransactionStatus::Sequenced.to_string(),
+    })
+}
+
+pub async fn fee_policy(State(state): State<Arc<AppState>>) -> Json<FeePolicyResponse> {
+    Json(FeePolicyResponse {
+        policy: state.fee_policy,
+        treasury: state.treasury.map(hex::encode),
+    })
+}
+
+/// Notifies WebSocket subscribers of a newly accepted transaction.
+pub(crate) fn broadcast_preconfirmed(state: &AppState, transaction: Transaction) {
+    // Sending only fails when nobody is subscribed
+    let _ = state
+        .ws_tx
+        .send(WebSocketMessage::TransactionPreconfirmed(transaction));
+}
+
+/// Decodes a transfer request and checks its signature against the sender's
+/// current key.
+async fn verify_transfer_request(state: &AppState, req: &TransferRequest) -> Result<TransferEntry, AppError> {
+    let (mut entry, payload) = decode_transfer_request(state, req)?;
+    entry.signature = decode_signature(&req.signature)?;
+    verify_account_signature(&state.db, &payload, &entry.from, &entry.signature).await?;
+    Ok(entry)
+}
+
+/// Decodes a transfer request, leaving its signature unchecked and zeroed,
+/// along with the payload it is signed over.
+fn decode_transfer_request(
+    state: &AppState,
+    req: &TransferRequest,
+) -> Result<(TransferEntry, SignablePayload), AppError> {
+    // Token amounts are read with the decimals of the token being moved
+    let token = state.token(req.token_id)?;
+    let amount = req.amount.resolve(token)?;
+    let fee = req.fee.resolve(token)?;
+
+    // Validate amount
+    if amount.is_zero() {
+        return Err(AppError::InvalidInput("Transfer amount must be positive".into()));
+    }
+
+    // Tokens can only be created through the issuer-signed mint endpoint
+    let from = req.from.as_deref().ok_or_else(|| {
+        AppError::InvalidInput("Sender address is required; use /transaction/mint to issue tokens".into())
+    })?;
+    let from_bytes = decode_address(from, "from")?;
+    let to_bytes = decode_address(&req.to, "to")?;
+
+    let payload = SignablePayload::transfer(
+        state.chain_id,
+        from_bytes,
+        to_bytes,
+        amount,
+        fee,
+        req.nonce,
+    )
+    .with_valid_until(req.valid_until)
+    .with_token_id(req.token_id);
+
+    let valid_until = req
+        .valid_until
+        .map(|secs| {
+            DateTime::from_timestamp(secs, 0)
+                .ok_or_else(|| AppError::InvalidInput("Invalid valid_until timestamp".into()))
+        })
+        .transpose()?;
+
+    let entry = TransferEntry {
+        tx_id: payload.tx_id(),
+        token_id: req.token_id,
+        from: from_bytes,
+        to: to_bytes,
+        amount,
+        fee,
+        nonce: req.nonce,
+        signature: [0; 64],
+        valid_until,
+        order_id: None,
+        multisig_version: None,
+    };
+    Ok((entry, payload))
+}
+
+/// Checks a new transfer against its signed expiry and the fee policy.
+fn check_transfer_admission(state: &AppState, entry: &TransferEntry) -> Result<(), AppError> {
+    // Reject transfers submitted after their signed expiry
+    if entry.valid_until.is_some_and(|valid_until| valid_until < Utc::now()) {
+        return Err(AppError::TransactionExpired);
+    }
+
+    // Check the fee against the configured policy
+    check_fee(state, state.fee_policy.required_fee(entry.amount), entry.fee)?;
+    Ok(())
+}
+
+fn broadcast_allowance(state: &AppState, allowance: Allowance) {
+    // Sending only fails when nobody is subscribed
+    let _ = state.ws_tx.send(WebSocketMessage::AllowanceUpdated(allowance));
+}
+
+fn broadcast_escrow(state: &AppState, escrow: Escrow) {
+    // Sending only fails when nobody is subscribed
+    let _ = state.ws_tx.send(WebSocketMessage::EscrowUpdated(escrow));
+}
+
+fn broadcast_cancelled(state: &AppState, tx_id: &str, replaced_by: Option<String>) {
+    // Sending only fails when nobody is subscribed
+    let _ = state.ws_tx.send(WebSocketMessage::TransactionCancelled {
+        tx_id: tx_id.to_string(),
+        replaced_by,
+    });
+}
+
+/// Takes the row lock that serializes all nonce-consuming operations of
+/// `address` in `token_id`, and returns the account's nonce.
+pub(crate) async fn lock_account(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    address: &[u8; 32],
+    token_id: TokenId,
+) -> Result<i64, AppError> {
+    let account = sqlx::query!(
+        "SELECT nonce FROM accounts WHERE address = $1 AND token_id = $2 FOR UPDATE",
+        address.as_slice(),
+        to_token_column(token_id)
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound("Sender account not found".into()))?;
+    Ok(account.nonce)
+}
+
+/// An applied transfer that may still be cancelled or replaced.
+struct LiveTransfer {
+    tx_id: String,
+    token_id: TokenId,
+    to: [u8; 32],
+    amount: Amount,
+    fee: Amount,
+    /// Account the fee was credited to
+    fee_to: Option<[u8; 32]>,
+}
+
+/// Finds `from`'s single transfer of `token_id` at `nonce` that is still
+/// sequenced and not yet in a proof batch. Batch transfer legs can't be
+/// cancelled one by one.
+async fn live_transfer(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    from: &[u8; 32],
+    token_id: TokenId,
+    nonce: i64,
+) -> Result<Option<LiveTransfer>, AppError> {
+    let row = sqlx::query!(
+        r#"
+        SELECT tx_id, to_addr, amount, fee, fee_to, status::TEXT as "status!", batch_id
+        FROM transactions
+        WHERE from_addr = $1 AND token_id = $2 AND nonce = $3 AND kind = $4 AND group_id IS NULL
+          AND status <> $5::TEXT::transaction_status
+        FOR UPDATE
+        "#,
+        from.as_slice(),
+        to_token_column(token_id),
+        nonce,
+        TransactionKind::Transfer.to_string(),
+        TransactionStatus::Cancelled.to_string()
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let status = from_status_column(&row.status)?;
+    if !lifecycle::can_transition(status, TransactionStatus::Cancelled) || row.batch_id.is_some() {
+        return Err(AppError::InvalidInput(
+            "Transaction has already been batched and can't be changed".into(),
+        ));
+    }
+
+    Ok(Some(LiveTransfer {
+        tx_id: row.tx_id,
+        token_id,
+        to: row
+            .to_addr
+            .and_then(|addr| addr.try_into().ok())
+            .ok_or_else(|| AppError::DatabaseError("Transfer without receiver".into()))?,
+        amount: from_numeric(row.amount)?,
+        fee: from_numeric(row.fee)?,
+        fee_to: row
+            .fee_to
+            .map(|addr| addr.try_into())
+            .transpose()
+            .map_err(|_| AppError::DatabaseError("Corrupt fee recipient".into()))?,
+    }))
+}
+
+/// Undoes the balance effects of `transfer`. Fails if the receiver has
+/// already spent the funds.
+async fn revert_transfer(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    from: &[u8; 32],
+    transfer: &LiveTransfer,
+) -> Result<(), AppError> {
+    debit_account(tx, &transfer.to, transfer.token_id, transfer.amount)
+        .await
+        .map_err(|e| match e {
+            AppError::InsufficientBalance => {
+                AppError::InvalidInput("Receiver has already spent the transferred funds".into())
+            }
+            e => e,
+        })?;
+
+    if let Some(fee_to) = transfer.fee_to {
+        debit_account(tx, &fee_to, transfer.token_id, transfer.fee).await?;
+    }
+
+    let refund = transfer
+        .amount
+        .checked_add(transfer.fee)
+        .ok_or(AppError::AmountOverflow)?;
+    credit_account(tx, from, transfer.token_id, refund).await
+}
+
+/// Takes `amount` from `address`'s pending balance of `token_id`, failing if
+/// it doesn't cover it.
+pub(crate) async fn debit_account(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    address: &[u8; 32],
+    token_id: TokenId,
+    amount: Amount,
+) -> Result<(), AppError> {
+    let debited = sqlx::query!(
+        r#"
+        UPDATE accounts
+        SET pending_balance = pending_balance - $1
+        WHERE address = $2 AND token_id = $3 AND pending_balance >= $1
+        "#,
+        to_numeric(amount),
+        address.as_slice(),
+        to_token_column(token_id)
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    if debited.rows_affected() == 0 {
+        return Err(AppError::InsufficientBalance);
+    }
+    Ok(())
+}
+
+/// A validated, signed transfer waiting to be applied.
+pub(crate) struct TransferEntry {
+    pub(crate) tx_id: String,
+    pub(crate) token_id: TokenId,
+    pub(crate) from: [u8; 32],
+    pub(crate) to: [u8; 32],
+    pub(crate) amount: Amount,
+    pub(crate) fee: Amount,
+    pub(crate) nonce: i64,
+    pub(crate) signature: [u8; 64],
+    pub(crate) valid_until: Option<DateTime<Utc>>,
+    /// Standing order the transfer executes; its signature covers the order
+    pub(crate) order_id: Option<String>,
+    /// Version of the multisig policy that approved the transfer; its
+    /// signature is the first of the members'
+    pub(crate) multisig_version: Option<i64>,
+}
+
+impl TransferEntry {
+    fn kind(&self) -> TransactionKind {
+        match (&self.order_id, self.multisig_version) {
+            (Some(_), _) => TransactionKind::ScheduledTransfer,
+            (None, Some(_)) => TransactionKind::MultisigTransfer,
+            (None, None) => TransactionKind::Transfer,
+        }
+    }
+}
+
+/// Moves the funds of `entry` and records it. The caller must hold the
+/// sender's row lock and have checked that `entry.nonce` is next, or is the
+/// nonce of a transfer being replaced.
+async fn apply_transfer(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    state: &AppState,
+    entry: &TransferEntry,
+) -> Result<Transaction, AppError> {
+    let debit = entry
+        .amount
+        .checked_add(entry.fee)
+        .ok_or(AppError::AmountOverflow)?;
+    check_not_frozen(&mut **tx, entry.token_id, &[entry.from, entry.to]).await?;
+    check_spending_limits(state, tx, &entry.from, entry.token_id, &[entry.amount]).await?;
+
+    // Update sender's balance and nonce if the balance covers the transfer
+    let debited = sqlx::query!(
+        r#"
+        UPDATE accounts
+        SET pending_balance = pending_balance - $1,
+            nonce = GREATEST(nonce, $3::BIGINT + 1)
+        WHERE address = $2 AND token_id = $4 AND pending_balance >= $1
+        "#,
+        to_numeric(debit),
+        entry.from.as_slice(),
+        entry.nonce,
+        to_token_column(entry.token_id)
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if debited.rows_affected() == 0 {
+        return Err(AppError::InsufficientBalance);
+    }
+
+    // Update receiver's balance
+    credit_account(tx, &entry.to, entry.token_id, entry.amount).await?;
+
+    // Credit the fee to the treasury
+    let fee_to = state.treasury.filter(|_| !entry.fee.is_zero());
+    if let Some(treasury) = fee_to {
+        credit_account(tx, &treasury, entry.token_id, entry.fee).await?;
+    }
+
+    // Create transaction record
+    let record = sqlx::query!(
+        r#"
+        INSERT INTO transactions (tx_id, kind, token_id, from_addr, to_addr, amount, fee, fee_to, nonce, signature, timestamp, status, valid_until, order_id, multisig_version)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, NOW(), $11::TEXT::transaction_status, $12, $13, $14)
+        RETURNING timestamp
+        "#,
+        entry.tx_id,
+        entry.kind().to_string(),
+        to_token_column(entry.token_id),
+        entry.from.as_slice(),
+        entry.to.as_slice(),
+        to_numeric(entry.amount),
+        to_numeric(entry.fee),
+        fee_to.as_ref().map(|fee_to| fee_to.as_slice()),
+        entry.nonce,
+        entry.signature.as_slice(),
+        TransactionStatus::Sequenced.to_string(),
+        entry.valid_until,
+        entry.order_id,
+        entry.multisig_version
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Transaction {
+        tx_id: entry.tx_id.clone(),
+        kind: entry.kind(),
+        token_id: entry.token_id,
+        from: Some(entry.from),
+        to: Some(entry.to),
+        spender: None,
+        amount: entry.amount,
+        fee: entry.fee,
+        nonce: entry.nonce,
+        signature: entry.signature,
+        timestamp: record.timestamp,
+        status: TransactionStatus::Sequenced,
+        redemption_ref: None,
+        group_id: None,
+        valid_until: entry.valid_until,
+    })
+}
+
+/// Applies `entry`, a transfer its sender authorized ahead of time, at the
+/// sender's next nonce and drains the transfers queued behind it, like
+/// [`transfer`] does for a transfer arriving at the expected nonce. Returns
+/// the applied transactions for the caller to broadcast once committed.
+pub(crate) async fn apply_at_next_nonce(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    state: &AppState,
+    mut entry: TransferEntry,
+) -> Result<Vec<Transaction>, AppError> {
+    check_transfer_admission(state, &entry)?;
+    entry.nonce = lock_account(tx, &entry.from, entry.token_id).await?;
+
+    let transaction = apply_transfer(tx, state, &entry).await?;
+    let drained =
+        drain_queued_transfers(tx, state, &entry.from, entry.token_id, entry.nonce + 1).await?;
+    Ok(std::iter::once(transaction).chain(drained).collect())
+}
+
+/// A transfer waiting in the nonce queue, for [`get_transaction`].
+async fn queued_transaction(state: &AppState, tx_id: &str) -> Result<TransactionLookup, AppError> {
+    let queued = sqlx::query!(
+        r#"
+        SELECT tx_id, token_id, from_addr, to_addr, amount, fee, nonce, signature, queued_at, valid_until
+        FROM queued_transfers
+        WHERE tx_id = $1
+        "#,
+        tx_id
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound("Transaction not found".into()))?;
+
+    let corrupt = || AppError::DatabaseError("Corrupt queued transfer".into());
+    Ok(TransactionLookup {
+        transaction: Transaction {
+            tx_id: queued.tx_id,
+            kind: TransactionKind::Transfer,
+            token_id: from_token_column(queued.token_id)?,
+            from: Some(queued.from_addr.try_into().map_err(|_| corrupt())?),
+            to: Some(queued.to_addr.try_into().map_err(|_| corrupt())?),
+            spender: None,
+            amount: from_numeric(queued.amount)?,
+            fee: from_numeric(queued.fee)?,
+            nonce: queued.nonce,
+            signature: queued.signature.try_into().map_err(|_| corrupt())?,
+            timestamp: queued.queued_at,
+            status: TransactionStatus::Received,
+            redemption_ref: None,
+            group_id: None,
+            valid_until: queued.valid_until,
+        },
+        receipt: TransactionReceipt {
+            status: TransactionStatus::Received,
+            batch_id: None,
+            proven_at: None,
+            finalized_at: None,
+            vkey_hash: None,
+            reason: None,
+        },
+    })
+}
+
+/// Outcome of [`submit_transfer`].
+enum Submission {
+    /// The transfer was already accepted
+    Existing(TransactionResponse),
+    /// The transfer waits for the sender's nonce to catch up
+    Queued,
+    /// The transfer, then the queued transfers it unblocked
+    Applied(Vec<Transaction>),
+}
+
+/// Applies `entry` if its nonce is the sender's next, queues it if the nonce
+/// is ahead, and otherwise finds the transfer already accepted at it.
+async fn submit_transfer(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    state: &AppState,
+    entry: &TransferEntry,
+) -> Result<Submission, AppError> {
+    let nonce = lock_account(tx, &entry.from, entry.token_id).await?;
+
+    // Verify nonce
+    if entry.nonce < nonce {
+        // A concurrent submission of the same transaction may have won the lock
+        if let Some(existing) = existing_transaction(&mut **tx, &entry.tx_id).await? {
+            return Ok(Submission::Existing(existing));
+        }
+        return Err(AppError::InvalidNonce);
+    }
+
+    // Hold transfers that arrive ahead of the sender's nonce
+    if entry.nonce > nonce {
+        if entry.nonce - nonce > state.max_nonce_gap {
+            return Err(AppError::InvalidNonce);
+        }
+        queue_transfer(tx, state, entry).await?;
+        return Ok(Submission::Queued);
+    }
+
+    let transaction = apply_transfer(tx, state, entry).await?;
+
+    // The nonce gap may now be filled for transfers queued behind this one
+    let drained =
+        drain_queued_transfers(tx, state, &entry.from, entry.token_id, entry.nonce + 1).await?;
+    Ok(Submission::Applied(std::iter::once(transaction).chain(drained).collect()))
+}
+
+/// Holds `entry` until the sender's nonce catches up with it. Queued
+/// transfers expire after the configured timeout or their own expiry.
+async fn queue_transfer(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    state: &AppState,
+    entry: &TransferEntry,
+) -> Result<(), AppError> {
+    let queued = sqlx::query!(
+        r#"
+        INSERT INTO queued_transfers (from_addr, token_id, nonce, tx_id, to_addr, amount, fee, signature, valid_until, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, LEAST(NOW() + make_interval(secs => $10), $9))
+        ON CONFLICT (from_addr, token_id, nonce) DO NOTHING
+        "#,
+        entry.from.as_slice(),
+        to_token_column(entry.token_id),
+        entry.nonce,
+        entry.tx_id,
+        entry.to.as_slice(),
+        to_numeric(entry.amount),
+        to_numeric(entry.fee),
+        entry.signature.as_slice(),
+        entry.valid_until,
+        state.queue_timeout.as_secs_f64()
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Another transfer is already waiting on this nonce
+    if queued.rows_affected() == 0 {
+        return Err(AppError::InvalidNonce);
+    }
+    Ok(())
+}
+
+/// Applies transfers of `token_id` queued by `from` starting at `next_nonce`,
+/// until a nonce is missing. A queued transfer that has expired or can't be
+/// paid for is dropped and ends the run, leaving its nonce open.
+pub(crate) async fn drain_queued_transfers(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    state: &AppState,
+    from: &[u8; 32],
+    token_id: TokenId,
+    mut next_nonce: i64,
+) -> Result<Vec<Transaction>, AppError> {
+    let mut applied = Vec::new();
+    loop {
+        let Some(queued) = sqlx::query!(
+            r#"
+            DELETE FROM queued_transfers
+            WHERE from_addr = $1 AND token_id = $2 AND nonce = $3
+            RETURNING tx_id, to_addr, amount, fee, signature, valid_until, expires_at > NOW() AS "live!"
+            "#,
+            from.as_slice(),
+            to_token_column(token_id),
+            next_nonce
+        )
+        .fetch_optional(&mut **tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?
+        else {
+            break;
+        };
+
+        if !queued.live {
+            break;
+        }
+
+        let entry = TransferEntry {
+            tx_id: queued.tx_id,
+            token_id,
+            from: *from,
+            to: queued
+                .to_addr
+                .try_into()
+                .map_err(|_| AppError::DatabaseError("Corrupt queued transfer".into()))?,
+            amount: from_numeric(queued.amount)?,
+            fee: from_numeric(queued.fee)?,
+            nonce: next_nonce,
+            signature: queued
+                .signature
+                .try_into()
+                .map_err(|_| AppError::DatabaseError("Corrupt queued transfer".into()))?,
+            valid_until: queued.valid_until,
+            order_id: None,
+            multisig_version: None,
+        };
+        match apply_transfer(tx, state, &entry).await {
+            Ok(transaction) => applied.push(transaction),
+            Err(
+                AppError::InsufficientBalance
+                | AppError::AccountFrozen
+                | AppError::SpendingLimitExceeded(_),
+            ) => break,
+            Err(e) => return Err(e),
+        }
+        next_nonce += 1;
+    }
+    Ok(applied)
+}
+
+/// Removes queued transfers whose timeout has passed. Returns how many were dropped.
+pub async fn prune_queued_transfers(db: &sqlx::PgPool) -> Result<u64, AppError> {
+    let pruned = sqlx::query!("DELETE FROM queued_transfers WHERE expires_at <= NOW()")
+        .execute(db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(pruned.rows_affected())
+}
+
+/// Looks up a previously accepted transaction so identical resubmissions are
+/// answered with the original record.
+pub(crate) async fn existing_transaction<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    tx_id: &str,
+) -> Result<Option<TransactionResponse>, AppError> {
+    let row = sqlx::query!(
+        r#"
+        SELECT tx_id AS "tx_id!", status::TEXT AS "status!" FROM transactions WHERE tx_id = $1
+        UNION ALL
+        SELECT tx_id, $2 FROM queued_transfers WHERE tx_id = $1
+        "#,
+        tx_id,
+        TransactionStatus::Received.to_string()
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(row.map(|row| TransactionResponse {
+        tx_id: row.tx_id,
+        status: row.status,
+    }))
+}
+
+/// Batch transfer counterpart of [`existing_transaction`].
+async fn existing_batch<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    group_id: &str,
+) -> Result<Option<BatchTransferResponse>, AppError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT tx_id, status::TEXT AS "status!"
+        FROM transactions
+        WHERE group_id = $1
+        ORDER BY leg_index
+        "#,
+        group_id
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let Some(first) = rows.first() else {
+        return Ok(None);
+    };
+    Ok(Some(BatchTransferResponse {
+        group_id: group_id.to_string(),
+        status: first.status.clone(),
+        tx_ids: rows.into_iter().map(|row| row.tx_id).collect(),
+    }))
+}
+
+/// Checks `fee` against the policy minimum and returns the treasury that
+/// collects it, if any fee is paid.
+pub(crate) fn check_fee(state: &AppState, required_fee: Amount, fee: Amount) -> Result<Option<[u8; 32]>, AppError> {
+    if fee < required_fee {
+        return Err(AppError::InsufficientFee { required: required_fee });
+    }
+    match (fee.is_zero(), state.treasury) {
+        (true, _) => Ok(None),
+        (false, Some(treasury)) => Ok(Some(treasury)),
+        (false, None) => Err(AppError::InvalidInput(
+            "Fees are not accepted: no treasury configured".into(),
+        )),
+    }
+}
+
+/// Adds `amount` to `address`'s pending balance of `token_id`, creating the
+/// account if needed. The finalized balance only changes when the proof
+/// batch settles.
+pub(crate) async fn credit_account(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    address: &[u8; 32],
+    token_id: TokenId,
+    amount: Amount,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO accounts (address, token_id, balance, pending_balance, nonce)
+        VALUES ($1, $2, 0, $3, 0)
+        ON CONFLICT (address, token_id) DO UPDATE
+        SET pending_balance = accounts.pending_balance + $3
+        "#,
+        address.as_slice(),
+        to_token_column(token_id),
+        to_numeric(amount)
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Pending balance of `address` in `token_id`; zero without an account.
+async fn pending_balance(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    address: &[u8; 32],
+    token_id: TokenId,
+) -> Result<Amount, AppError> {
+    let balance = sqlx::query_scalar!(
+        "SELECT pending_balance FROM accounts WHERE address = $1 AND token_id = $2",
+        address.as_slice(),
+        to_token_column(token_id)
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    balance.map(from_numeric).transpose().map(Option::unwrap_or_default)
+}
+
+/// Decodes a hex encoded 32-byte address; `field` names it in error messages.
+pub(crate) fn decode_address(address: &str, field: &str) -> Result<[u8; 32], AppError> {
+    let bytes = hex::decode(address)
+        .map_err(|_| AppError::InvalidInput(format!("Invalid {} address", field)))?;
+    bytes
+        .try_into()
+        .map_err(|_| AppError::InvalidInput(format!("Invalid {} address length", field)))
+}
+
+/// Decodes a hex encoded 32-byte hash or preimage; `field` names it in error messages.
+pub(crate) fn decode_hash(hash: &str, field: &str) -> Result<[u8; 32], AppError> {
+    let bytes = hex::decode(hash)
+        .map_err(|_| AppError::InvalidInput(format!("Invalid {}", field)))?;
+    bytes
+        .try_into()
+        .map_err(|_| AppError::InvalidInput(format!("Invalid {} length", field)))
+}
+
+pub(crate) fn decode_signature(signature: &str) -> Result<[u8; 64], AppError> {
+    let bytes = hex::decode(signature)
+        .map_err(|_| AppError::InvalidInput("Invalid signature".into()))?;
+    bytes
+        .try_into()
+        .map_err(|_| AppError::InvalidInput("Invalid signature length".into()))
+}
+
+/// Checks `signature` over the canonical encoding of `payload`.
+pub(crate) fn verify_payload(
+    payload: &SignablePayload,
+    public_key: &[u8; 32],
+    signature: &[u8; 64],
+) -> Result<(), AppError> {
+    if payload.verify(public_key, signature) {
+        Ok(())
+    } else {
+        Err(AppError::InvalidSignature)
+    }
+}
@@ -4,6 +4,8 @@ use axum::{
     Json,
 };
 use serde_json::json;
+use std::fmt;
+use usda_common::{Amount, LimitViolation, TokenId};
 
 #[derive(Debug)]
 pub enum AppError {
@@ -13,14 +15,26 @@ pub enum AppError {
     InsufficientBalance,
     InvalidSignature,
     InvalidNonce,
+    InsufficientFee { required: Amount },
+    TransactionExpired,
+    AmountOverflow,
+    UnknownToken(TokenId),
+    InsufficientAllowance,
+    AllowanceExpired,
+    InvalidPreimage,
+    /// The sender or receiver is frozen by the token's issuer
+    AccountFrozen,
+    /// The transfer breaks one of the sender's spending limits
+    SpendingLimitExceeded(LimitViolation),
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let (status, message) = match self {
-            AppError::InvalidInput(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
-            AppError::DatabaseError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+impl AppError {
+    /// HTTP status and client-facing message of the error.
+    fn parts(&self) -> (StatusCode, String) {
+        match self {
+            AppError::InvalidInput(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
+            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
+            AppError::DatabaseError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
             AppError::InsufficientBalance => (
                 StatusCode::BAD_REQUEST,
                 "Insufficient balance for transaction".into(),
@@ -33,9 +47,63 @@ impl IntoResponse for AppError {
                 StatusCode::BAD_REQUEST,
                 "Invalid transaction nonce".into(),
             ),
-        };
+            AppError::InsufficientFee { required } => (
+                StatusCode::BAD_REQUEST,
+                format!("Transaction fee must be at least {}", required),
+            ),
+            AppError::TransactionExpired => (
+                StatusCode::BAD_REQUEST,
+                "Transaction has expired".into(),
+            ),
+            AppError::AmountOverflow => (
+                StatusCode::BAD_REQUEST,
+                "Amount exceeds the supported range".into(),
+            ),
+            AppError::UnknownToken(token_id) => (
+                StatusCode::BAD_REQUEST,
+                format!("Unknown token {}", token_id),
+            ),
+            AppError::InsufficientAllowance => (
+                StatusCode::BAD_REQUEST,
+                "Insufficient allowance for transaction".into(),
+            ),
+            AppError::AllowanceExpired => (
+                StatusCode::BAD_REQUEST,
+                "Allowance has expired".into(),
+            ),
+            AppError::InvalidPreimage => (
+                StatusCode::BAD_REQUEST,
+                "Preimage does not match the escrow hashlock".into(),
+            ),
+            AppError::AccountFrozen => (
+                StatusCode::FORBIDDEN,
+                "Account is frozen".into(),
+            ),
+            AppError::SpendingLimitExceeded(violation) => (
+                StatusCode::FORBIDDEN,
+                violation.to_string(),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.parts().1)
+    }
+}
 
-        (status, Json(json!({ "error": message }))).into_response()
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let (status, message) = self.parts();
+        let body = match self {
+            // Clients can retry later or send less without parsing the message
+            AppError::SpendingLimitExceeded(violation) => {
+                json!({ "error": message, "violation": violation })
+            }
+            _ => json!({ "error": message }),
+        };
+        (status, Json(body)).into_response()
     }
 }
 
@@ -1,6 +1,12 @@
 pub mod api;
 pub mod state;
 pub mod error;
+pub mod lifecycle;
+pub mod numeric;
+pub mod prover;
+pub mod recovery;
+pub mod scheduler;
+pub mod settlement;
 pub mod websocket;
 
 #[cfg(test)]
@@ -9,12 +15,36 @@ mod tests {
     mod account_tests;
     mod transaction_tests;
     mod mint_tests;
+    mod burn_tests;
+    mod batch_transfer_tests;
+    mod nonce_queue_tests;
+    mod cancel_tests;
+    mod settlement_tests;
+    mod token_tests;
+    mod multi_asset_tests;
+    mod allowance_tests;
+    mod escrow_tests;
+    mod scheduler_tests;
+    mod multisig_tests;
+    mod compliance_tests;
+    mod limits_tests;
+    mod key_tests;
+    mod recovery_tests;
+    mod prover_tests;
 
+    use crate::api::transaction::{transfer, TransferRequest};
     use crate::state::AppState;
     use sqlx::postgres::PgPoolOptions;
+    use util::{balances, fund, new_key, pending, pending_balance, send, setup_account, signed_transfer, status_of, TestAccount};
     use std::sync::Arc;
+    use usda_common::{Amount, DEFAULT_TOKEN_ID};
 
     async fn setup_test_state() -> Arc<AppState> {
+        Arc::new(test_app_state().await)
+    }
+
+    /// Unshared test state, for tests that configure it further.
+    async fn test_app_state() -> AppState {
         let database_url = std::env::var("DATABASE_URL")
             .unwrap_or_else(|_| "postgres://localhost/usda_test".to_string());
 
@@ -27,6 +57,15 @@ mod tests {
         // Clear database and run migrations
         util::setup_test_database(&pool).await;
 
-        Arc::new(AppState::new(pool))
+        AppState::new(pool).with_treasury(TEST_TREASURY)
+    }
+
+    /// Address collecting transfer fees in tests
+    const TEST_TREASURY: [u8; 32] = [0xee; 32];
+
+    /// Tests do their arithmetic on `i64`, matching the `::BIGINT` casts they
+    /// read balances back with; this converts at the API boundary.
+    fn tokens(value: i64) -> Amount {
+        Amount::from(u64::try_from(value).expect("test amounts are non-negative"))
     }
 }
@@ -0,0 +1,89 @@
+//! Transaction lifecycle.
+//!
+//! A transaction is `RECEIVED` while it waits in the nonce queue,
+//! `SEQUENCED` once applied at its nonce, `BATCHED` when sealed into a proof
+//! batch, `PROVEN` when the batch settles and `FINALIZED` once the batch's
+//! proof is verified. It can be `CANCELLED` by its sender before it is
+//! batched, and `FAILED` if its batch can't be proven, or if it depended on a
+//! transaction whose batch couldn't be, before it settles. [`transition`] is the
+//! only way a stored transaction changes status, and only makes the moves
+//! [`can_transition`] allows.
+//!
+//! Statuses are stored in the `transaction_status` Postgres enum. Queries
+//! bind them as text cast to the enum (`$1::TEXT::transaction_status`) and
+//! read them back as text (`status::TEXT`).
+
+use usda_common::TransactionStatus;
+
+use crate::error::AppError;
+
+const STATUSES: [TransactionStatus; 7] = [
+    TransactionStatus::Received,
+    TransactionStatus::Sequenced,
+    TransactionStatus::Batched,
+    TransactionStatus::Proven,
+    TransactionStatus::Finalized,
+    TransactionStatus::Failed,
+    TransactionStatus::Cancelled,
+];
+
+/// Whether a transaction in status `from` may move to `to`.
+pub fn can_transition(from: TransactionStatus, to: TransactionStatus) -> bool {
+    use TransactionStatus::*;
+    matches!(
+        (from, to),
+        (Received, Sequenced)
+            | (Received, Cancelled)
+            | (Sequenced, Batched)
+            | (Sequenced, Cancelled)
+            | (Sequenced, Failed)
+            | (Batched, Proven)
+            | (Batched, Failed)
+            | (Proven, Finalized)
+    )
+}
+
+pub fn from_status_column(value: &str) -> Result<TransactionStatus, AppError> {
+    value
+        .parse()
+        .map_err(|_| AppError::DatabaseError(format!("Corrupt transaction status {}", value)))
+}
+
+/// Moves the transactions `tx_ids` to `to`, recording `reason` for it. Fails
+/// if any of them can't make the move; the caller's database transaction
+/// should then be rolled back.
+pub(crate) async fn transition(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    tx_ids: &[String],
+    to: TransactionStatus,
+    reason: Option<&str>,
+) -> Result<(), AppError> {
+    let from: Vec<String> = STATUSES
+        .iter()
+        .filter(|from| can_transition(**from, to))
+        .map(|from| from.to_string())
+        .collect();
+
+    let updated = sqlx::query!(
+        r#"
+        UPDATE transactions
+        SET status = $1::TEXT::transaction_status, status_reason = $2
+        WHERE tx_id = ANY($3) AND status = ANY($4::TEXT[]::transaction_status[])
+        "#,
+        to.to_string(),
+        reason,
+        tx_ids,
+        &from
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    if updated.rows_affected() != tx_ids.len() as u64 {
+        return Err(AppError::InvalidInput(format!(
+            "Transaction can't become {} from its current status",
+            to
+        )));
+    }
+    Ok(())
+}
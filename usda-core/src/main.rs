@@ -3,14 +3,31 @@ use axum::{
     Router,
 };
 use std::sync::Arc;
+use std::time::Duration;
 use tower_http::cors::{Any, CorsLayer};
+use usda_common::{token::MAX_DECIMALS, TokenId, TokenMetadata, DEFAULT_TOKEN_ID};
+use usda_core::{
+    api,
+    prover::{self, BatchOutcome, CommandProver},
+    recovery, scheduler,
+    state::AppState,
+    websocket,
+};
+
+/// How often expired queued transfers are removed
+const QUEUE_PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often the scheduler looks for standing order executions that are due
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(5);
 
-mod api;
-mod error;
-mod state;
-mod websocket;
+/// How often guardian recoveries whose delay has passed are completed
+const RECOVERY_INTERVAL: Duration = Duration::from_secs(5);
 
-use state::AppState;
+/// How often sequenced transactions are sealed into a batch and proven
+const BATCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Most transactions proven in one batch
+const MAX_BATCH_TXS: i64 = 1000;
 
 #[tokio::main]
 async fn main() {
@@ -21,8 +38,169 @@ async fn main() {
         .await
         .expect("Failed to connect to Postgres");
 
+    // Deployment ID that signatures are bound to
+    let chain_id = std::env::var("USDA_CHAIN_ID")
+        .map(|id| id.parse().expect("USDA_CHAIN_ID must be an integer"))
+        .unwrap_or(usda_common::signing::DEFAULT_CHAIN_ID);
+
     // Create app state
-    let state = Arc::new(AppState::new(pool));
+    let mut state = AppState::new(pool).with_chain_id(chain_id);
+
+    // Fee policy, e.g. `flat:10`, `bps:25` or `bps:25:1:1000`
+    if let Ok(policy) = std::env::var("USDA_FEE_POLICY") {
+        state = state.with_fee_policy(policy.parse().expect("Invalid USDA_FEE_POLICY"));
+    }
+
+    // Hex encoded address that collects transfer fees
+    if let Ok(treasury) = std::env::var("USDA_TREASURY_ADDRESS") {
+        let treasury: [u8; 32] = hex::decode(treasury)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .expect("USDA_TREASURY_ADDRESS must be a hex encoded 32-byte address");
+        state = state.with_treasury(treasury);
+    }
+
+    // Out-of-order transfers: how far ahead a nonce may be queued and for how long
+    if let Ok(gap) = std::env::var("USDA_MAX_NONCE_GAP") {
+        state = state.with_max_nonce_gap(gap.parse().expect("USDA_MAX_NONCE_GAP must be an integer"));
+    }
+    if let Ok(secs) = std::env::var("USDA_NONCE_QUEUE_TIMEOUT_SECS") {
+        let secs = secs.parse().expect("USDA_NONCE_QUEUE_TIMEOUT_SECS must be an integer");
+        state = state.with_queue_timeout(Duration::from_secs(secs));
+    }
+
+    // How long guardian recoveries wait for the owner's veto
+    if let Ok(secs) = std::env::var("USDA_RECOVERY_DELAY_SECS") {
+        let secs = secs.parse().expect("USDA_RECOVERY_DELAY_SECS must be an integer");
+        state = state.with_recovery_delay(Duration::from_secs(secs));
+    }
+
+    // Default token's spending limits for accounts the issuer hasn't set limits for,
+    // e.g. `per_transfer:1000000,daily:5000000,hourly_transfers:10` in base units
+    if let Ok(limits) = std::env::var("USDA_SPENDING_LIMITS") {
+        state = state.with_default_limits(
+            DEFAULT_TOKEN_ID,
+            limits.parse().expect("Invalid USDA_SPENDING_LIMITS"),
+        );
+    }
+
+    // Default token display metadata; each setting falls back to the built-in token
+    let mut token = TokenMetadata::default();
+    if let Ok(name) = std::env::var("USDA_TOKEN_NAME") {
+        token.name = name;
+    }
+    if let Ok(symbol) = std::env::var("USDA_TOKEN_SYMBOL") {
+        token.symbol = parse_symbol(&symbol, "USDA_TOKEN_SYMBOL");
+    }
+    if let Ok(decimals) = std::env::var("USDA_TOKEN_DECIMALS") {
+        token.decimals = parse_decimals(&decimals, "USDA_TOKEN_DECIMALS");
+    }
+    state = state.with_token(DEFAULT_TOKEN_ID, token);
+
+    // Further tokens as `id:symbol:decimals:issuer_key[:name]`, separated by `;`
+    let mut issuer_keys = Vec::new();
+    if let Ok(tokens) = std::env::var("USDA_TOKENS") {
+        for spec in tokens.split(';').filter(|spec| !spec.trim().is_empty()) {
+            let fields: Vec<&str> = spec.trim().splitn(5, ':').collect();
+            let [id, symbol, decimals, issuer_key, name @ ..] = fields.as_slice() else {
+                panic!("USDA_TOKENS entry {:?} must be id:symbol:decimals:issuer_key[:name]", spec);
+            };
+            let token_id: TokenId = id.parse().expect("USDA_TOKENS token ID must be an integer");
+            assert!(token_id != DEFAULT_TOKEN_ID, "USDA_TOKENS can't redefine the default token");
+            let symbol = parse_symbol(symbol, "USDA_TOKENS symbol");
+            state = state.with_token(
+                token_id,
+                TokenMetadata {
+                    name: name.first().map_or_else(|| symbol.clone(), |name| name.to_string()),
+                    decimals: parse_decimals(decimals, "USDA_TOKENS decimals"),
+                    symbol,
+                },
+            );
+            issuer_keys.push((token_id, parse_issuer_key(issuer_key, "USDA_TOKENS issuer key")));
+        }
+    }
+
+    let state = Arc::new(state);
+
+    // Periodically drop queued transfers whose nonce gap never filled
+    let pruner = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(QUEUE_PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = api::transaction::prune_queued_transfers(&pruner.db).await {
+                eprintln!("Failed to prune queued transfers: {:?}", e);
+            }
+        }
+    });
+
+    // Execute standing orders as they fall due
+    let scheduler_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = scheduler::run_due_orders(&scheduler_state).await {
+                eprintln!("Failed to run standing orders: {:?}", e);
+            }
+        }
+    });
+
+    // Replace the keys of accounts whose guardian recovery went unvetoed
+    let recovery_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RECOVERY_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = recovery::complete_due_recoveries(&recovery_state).await {
+                eprintln!("Failed to complete account recoveries: {:?}", e);
+            }
+        }
+    });
+
+    // Issuer key that authorizes mints of the default token (hex encoded Ed25519 public key)
+    if let Ok(issuer_key) = std::env::var("USDA_ISSUER_PUBLIC_KEY") {
+        state.set_issuer_key(
+            DEFAULT_TOKEN_ID,
+            parse_issuer_key(&issuer_key, "USDA_ISSUER_PUBLIC_KEY"),
+        );
+    }
+    for (token_id, key) in issuer_keys {
+        state.set_issuer_key(token_id, key);
+    }
+
+    // Prove batches with an external command, e.g. `usda-script/target/release/usda --prove`;
+    // without one, transactions stay preconfirmed
+    if let Ok(command) = std::env::var("USDA_PROVER_COMMAND") {
+        let mut words = command.split_whitespace().map(str::to_string);
+        let program = words.next().expect("USDA_PROVER_COMMAND must name a program");
+        let batch_prover = CommandProver::new(program, words.collect());
+        let batch_state = state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(BATCH_INTERVAL);
+            loop {
+                interval.tick().await;
+                // Work through the backlog until a batch has to wait for the prover
+                loop {
+                    match prover::prove_next_batch(&batch_state, &batch_prover, MAX_BATCH_TXS).await {
+                        Ok(Some(BatchOutcome::Settled(_))) => {}
+                        Ok(Some(BatchOutcome::Failed(batch_id, reason))) => {
+                            eprintln!("Failed batch {}: {}", batch_id, reason);
+                        }
+                        Ok(Some(BatchOutcome::Deferred(batch_id, reason))) => {
+                            eprintln!("Couldn't prove batch {}: {}", batch_id, reason);
+                            break;
+                        }
+                        Ok(None) => break,
+                        Err(e) => {
+                            eprintln!("Failed to prove batch: {:?}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+    }
 
     // Create CORS layer
     let cors = CorsLayer::new()
@@ -34,12 +212,56 @@ async fn main() {
     let app = Router::new()
         // Health check route
         .route("/health", get(health_check))
+        // Token metadata
+        .route("/token", get(api::token::metadata))
+        .route("/tokens", get(api::token::list))
         // Account routes
         .route("/account/create", post(api::account::create))
+        .route("/account/rotate-key", post(api::account::rotate_key))
+        .route("/account/:address/key", get(api::account::get_key))
+        .route("/account/guardians", post(api::recovery::set_guardians))
+        .route("/account/:address/guardians", get(api::recovery::get_guardians))
+        .route("/account/:address/recoveries", get(api::recovery::get_recoveries))
         .route("/account/:address/balance", get(api::account::get_balance))
         .route("/account/:address/transactions", get(api::account::get_transactions))
+        .route("/account/:address/allowances", get(api::account::get_allowances))
         // Transaction routes
         .route("/transaction/transfer", post(api::transaction::transfer))
+        .route("/transaction/simulate", post(api::transaction::simulate))
+        .route("/transaction/batch-transfer", post(api::transaction::batch_transfer))
+        .route("/transaction/multisig-transfer", post(api::transaction::multisig_transfer))
+        .route("/transaction/cancel", post(api::transaction::cancel))
+        .route("/transaction/replace", post(api::transaction::replace))
+        .route("/transaction/mint", post(api::transaction::mint))
+        .route("/transaction/burn", post(api::transaction::burn))
+        .route("/transaction/approve", post(api::transaction::approve))
+        .route("/transaction/transfer-from", post(api::transaction::transfer_from))
+        .route("/transaction/escrow/lock", post(api::transaction::escrow_lock))
+        .route("/transaction/escrow/claim", post(api::transaction::escrow_claim))
+        .route("/transaction/escrow/refund", post(api::transaction::escrow_refund))
+        .route("/transaction/escrow/:escrow_id", get(api::transaction::get_escrow))
+        .route("/transaction/fee-policy", get(api::transaction::fee_policy))
+        .route("/transaction/:tx_id", get(api::transaction::get_transaction))
+        // Standing order routes
+        .route("/schedule/create", post(api::schedule::create))
+        .route("/schedule/cancel", post(api::schedule::cancel))
+        .route("/schedule/:order_id", get(api::schedule::get))
+        .route("/schedule/:order_id/executions", get(api::schedule::executions))
+        // Multisig routes
+        .route("/multisig/create", post(api::multisig::create))
+        .route("/multisig/update", post(api::multisig::update))
+        .route("/multisig/:address", get(api::multisig::get))
+        // Guardian recovery routes
+        .route("/recovery/initiate", post(api::recovery::initiate))
+        .route("/recovery/veto", post(api::recovery::veto))
+        .route("/recovery/:recovery_id", get(api::recovery::get))
+        // Compliance routes
+        .route("/compliance/freeze", post(api::compliance::freeze))
+        .route("/compliance/unfreeze", post(api::compliance::unfreeze))
+        .route("/compliance/clawback", post(api::compliance::clawback))
+        .route("/compliance/limits", post(api::compliance::set_limits))
+        .route("/compliance/:address", get(api::compliance::get_status))
+        .route("/compliance/:address/limits", get(api::compliance::get_limits))
         // WebSocket route
         .route("/ws", get(websocket::handler))
         .layer(cors)
@@ -56,3 +278,29 @@ async fn main() {
 async fn health_check() -> &'static str {
     "OK"
 }
+
+fn parse_symbol(symbol: &str, var: &str) -> String {
+    assert!(
+        !symbol.is_empty() && !symbol.contains(char::is_whitespace),
+        "{} must be a single word",
+        var
+    );
+    symbol.to_string()
+}
+
+fn parse_decimals(decimals: &str, var: &str) -> u8 {
+    decimals
+        .parse()
+        .ok()
+        .filter(|decimals| *decimals <= MAX_DECIMALS)
+        .unwrap_or_else(|| panic!("{} must be an integer between 0 and {}", var, MAX_DECIMALS))
+}
+
+fn parse_issuer_key(issuer_key: &str, var: &str) -> ed25519_dalek::VerifyingKey {
+    let bytes: [u8; 32] = hex::decode(issuer_key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .unwrap_or_else(|| panic!("{} must be a hex encoded 32-byte key", var));
+    ed25519_dalek::VerifyingKey::from_bytes(&bytes)
+        .unwrap_or_else(|_| panic!("{} is not a valid Ed25519 key", var))
+}
@@ -0,0 +1,32 @@
+//! Conversions between [`Amount`] and Postgres `NUMERIC` columns, and
+//! between [`TokenId`] and the `BIGINT` token columns.
+
+use bigdecimal::{num_bigint::BigInt, BigDecimal};
+use usda_common::{Amount, TokenId};
+
+use crate::error::AppError;
+
+pub fn to_numeric(amount: Amount) -> BigDecimal {
+    BigDecimal::new(BigInt::from(amount.get()), 0)
+}
+
+/// Reads a stored amount; anything that isn't a whole number in `Amount`'s
+/// range means the row is corrupt.
+pub fn from_numeric(value: BigDecimal) -> Result<Amount, AppError> {
+    let whole = value.with_scale(0);
+    if whole != value {
+        return Err(AppError::DatabaseError(format!("Corrupt amount {}", value)));
+    }
+    whole
+        .to_string()
+        .parse()
+        .map_err(|_| AppError::DatabaseError(format!("Corrupt amount {}", value)))
+}
+
+pub fn to_token_column(token_id: TokenId) -> i64 {
+    i64::from(token_id)
+}
+
+pub fn from_token_column(value: i64) -> Result<TokenId, AppError> {
+    TokenId::try_from(value).map_err(|_| AppError::DatabaseError(format!("Corrupt token ID {}", value)))
+}
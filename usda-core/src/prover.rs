@@ -0,0 +1,496 @@
+//! Proof generation for sealed batches.
+//!
+//! [`prove_next_batch`] takes the earliest batch still awaiting its proof, or
+//! seals a new one, rebuilds the signed entries of its transactions as a
+//! [`BatchInput`] and hands them to a [`BatchProver`]. A proof settles the
+//! batch; a batch the program rejects is failed. A prover that is
+//! unavailable leaves the batch to be retried, since later batches can't
+//! settle before it.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use usda_common::{
+    batch::{
+        BatchEntry, BatchInput, BatchProof, BatchTransferProof, BurnProof, ClawbackProof,
+        EscrowClaimProof, EscrowLockProof, EscrowRefundProof, EscrowTerms, MintProof,
+        MultisigTransferProof, ScheduledTransferProof, StandingOrderTerms, TransferFromProof,
+        TransferProof, PROVER_REJECTED_EXIT_CODE,
+    },
+    MemberSignature, SignablePayload, TokenId, TransactionKind,
+};
+
+use crate::{
+    api::multisig::policy_from_columns,
+    error::AppError,
+    numeric::{from_numeric, from_token_column},
+    settlement::{fail_batch, next_unproven_batch, seal_batch, settle_batch},
+    state::AppState,
+};
+
+/// Why a batch wasn't proven.
+#[derive(Debug)]
+pub enum ProverError {
+    /// The program rejects the batch, so no proof of it will ever exist
+    Rejected(String),
+    /// The prover couldn't run; the batch can be tried again
+    Unavailable(String),
+}
+
+#[async_trait]
+pub trait BatchProver: Send + Sync {
+    /// Proves `input`, the entries of batch `batch_id`, and verifies the proof.
+    async fn prove(&self, batch_id: &str, input: &BatchInput) -> Result<BatchProof, ProverError>;
+}
+
+/// Proves batches with an external command, such as the `usda` prover
+/// script with `--prove`. The command is passed `--input <file>` holding
+/// the bincode [`BatchInput`] and `--output <file>` to write the bincode
+/// [`BatchProof`] to, and exits with [`PROVER_REJECTED_EXIT_CODE`] if the
+/// program rejects the batch.
+pub struct CommandProver {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandProver {
+    pub fn new(program: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            program: program.into(),
+            args,
+        }
+    }
+}
+
+#[async_trait]
+impl BatchProver for CommandProver {
+    async fn prove(&self, batch_id: &str, input: &BatchInput) -> Result<BatchProof, ProverError> {
+        let input_path = batch_file(batch_id, "input");
+        let output_path = batch_file(batch_id, "proof");
+        let bytes = bincode::serialize(input)
+            .map_err(|e| ProverError::Unavailable(format!("Failed to encode batch input: {}", e)))?;
+        tokio::fs::write(&input_path, bytes)
+            .await
+            .map_err(|e| ProverError::Unavailable(format!("Failed to write batch input: {}", e)))?;
+
+        let output = tokio::process::Command::new(&self.program)
+            .args(&self.args)
+            .arg("--input")
+            .arg(&input_path)
+            .arg("--output")
+            .arg(&output_path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .await;
+        let _ = tokio::fs::remove_file(&input_path).await;
+        let output = output.map_err(|e| ProverError::Unavailable(format!("Failed to run prover: {}", e)))?;
+
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        match output.status.code() {
+            Some(0) => {}
+            Some(PROVER_REJECTED_EXIT_CODE) => return Err(ProverError::Rejected(stderr)),
+            _ => {
+                return Err(ProverError::Unavailable(format!(
+                    "Prover exited with {}: {}",
+                    output.status, stderr
+                )))
+            }
+        }
+
+        let proof = tokio::fs::read(&output_path).await;
+        let _ = tokio::fs::remove_file(&output_path).await;
+        let proof = proof.map_err(|e| ProverError::Unavailable(format!("Failed to read proof: {}", e)))?;
+        bincode::deserialize(&proof)
+            .map_err(|e| ProverError::Unavailable(format!("Failed to decode proof: {}", e)))
+    }
+}
+
+fn batch_file(batch_id: &str, suffix: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("usda-batch-{}.{}", batch_id, suffix))
+}
+
+/// What [`prove_next_batch`] did with a batch.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BatchOutcome {
+    Settled(String),
+    /// The batch was failed, with the reason the proof was refused
+    Failed(String, String),
+    /// The prover was unavailable; the batch is retried on the next call
+    Deferred(String, String),
+}
+
+/// Proves the earliest batch awaiting its proof, sealing up to `max_txs`
+/// sequenced transactions into a new one if there is none. Returns `None`
+/// when there is nothing to prove.
+pub async fn prove_next_batch(
+    state: &AppState,
+    prover: &dyn BatchProver,
+    max_txs: i64,
+) -> Result<Option<BatchOutcome>, AppError> {
+    let batch_id = match next_unproven_batch(state).await? {
+        Some(batch_id) => batch_id,
+        None => match seal_batch(state, max_txs).await? {
+            Some(batch) => batch.batch_id,
+            None => return Ok(None),
+        },
+    };
+
+    let input = batch_input(state, &batch_id).await?;
+    let reason = match prover.prove(&batch_id, &input).await {
+        Ok(proof) => match settle_batch(state, &batch_id, &proof.proof, &proof.vkey_hash, &proof.result).await {
+            Ok(()) => return Ok(Some(BatchOutcome::Settled(batch_id))),
+            // The proof doesn't vouch for this ledger's batch
+            Err(AppError::InvalidInput(reason)) => reason,
+            Err(e) => return Err(e),
+        },
+        Err(ProverError::Rejected(reason)) => reason,
+        Err(ProverError::Unavailable(reason)) => return Ok(Some(BatchOutcome::Deferred(batch_id, reason))),
+    };
+    fail_batch(state, &batch_id).await?;
+    Ok(Some(BatchOutcome::Failed(batch_id, reason)))
+}
+
+/// The signed entries of batch `batch_id`, in the order they were applied.
+pub async fn batch_input(state: &AppState, batch_id: &str) -> Result<BatchInput, AppError> {
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT tx_id, kind, token_id, from_addr, to_addr, spender, amount, fee, nonce, signature,
+               timestamp, valid_until, redemption_ref, group_id, leg_index, escrow_id, order_id,
+               multisig_version
+        FROM transactions
+        WHERE batch_id = $1
+        ORDER BY seq
+        "#,
+        batch_id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let mut entries = Vec::with_capacity(rows.len());
+    let mut issued = BTreeSet::new();
+    for row in rows {
+        let corrupt = || AppError::DatabaseError(format!("Corrupt transaction {}", row.tx_id));
+        let address = |column: Option<Vec<u8>>| -> Result<[u8; 32], AppError> {
+            column.and_then(|bytes| bytes.try_into().ok()).ok_or_else(corrupt)
+        };
+        let kind: TransactionKind = row.kind.parse().map_err(|_| corrupt())?;
+        let token_id = from_token_column(row.token_id)?;
+        let amount = from_numeric(row.amount)?;
+        let fee = from_numeric(row.fee)?;
+        let signature: [u8; 64] = row.signature.try_into().map_err(|_| corrupt())?;
+        let sequenced_at = Some(row.timestamp.timestamp());
+        let valid_until = row.valid_until.map(|valid_until| valid_until.timestamp());
+
+        let mut entry = match kind {
+            // Each leg of a batch transfer is its own transaction, but one
+            // signature covers them all
+            TransactionKind::Transfer if row.group_id.is_some() => {
+                if row.leg_index != Some(0) {
+                    continue;
+                }
+                let legs = sqlx::query!(
+                    "SELECT to_addr, amount FROM transactions WHERE group_id = $1 ORDER BY leg_index",
+                    row.group_id
+                )
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?
+                .into_iter()
+                .map(|leg| Ok((address(leg.to_addr)?, from_numeric(leg.amount)?)))
+                .collect::<Result<Vec<_>, AppError>>()?;
+                BatchEntry::BatchTransfer(BatchTransferProof {
+                    token_id,
+                    from_addr: address(row.from_addr)?,
+                    legs,
+                    fee,
+                    nonce: row.nonce,
+                    signature,
+                    public_key: [0; 32],
+                })
+            }
+            TransactionKind::Transfer => BatchEntry::Transfer(TransferProof {
+                token_id,
+                from_addr: address(row.from_addr)?,
+                to_addr: address(row.to_addr)?,
+                amount,
+                fee,
+                nonce: row.nonce,
+                valid_until,
+                sequenced_at,
+                signature,
+                public_key: [0; 32],
+            }),
+            TransactionKind::Mint => {
+                issued.insert(token_id);
+                BatchEntry::Mint(MintProof {
+                    token_id,
+                    to_addr: address(row.to_addr)?,
+                    amount,
+                    nonce: row.nonce,
+                    signature,
+                })
+            }
+            TransactionKind::Burn => BatchEntry::Burn(BurnProof {
+                token_id,
+                from_addr: address(row.from_addr)?,
+                amount,
+                nonce: row.nonce,
+                redemption_ref: row.redemption_ref,
+                signature,
+                public_key: [0; 32],
+            }),
+            TransactionKind::TransferFrom => BatchEntry::TransferFrom(TransferFromProof {
+                token_id,
+                owner_addr: address(row.from_addr)?,
+                spender_addr: address(row.spender)?,
+                to_addr: address(row.to_addr)?,
+                amount,
+                fee,
+                nonce: row.nonce,
+                signature,
+                public_key: [0; 32],
+            }),
+            TransactionKind::EscrowLock | TransactionKind::EscrowClaim | TransactionKind::EscrowRefund => {
+                let escrow_id = row.escrow_id.ok_or_else(corrupt)?;
+                let (terms, preimage) = escrow_terms(&mut tx, &escrow_id).await?;
+                match kind {
+                    TransactionKind::EscrowLock => BatchEntry::EscrowLock(EscrowLockProof {
+                        terms,
+                        signature,
+                        public_key: [0; 32],
+                    }),
+                    TransactionKind::EscrowClaim => BatchEntry::EscrowClaim(EscrowClaimProof {
+                        terms,
+                        preimage: preimage.ok_or_else(corrupt)?,
+                        signature,
+                        public_key: [0; 32],
+                    }),
+                    _ => BatchEntry::EscrowRefund(EscrowRefundProof {
+                        terms,
+                        signature,
+                        public_key: [0; 32],
+                    }),
+                }
+            }
+            TransactionKind::ScheduledTransfer => {
+                let order_id = row.order_id.ok_or_else(corrupt)?;
+                let (terms, execution) = order_execution(&mut tx, &order_id, &row.tx_id).await?;
+                BatchEntry::ScheduledTransfer(ScheduledTransferProof {
+                    terms,
+                    execution,
+                    signature,
+                    public_key: [0; 32],
+                })
+            }
+            TransactionKind::MultisigTransfer => {
+                let from_addr = address(row.from_addr)?;
+                let policy = sqlx::query!(
+                    "SELECT threshold, members FROM multisig_policies WHERE address = $1 AND version = $2",
+                    from_addr.as_slice(),
+                    row.multisig_version
+                )
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?
+                .ok_or_else(corrupt)?;
+                let signatures = sqlx::query!(
+                    "SELECT member, signature FROM multisig_signatures WHERE tx_id = $1 ORDER BY member",
+                    row.tx_id
+                )
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?
+                .into_iter()
+                .map(|signature| {
+                    Ok(MemberSignature {
+                        public_key: signature.member.try_into().map_err(|_| corrupt())?,
+                        signature: signature.signature.try_into().map_err(|_| corrupt())?,
+                    })
+                })
+                .collect::<Result<Vec<_>, AppError>>()?;
+                BatchEntry::MultisigTransfer(MultisigTransferProof {
+                    token_id,
+                    from_addr,
+                    to_addr: address(row.to_addr)?,
+                    amount,
+                    fee,
+                    nonce: row.nonce,
+                    valid_until,
+                    sequenced_at,
+                    policy: policy_from_columns(policy.threshold, policy.members)?,
+                    signatures,
+                })
+            }
+            TransactionKind::Clawback => {
+                issued.insert(token_id);
+                BatchEntry::Clawback(ClawbackProof {
+                    token_id,
+                    from_addr: address(row.from_addr)?,
+                    to_addr: address(row.to_addr)?,
+                    amount,
+                    nonce: row.nonce,
+                    signature,
+                })
+            }
+        };
+
+        // The account may have rotated its key since; find the one that signed
+        let payload = entry.payload(state.chain_id);
+        if let Some((signer, signature, public_key)) = signer_slot(&mut entry) {
+            *public_key = signing_key(&mut tx, &row.tx_id, &signer, &payload, &signature).await?;
+        }
+        entries.push(entry);
+    }
+
+    let issuer_keys = issued
+        .into_iter()
+        .map(|token_id: TokenId| {
+            state
+                .issuer_key(token_id)
+                .map(|key| (token_id, key.to_bytes()))
+                .ok_or(AppError::UnknownToken(token_id))
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    Ok(BatchInput {
+        chain_id: state.chain_id,
+        batch_time: Utc::now().timestamp(),
+        issuer_keys,
+        entries,
+    })
+}
+
+/// Account that signed `entry` with a key of its own, the signature, and
+/// where the entry takes the key. Issuer and multisig signatures are checked
+/// against keys the entry carries otherwise.
+fn signer_slot(entry: &mut BatchEntry) -> Option<([u8; 32], [u8; 64], &mut [u8; 32])> {
+    match entry {
+        BatchEntry::Transfer(proof) => Some((proof.from_addr, proof.signature, &mut proof.public_key)),
+        BatchEntry::Burn(proof) => Some((proof.from_addr, proof.signature, &mut proof.public_key)),
+        BatchEntry::BatchTransfer(proof) => Some((proof.from_addr, proof.signature, &mut proof.public_key)),
+        BatchEntry::TransferFrom(proof) => Some((proof.spender_addr, proof.signature, &mut proof.public_key)),
+        BatchEntry::EscrowLock(proof) => Some((proof.terms.from_addr, proof.signature, &mut proof.public_key)),
+        BatchEntry::EscrowClaim(proof) => Some((proof.terms.to_addr, proof.signature, &mut proof.public_key)),
+        BatchEntry::EscrowRefund(proof) => Some((proof.terms.from_addr, proof.signature, &mut proof.public_key)),
+        BatchEntry::ScheduledTransfer(proof) => {
+            Some((proof.terms.from_addr, proof.signature, &mut proof.public_key))
+        }
+        BatchEntry::Mint(_) | BatchEntry::MultisigTransfer(_) | BatchEntry::Clawback(_) => None,
+    }
+}
+
+/// The key of `address`, current or past, that made `signature` over `payload`.
+async fn signing_key(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    tx_id: &str,
+    address: &[u8; 32],
+    payload: &SignablePayload,
+    signature: &[u8; 64],
+) -> Result<[u8; 32], AppError> {
+    let mut keys: Vec<[u8; 32]> = sqlx::query!(
+        "SELECT public_key FROM account_key_history WHERE address = $1 ORDER BY version DESC",
+        address.as_slice()
+    )
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .into_iter()
+    .filter_map(|row| row.public_key.try_into().ok())
+    .collect();
+    // Accounts that never registered a key sign with their address
+    if keys.is_empty() {
+        keys.push(*address);
+    }
+
+    keys.into_iter()
+        .find(|key| payload.verify(key, signature))
+        .ok_or_else(|| {
+            AppError::DatabaseError(format!(
+                "No key of account {} signed transaction {}",
+                hex::encode(address),
+                tx_id
+            ))
+        })
+}
+
+/// Signed terms of an escrow, and the preimage it was claimed with.
+async fn escrow_terms(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    escrow_id: &str,
+) -> Result<(EscrowTerms, Option<[u8; 32]>), AppError> {
+    let escrow = sqlx::query!(
+        r#"
+        SELECT token_id, sender, recipient, amount, fee, hashlock, timeout, nonce, preimage
+        FROM escrows
+        WHERE escrow_id = $1
+        "#,
+        escrow_id
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::DatabaseError(format!("Escrow {} not found", escrow_id)))?;
+
+    let corrupt = || AppError::DatabaseError(format!("Corrupt escrow {}", escrow_id));
+    let terms = EscrowTerms {
+        token_id: from_token_column(escrow.token_id)?,
+        from_addr: escrow.sender.try_into().map_err(|_| corrupt())?,
+        to_addr: escrow.recipient.try_into().map_err(|_| corrupt())?,
+        amount: from_numeric(escrow.amount)?,
+        fee: from_numeric(escrow.fee)?,
+        hashlock: escrow.hashlock.try_into().map_err(|_| corrupt())?,
+        timeout: escrow.timeout.timestamp(),
+        nonce: escrow.nonce,
+    };
+    let preimage = escrow
+        .preimage
+        .map(|preimage| preimage.try_into().map_err(|_| corrupt()))
+        .transpose()?;
+    Ok((terms, preimage))
+}
+
+/// Signed terms of a standing order, and which of its executions `tx_id` is.
+async fn order_execution(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    order_id: &str,
+    tx_id: &str,
+) -> Result<(StandingOrderTerms, u32), AppError> {
+    let order = sqlx::query!(
+        r#"
+        SELECT orders.token_id, orders.from_addr, orders.to_addr, orders.amount, orders.fee,
+               orders.start_at, orders.interval_secs, orders.max_executions, orders.nonce,
+               executions.execution
+        FROM standing_orders AS orders
+        JOIN standing_order_executions AS executions USING (order_id)
+        WHERE order_id = $1 AND executions.tx_id = $2
+        "#,
+        order_id,
+        tx_id
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::DatabaseError(format!("Execution {} of standing order {} not found", tx_id, order_id)))?;
+
+    let corrupt = || AppError::DatabaseError(format!("Corrupt standing order {}", order_id));
+    let terms = StandingOrderTerms {
+        token_id: from_token_column(order.token_id)?,
+        from_addr: order.from_addr.try_into().map_err(|_| corrupt())?,
+        to_addr: order.to_addr.try_into().map_err(|_| corrupt())?,
+        amount: from_numeric(order.amount)?,
+        fee: from_numeric(order.fee)?,
+        start: order.start_at.timestamp(),
+        interval: order.interval_secs,
+        max_executions: u32::try_from(order.max_executions).map_err(|_| corrupt())?,
+        nonce: order.nonce,
+    };
+    Ok((terms, u32::try_from(order.execution).map_err(|_| corrupt())?))
+}
@@ -0,0 +1,82 @@
+//! Completes guardian recoveries once their delay has passed.
+//!
+//! A recovery requested by an account's guardians waits out the configured
+//! recovery delay, during which the account's current key can veto it. Once
+//! the delay has passed it replaces the key through the same steps as a
+//! rotation, or is superseded if the key changed in the meantime.
+
+use usda_common::RecoveryStatus;
+
+use crate::{
+    api::account::{lock_key, replace_key},
+    api::recovery::{broadcast_recovery, load_recovery},
+    error::AppError,
+    state::AppState,
+};
+
+/// Resolves every recovery whose delay has passed, oldest first, and returns
+/// how many were resolved. Recoveries locked by a concurrent run are skipped.
+pub async fn complete_due_recoveries(state: &AppState) -> Result<usize, AppError> {
+    let mut resolved = 0;
+    while complete_next_due(state).await? {
+        resolved += 1;
+    }
+    Ok(resolved)
+}
+
+/// Completes or supersedes the most overdue recovery, if any.
+async fn complete_next_due(state: &AppState) -> Result<bool, AppError> {
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let Some(due) = sqlx::query!(
+        r#"
+        SELECT recovery_id, address, new_key, key_version
+        FROM account_recoveries
+        WHERE status = $1 AND executable_at <= NOW()
+        ORDER BY executable_at
+        LIMIT 1
+        FOR UPDATE SKIP LOCKED
+        "#,
+        RecoveryStatus::Pending.to_string()
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    else {
+        return Ok(false);
+    };
+
+    let corrupt = || AppError::DatabaseError(format!("Corrupt recovery {}", due.recovery_id));
+    let address: [u8; 32] = due.address.try_into().map_err(|_| corrupt())?;
+    let new_key: [u8; 32] = due.new_key.try_into().map_err(|_| corrupt())?;
+
+    // The owner may have rotated, or another recovery completed, since the
+    // guardians signed
+    let (_, key_version) = lock_key(&mut tx, &address).await?;
+    let outcome = if key_version == due.key_version {
+        replace_key(&mut tx, &address, &new_key, key_version + 1, None, Some(&due.recovery_id)).await?;
+        RecoveryStatus::Completed
+    } else {
+        RecoveryStatus::Superseded
+    };
+
+    sqlx::query!(
+        "UPDATE account_recoveries SET status = $1, resolved_at = NOW() WHERE recovery_id = $2",
+        outcome.to_string(),
+        due.recovery_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let recovery = load_recovery(&mut *tx, &due.recovery_id)
+        .await?
+        .ok_or_else(corrupt)?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    broadcast_recovery(state, recovery);
+    Ok(true)
+}
@@ -0,0 +1,196 @@
+//! Executes standing orders as they fall due.
+//!
+//! Execution `k` of an order falls due at `start + k * interval` and moves
+//! the order's amount at the sender's next nonce, through the same checks and
+//! balance updates as `POST /transaction/transfer`. Each execution is
+//! attempted once: one that can't be applied, e.g. for lack of funds, is
+//! recorded with its error and the order moves on to the next. An order
+//! finishes after `max_executions` attempts, or when its sender cancels it.
+//! An order whose stored terms can't be read is stopped as failed, so it
+//! doesn't hold up the orders due after it.
+
+use chrono::{DateTime, Utc};
+use sqlx::Acquire;
+use usda_common::{batch::StandingOrderTerms, Transaction};
+
+use crate::{
+    api::transaction::{apply_at_next_nonce, broadcast_preconfirmed, TransferEntry},
+    error::AppError,
+    numeric::{from_numeric, from_token_column},
+    state::AppState,
+};
+
+/// Order with executions still to come
+pub const ORDER_ACTIVE: &str = "ACTIVE";
+/// Order whose executions have all been attempted
+pub const ORDER_COMPLETED: &str = "COMPLETED";
+/// Order stopped by its sender
+pub const ORDER_CANCELLED: &str = "CANCELLED";
+/// Order stopped because its stored terms can't be read
+pub const ORDER_FAILED: &str = "FAILED";
+
+/// Attempts every execution that has fallen due, oldest first, and returns
+/// how many were attempted. Orders locked by a concurrent run are skipped.
+pub async fn run_due_orders(state: &AppState) -> Result<usize, AppError> {
+    let mut attempted = 0;
+    while execute_next_due(state).await? {
+        attempted += 1;
+    }
+    Ok(attempted)
+}
+
+/// Attempts the most overdue execution, if any, and moves its order on.
+async fn execute_next_due(state: &AppState) -> Result<bool, AppError> {
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let Some(order) = sqlx::query!(
+        r#"
+        SELECT order_id, token_id, from_addr, to_addr, amount, fee, start_at, interval_secs,
+               max_executions, executions, next_run_at AS "next_run_at!", nonce, signature
+        FROM standing_orders
+        WHERE status = $1 AND next_run_at <= NOW()
+        ORDER BY next_run_at
+        LIMIT 1
+        FOR UPDATE SKIP LOCKED
+        "#,
+        ORDER_ACTIVE
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    else {
+        return Ok(false);
+    };
+
+    let corrupt = || AppError::DatabaseError(format!("Corrupt standing order {}", order.order_id));
+    let read_terms = || -> Result<(StandingOrderTerms, u32, [u8; 64]), AppError> {
+        let terms = StandingOrderTerms {
+            token_id: from_token_column(order.token_id)?,
+            from_addr: order.from_addr.as_slice().try_into().map_err(|_| corrupt())?,
+            to_addr: order.to_addr.as_slice().try_into().map_err(|_| corrupt())?,
+            amount: from_numeric(order.amount.clone())?,
+            fee: from_numeric(order.fee.clone())?,
+            start: order.start_at.timestamp(),
+            interval: order.interval_secs,
+            max_executions: u32::try_from(order.max_executions).map_err(|_| corrupt())?,
+            nonce: order.nonce,
+        };
+        let execution = u32::try_from(order.executions).map_err(|_| corrupt())?;
+        let signature = order.signature.as_slice().try_into().map_err(|_| corrupt())?;
+        Ok((terms, execution, signature))
+    };
+    let (terms, execution, signature) = match read_terms() {
+        Ok(read) => read,
+        Err(e) => {
+            record_execution(&mut tx, &order.order_id, order.executions, order.next_run_at, None, Some(e.to_string()))
+                .await?;
+            sqlx::query!(
+                "UPDATE standing_orders SET status = $1, next_run_at = NULL WHERE order_id = $2",
+                ORDER_FAILED,
+                order.order_id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            tx.commit()
+                .await
+                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            return Ok(true);
+        }
+    };
+    let tx_id = terms.payload(state.chain_id).leg_tx_id(execution);
+    let entry = TransferEntry {
+        tx_id: tx_id.clone(),
+        token_id: terms.token_id,
+        from: terms.from_addr,
+        to: terms.to_addr,
+        amount: terms.amount,
+        fee: terms.fee,
+        nonce: 0, // taken from the sender's account when applied
+        signature,
+        valid_until: None,
+        order_id: Some(order.order_id.clone()),
+        multisig_version: None,
+    };
+
+    // Apply under a savepoint, so a failed execution leaves only its record
+    let mut attempt = tx.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let (applied, error): (Vec<Transaction>, Option<String>) =
+        match apply_at_next_nonce(&mut attempt, state, entry).await {
+            Ok(applied) => {
+                attempt
+                    .commit()
+                    .await
+                    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+                (applied, None)
+            }
+            // Database errors too: one this execution keeps hitting mustn't stall the others
+            Err(e) => {
+                attempt
+                    .rollback()
+                    .await
+                    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+                (Vec::new(), Some(e.to_string()))
+            }
+        };
+
+    let tx_id = error.is_none().then_some(tx_id);
+    record_execution(&mut tx, &order.order_id, order.executions, order.next_run_at, tx_id, error).await?;
+
+    let next = execution + 1;
+    let next_run_at = if next < terms.max_executions {
+        terms.due_at(next).and_then(|secs| DateTime::from_timestamp(secs, 0))
+    } else {
+        None
+    };
+    sqlx::query!(
+        r#"
+        UPDATE standing_orders
+        SET executions = executions + 1,
+            next_run_at = $1,
+            status = $2
+        WHERE order_id = $3
+        "#,
+        next_run_at,
+        if next_run_at.is_some() { ORDER_ACTIVE } else { ORDER_COMPLETED },
+        order.order_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    for transaction in applied {
+        broadcast_preconfirmed(state, transaction);
+    }
+    Ok(true)
+}
+
+/// Records the outcome of an order's execution: the transfer it made, or why it made none.
+async fn record_execution(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    order_id: &str,
+    execution: i32,
+    due_at: DateTime<Utc>,
+    tx_id: Option<String>,
+    error: Option<String>,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO standing_order_executions (order_id, execution, due_at, tx_id, error)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        order_id,
+        execution,
+        due_at,
+        tx_id,
+        error
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
@@ -0,0 +1,789 @@
+//! Two-phase settlement of preconfirmed transactions.
+//!
+//! The transaction handlers only move `pending_balance`. The proof generator
+//! ([`crate::prover`]) seals sequenced transactions into a batch with
+//! [`seal_batch`], proves it, and then either [`settle_batch`]es it, moving
+//! its effects into `balance`, or [`fail_batch`]es it, rolling its effects
+//! back out of `pending_balance` together with the later transactions that
+//! built on them.
+//! Batches must be settled in the order they were sealed. Once the proof is
+//! verified, [`finalize_batch`] makes its transactions final.
+//!
+//! Transaction statuses follow the moves [`crate::lifecycle`] allows.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bigdecimal::BigDecimal;
+use chrono::Utc;
+use usda_common::{
+    batch::BatchResult, Allowance, Amount, EscrowStatus, TokenId, Transaction, TransactionKind, TransactionStatus,
+    WebSocketMessage,
+};
+use uuid::Uuid;
+
+use crate::{
+    api::multisig::policy_from_columns,
+    error::AppError,
+    lifecycle::{self, from_status_column},
+    numeric::{from_numeric, from_token_column, to_numeric, to_token_column},
+    state::AppState,
+};
+
+/// Batch status while its proof is being generated
+const BATCH_PROCESSING: &str = "PROCESSING";
+const BATCH_COMPLETED: &str = "COMPLETED";
+const BATCH_FAILED: &str = "FAILED";
+
+/// Sequenced transactions assigned to a proof batch.
+#[derive(Debug)]
+pub struct SealedBatch {
+    pub batch_id: String,
+    /// In the order they have to be proven
+    pub tx_ids: Vec<String>,
+}
+
+/// Assigns up to `max_txs` of the earliest applied sequenced transactions to
+/// a new batch, moving them to `BATCHED`. Returns `None` when there is nothing to prove.
+///
+/// The legs of a batch transfer share one signature and settle or fail
+/// together, so they are always sealed into the same batch; a batch may
+/// exceed `max_txs` by the rest of its last batch transfer.
+pub async fn seal_batch(state: &AppState, max_txs: i64) -> Result<Option<SealedBatch>, AppError> {
+    let batch_id = Uuid::new_v4().to_string();
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT tx_id, group_id
+        FROM transactions
+        WHERE status = $1::TEXT::transaction_status AND batch_id IS NULL
+        ORDER BY seq
+        LIMIT $2
+        FOR UPDATE SKIP LOCKED
+        "#,
+        TransactionStatus::Sequenced.to_string(),
+        max_txs
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    if rows.is_empty() {
+        return Ok(None);
+    }
+    let group_ids: Vec<String> = rows.iter().filter_map(|row| row.group_id.clone()).collect();
+    let mut tx_ids: Vec<String> = rows.into_iter().map(|row| row.tx_id).collect();
+
+    // Pull in the legs of any batch transfer the limit cut short
+    if !group_ids.is_empty() {
+        tx_ids = sqlx::query_scalar!(
+            r#"
+            SELECT tx_id
+            FROM transactions
+            WHERE (tx_id = ANY($1) OR group_id = ANY($2))
+              AND status = $3::TEXT::transaction_status AND batch_id IS NULL
+            ORDER BY seq
+            FOR UPDATE
+            "#,
+            &tx_ids,
+            &group_ids,
+            TransactionStatus::Sequenced.to_string()
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    }
+
+    sqlx::query!(
+        "UPDATE transactions SET batch_id = $1 WHERE tx_id = ANY($2)",
+        batch_id,
+        &tx_ids
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    lifecycle::transition(&mut tx, &tx_ids, TransactionStatus::Batched, None).await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO proof_batches (batch_id, proof_data, transaction_count, timestamp, status)
+        VALUES ($1, '', $2, NOW(), $3)
+        "#,
+        batch_id,
+        tx_ids.len() as i32,
+        BATCH_PROCESSING
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(Some(SealedBatch { batch_id, tx_ids }))
+}
+
+/// The earliest sealed batch still awaiting its proof, which has to be
+/// settled or failed before any later one.
+pub async fn next_unproven_batch(state: &AppState) -> Result<Option<String>, AppError> {
+    let batch = sqlx::query!(
+        "SELECT batch_id FROM proof_batches WHERE status = $1 ORDER BY timestamp LIMIT 1",
+        BATCH_PROCESSING
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok(batch.map(|batch| batch.batch_id))
+}
+
+/// Records `proof` for the batch and moves its transactions to `PROVEN`.
+/// `result` holds the proof's public values, which must match what the
+/// ledger knows about the batch's signers.
+/// `vkey_hash` identifies the program proven, for transaction receipts.
+pub async fn settle_batch(
+    state: &AppState,
+    batch_id: &str,
+    proof: &[u8],
+    vkey_hash: &str,
+    result: &BatchResult,
+) -> Result<(), AppError> {
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    lock_processing_batch(&mut tx, batch_id).await?;
+    check_batch_result(&mut tx, state, batch_id, result).await?;
+
+    // An earlier batch's effects have to be in `balance` before this one's
+    let earlier = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM proof_batches
+        WHERE status = $1
+          AND timestamp < (SELECT timestamp FROM proof_batches WHERE batch_id = $2)
+        "#,
+        BATCH_PROCESSING,
+        batch_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if earlier.count > 0 {
+        return Err(AppError::InvalidInput(
+            "An earlier batch is still awaiting its proof".into(),
+        ));
+    }
+
+    let transactions = batch_transactions(&mut tx, batch_id).await?;
+    let fee_to = fee_recipients(&mut tx, &transactions).await?;
+    let mut touched = BTreeSet::new();
+    for transaction in &transactions {
+        for (address, delta) in balance_effects(transaction, &fee_to) {
+            sqlx::query!(
+                "UPDATE accounts SET balance = balance + $1 WHERE address = $2 AND token_id = $3",
+                &delta,
+                address.as_slice(),
+                to_token_column(transaction.token_id)
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            touched.insert((address, transaction.token_id));
+        }
+    }
+
+    finish_batch(&mut tx, batch_id, &transactions, TransactionStatus::Proven, None, BATCH_COMPLETED, proof).await?;
+    sqlx::query!(
+        "UPDATE proof_batches SET proven_at = NOW(), vkey_hash = $1 WHERE batch_id = $2",
+        vkey_hash,
+        batch_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Report the settled balances of every account the batch touched
+    let (addresses, token_ids): (Vec<Vec<u8>>, Vec<i64>) = touched
+        .into_iter()
+        .map(|(address, token_id)| (address.to_vec(), to_token_column(token_id)))
+        .unzip();
+    let balances = sqlx::query!(
+        r#"
+        SELECT address, token_id, balance
+        FROM accounts
+        JOIN UNNEST($1::BYTEA[], $2::BIGINT[]) AS touched (address, token_id)
+            USING (address, token_id)
+        "#,
+        &addresses,
+        &token_ids
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Sending only fails when nobody is subscribed
+    for mut transaction in transactions {
+        transaction.status = TransactionStatus::Proven;
+        let _ = state.ws_tx.send(WebSocketMessage::TransactionProven(transaction));
+    }
+    for row in balances {
+        if let (Ok(address), Ok(token_id), Ok(balance)) = (
+            row.address.try_into(),
+            from_token_column(row.token_id),
+            from_numeric(row.balance),
+        ) {
+            let _ = state.ws_tx.send(WebSocketMessage::BalanceUpdated {
+                address,
+                token_id,
+                balance,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves the transactions of a settled batch to `FINALIZED` once its proof
+/// has been verified.
+pub async fn finalize_batch(state: &AppState, batch_id: &str) -> Result<(), AppError> {
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    let batch = sqlx::query!(
+        "SELECT status, finalized_at FROM proof_batches WHERE batch_id = $1 FOR UPDATE",
+        batch_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound("Batch not found".into()))?;
+
+    if batch.status != BATCH_COMPLETED || batch.finalized_at.is_some() {
+        return Err(AppError::InvalidInput(format!(
+            "Batch {} is not awaiting finalization",
+            batch_id
+        )));
+    }
+
+    let tx_ids: Vec<String> = sqlx::query!(
+        "SELECT tx_id FROM transactions WHERE batch_id = $1",
+        batch_id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .into_iter()
+    .map(|row| row.tx_id)
+    .collect();
+    lifecycle::transition(&mut tx, &tx_ids, TransactionStatus::Finalized, None).await?;
+
+    sqlx::query!(
+        "UPDATE proof_batches SET finalized_at = NOW() WHERE batch_id = $1",
+        batch_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Marks the batch failed and rolls its transactions out of the pending
+/// balances, allowances and escrows they were applied to. Later transactions
+/// that can't stand without them, because they spent funds the batch
+/// credited or settled an escrow it locked, fail with it.
+pub async fn fail_batch(state: &AppState, batch_id: &str) -> Result<(), AppError> {
+    let mut tx = state.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    lock_processing_batch(&mut tx, batch_id).await?;
+
+    let transactions = batch_transactions(&mut tx, batch_id).await?;
+    let dependents = dependent_transactions(&mut tx, batch_id, &transactions).await?;
+    let fee_to = fee_recipients(&mut tx, transactions.iter().chain(&dependents)).await?;
+    let mut pending_deltas: BTreeMap<([u8; 32], TokenId), BigDecimal> = BTreeMap::new();
+    let mut allowances = Vec::new();
+    let mut escrows = Vec::new();
+    // Undo last to first, so an escrow claimed or refunded after it was
+    // locked is unwound before its lock
+    for transaction in transactions.iter().chain(&dependents).rev() {
+        for (address, delta) in balance_effects(transaction, &fee_to) {
+            *pending_deltas.entry((address, transaction.token_id)).or_default() -= delta;
+        }
+
+        // Failed transfers out of an allowance give it back, unless the
+        // owner has approved a new one since
+        if transaction.kind == TransactionKind::TransferFrom {
+            let debit = transaction
+                .amount
+                .checked_add(transaction.fee)
+                .ok_or(AppError::AmountOverflow)?;
+            let restored = sqlx::query!(
+                r#"
+                UPDATE allowances
+                SET remaining = allowances.remaining + $1,
+                    updated_at = NOW()
+                FROM transactions
+                WHERE transactions.tx_id = $2
+                  AND allowances.owner = transactions.from_addr
+                  AND allowances.spender = transactions.spender
+                  AND allowances.token_id = transactions.token_id
+                  AND allowances.approval_id = transactions.approval_id
+                RETURNING allowances.remaining, allowances.expires_at, allowances.nonce
+                "#,
+                to_numeric(debit),
+                transaction.tx_id
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+            if let (Some(row), Some(owner), Some(spender)) = (restored, transaction.from, transaction.spender) {
+                allowances.push(Allowance {
+                    owner,
+                    spender,
+                    token_id: transaction.token_id,
+                    remaining: from_numeric(row.remaining)?,
+                    expires_at: row.expires_at,
+                    nonce: row.nonce,
+                });
+            }
+        }
+
+        // A failed lock leaves the funds with the sender; a failed claim or
+        // refund puts them back into the escrow
+        let escrow_rollback = match transaction.kind {
+            TransactionKind::EscrowLock => Some((EscrowStatus::Locked, EscrowStatus::Refunded)),
+            TransactionKind::EscrowClaim => Some((EscrowStatus::Claimed, EscrowStatus::Locked)),
+            TransactionKind::EscrowRefund => Some((EscrowStatus::Refunded, EscrowStatus::Locked)),
+            _ => None,
+        };
+        if let Some((settled, restored)) = escrow_rollback {
+            let settled_at = (restored != EscrowStatus::Locked).then(Utc::now);
+            let rolled_back = sqlx::query!(
+                r#"
+                UPDATE escrows
+                SET status = $1,
+                    preimage = NULL,
+                    settled_at = $2
+                FROM transactions
+                WHERE transactions.tx_id = $3
+                  AND escrows.escrow_id = transactions.escrow_id
+                  AND escrows.status = $4
+                RETURNING escrows.escrow_id
+                "#,
+                restored.to_string(),
+                settled_at,
+                transaction.tx_id,
+                settled.to_string()
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            escrows.extend(rolled_back.map(|row| row.escrow_id));
+        }
+
+        // Failed mints and burns no longer count towards the token's supply
+        let (minted, burned) = match transaction.kind {
+            TransactionKind::Mint => (transaction.amount, Amount::ZERO),
+            TransactionKind::Burn => (Amount::ZERO, transaction.amount),
+            TransactionKind::Transfer
+            | TransactionKind::TransferFrom
+            | TransactionKind::EscrowLock
+            | TransactionKind::EscrowClaim
+            | TransactionKind::EscrowRefund
+            | TransactionKind::ScheduledTransfer
+            | TransactionKind::MultisigTransfer
+            | TransactionKind::Clawback => continue,
+        };
+        sqlx::query!(
+            r#"
+            UPDATE tokens
+            SET minted = minted - $1,
+                burned = burned - $2
+            WHERE token_id = $3
+            "#,
+            to_numeric(minted),
+            to_numeric(burned),
+            to_token_column(transaction.token_id)
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    }
+
+    // Net out each account's changes first: taking back a credit before the
+    // spends built on it are refunded would overdraw the account midway
+    for ((address, token_id), delta) in pending_deltas {
+        sqlx::query!(
+            r#"
+            UPDATE accounts
+            SET pending_balance = pending_balance + $1
+            WHERE address = $2 AND token_id = $3
+            "#,
+            delta,
+            address.as_slice(),
+            to_token_column(token_id)
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    }
+
+    let reason = format!("Proof batch {} failed", batch_id);
+    finish_batch(&mut tx, batch_id, &transactions, TransactionStatus::Failed, Some(&reason), BATCH_FAILED, &[]).await?;
+    let dependent_ids: Vec<String> = dependents.iter().map(|transaction| transaction.tx_id.clone()).collect();
+    let reason = format!("Depended on proof batch {}, which failed", batch_id);
+    lifecycle::transition(&mut tx, &dependent_ids, TransactionStatus::Failed, Some(&reason)).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    // Sending only fails when nobody is subscribed
+    for mut transaction in transactions.into_iter().chain(dependents) {
+        transaction.status = TransactionStatus::Failed;
+        let _ = state.ws_tx.send(WebSocketMessage::TransactionFailed(transaction));
+    }
+    for allowance in allowances {
+        let _ = state.ws_tx.send(WebSocketMessage::AllowanceUpdated(allowance));
+    }
+    for escrow_id in escrows {
+        if let Ok(Some(escrow)) = state.get_escrow(&escrow_id).await {
+            let _ = state.ws_tx.send(WebSocketMessage::EscrowUpdated(escrow));
+        }
+    }
+
+    Ok(())
+}
+
+async fn lock_processing_batch(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    batch_id: &str,
+) -> Result<(), AppError> {
+    let batch = sqlx::query!(
+        "SELECT status FROM proof_batches WHERE batch_id = $1 FOR UPDATE",
+        batch_id
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| AppError::NotFound("Batch not found".into()))?;
+
+    if batch.status != BATCH_PROCESSING {
+        return Err(AppError::InvalidInput(format!(
+            "Batch {} is not awaiting a proof",
+            batch_id
+        )));
+    }
+    Ok(())
+}
+
+/// Checks the keys and policies the proof verified signatures against. The
+/// program only shows that the entries were signed by them; whether they were
+/// the signers' is the ledger's to vouch for.
+async fn check_batch_result(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    state: &AppState,
+    batch_id: &str,
+    result: &BatchResult,
+) -> Result<(), AppError> {
+    if result.chain_id != state.chain_id {
+        return Err(AppError::InvalidInput(format!(
+            "Proof of batch {} is for chain {}",
+            batch_id, result.chain_id
+        )));
+    }
+
+    for (token_id, key) in &result.issuer_keys {
+        if state.issuer_key(*token_id).map(|issuer| issuer.to_bytes()) != Some(*key) {
+            return Err(AppError::InvalidInput(format!(
+                "Proof of batch {} checked token {} against a key that isn't its issuer's",
+                batch_id, token_id
+            )));
+        }
+    }
+
+    // Multisig transfers must have been checked against the policy version
+    // each was approved under
+    let rows = sqlx::query!(
+        r#"
+        SELECT DISTINCT policies.address, policies.threshold, policies.members
+        FROM transactions
+        JOIN multisig_policies AS policies
+            ON policies.address = transactions.from_addr AND policies.version = transactions.multisig_version
+        WHERE transactions.batch_id = $1
+        "#,
+        batch_id
+    )
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let approved = rows
+        .into_iter()
+        .map(|row| {
+            let address: [u8; 32] = row
+                .address
+                .try_into()
+                .map_err(|_| AppError::DatabaseError("Corrupt multisig policy".into()))?;
+            Ok((address, policy_from_columns(row.threshold, row.members)?.digest()))
+        })
+        .collect::<Result<BTreeSet<_>, AppError>>()?;
+    if result.multisig_policies.iter().copied().collect::<BTreeSet<_>>() != approved {
+        return Err(AppError::InvalidInput(format!(
+            "Proof of batch {} checked multisig transfers against policies they weren't approved under",
+            batch_id
+        )));
+    }
+
+    // Every other signer must have signed with a key its account held at some
+    // point; accounts that never registered one sign with their address
+    let (addresses, keys): (Vec<Vec<u8>>, Vec<Vec<u8>>) = result
+        .account_keys
+        .iter()
+        .map(|(address, key)| (address.to_vec(), key.to_vec()))
+        .unzip();
+    let unknown = sqlx::query!(
+        r#"
+        SELECT signers.address AS "address!"
+        FROM UNNEST($1::BYTEA[], $2::BYTEA[]) AS signers (address, public_key)
+        WHERE NOT EXISTS (
+            SELECT 1 FROM account_key_history AS history
+            WHERE history.address = signers.address AND history.public_key = signers.public_key
+        )
+        AND NOT (
+            signers.address = signers.public_key
+            AND NOT EXISTS (SELECT 1 FROM account_keys WHERE account_keys.address = signers.address)
+        )
+        LIMIT 1
+        "#,
+        &addresses,
+        &keys
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    if let Some(unknown) = unknown {
+        return Err(AppError::InvalidInput(format!(
+            "Proof of batch {} checked account {} against a key it never held",
+            batch_id,
+            hex::encode(unknown.address)
+        )));
+    }
+    Ok(())
+}
+
+async fn finish_batch(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    batch_id: &str,
+    transactions: &[Transaction],
+    tx_status: TransactionStatus,
+    reason: Option<&str>,
+    batch_status: &str,
+    proof: &[u8],
+) -> Result<(), AppError> {
+    let tx_ids: Vec<String> = transactions.iter().map(|transaction| transaction.tx_id.clone()).collect();
+    lifecycle::transition(tx, &tx_ids, tx_status, reason).await?;
+
+    sqlx::query!(
+        "UPDATE proof_batches SET status = $1, proof_data = $2 WHERE batch_id = $3",
+        batch_status,
+        proof,
+        batch_id
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Transactions of the batch awaiting its proof, in the order they were applied.
+async fn batch_transactions(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    batch_id: &str,
+) -> Result<Vec<Transaction>, AppError> {
+    let tx_ids = sqlx::query_scalar!(
+        "SELECT tx_id FROM transactions WHERE batch_id = $1 AND status = $2::TEXT::transaction_status",
+        batch_id,
+        TransactionStatus::Batched.to_string()
+    )
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    load_transactions(tx, &tx_ids).await
+}
+
+/// Unsettled transactions applied after the batch that depend on `failed`,
+/// its transactions: spends by an account they credited, settlements of an
+/// escrow they locked, and in turn whatever depends on those. Returned in
+/// the order they were applied, and locked.
+async fn dependent_transactions(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    batch_id: &str,
+    failed: &[Transaction],
+) -> Result<Vec<Transaction>, AppError> {
+    let unsettled = [TransactionStatus::Sequenced, TransactionStatus::Batched].map(|status| status.to_string());
+    let mut credited = BTreeSet::new();
+    let mut escrow_ids = BTreeSet::new();
+    let mut dependent_ids: Vec<String> = Vec::new();
+    let mut found = failed.to_vec();
+
+    while !found.is_empty() {
+        let fee_to = fee_recipients(tx, &found).await?;
+        for transaction in &found {
+            credited.extend(
+                balance_effects(transaction, &fee_to)
+                    .into_iter()
+                    .filter(|(_, delta)| *delta > BigDecimal::from(0))
+                    .map(|(address, _)| (address, transaction.token_id)),
+            );
+            // A lock's transaction ID is its escrow's ID
+            if transaction.kind == TransactionKind::EscrowLock {
+                escrow_ids.insert(transaction.tx_id.clone());
+            }
+        }
+
+        let (addresses, token_ids): (Vec<Vec<u8>>, Vec<i64>) = credited
+            .iter()
+            .map(|(address, token_id)| (address.to_vec(), to_token_column(*token_id)))
+            .unzip();
+        let escrows: Vec<String> = escrow_ids.iter().cloned().collect();
+        let tx_ids = sqlx::query_scalar!(
+            r#"
+            SELECT tx_id
+            FROM transactions
+            WHERE status = ANY($1::TEXT[]::transaction_status[])
+              AND batch_id IS DISTINCT FROM $2
+              AND tx_id <> ALL($3)
+              AND seq > (SELECT MIN(seq) FROM transactions WHERE batch_id = $2)
+              AND (
+                  (from_addr, token_id) IN (SELECT * FROM UNNEST($4::BYTEA[], $5::BIGINT[]))
+                  OR escrow_id = ANY($6)
+              )
+            FOR UPDATE
+            "#,
+            &unsettled,
+            batch_id,
+            &dependent_ids,
+            &addresses,
+            &token_ids,
+            &escrows
+        )
+        .fetch_all(&mut **tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        found = load_transactions(tx, &tx_ids).await?;
+        dependent_ids.extend(tx_ids);
+    }
+
+    load_transactions(tx, &dependent_ids).await
+}
+
+/// The transactions `tx_ids`, in the order they were applied.
+async fn load_transactions(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    tx_ids: &[String],
+) -> Result<Vec<Transaction>, AppError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            tx_id,
+            kind,
+            token_id,
+            from_addr as "from_addr?: Vec<u8>",
+            to_addr as "to_addr?: Vec<u8>",
+            spender,
+            amount,
+            fee,
+            nonce,
+            signature,
+            timestamp,
+            status::TEXT AS "status!",
+            redemption_ref,
+            group_id,
+            valid_until
+        FROM transactions
+        WHERE tx_id = ANY($1)
+        ORDER BY seq
+        "#,
+        tx_ids
+    )
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    rows.into_iter()
+        .map(|row| {
+            let corrupt = || AppError::DatabaseError(format!("Corrupt transaction {}", row.tx_id));
+            Ok(Transaction {
+                kind: row.kind.parse().map_err(|_| corrupt())?,
+                token_id: from_token_column(row.token_id)?,
+                from: row.from_addr.map(|addr| addr.try_into()).transpose().map_err(|_| corrupt())?,
+                to: row.to_addr.map(|addr| addr.try_into()).transpose().map_err(|_| corrupt())?,
+                spender: row.spender.map(|addr| addr.try_into()).transpose().map_err(|_| corrupt())?,
+                amount: from_numeric(row.amount)?,
+                fee: from_numeric(row.fee)?,
+                nonce: row.nonce,
+                signature: row.signature[..].try_into().map_err(|_| corrupt())?,
+                timestamp: row.timestamp,
+                status: from_status_column(&row.status)?,
+                redemption_ref: row.redemption_ref.clone(),
+                group_id: row.group_id.clone(),
+                valid_until: row.valid_until,
+                tx_id: row.tx_id,
+            })
+        })
+        .collect()
+}
+
+/// Accounts the fees of `transactions` were credited to when they were
+/// sequenced, by transaction ID.
+async fn fee_recipients<'a>(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    transactions: impl IntoIterator<Item = &'a Transaction>,
+) -> Result<BTreeMap<String, [u8; 32]>, AppError> {
+    let tx_ids: Vec<String> = transactions.into_iter().map(|transaction| transaction.tx_id.clone()).collect();
+    let rows = sqlx::query!(
+        r#"SELECT tx_id, fee_to AS "fee_to!" FROM transactions WHERE tx_id = ANY($1) AND fee_to IS NOT NULL"#,
+        &tx_ids
+    )
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    rows.into_iter()
+        .map(|row| {
+            let fee_to = row
+                .fee_to
+                .try_into()
+                .map_err(|_| AppError::DatabaseError(format!("Corrupt transaction {}", row.tx_id)))?;
+            Ok((row.tx_id, fee_to))
+        })
+        .collect()
+}
+
+/// Per-account balance changes of a transaction, all in its token: the sender
+/// pays amount and fee, the receiver gets the amount and the account in
+/// `fee_to` that collected it the fee. Deltas are signed, so they are
+/// `NUMERIC` rather than [`Amount`].
+fn balance_effects(transaction: &Transaction, fee_to: &BTreeMap<String, [u8; 32]>) -> Vec<([u8; 32], BigDecimal)> {
+    let amount = to_numeric(transaction.amount);
+    let fee = to_numeric(transaction.fee);
+    let mut effects = Vec::with_capacity(3);
+    if let Some(from) = transaction.from {
+        effects.push((from, -(&amount + &fee)));
+    }
+    if let Some(to) = transaction.to {
+        effects.push((to, amount));
+    }
+    if let (Some(fee_to), false) = (fee_to.get(&transaction.tx_id), transaction.fee.is_zero()) {
+        effects.push((*fee_to, fee));
+    }
+    effects
+}
@@ -1,12 +1,53 @@
+use ed25519_dalek::VerifyingKey;
 use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+use std::time::Duration;
 use tokio::sync::broadcast;
-use usda_common::{Account, WebSocketMessage};
+use usda_common::{
+    account_address, signing::DEFAULT_CHAIN_ID, Account, Escrow, FeePolicy, SpendingLimits, TokenId, TokenMetadata,
+    WebSocketMessage, DEFAULT_TOKEN_ID,
+};
 
-use crate::error::AppError;
+use crate::{
+    error::AppError,
+    numeric::{from_numeric, from_token_column, to_token_column},
+};
+
+/// How far ahead of an account's nonce a transfer may be queued by default
+pub const DEFAULT_MAX_NONCE_GAP: i64 = 16;
+
+/// How long a queued transfer waits for the missing nonces by default
+pub const DEFAULT_QUEUE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long a guardian recovery waits for the owner's veto by default
+pub const DEFAULT_RECOVERY_DELAY: Duration = Duration::from_secs(48 * 60 * 60);
 
 pub struct AppState {
     pub db: PgPool,
     pub ws_tx: broadcast::Sender<WebSocketMessage>,
+    /// Deployment ID bound into every signed payload
+    pub chain_id: u64,
+    /// Key allowed to sign mint requests of each token; a token can't be
+    /// minted while its key is unset
+    issuer_keys: RwLock<HashMap<TokenId, VerifyingKey>>,
+    /// Minimum fee charged on transfers
+    pub fee_policy: FeePolicy,
+    /// Account credited with collected fees; fees are refused while unset
+    pub treasury: Option<[u8; 32]>,
+    /// Largest nonce gap a transfer may be queued across; 0 disables queueing
+    pub max_nonce_gap: i64,
+    /// How long a queued transfer is kept before it's dropped
+    pub queue_timeout: Duration,
+    /// How long a guardian recovery waits before it replaces the account's
+    /// key; the owner can veto it until then
+    pub recovery_delay: Duration,
+    /// Tokens the ledger accepts, with the name, symbol and decimals used to
+    /// display and parse their amounts
+    tokens: BTreeMap<TokenId, TokenMetadata>,
+    /// Limits of accounts the issuer hasn't set limits for, per token;
+    /// tokens without an entry have none
+    default_limits: HashMap<TokenId, SpendingLimits>,
 }
 
 impl AppState {
@@ -15,53 +56,210 @@ impl AppState {
         Self {
             db,
             ws_tx,
+            chain_id: DEFAULT_CHAIN_ID,
+            issuer_keys: RwLock::new(HashMap::new()),
+            fee_policy: FeePolicy::default(),
+            treasury: None,
+            max_nonce_gap: DEFAULT_MAX_NONCE_GAP,
+            queue_timeout: DEFAULT_QUEUE_TIMEOUT,
+            recovery_delay: DEFAULT_RECOVERY_DELAY,
+            tokens: BTreeMap::from([(DEFAULT_TOKEN_ID, TokenMetadata::default())]),
+            default_limits: HashMap::new(),
         }
     }
 
-    pub async fn create_account(&self, public_key: [u8; 32]) -> Result<Account, AppError> {
-        let account = sqlx::query_as!(
-            Account,
+    pub fn with_chain_id(mut self, chain_id: u64) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
+    pub fn with_fee_policy(mut self, fee_policy: FeePolicy) -> Self {
+        self.fee_policy = fee_policy;
+        self
+    }
+
+    pub fn with_treasury(mut self, treasury: [u8; 32]) -> Self {
+        self.treasury = Some(treasury);
+        self
+    }
+
+    pub fn with_max_nonce_gap(mut self, max_nonce_gap: i64) -> Self {
+        self.max_nonce_gap = max_nonce_gap;
+        self
+    }
+
+    pub fn with_queue_timeout(mut self, queue_timeout: Duration) -> Self {
+        self.queue_timeout = queue_timeout;
+        self
+    }
+
+    pub fn with_recovery_delay(mut self, recovery_delay: Duration) -> Self {
+        self.recovery_delay = recovery_delay;
+        self
+    }
+
+    /// Adds a token to the ledger, or replaces the metadata of `token_id`.
+    pub fn with_token(mut self, token_id: TokenId, token: TokenMetadata) -> Self {
+        self.tokens.insert(token_id, token);
+        self
+    }
+
+    /// Sets the limits of `token_id`'s accounts that have none of their own.
+    pub fn with_default_limits(mut self, token_id: TokenId, limits: SpendingLimits) -> Self {
+        self.default_limits.insert(token_id, limits);
+        self
+    }
+
+    pub fn default_limits(&self, token_id: TokenId) -> SpendingLimits {
+        self.default_limits.get(&token_id).copied().unwrap_or_default()
+    }
+
+    /// Metadata of `token_id`, if the ledger accepts it.
+    pub fn token(&self, token_id: TokenId) -> Result<&TokenMetadata, AppError> {
+        self.tokens
+            .get(&token_id)
+            .ok_or(AppError::UnknownToken(token_id))
+    }
+
+    /// Every accepted token, ordered by ID.
+    pub fn tokens(&self) -> impl Iterator<Item = (TokenId, &TokenMetadata)> {
+        self.tokens.iter().map(|(token_id, token)| (*token_id, token))
+    }
+
+    pub fn set_issuer_key(&self, token_id: TokenId, key: VerifyingKey) {
+        self.issuer_keys.write().unwrap().insert(token_id, key);
+    }
+
+    pub fn issuer_key(&self, token_id: TokenId) -> Option<VerifyingKey> {
+        self.issuer_keys.read().unwrap().get(&token_id).copied()
+    }
+
+    /// Creates the `token_id` account of the address derived from
+    /// `public_key`, which signs for it until the account rotates its key.
+    /// Accounts of the same address in other tokens share its key. An account
+    /// that was paid before it was created is kept as it is.
+    pub async fn create_account(
+        &self,
+        public_key: [u8; 32],
+        token_id: TokenId,
+    ) -> Result<Account, AppError> {
+        self.token(token_id)?;
+        let address = account_address(&public_key);
+        let mut tx = self.db.begin().await.map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        let registered = sqlx::query!(
+            r#"
+            INSERT INTO account_keys (address, public_key)
+            VALUES ($1, $2)
+            ON CONFLICT (address) DO NOTHING
+            "#,
+            address.as_slice(),
+            public_key.as_slice()
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        if registered.rows_affected() > 0 {
+            sqlx::query!(
+                r#"
+                INSERT INTO account_key_history (address, version, public_key)
+                VALUES ($1, 0, $2)
+                "#,
+                address.as_slice(),
+                public_key.as_slice()
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        }
+
+        let row = sqlx::query!(
             r#"
-            INSERT INTO accounts (address, balance, pending_balance, nonce, created_at)
-            VALUES ($1, $2, $3, $4, NOW())
-            RETURNING 
-                address as "address!: [u8; 32]",
-                balance as "balance!: i64",
-                pending_balance as "pending_balance!: i64",
-                nonce as "nonce!: i64",
-                created_at as "created_at!"
+            INSERT INTO accounts (address, token_id, balance, pending_balance, nonce, created_at)
+            VALUES ($1, $2, 0, 0, 0, NOW())
+            ON CONFLICT (address, token_id) DO UPDATE SET address = accounts.address
+            RETURNING balance, pending_balance, nonce, created_at
             "#,
-            &public_key[..],
-            0_i64,
-            0_i64,
-            0_i64
+            address.as_slice(),
+            to_token_column(token_id)
         )
-        .fetch_one(&self.db)
+        .fetch_one(&mut *tx)
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-        Ok(account)
+        tx.commit()
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        Ok(Account {
+            address,
+            token_id,
+            balance: from_numeric(row.balance)?,
+            pending_balance: from_numeric(row.pending_balance)?,
+            nonce: row.nonce,
+            created_at: row.created_at,
+        })
     }
 
-    pub async fn get_account(&self, address: &[u8; 32]) -> Result<Option<Account>, AppError> {
-        let account = sqlx::query_as!(
-            Account,
+    pub async fn get_account(
+        &self,
+        address: &[u8; 32],
+        token_id: TokenId,
+    ) -> Result<Option<Account>, AppError> {
+        let row = sqlx::query!(
             r#"
-            SELECT 
-                address as "address!: [u8; 32]",
-                balance as "balance!: i64",
-                pending_balance as "pending_balance!: i64",
-                nonce as "nonce!: i64",
-                created_at as "created_at!"
+            SELECT balance, pending_balance, nonce, created_at
             FROM accounts
-            WHERE address = $1
+            WHERE address = $1 AND token_id = $2
+            "#,
+            address.as_ref(),
+            to_token_column(token_id)
+        )
+        .fetch_optional(&self.db)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+        row.map(|row| {
+            Ok(Account {
+                address: *address,
+                token_id,
+                balance: from_numeric(row.balance)?,
+                pending_balance: from_numeric(row.pending_balance)?,
+                nonce: row.nonce,
+                created_at: row.created_at,
+            })
+        })
+        .transpose()
+    }
+
+    pub async fn get_escrow(&self, escrow_id: &str) -> Result<Option<Escrow>, AppError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT token_id, sender, recipient, amount, hashlock, timeout, status, preimage
+            FROM escrows
+            WHERE escrow_id = $1
             "#,
-            address.as_ref()
+            escrow_id
         )
         .fetch_optional(&self.db)
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-        Ok(account)
+        row.map(|row| {
+            let corrupt = || AppError::DatabaseError(format!("Corrupt escrow {}", escrow_id));
+            Ok(Escrow {
+                escrow_id: escrow_id.to_string(),
+                token_id: from_token_column(row.token_id)?,
+                sender: row.sender.try_into().map_err(|_| corrupt())?,
+                recipient: row.recipient.try_into().map_err(|_| corrupt())?,
+                amount: from_numeric(row.amount)?,
+                hashlock: row.hashlock.try_into().map_err(|_| corrupt())?,
+                timeout: row.timeout,
+                status: row.status.parse().map_err(|_| corrupt())?,
+                preimage: row.preimage.map(|preimage| preimage.try_into()).transpose().map_err(|_| corrupt())?,
+            })
+        })
+        .transpose()
     }
 }
@@ -1,33 +1,44 @@
 use super::*;
-use crate::api::account::{create, get_balance, CreateAccountRequest};
-use axum::{extract::Path, Json};
-use ed25519_dalek::SigningKey;
-use rand::{rngs::OsRng, RngCore};
+use crate::api::account::{create, get_balance, get_key, get_transactions, CreateAccountRequest};
+use crate::error::AppError;
+use crate::api::token::TokenQuery;
+use axum::{
+    extract::{Path, Query},
+    Json,
+};
+use usda_common::account_address;
 
 #[tokio::test]
 async fn test_create_account() {
     let state = setup_test_state().await;
     
     // Generate a keypair
-    let mut secret = [0u8; 32];
-    OsRng.fill_bytes(&mut secret);
-    let signing_key = SigningKey::from_bytes(&secret);
-    let address = signing_key.verifying_key().to_bytes();
+    let public_key = new_key().verifying_key().to_bytes();
     
     // Create account request
     let req = Json(CreateAccountRequest {
-        public_key: address,
+        token_id: DEFAULT_TOKEN_ID,
+        public_key,
     });
     
     // Create account
-    let _response = create(axum::extract::State(state.clone()), req)
+    let response = create(axum::extract::State(state.clone()), req)
         .await
         .expect("Failed to create account");
     
+    // The address is derived from the key, which is registered to sign for it
+    let address = account_address(&public_key);
+    assert_eq!(response.0.address, address);
+    let key = get_key(axum::extract::State(state.clone()), Path(hex::encode(address)))
+        .await
+        .expect("Failed to get account key")
+        .0;
+    assert_eq!((key.public_key, key.version), (hex::encode(public_key), 0));
+    
     // Verify account was created
     let account = sqlx::query!(
         r#"
-        SELECT address, balance, pending_balance
+        SELECT address, balance::BIGINT AS "balance!", pending_balance::BIGINT AS "pending_balance!"
         FROM accounts
         WHERE address = $1
         "#,
@@ -45,36 +56,97 @@ async fn test_create_account() {
 async fn test_get_balance() {
     let state = setup_test_state().await;
     
-    // Generate a keypair
-    let mut secret = [0u8; 32];
-    OsRng.fill_bytes(&mut secret);
-    let signing_key = SigningKey::from_bytes(&secret);
-    let address = signing_key.verifying_key().to_bytes();
-    
-    // Insert account with balance
-    sqlx::query!(
-        r#"
-        INSERT INTO accounts (address, balance, pending_balance, nonce, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        "#,
-        address.as_slice(),
-        1000_i64,
-        1000_i64,
-        0_i64
-    )
-    .execute(&state.db)
-    .await
-    .expect("Failed to insert account");
+    // Create an account with a balance
+    let address = setup_account(&state, 1000).await.address;
     
     // Get balance
     let response = get_balance(
         axum::extract::State(state.clone()),
         Path(address),
+        Query(TokenQuery::default()),
     )
     .await
     .expect("Failed to get balance");
     
     // Verify balance
-    assert_eq!(response.0.balance, 1000);
-    assert_eq!(response.0.pending_balance, 1000);
+    assert_eq!(response.0.balance, Amount::new(1000));
+    assert_eq!(response.0.pending_balance, Amount::new(1000));
+}
+
+#[tokio::test]
+async fn test_create_account_after_it_was_paid() {
+    let state = setup_test_state().await;
+    let payer = setup_account(&state, 1000).await;
+    let bob = setup_account(&state, 0).await;
+
+    // Funds reach the derived address before its owner creates the account
+    let key = new_key();
+    let public_key = key.verifying_key().to_bytes();
+    let address = account_address(&public_key);
+    send(&state, &payer, address, 300, 0, 0).await;
+
+    let response = create(
+        axum::extract::State(state.clone()),
+        Json(CreateAccountRequest {
+            token_id: DEFAULT_TOKEN_ID,
+            public_key,
+        }),
+    )
+    .await
+    .expect("Failed to create account");
+    assert_eq!(response.0.address, address);
+    assert_eq!(response.0.pending_balance, Amount::new(300));
+
+    // The key is registered, so the owner can spend what they were paid
+    let registered = get_key(axum::extract::State(state.clone()), Path(hex::encode(address)))
+        .await
+        .expect("Failed to get account key")
+        .0;
+    assert_eq!((registered.public_key, registered.version), (hex::encode(public_key), 0));
+    let owner = TestAccount { key, address };
+    send(&state, &owner, bob.address, 100, 0, 0).await;
+    assert_eq!(pending_balance(&state, address).await, Amount::new(200));
+    assert_eq!(pending_balance(&state, bob.address).await, Amount::new(100));
+}
+
+#[tokio::test]
+async fn test_transaction_history_reports_corrupt_rows() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let receiver = setup_account(&state, 0).await;
+    let tx_id = send(&state, &sender, receiver.address, 100, 0, 0).await;
+
+    let history = get_transactions(
+        axum::extract::State(state.clone()),
+        Path(sender.address),
+        Query(TokenQuery::default()),
+    )
+    .await
+    .expect("Failed to get transactions");
+    assert_eq!(history.0.len(), 1);
+
+    // An unknown kind isn't passed off as a transfer
+    sqlx::query!("UPDATE transactions SET kind = 'TELEPORT' WHERE tx_id = $1", tx_id)
+        .execute(&state.db)
+        .await
+        .unwrap();
+    let result = get_transactions(
+        axum::extract::State(state.clone()),
+        Path(sender.address),
+        Query(TokenQuery::default()),
+    )
+    .await;
+    assert!(matches!(result, Err(AppError::DatabaseError(_))));
+
+    sqlx::query!("UPDATE transactions SET kind = 'TRANSFER', spender = '\\x01' WHERE tx_id = $1", tx_id)
+        .execute(&state.db)
+        .await
+        .unwrap();
+    let result = get_transactions(
+        axum::extract::State(state.clone()),
+        Path(sender.address),
+        Query(TokenQuery::default()),
+    )
+    .await;
+    assert!(matches!(result, Err(AppError::DatabaseError(_))));
 }
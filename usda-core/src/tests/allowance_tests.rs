@@ -0,0 +1,215 @@
+use super::*;
+use crate::api::account::get_allowances;
+use crate::api::token::TokenQuery;
+use crate::api::transaction::{approve, transfer_from, ApproveRequest, TransferFromRequest};
+use crate::error::AppError;
+use crate::settlement::{fail_batch, seal_batch};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::Utc;
+use usda_common::{Allowance, SignablePayload, TransactionKind, WebSocketMessage};
+
+fn signed_approve(
+    state: &AppState,
+    owner: &TestAccount,
+    spender: [u8; 32],
+    limit: i64,
+    expiry: Option<i64>,
+    nonce: i64,
+) -> Json<ApproveRequest> {
+    let owner_bytes = owner.address;
+    let payload = SignablePayload::approve(state.chain_id, owner_bytes, spender, tokens(limit), expiry, nonce);
+
+    Json(ApproveRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        owner: hex::encode(owner_bytes),
+        spender: hex::encode(spender),
+        limit: tokens(limit),
+        expiry,
+        nonce,
+        signature: hex::encode(payload.sign(&owner.key)),
+    })
+}
+
+fn signed_transfer_from(
+    state: &AppState,
+    spender: &TestAccount,
+    owner: [u8; 32],
+    to: [u8; 32],
+    amount: i64,
+    nonce: i64,
+) -> Json<TransferFromRequest> {
+    let spender_bytes = spender.address;
+    let payload = SignablePayload::transfer_from(
+        state.chain_id,
+        spender_bytes,
+        owner,
+        to,
+        tokens(amount),
+        Amount::ZERO,
+        nonce,
+    );
+
+    Json(TransferFromRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        spender: hex::encode(spender_bytes),
+        owner: hex::encode(owner),
+        to: hex::encode(to),
+        amount: tokens(amount).into(),
+        fee: Amount::ZERO.into(),
+        nonce,
+        signature: hex::encode(payload.sign(&spender.key)),
+    })
+}
+
+async fn allowance_of(state: &Arc<AppState>, owner: [u8; 32], spender: [u8; 32]) -> Option<Allowance> {
+    get_allowances(State(state.clone()), Path(owner), Query(TokenQuery::default()))
+        .await
+        .expect("Failed to list allowances")
+        .0
+        .into_iter()
+        .find(|allowance| allowance.spender == spender)
+}
+
+#[tokio::test]
+async fn test_transfer_from_spends_allowance() {
+    let state = setup_test_state().await;
+    let owner = setup_account(&state, 1000).await;
+    let owner_bytes = owner.address;
+    let spender = setup_account(&state, 0).await;
+    let spender_bytes = spender.address;
+    let receiver = new_key().verifying_key().to_bytes();
+
+    let mut ws_rx = state.ws_tx.subscribe();
+    let approved = approve(State(state.clone()), signed_approve(&state, &owner, spender_bytes, 300, None, 0))
+        .await
+        .expect("Failed to approve");
+    assert_eq!((approved.0.remaining, approved.0.nonce), (tokens(300), 0));
+    match ws_rx.try_recv() {
+        Ok(WebSocketMessage::AllowanceUpdated(allowance)) => assert_eq!(allowance, approved.0),
+        other => panic!("Expected an allowance update, got {:?}", other),
+    }
+
+    // Approving uses up the owner's nonce, so the same approval can't be replayed
+    let owner_account = state.get_account(&owner_bytes, DEFAULT_TOKEN_ID).await.unwrap().unwrap();
+    assert_eq!(owner_account.nonce, 1);
+    let resubmitted = approve(State(state.clone()), signed_approve(&state, &owner, spender_bytes, 300, None, 0))
+        .await
+        .expect("Resubmitted approval should succeed");
+    assert_eq!(resubmitted.0, approved.0);
+
+    let sent = transfer_from(State(state.clone()), signed_transfer_from(&state, &spender, owner_bytes, receiver, 200, 0))
+        .await
+        .expect("Failed to transfer from allowance");
+    assert_eq!(pending_balance(&state, owner_bytes).await, tokens(800));
+    assert_eq!(pending_balance(&state, receiver).await, tokens(200));
+
+    let allowance = allowance_of(&state, owner_bytes, spender_bytes).await.unwrap();
+    assert_eq!((allowance.remaining, allowance.nonce), (tokens(100), 1));
+
+    // The owner is the sender of record, the spender is kept alongside
+    let record = sqlx::query!(
+        "SELECT kind, from_addr, spender FROM transactions WHERE tx_id = $1",
+        sent.0.tx_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(record.kind, TransactionKind::TransferFrom.to_string());
+    assert_eq!(record.from_addr, Some(owner_bytes.to_vec()));
+    assert_eq!(record.spender, Some(spender_bytes.to_vec()));
+
+    // More than what's left is refused, even though the owner could pay
+    let result = transfer_from(State(state.clone()), signed_transfer_from(&state, &spender, owner_bytes, receiver, 150, 1)).await;
+    assert!(matches!(result, Err(AppError::InsufficientAllowance)));
+
+    // A spent signature can't be used again
+    let result = transfer_from(State(state.clone()), signed_transfer_from(&state, &spender, owner_bytes, receiver, 50, 0)).await;
+    assert!(matches!(result, Err(AppError::InvalidNonce)));
+
+    // Re-approving replaces the limit but keeps the spender's nonce
+    let replaced = approve(State(state.clone()), signed_approve(&state, &owner, spender_bytes, 500, None, 1))
+        .await
+        .expect("Failed to re-approve");
+    assert_eq!((replaced.0.remaining, replaced.0.nonce), (tokens(500), 1));
+}
+
+#[tokio::test]
+async fn test_transfer_from_requires_valid_allowance() {
+    let state = setup_test_state().await;
+    let owner = setup_account(&state, 1000).await;
+    let owner_bytes = owner.address;
+    let spender = setup_account(&state, 0).await;
+    let spender_bytes = spender.address;
+    let receiver = new_key().verifying_key().to_bytes();
+
+    // No allowance yet
+    let result = transfer_from(State(state.clone()), signed_transfer_from(&state, &spender, owner_bytes, receiver, 10, 0)).await;
+    assert!(matches!(result, Err(AppError::InsufficientAllowance)));
+
+    // Owners can't approve themselves
+    let result = approve(State(state.clone()), signed_approve(&state, &owner, owner_bytes, 100, None, 0)).await;
+    assert!(matches!(result, Err(AppError::InvalidInput(_))));
+
+    // Only the owner can grant an allowance on their account
+    let mut forged = signed_approve(&state, &spender, spender_bytes, 100, None, 0);
+    forged.owner = hex::encode(owner_bytes);
+    let result = approve(State(state.clone()), forged).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+
+    // Only the spender can draw on it
+    let soon = Utc::now().timestamp() + 2;
+    let approved = approve(State(state.clone()), signed_approve(&state, &owner, spender_bytes, 100, Some(soon), 0))
+        .await
+        .expect("Failed to approve");
+    assert_eq!(approved.0.expires_at.map(|expires_at| expires_at.timestamp()), Some(soon));
+    let stranger = setup_account(&state, 0).await;
+    let result = transfer_from(State(state.clone()), signed_transfer_from(&state, &stranger, owner_bytes, receiver, 10, 0)).await;
+    assert!(matches!(result, Err(AppError::InsufficientAllowance)));
+    let mut forged = signed_transfer_from(&state, &stranger, owner_bytes, receiver, 10, 0);
+    forged.spender = hex::encode(spender_bytes);
+    let result = transfer_from(State(state.clone()), forged).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+
+    // Nothing can be drawn after the allowance expires
+    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+    let result = transfer_from(State(state.clone()), signed_transfer_from(&state, &spender, owner_bytes, receiver, 10, 0)).await;
+    assert!(matches!(result, Err(AppError::AllowanceExpired)));
+    assert_eq!(pending_balance(&state, owner_bytes).await, tokens(1000));
+}
+
+#[tokio::test]
+async fn test_failed_batch_restores_allowance() {
+    let state = setup_test_state().await;
+    let owner = setup_account(&state, 1000).await;
+    let owner_bytes = owner.address;
+    let spender = setup_account(&state, 0).await;
+    let spender_bytes = spender.address;
+    let receiver = new_key().verifying_key().to_bytes();
+
+    let approved = approve(State(state.clone()), signed_approve(&state, &owner, spender_bytes, 300, None, 0))
+        .await
+        .expect("Failed to approve");
+    let sent = transfer_from(State(state.clone()), signed_transfer_from(&state, &spender, owner_bytes, receiver, 200, 0))
+        .await
+        .expect("Failed to transfer from allowance");
+
+    let batch = seal_batch(&state, 100).await.unwrap().expect("Expected a batch");
+    assert_eq!(batch.tx_ids, vec![sent.0.tx_id]);
+    let mut ws_rx = state.ws_tx.subscribe();
+    fail_batch(&state, &batch.batch_id).await.expect("Failed to fail batch");
+
+    assert_eq!(pending_balance(&state, owner_bytes).await, tokens(1000));
+    let allowance = allowance_of(&state, owner_bytes, spender_bytes).await.unwrap();
+    assert_eq!((allowance.remaining, allowance.nonce), (approved.0.remaining, 1));
+
+    let restored = std::iter::from_fn(|| ws_rx.try_recv().ok())
+        .find_map(|message| match message {
+            WebSocketMessage::AllowanceUpdated(allowance) => Some(allowance),
+            _ => None,
+        })
+        .expect("Expected an allowance update");
+    assert_eq!(restored, allowance);
+}
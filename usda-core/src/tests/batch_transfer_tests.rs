@@ -0,0 +1,175 @@
+use super::*;
+use crate::api::transaction::{batch_transfer, BatchTransferRequest, TransferLeg};
+use crate::error::AppError;
+use crate::settlement::{fail_batch, seal_batch};
+use axum::{extract::State, Json};
+use rand::{rngs::OsRng, RngCore};
+use usda_common::SignablePayload;
+
+fn random_address() -> [u8; 32] {
+    let mut address = [0u8; 32];
+    OsRng.fill_bytes(&mut address);
+    address
+}
+
+fn signed_request(
+    state: &AppState,
+    sender: &TestAccount,
+    legs: &[([u8; 32], i64)],
+    fee: i64,
+    nonce: i64,
+) -> Json<BatchTransferRequest> {
+    let from = sender.address;
+    let signed_legs = legs.iter().map(|(to, amount)| (*to, tokens(*amount))).collect();
+    let payload =
+        SignablePayload::batch_transfer(state.chain_id, from, signed_legs, tokens(fee), nonce);
+    
+    Json(BatchTransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: hex::encode(from),
+        legs: legs
+            .iter()
+            .map(|(to, amount)| TransferLeg {
+                to: hex::encode(to),
+                amount: tokens(*amount),
+            })
+            .collect(),
+        fee: tokens(fee),
+        nonce,
+        signature: hex::encode(payload.sign(&sender.key)),
+    })
+}
+
+#[tokio::test]
+async fn test_batch_transfer() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    
+    let legs = vec![
+        (random_address(), 100),
+        (random_address(), 200),
+        (random_address(), 300),
+    ];
+    
+    let req = signed_request(&state, &sender, &legs, 3, 0);
+    let response = batch_transfer(State(state.clone()), req)
+        .await
+        .expect("Failed to execute batch transfer");
+    assert_eq!(response.0.tx_ids.len(), legs.len());
+    
+    // Sender is debited once for every leg plus the fee
+    let sender_account = sqlx::query!(
+        r#"SELECT pending_balance::BIGINT AS "pending_balance!", nonce FROM accounts WHERE address = $1"#,
+        sender_bytes.as_slice()
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(sender_account.pending_balance, 1000 - 600 - 3);
+    assert_eq!(sender_account.nonce, 1);
+    
+    for (to, amount) in &legs {
+        let receiver = sqlx::query!(
+            r#"SELECT pending_balance::BIGINT AS "pending_balance!" FROM accounts WHERE address = $1"#,
+            to.as_slice()
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(receiver.pending_balance, *amount);
+    }
+    
+    // Every leg is linked by the group ID
+    let rows = sqlx::query!(
+        "SELECT tx_id, leg_index FROM transactions WHERE group_id = $1 ORDER BY leg_index",
+        response.0.group_id
+    )
+    .fetch_all(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(rows.len(), legs.len());
+    for (i, row) in rows.iter().enumerate() {
+        assert_eq!(row.tx_id, response.0.tx_ids[i]);
+        assert_eq!(row.leg_index, Some(i as i32));
+    }
+}
+
+#[tokio::test]
+async fn test_batch_transfer_is_sealed_whole() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let receiver = random_address();
+
+    let single = send(&state, &sender, receiver, 50, 0, 0).await;
+    let legs = vec![
+        (random_address(), 100),
+        (random_address(), 200),
+        (random_address(), 300),
+    ];
+    let response = batch_transfer(State(state.clone()), signed_request(&state, &sender, &legs, 0, 1))
+        .await
+        .expect("Failed to execute batch transfer");
+
+    // The limit cuts into the batch transfer, whose remaining legs come along
+    let batch = seal_batch(&state, 2).await.unwrap().expect("Expected a batch");
+    let mut expected = vec![single];
+    expected.extend(response.0.tx_ids.iter().cloned());
+    assert_eq!(batch.tx_ids, expected);
+    assert!(seal_batch(&state, 2).await.unwrap().is_none());
+
+    // Failing the batch rolls back every leg
+    fail_batch(&state, &batch.batch_id).await.expect("Failed to fail batch");
+    assert_eq!(pending_balance(&state, sender.address).await, Amount::new(1000));
+    for (to, _) in legs.iter().chain([(receiver, 50)].iter()) {
+        assert_eq!(pending_balance(&state, *to).await, Amount::ZERO);
+    }
+}
+
+#[tokio::test]
+async fn test_batch_transfer_is_atomic() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 500).await;
+    let sender_bytes = sender.address;
+    
+    // The second leg pushes the total past the sender's balance
+    let legs = vec![(random_address(), 300), (random_address(), 300)];
+    
+    let req = signed_request(&state, &sender, &legs, 0, 0);
+    let result = batch_transfer(State(state.clone()), req).await;
+    assert!(matches!(result, Err(AppError::InsufficientBalance)));
+    
+    let sender_account = sqlx::query!(
+        r#"SELECT pending_balance::BIGINT AS "pending_balance!", nonce FROM accounts WHERE address = $1"#,
+        sender_bytes.as_slice()
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(sender_account.pending_balance, 500);
+    assert_eq!(sender_account.nonce, 0);
+    
+    let count = sqlx::query!(
+        "SELECT COUNT(*) AS count FROM transactions WHERE from_addr = $1",
+        sender_bytes.as_slice()
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(count.count, Some(0));
+}
+
+#[tokio::test]
+async fn test_batch_transfer_tampered_leg() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    
+    let legs = vec![(random_address(), 100), (random_address(), 200)];
+    let mut req = signed_request(&state, &sender, &legs, 0, 0);
+    
+    // Redirect one leg after signing
+    req.0.legs[1].to = hex::encode(random_address());
+    
+    let result = batch_transfer(State(state.clone()), req).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+}
@@ -0,0 +1,137 @@
+use super::*;
+use crate::api::transaction::{burn, BurnRequest};
+use crate::error::AppError;
+use axum::{extract::State, Json};
+use usda_common::{SignablePayload, TransactionKind, WebSocketMessage};
+
+#[tokio::test]
+async fn test_burn() {
+    let state = setup_test_state().await;
+    let holder = setup_account(&state, 1000).await;
+    let holder_bytes = holder.address;
+    let mut ws_rx = state.ws_tx.subscribe();
+    
+    let amount = 400_i64;
+    let redemption_ref = Some("wire-0001".to_string());
+    let payload = SignablePayload::burn(
+        state.chain_id,
+        holder_bytes,
+        tokens(amount),
+        0,
+        redemption_ref.clone(),
+    );
+    let signature = payload.sign(&holder.key);
+    
+    let req = Json(BurnRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: hex::encode(holder_bytes),
+        amount: tokens(amount),
+        nonce: 0,
+        redemption_ref: redemption_ref.clone(),
+        signature: hex::encode(signature),
+    });
+    
+    let response = burn(State(state.clone()), req)
+        .await
+        .expect("Failed to execute burn");
+    
+    // Tokens are removed from the holder
+    let holder = sqlx::query!(
+        r#"SELECT pending_balance::BIGINT AS "pending_balance!", nonce FROM accounts WHERE address = $1"#,
+        holder_bytes.as_slice()
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(holder.pending_balance, 1000 - amount);
+    assert_eq!(holder.nonce, 1);
+    
+    // The burn is recorded without a receiver
+    let record = sqlx::query!(
+        "SELECT kind, to_addr, redemption_ref FROM transactions WHERE tx_id = $1",
+        response.0.tx_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(record.kind, TransactionKind::Burn.to_string());
+    assert!(record.to_addr.is_none());
+    assert_eq!(record.redemption_ref, redemption_ref);
+    
+    // Subscribers are notified
+    match ws_rx.try_recv().unwrap() {
+        WebSocketMessage::TransactionPreconfirmed(tx) => {
+            assert_eq!(tx.tx_id, response.0.tx_id);
+            assert_eq!(tx.kind, TransactionKind::Burn);
+            assert_eq!(tx.amount, tokens(amount));
+        }
+        other => panic!("Unexpected message: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_burn_insufficient_balance() {
+    let state = setup_test_state().await;
+    let holder = setup_account(&state, 100).await;
+    let holder_bytes = holder.address;
+    
+    let payload = SignablePayload::burn(state.chain_id, holder_bytes, Amount::new(500), 0, None);
+    let req = Json(BurnRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: hex::encode(holder_bytes),
+        amount: Amount::new(500),
+        nonce: 0,
+        redemption_ref: None,
+        signature: hex::encode(payload.sign(&holder.key)),
+    });
+    
+    let result = burn(State(state), req).await;
+    assert!(matches!(result, Err(AppError::InsufficientBalance)));
+}
+
+#[tokio::test]
+async fn test_burn_wrong_nonce() {
+    let state = setup_test_state().await;
+    let holder = setup_account(&state, 100).await;
+    let holder_bytes = holder.address;
+
+    let payload = SignablePayload::burn(state.chain_id, holder_bytes, Amount::new(50), 1, None);
+    let req = Json(BurnRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: hex::encode(holder_bytes),
+        amount: Amount::new(50),
+        nonce: 1,
+        redemption_ref: None,
+        signature: hex::encode(payload.sign(&holder.key)),
+    });
+
+    let result = burn(State(state), req).await;
+    assert!(matches!(result, Err(AppError::InvalidNonce)));
+}
+
+#[tokio::test]
+async fn test_burn_tampered_redemption_ref() {
+    let state = setup_test_state().await;
+    let holder = setup_account(&state, 1000).await;
+    let holder_bytes = holder.address;
+    
+    // Signature covers a different redemption reference than the one submitted
+    let payload = SignablePayload::burn(
+        state.chain_id,
+        holder_bytes,
+        Amount::new(100),
+        0,
+        Some("wire-0001".into()),
+    );
+    let req = Json(BurnRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: hex::encode(holder_bytes),
+        amount: Amount::new(100),
+        nonce: 0,
+        redemption_ref: Some("wire-0002".into()),
+        signature: hex::encode(payload.sign(&holder.key)),
+    });
+    
+    let result = burn(State(state), req).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+}
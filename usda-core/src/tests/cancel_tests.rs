@@ -0,0 +1,149 @@
+use super::*;
+use crate::api::transaction::{cancel, replace, transfer, CancelRequest};
+use crate::error::AppError;
+use axum::{extract::State, Json};
+use usda_common::{SignablePayload, TransactionStatus, WebSocketMessage};
+
+fn signed_cancel(state: &AppState, sender: &TestAccount) -> Json<CancelRequest> {
+    let from = sender.address;
+    let payload = SignablePayload::cancel(state.chain_id, from, 0);
+    
+    Json(CancelRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: hex::encode(from),
+        nonce: 0,
+        signature: hex::encode(payload.sign(&sender.key)),
+    })
+}
+
+#[tokio::test]
+async fn test_cancel_pending_transfer() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let receiver_bytes = setup_account(&state, 0).await.address;
+    
+    let sent = transfer(State(state.clone()), signed_transfer(&state, &sender, receiver_bytes, 100, 5, 0))
+        .await
+        .expect("Failed to execute transfer");
+    
+    let mut ws_rx = state.ws_tx.subscribe();
+    let response = cancel(State(state.clone()), signed_cancel(&state, &sender))
+        .await
+        .expect("Failed to cancel transfer");
+    assert_eq!(response.tx_id, sent.tx_id);
+    assert_eq!(response.status, TransactionStatus::Cancelled.to_string());
+    
+    // Amount and fee are returned; the nonce stays used
+    assert_eq!(pending_balance(&state, sender_bytes).await, tokens(1000));
+    assert_eq!(pending_balance(&state, receiver_bytes).await, Amount::ZERO);
+    assert_eq!(pending_balance(&state, TEST_TREASURY).await, Amount::ZERO);
+    let record = sqlx::query!(
+        r#"SELECT status::TEXT AS "status!", status_reason, cancelled_at FROM transactions WHERE tx_id = $1"#,
+        sent.tx_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(record.status, TransactionStatus::Cancelled.to_string());
+    assert_eq!(record.status_reason.as_deref(), Some("Cancelled by sender"));
+    assert!(record.cancelled_at.is_some());
+    
+    match ws_rx.try_recv() {
+        Ok(WebSocketMessage::TransactionCancelled { tx_id, replaced_by }) => {
+            assert_eq!(tx_id, sent.tx_id);
+            assert_eq!(replaced_by, None);
+        }
+        other => panic!("Expected a cancellation event, got {:?}", other),
+    }
+    
+    // Resubmitting the cancellation returns the cancelled record
+    let again = cancel(State(state.clone()), signed_cancel(&state, &sender))
+        .await
+        .expect("Failed to resubmit cancellation");
+    assert_eq!(again.tx_id, sent.tx_id);
+    assert_eq!(pending_balance(&state, sender_bytes).await, tokens(1000));
+}
+
+#[tokio::test]
+async fn test_cancel_returns_fee_from_the_treasury_that_collected_it() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let receiver_bytes = setup_account(&state, 0).await.address;
+    let _ = send(&state, &sender, receiver_bytes, 100, 5, 0).await;
+
+    // Restarted without a treasury, the fee is still taken back from the old one
+    let restarted = Arc::new(AppState::new(state.db.clone()));
+    let response = cancel(State(restarted.clone()), signed_cancel(&restarted, &sender))
+        .await
+        .expect("Failed to cancel transfer");
+    assert_eq!(response.status, TransactionStatus::Cancelled.to_string());
+    assert_eq!(pending_balance(&state, sender.address).await, tokens(1000));
+    assert_eq!(pending_balance(&state, TEST_TREASURY).await, Amount::ZERO);
+}
+
+#[tokio::test]
+async fn test_replace_by_fee() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let receiver_bytes = setup_account(&state, 0).await.address;
+    let other_bytes = setup_account(&state, 0).await.address;
+    
+    let sent = transfer(State(state.clone()), signed_transfer(&state, &sender, receiver_bytes, 100, 5, 0))
+        .await
+        .expect("Failed to execute transfer");
+    
+    // The replacement has to pay a higher fee
+    let result = replace(State(state.clone()), signed_transfer(&state, &sender, other_bytes, 200, 5, 0)).await;
+    assert!(matches!(result, Err(AppError::InsufficientFee { required }) if required == Amount::new(6)));
+    
+    let replacement = replace(State(state.clone()), signed_transfer(&state, &sender, other_bytes, 200, 10, 0))
+        .await
+        .expect("Failed to replace transfer");
+    assert_ne!(replacement.tx_id, sent.tx_id);
+    
+    assert_eq!(pending_balance(&state, sender_bytes).await, tokens(1000 - 210));
+    assert_eq!(pending_balance(&state, receiver_bytes).await, Amount::ZERO);
+    assert_eq!(pending_balance(&state, other_bytes).await, tokens(200));
+    assert_eq!(pending_balance(&state, TEST_TREASURY).await, tokens(10));
+    
+    let original = sqlx::query!(
+        r#"SELECT status::TEXT AS "status!", status_reason, replaced_by FROM transactions WHERE tx_id = $1"#,
+        sent.tx_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(original.status, TransactionStatus::Cancelled.to_string());
+    assert_eq!(original.replaced_by, Some(replacement.tx_id.clone()));
+    assert_eq!(original.status_reason, Some(format!("Replaced by {}", replacement.tx_id)));
+    
+    // The sender's next nonce is unaffected
+    let sender_nonce = sqlx::query!("SELECT nonce FROM accounts WHERE address = $1", sender_bytes.as_slice())
+        .fetch_one(&state.db)
+        .await
+        .unwrap()
+        .nonce;
+    assert_eq!(sender_nonce, 1);
+}
+
+#[tokio::test]
+async fn test_cancel_after_receiver_spent_rejected() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let receiver = setup_account(&state, 0).await;
+    let receiver_bytes = receiver.address;
+    
+    let _ = transfer(State(state.clone()), signed_transfer(&state, &sender, receiver_bytes, 100, 0, 0))
+        .await
+        .expect("Failed to execute transfer");
+    let _ = transfer(State(state.clone()), signed_transfer(&state, &receiver, sender_bytes, 100, 0, 0))
+        .await
+        .expect("Failed to spend received funds");
+    
+    let result = cancel(State(state.clone()), signed_cancel(&state, &sender)).await;
+    assert!(matches!(result, Err(AppError::InvalidInput(_))));
+    assert_eq!(pending_balance(&state, sender_bytes).await, tokens(1000));
+}
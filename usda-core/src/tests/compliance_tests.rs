@@ -0,0 +1,169 @@
+use super::*;
+use crate::api::account::get_transactions;
+use crate::api::compliance::{
+    clawback, freeze, get_status, unfreeze, ClawbackRequest, FreezeRequest,
+};
+use crate::api::token::TokenQuery;
+use crate::api::transaction::transfer;
+use crate::error::AppError;
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use ed25519_dalek::SigningKey;
+use usda_common::{SignablePayload, TransactionKind, TransactionStatus};
+
+fn setup_issuer(state: &AppState) -> SigningKey {
+    let issuer = new_key();
+    state.set_issuer_key(DEFAULT_TOKEN_ID, issuer.verifying_key());
+    issuer
+}
+
+fn signed_freeze(state: &AppState, signer: &SigningKey, address: [u8; 32], frozen: bool, nonce: i64) -> Json<FreezeRequest> {
+    let payload = if frozen {
+        SignablePayload::freeze(state.chain_id, address, nonce)
+    } else {
+        SignablePayload::unfreeze(state.chain_id, address, nonce)
+    };
+
+    Json(FreezeRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        address: hex::encode(address),
+        nonce,
+        signature: hex::encode(payload.sign(signer)),
+    })
+}
+
+fn signed_clawback(
+    state: &AppState,
+    issuer: &SigningKey,
+    from: [u8; 32],
+    to: [u8; 32],
+    amount: i64,
+    nonce: i64,
+) -> Json<ClawbackRequest> {
+    let payload = SignablePayload::clawback(state.chain_id, from, to, tokens(amount), nonce);
+
+    Json(ClawbackRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: hex::encode(from),
+        to: hex::encode(to),
+        amount: tokens(amount),
+        nonce,
+        signature: hex::encode(payload.sign(issuer)),
+    })
+}
+
+#[tokio::test]
+async fn test_frozen_account_cannot_send_or_receive() {
+    let state = setup_test_state().await;
+    let issuer = setup_issuer(&state);
+    let alice = setup_account(&state, 1000).await;
+    let bob = setup_account(&state, 1000).await;
+    let bob_bytes = bob.address;
+
+    // Only the issuer can freeze
+    let result = freeze(State(state.clone()), signed_freeze(&state, &alice.key, bob_bytes, true, 0)).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+
+    let status = freeze(State(state.clone()), signed_freeze(&state, &issuer, bob_bytes, true, 0))
+        .await
+        .expect("Failed to freeze account")
+        .0;
+    assert!(status.frozen);
+
+    // Resubmitting the freeze returns the status without using another nonce
+    let resubmitted = freeze(State(state.clone()), signed_freeze(&state, &issuer, bob_bytes, true, 0))
+        .await
+        .expect("Resubmitted freeze should succeed");
+    assert!(resubmitted.0.frozen);
+
+    // Transfers to and from the frozen address fail
+    let result = transfer(State(state.clone()), signed_transfer(&state, &alice, bob_bytes, 100, 0, 0)).await;
+    assert!(matches!(result, Err(AppError::AccountFrozen)));
+    let result = transfer(State(state.clone()), signed_transfer(&state, &bob, alice.address, 100, 0, 0)).await;
+    assert!(matches!(result, Err(AppError::AccountFrozen)));
+    assert_eq!(pending(&state, bob_bytes).await, (tokens(1000), 0));
+
+    // An unfreeze signed for a used nonce is rejected
+    let result = unfreeze(State(state.clone()), signed_freeze(&state, &issuer, bob_bytes, false, 0)).await;
+    assert!(matches!(result, Err(AppError::InvalidNonce)));
+
+    let status = unfreeze(State(state.clone()), signed_freeze(&state, &issuer, bob_bytes, false, 1))
+        .await
+        .expect("Failed to unfreeze account")
+        .0;
+    assert!(!status.frozen);
+    let status = get_status(
+        State(state.clone()),
+        Path(hex::encode(bob_bytes)),
+        Query(TokenQuery { token_id: None }),
+    )
+    .await
+    .unwrap()
+    .0;
+    assert_eq!((status.frozen, status.frozen_at), (false, None));
+
+    let response = transfer(State(state.clone()), signed_transfer(&state, &alice, bob_bytes, 100, 0, 0))
+        .await
+        .expect("Transfer after unfreezing should succeed");
+    assert_eq!(response.0.status, TransactionStatus::Sequenced.to_string());
+    assert_eq!(pending(&state, bob_bytes).await, (tokens(1100), 0));
+}
+
+#[tokio::test]
+async fn test_clawback_seizes_frozen_funds() {
+    let state = setup_test_state().await;
+    let issuer = setup_issuer(&state);
+    let alice = setup_account(&state, 1000).await;
+    let alice_bytes = alice.address;
+    let custody = new_key().verifying_key().to_bytes();
+
+    let status = freeze(State(state.clone()), signed_freeze(&state, &issuer, alice_bytes, true, 0))
+        .await
+        .expect("Failed to freeze account");
+    assert!(status.0.frozen);
+
+    // More than the account holds can't be seized
+    let result = clawback(State(state.clone()), signed_clawback(&state, &issuer, alice_bytes, custody, 1001, 1)).await;
+    assert!(matches!(result, Err(AppError::InsufficientBalance)));
+    let result = clawback(State(state.clone()), signed_clawback(&state, &alice.key, alice_bytes, custody, 300, 1)).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+
+    let response = clawback(State(state.clone()), signed_clawback(&state, &issuer, alice_bytes, custody, 300, 1))
+        .await
+        .expect("Clawback from a frozen account should succeed")
+        .0;
+    assert_eq!(response.status, TransactionStatus::Sequenced.to_string());
+
+    // The account's nonce is untouched
+    assert_eq!(pending(&state, alice_bytes).await, (tokens(700), 0));
+    assert_eq!(pending(&state, custody).await, (tokens(300), 0));
+
+    // Resubmitting returns the original record
+    let resubmitted = clawback(State(state.clone()), signed_clawback(&state, &issuer, alice_bytes, custody, 300, 1))
+        .await
+        .expect("Resubmitted clawback should succeed");
+    assert_eq!(resubmitted.0.tx_id, response.tx_id);
+    assert_eq!(pending(&state, alice_bytes).await, (tokens(700), 0));
+
+    // The clawback shows up in the account's history as its own kind
+    let history = get_transactions(
+        State(state.clone()),
+        Path(alice_bytes),
+        Query(TokenQuery { token_id: None }),
+    )
+    .await
+    .unwrap()
+    .0;
+    let record = history
+        .iter()
+        .find(|transaction| transaction.tx_id == response.tx_id)
+        .expect("Clawback missing from history");
+    assert_eq!(record.kind, TransactionKind::Clawback);
+    assert_eq!((record.from, record.to), (Some(alice_bytes), Some(custody)));
+
+    // Seized funds can't be paid into a frozen account
+    let result = clawback(State(state.clone()), signed_clawback(&state, &issuer, custody, alice_bytes, 100, 2)).await;
+    assert!(matches!(result, Err(AppError::AccountFrozen)));
+}
@@ -0,0 +1,215 @@
+use super::*;
+use crate::api::transaction::{
+    escrow_claim, escrow_lock, escrow_refund, get_escrow, EscrowClaimRequest, EscrowLockRequest,
+    EscrowRefundRequest,
+};
+use crate::error::AppError;
+use crate::settlement::{fail_batch, seal_batch};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::Utc;
+use ed25519_dalek::SigningKey;
+use usda_common::{Escrow, EscrowStatus, SignablePayload, TransactionStatus, WebSocketMessage};
+
+const PREIMAGE: [u8; 32] = [7u8; 32];
+
+/// Locks `amount` plus a fee of 1 for `recipient` under the hash of [`PREIMAGE`].
+async fn lock(state: &Arc<AppState>, sender: &TestAccount, recipient: [u8; 32], amount: i64, timeout: i64) -> String {
+    let from = sender.address;
+    let hashlock = Escrow::hashlock(&PREIMAGE);
+    let payload = SignablePayload::escrow_lock(
+        state.chain_id,
+        from,
+        recipient,
+        tokens(amount),
+        tokens(1),
+        hashlock,
+        timeout,
+        0,
+    );
+
+    escrow_lock(
+        State(state.clone()),
+        Json(EscrowLockRequest {
+            token_id: DEFAULT_TOKEN_ID,
+            from: hex::encode(from),
+            to: hex::encode(recipient),
+            amount: tokens(amount),
+            fee: tokens(1),
+            hashlock: hex::encode(hashlock),
+            timeout,
+            nonce: 0,
+            signature: hex::encode(payload.sign(&sender.key)),
+        }),
+    )
+    .await
+    .expect("Failed to lock escrow")
+    .0
+    .tx_id
+}
+
+fn signed_claim(state: &AppState, escrow_id: &str, preimage: [u8; 32], signer: &SigningKey) -> Json<EscrowClaimRequest> {
+    let escrow: [u8; 32] = hex::decode(escrow_id).unwrap().try_into().unwrap();
+    let payload = SignablePayload::escrow_claim(state.chain_id, escrow, preimage);
+
+    Json(EscrowClaimRequest {
+        escrow_id: escrow_id.to_string(),
+        preimage: hex::encode(preimage),
+        signature: hex::encode(payload.sign(signer)),
+    })
+}
+
+fn signed_refund(state: &AppState, escrow_id: &str, signer: &SigningKey) -> Json<EscrowRefundRequest> {
+    let escrow: [u8; 32] = hex::decode(escrow_id).unwrap().try_into().unwrap();
+    let payload = SignablePayload::escrow_refund(state.chain_id, escrow);
+
+    Json(EscrowRefundRequest {
+        escrow_id: escrow_id.to_string(),
+        signature: hex::encode(payload.sign(signer)),
+    })
+}
+
+async fn escrow(state: &Arc<AppState>, escrow_id: &str) -> Escrow {
+    get_escrow(State(state.clone()), Path(escrow_id.to_string()))
+        .await
+        .expect("Escrow not found")
+        .0
+}
+
+#[tokio::test]
+async fn test_escrow_claim_with_preimage() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let recipient = setup_account(&state, 0).await;
+    let recipient_bytes = recipient.address;
+
+    let mut ws_rx = state.ws_tx.subscribe();
+    let escrow_id = lock(&state, &sender, recipient_bytes, 300, Utc::now().timestamp() + 3600).await;
+    assert_eq!(pending_balance(&state, sender_bytes).await, tokens(699));
+    assert_eq!(pending_balance(&state, TEST_TREASURY).await, tokens(1));
+    assert_eq!(escrow(&state, &escrow_id).await.status, EscrowStatus::Locked);
+
+    let updated = std::iter::from_fn(|| ws_rx.try_recv().ok())
+        .find_map(|message| match message {
+            WebSocketMessage::EscrowUpdated(escrow) => Some(escrow),
+            _ => None,
+        })
+        .expect("Expected an escrow update");
+    assert_eq!(updated.escrow_id, escrow_id);
+
+    // Only the right preimage, revealed by the recipient, opens the escrow
+    let result = escrow_claim(State(state.clone()), signed_claim(&state, &escrow_id, [8u8; 32], &recipient.key)).await;
+    assert!(matches!(result, Err(AppError::InvalidPreimage)));
+    let result = escrow_claim(State(state.clone()), signed_claim(&state, &escrow_id, PREIMAGE, &sender.key)).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+
+    // The sender has to wait for the timeout
+    let result = escrow_refund(State(state.clone()), signed_refund(&state, &escrow_id, &sender.key)).await;
+    assert!(matches!(result, Err(AppError::InvalidInput(_))));
+
+    let claimed = escrow_claim(State(state.clone()), signed_claim(&state, &escrow_id, PREIMAGE, &recipient.key))
+        .await
+        .expect("Failed to claim escrow");
+    assert_eq!(pending_balance(&state, recipient_bytes).await, tokens(300));
+
+    let settled = escrow(&state, &escrow_id).await;
+    assert_eq!(settled.status, EscrowStatus::Claimed);
+    assert_eq!(settled.preimage, Some(PREIMAGE));
+
+    // Resubmitting the claim returns the original record without paying twice
+    let resubmitted = escrow_claim(State(state.clone()), signed_claim(&state, &escrow_id, PREIMAGE, &recipient.key))
+        .await
+        .expect("Resubmitted claim should succeed");
+    assert_eq!(resubmitted.0.tx_id, claimed.0.tx_id);
+    assert_eq!(pending_balance(&state, recipient_bytes).await, tokens(300));
+}
+
+#[tokio::test]
+async fn test_escrow_refund_after_timeout() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let recipient = setup_account(&state, 0).await;
+    let recipient_bytes = recipient.address;
+
+    let escrow_id = lock(&state, &sender, recipient_bytes, 300, Utc::now().timestamp() + 2).await;
+    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+
+    // Past the timeout even the right preimage is too late
+    let result = escrow_claim(State(state.clone()), signed_claim(&state, &escrow_id, PREIMAGE, &recipient.key)).await;
+    assert!(matches!(result, Err(AppError::InvalidInput(_))));
+
+    // Only the sender can reclaim the funds; the fee stays paid
+    let result = escrow_refund(State(state.clone()), signed_refund(&state, &escrow_id, &recipient.key)).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+    let refunded = escrow_refund(State(state.clone()), signed_refund(&state, &escrow_id, &sender.key))
+        .await
+        .expect("Failed to refund escrow");
+    assert_eq!(refunded.0.status, TransactionStatus::Sequenced.to_string());
+    assert_eq!(pending_balance(&state, sender_bytes).await, tokens(999));
+    assert_eq!(pending_balance(&state, recipient_bytes).await, Amount::ZERO);
+    assert_eq!(escrow(&state, &escrow_id).await.status, EscrowStatus::Refunded);
+}
+
+#[tokio::test]
+async fn test_failed_batch_unwinds_escrow() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let recipient = setup_account(&state, 0).await;
+    let recipient_bytes = recipient.address;
+
+    let escrow_id = lock(&state, &sender, recipient_bytes, 300, Utc::now().timestamp() + 3600).await;
+    let claimed = escrow_claim(State(state.clone()), signed_claim(&state, &escrow_id, PREIMAGE, &recipient.key))
+        .await
+        .expect("Failed to claim escrow");
+
+    let batch = seal_batch(&state, 100).await.unwrap().expect("Expected a batch");
+    assert_eq!(batch.tx_ids.len(), 2);
+    assert!(batch.tx_ids.contains(&claimed.0.tx_id));
+    fail_batch(&state, &batch.batch_id).await.expect("Failed to fail batch");
+
+    // The claim is undone, then the lock: the funds are back with the sender
+    assert_eq!(pending_balance(&state, sender_bytes).await, tokens(1000));
+    assert_eq!(pending_balance(&state, recipient_bytes).await, Amount::ZERO);
+    let unwound = escrow(&state, &escrow_id).await;
+    assert_eq!(unwound.status, EscrowStatus::Refunded);
+    assert_eq!(unwound.preimage, None);
+
+    // Resubmitting the claim reports it failed instead of paying out again
+    let resubmitted = escrow_claim(State(state.clone()), signed_claim(&state, &escrow_id, PREIMAGE, &recipient.key))
+        .await
+        .expect("Resubmitted claim should return its record");
+    assert_eq!(resubmitted.0.status, TransactionStatus::Failed.to_string());
+    assert_eq!(pending_balance(&state, recipient_bytes).await, Amount::ZERO);
+}
+
+#[tokio::test]
+async fn test_failed_lock_fails_later_claim() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let recipient = setup_account(&state, 0).await;
+    let recipient_bytes = recipient.address;
+
+    // The escrow is claimed after its lock was sealed into a batch
+    let escrow_id = lock(&state, &sender, recipient_bytes, 300, Utc::now().timestamp() + 3600).await;
+    let batch = seal_batch(&state, 100).await.unwrap().expect("Expected a batch");
+    let claimed = escrow_claim(State(state.clone()), signed_claim(&state, &escrow_id, PREIMAGE, &recipient.key))
+        .await
+        .expect("Failed to claim escrow");
+
+    // The claim can't pay out of a lock that never happened
+    fail_batch(&state, &batch.batch_id).await.expect("Failed to fail batch");
+    assert_eq!(pending_balance(&state, sender_bytes).await, tokens(1000));
+    assert_eq!(pending_balance(&state, recipient_bytes).await, Amount::ZERO);
+    assert_eq!(escrow(&state, &escrow_id).await.status, EscrowStatus::Refunded);
+    let resubmitted = escrow_claim(State(state.clone()), signed_claim(&state, &escrow_id, PREIMAGE, &recipient.key))
+        .await
+        .expect("Resubmitted claim should return its record");
+    assert_eq!(resubmitted.0.tx_id, claimed.0.tx_id);
+    assert_eq!(resubmitted.0.status, TransactionStatus::Failed.to_string());
+}
@@ -0,0 +1,137 @@
+use super::*;
+use crate::api::account::{create, get_key, rotate_key, CreateAccountRequest, RotateKeyRequest};
+use crate::api::transaction::{get_transaction, transfer};
+use crate::error::AppError;
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use ed25519_dalek::SigningKey;
+use usda_common::{SignablePayload, TransactionStatus};
+
+fn signed_rotation(
+    state: &AppState,
+    signer: &SigningKey,
+    address: [u8; 32],
+    new_key: &SigningKey,
+    version: i64,
+) -> Json<RotateKeyRequest> {
+    let new_key = new_key.verifying_key().to_bytes();
+    let payload = SignablePayload::rotate_key(state.chain_id, address, new_key, version);
+
+    Json(RotateKeyRequest {
+        address: hex::encode(address),
+        new_public_key: hex::encode(new_key),
+        version,
+        signature: hex::encode(payload.sign(signer)),
+    })
+}
+
+#[tokio::test]
+async fn test_rotated_key_signs_for_the_same_address() {
+    let state = setup_test_state().await;
+    let old_key = new_key();
+    let account = create(
+        State(state.clone()),
+        Json(CreateAccountRequest {
+            public_key: old_key.verifying_key().to_bytes(),
+            token_id: DEFAULT_TOKEN_ID,
+        }),
+    )
+    .await
+    .expect("Failed to create account")
+    .0;
+    let address = account.address;
+    fund(&state, address, 1000).await;
+    let bob = new_key().verifying_key().to_bytes();
+    let old_signer = TestAccount { key: old_key.clone(), address };
+
+    let response = transfer(State(state.clone()), signed_transfer(&state, &old_signer, bob, 100, 0, 0)).await;
+    assert!(response.is_ok(), "Transfer signed by the current key should succeed");
+
+    // Only the current key can rotate, and only from the current version
+    let rotated_key = new_key();
+    let result = rotate_key(State(state.clone()), signed_rotation(&state, &rotated_key, address, &rotated_key, 0)).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+    let result = rotate_key(State(state.clone()), signed_rotation(&state, &old_key, address, &rotated_key, 1)).await;
+    assert!(matches!(result, Err(AppError::InvalidInput(_))));
+
+    let key = rotate_key(State(state.clone()), signed_rotation(&state, &old_key, address, &rotated_key, 0))
+        .await
+        .expect("Failed to rotate key")
+        .0;
+    assert_eq!(key.address, hex::encode(address));
+    assert_eq!((key.public_key, key.version), (hex::encode(rotated_key.verifying_key().to_bytes()), 1));
+
+    // Resubmitting the rotation returns the key without rotating again
+    let resubmitted = rotate_key(State(state.clone()), signed_rotation(&state, &old_key, address, &rotated_key, 0))
+        .await
+        .expect("Resubmitted rotation should succeed");
+    assert_eq!(resubmitted.0.version, 1);
+
+    // The old key no longer signs for the account; the new one does
+    let result = transfer(State(state.clone()), signed_transfer(&state, &old_signer, bob, 100, 0, 1)).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+    let rotated_signer = TestAccount { key: rotated_key.clone(), address };
+    let response = transfer(State(state.clone()), signed_transfer(&state, &rotated_signer, bob, 100, 0, 1))
+        .await
+        .expect("Transfer signed by the rotated key should succeed");
+    assert_eq!(response.0.status, TransactionStatus::Sequenced.to_string());
+
+    let history = sqlx::query!(
+        "SELECT version, public_key FROM account_key_history WHERE address = $1 ORDER BY version",
+        address.as_slice()
+    )
+    .fetch_all(&state.db)
+    .await
+    .unwrap();
+    let history: Vec<(i64, Vec<u8>)> = history.into_iter().map(|row| (row.version, row.public_key)).collect();
+    assert_eq!(
+        history,
+        vec![
+            (0, old_key.verifying_key().to_bytes().to_vec()),
+            (1, rotated_key.verifying_key().to_bytes().to_vec()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_rotation_drops_transfers_signed_by_the_old_key() {
+    let state = setup_test_state().await;
+    // An account whose address is its raw key
+    let old_key = new_key();
+    let address = old_key.verifying_key().to_bytes();
+    fund(&state, address, 1000).await;
+    let bob = new_key().verifying_key().to_bytes();
+    let old_signer = TestAccount { key: old_key.clone(), address };
+
+    let key = get_key(State(state.clone()), Path(hex::encode(address))).await.unwrap().0;
+    assert_eq!((key.public_key, key.version), (hex::encode(address), 0));
+
+    // Waiting in the nonce queue for nonce 0
+    let queued = transfer(State(state.clone()), signed_transfer(&state, &old_signer, bob, 100, 0, 1))
+        .await
+        .expect("Failed to queue transfer")
+        .0;
+    assert_eq!(queued.status, TransactionStatus::Received.to_string());
+
+    let rotated_key = new_key();
+    let key = rotate_key(State(state.clone()), signed_rotation(&state, &old_key, address, &rotated_key, 0))
+        .await
+        .expect("Failed to rotate key")
+        .0;
+    assert_eq!(key.version, 1);
+
+    let result = get_transaction(State(state.clone()), Path(queued.tx_id)).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+
+    // Nonce 1 is free again for a transfer signed by the new key
+    let rotated_signer = TestAccount { key: rotated_key.clone(), address };
+    for nonce in [0, 1] {
+        let response = transfer(State(state.clone()), signed_transfer(&state, &rotated_signer, bob, 100, 0, nonce)).await;
+        assert!(response.is_ok(), "Transfer signed by the rotated key should succeed");
+    }
+
+    let result = rotate_key(State(state.clone()), signed_rotation(&state, &old_key, [0xab; 32], &rotated_key, 0)).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+}
@@ -0,0 +1,267 @@
+use super::*;
+use crate::api::compliance::{get_limits, set_limits, SpendingLimitsRequest};
+use crate::api::token::TokenQuery;
+use crate::api::transaction::{
+    approve, batch_transfer, burn, escrow_lock, transfer, transfer_from, ApproveRequest, BatchTransferRequest, BurnRequest,
+    EscrowLockRequest, TransferFromRequest, TransferLeg,
+};
+use crate::error::AppError;
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use ed25519_dalek::SigningKey;
+use usda_common::{limits::HOUR_SECS, Escrow, LimitViolation, SignablePayload, SpendingLimits};
+
+fn signed_limits(
+    state: &AppState,
+    issuer: &SigningKey,
+    address: [u8; 32],
+    limits: Option<SpendingLimits>,
+    nonce: i64,
+) -> Json<SpendingLimitsRequest> {
+    let payload = SignablePayload::set_spending_limits(state.chain_id, address, limits, nonce);
+
+    Json(SpendingLimitsRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        address: hex::encode(address),
+        limits,
+        nonce,
+        signature: hex::encode(payload.sign(issuer)),
+    })
+}
+
+fn violation(result: Result<impl Sized, AppError>) -> LimitViolation {
+    match result {
+        Err(AppError::SpendingLimitExceeded(violation)) => violation,
+        Err(e) => panic!("Expected a spending limit violation, got {:?}", e),
+        Ok(_) => panic!("Expected a spending limit violation"),
+    }
+}
+
+#[tokio::test]
+async fn test_default_limits_reject_transfers() {
+    let limits = SpendingLimits {
+        per_transfer: Some(tokens(500)),
+        daily: Some(tokens(1000)),
+        hourly_transfers: Some(3),
+    };
+    let state = Arc::new(test_app_state().await.with_default_limits(DEFAULT_TOKEN_ID, limits));
+    let alice = setup_account(&state, 5000).await;
+    let bob = new_key().verifying_key().to_bytes();
+
+    let result = transfer(State(state.clone()), signed_transfer(&state, &alice, bob, 600, 0, 0)).await;
+    assert_eq!(violation(result), LimitViolation::PerTransfer { limit: tokens(500) });
+
+    for (amount, nonce) in [(400, 0), (400, 1)] {
+        let response = transfer(State(state.clone()), signed_transfer(&state, &alice, bob, amount, 0, nonce)).await;
+        assert!(response.is_ok(), "Transfer within the limits should succeed");
+    }
+
+    // The error says how much of the day's limit is left
+    let result = transfer(State(state.clone()), signed_transfer(&state, &alice, bob, 300, 0, 2)).await;
+    assert_eq!(
+        violation(result),
+        LimitViolation::Daily { limit: tokens(1000), remaining: tokens(200) }
+    );
+
+    // A batch counts as one transfer per leg
+    let legs = vec![(bob, tokens(50)), (bob, tokens(50))];
+    let payload = SignablePayload::batch_transfer(state.chain_id, alice.address, legs.clone(), Amount::ZERO, 2);
+    let batch = Json(BatchTransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: hex::encode(alice.address),
+        legs: legs
+            .iter()
+            .map(|(to, amount)| TransferLeg { to: hex::encode(to), amount: *amount })
+            .collect(),
+        fee: Amount::ZERO,
+        nonce: 2,
+        signature: hex::encode(payload.sign(&alice.key)),
+    });
+    match violation(batch_transfer(State(state.clone()), batch).await) {
+        LimitViolation::HourlyTransfers { limit, retry_after } => {
+            assert_eq!(limit, 3);
+            assert!(retry_after > 0 && retry_after <= HOUR_SECS);
+        }
+        other => panic!("Expected the hourly limit, got {:?}", other),
+    }
+
+    let response = transfer(State(state.clone()), signed_transfer(&state, &alice, bob, 100, 0, 2)).await;
+    assert!(response.is_ok(), "Third transfer of the hour should succeed");
+    let result = transfer(State(state.clone()), signed_transfer(&state, &alice, bob, 50, 0, 3)).await;
+    let violation = violation(result);
+    assert!(matches!(violation, LimitViolation::HourlyTransfers { limit: 3, .. }));
+
+    // The response body carries the violation for clients to act on
+    let response = AppError::SpendingLimitExceeded(violation).into_response();
+    assert_eq!(response.status(), axum::http::StatusCode::FORBIDDEN);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["violation"]["rule"], "hourly_transfers");
+    assert_eq!(body["violation"]["limit"], 3);
+}
+
+#[tokio::test]
+async fn test_issuer_sets_and_resets_account_limits() {
+    let defaults = SpendingLimits {
+        per_transfer: Some(tokens(500)),
+        ..Default::default()
+    };
+    let state = Arc::new(test_app_state().await.with_default_limits(DEFAULT_TOKEN_ID, defaults));
+    let issuer = new_key();
+    state.set_issuer_key(DEFAULT_TOKEN_ID, issuer.verifying_key());
+    let alice = setup_account(&state, 5000).await;
+    let alice_bytes = alice.address;
+    let bob = new_key().verifying_key().to_bytes();
+
+    // Only the issuer can raise an account's limits
+    let raised = SpendingLimits {
+        per_transfer: Some(tokens(2000)),
+        ..Default::default()
+    };
+    let result = set_limits(State(state.clone()), signed_limits(&state, &alice.key, alice_bytes, Some(raised), 0)).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+
+    let status = set_limits(State(state.clone()), signed_limits(&state, &issuer, alice_bytes, Some(raised), 0))
+        .await
+        .expect("Failed to set limits")
+        .0;
+    assert_eq!((status.limits, status.uses_defaults), (raised, false));
+
+    let response = transfer(State(state.clone()), signed_transfer(&state, &alice, bob, 1000, 0, 0)).await;
+    assert!(response.is_ok(), "Transfer within the raised limit should succeed");
+
+    let status = get_limits(
+        State(state.clone()),
+        Path(hex::encode(alice_bytes)),
+        Query(TokenQuery { token_id: None }),
+    )
+    .await
+    .unwrap()
+    .0;
+    assert_eq!(status.usage.sent_today, tokens(1000));
+    assert_eq!(status.usage.transfers_last_hour, 1);
+
+    // Resubmitting returns the current limits without using another nonce
+    let resubmitted = set_limits(State(state.clone()), signed_limits(&state, &issuer, alice_bytes, Some(raised), 0))
+        .await
+        .expect("Resubmitted limits should succeed");
+    assert_eq!(resubmitted.0.limits, raised);
+
+    // Resetting puts the account back on the defaults
+    let status = set_limits(State(state.clone()), signed_limits(&state, &issuer, alice_bytes, None, 1))
+        .await
+        .expect("Failed to reset limits")
+        .0;
+    assert_eq!((status.limits, status.uses_defaults), (defaults, true));
+
+    let result = transfer(State(state.clone()), signed_transfer(&state, &alice, bob, 1000, 0, 1)).await;
+    assert_eq!(violation(result), LimitViolation::PerTransfer { limit: tokens(500) });
+
+    // The raise can't be replayed over the reset
+    let result = set_limits(State(state.clone()), signed_limits(&state, &issuer, alice_bytes, Some(raised), 0)).await;
+    assert!(matches!(result, Err(AppError::InvalidNonce)));
+}
+
+#[tokio::test]
+async fn test_limits_cover_every_way_of_spending() {
+    let limits = SpendingLimits {
+        per_transfer: Some(tokens(500)),
+        daily: Some(tokens(1000)),
+        hourly_transfers: None,
+    };
+    let state = Arc::new(test_app_state().await.with_default_limits(DEFAULT_TOKEN_ID, limits));
+    let alice = setup_account(&state, 5000).await;
+    let alice_bytes = alice.address;
+    let spender = setup_account(&state, 0).await;
+    let spender_bytes = spender.address;
+    let bob = new_key().verifying_key().to_bytes();
+
+    // A spender moves the owner's funds within the owner's limits
+    let payload = SignablePayload::approve(state.chain_id, alice_bytes, spender_bytes, tokens(5000), None, 0);
+    let approved = approve(
+        State(state.clone()),
+        Json(ApproveRequest {
+            token_id: DEFAULT_TOKEN_ID,
+            owner: hex::encode(alice_bytes),
+            spender: hex::encode(spender_bytes),
+            limit: tokens(5000),
+            expiry: None,
+            nonce: 0,
+            signature: hex::encode(payload.sign(&alice.key)),
+        }),
+    )
+    .await;
+    assert!(approved.is_ok(), "Failed to approve spender");
+    let spend = |amount: i64| {
+        let payload =
+            SignablePayload::transfer_from(state.chain_id, spender_bytes, alice_bytes, bob, tokens(amount), Amount::ZERO, 0);
+        transfer_from(
+            State(state.clone()),
+            Json(TransferFromRequest {
+                token_id: DEFAULT_TOKEN_ID,
+                spender: hex::encode(spender_bytes),
+                owner: hex::encode(alice_bytes),
+                to: hex::encode(bob),
+                amount: tokens(amount).into(),
+                fee: Amount::ZERO.into(),
+                nonce: 0,
+                signature: hex::encode(payload.sign(&spender.key)),
+            }),
+        )
+    };
+    assert_eq!(violation(spend(600).await), LimitViolation::PerTransfer { limit: tokens(500) });
+    assert!(spend(400).await.is_ok(), "Spend within the owner's limits should succeed");
+
+    // Escrow locks count too
+    let hashlock = Escrow::hashlock(&[7u8; 32]);
+    let timeout = Utc::now().timestamp() + HOUR_SECS;
+    let lock = |amount: i64| {
+        let payload =
+            SignablePayload::escrow_lock(state.chain_id, alice_bytes, bob, tokens(amount), Amount::ZERO, hashlock, timeout, 1);
+        escrow_lock(
+            State(state.clone()),
+            Json(EscrowLockRequest {
+                token_id: DEFAULT_TOKEN_ID,
+                from: hex::encode(alice_bytes),
+                to: hex::encode(bob),
+                amount: tokens(amount),
+                fee: Amount::ZERO,
+                hashlock: hex::encode(hashlock),
+                timeout,
+                nonce: 1,
+                signature: hex::encode(payload.sign(&alice.key)),
+            }),
+        )
+    };
+    assert_eq!(violation(lock(600).await), LimitViolation::PerTransfer { limit: tokens(500) });
+    assert!(lock(400).await.is_ok(), "Lock within the limits should succeed");
+
+    // So do redemptions, which pay out off the ledger
+    let redeem = |amount: i64| {
+        let payload = SignablePayload::burn(state.chain_id, alice_bytes, tokens(amount), 2, None);
+        burn(
+            State(state.clone()),
+            Json(BurnRequest {
+                token_id: DEFAULT_TOKEN_ID,
+                from: hex::encode(alice_bytes),
+                amount: tokens(amount),
+                nonce: 2,
+                redemption_ref: None,
+                signature: hex::encode(payload.sign(&alice.key)),
+            }),
+        )
+    };
+    assert_eq!(
+        violation(redeem(300).await),
+        LimitViolation::Daily { limit: tokens(1000), remaining: tokens(200) }
+    );
+    assert!(redeem(200).await.is_ok(), "Burn within the limits should succeed");
+
+    // The day's limit is used up
+    let result = transfer(State(state.clone()), signed_transfer(&state, &alice, bob, 1, 0, 3)).await;
+    assert_eq!(violation(result), LimitViolation::Daily { limit: tokens(1000), remaining: Amount::ZERO });
+}
@@ -1,8 +1,9 @@
 use super::*;
 use crate::api::transaction::{mint, MintRequest};
 use axum::Json;
-use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
+use ed25519_dalek::{SigningKey, VerifyingKey};
 use rand::{rngs::OsRng, RngCore};
+use usda_common::SignablePayload;
 
 async fn setup_test_accounts(state: &AppState) -> (SigningKey, VerifyingKey) {
     // Generate issuer keypair
@@ -12,7 +13,7 @@ async fn setup_test_accounts(state: &AppState) -> (SigningKey, VerifyingKey) {
     let verifying_key = signing_key.verifying_key();
     
     // Set issuer key
-    state.set_issuer_key(verifying_key);
+    state.set_issuer_key(DEFAULT_TOKEN_ID, verifying_key);
     
     (signing_key, verifying_key)
 }
@@ -24,45 +25,24 @@ async fn test_mint() {
     // Setup test accounts
     let (signing_key, _) = setup_test_accounts(&state).await;
     
-    // Generate recipient keypair
-    let mut receiver_secret = [0u8; 32];
-    OsRng.fill_bytes(&mut receiver_secret);
-    let receiver_signing_key = SigningKey::from_bytes(&receiver_secret);
-    let receiver_verifying_key = receiver_signing_key.verifying_key();
-    let receiver_address = receiver_verifying_key.to_bytes();
-
     // Create recipient account
-    sqlx::query!(
-        r#"
-        INSERT INTO accounts (address, balance, pending_balance, nonce, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        "#,
-        receiver_address.as_slice(),
-        0_i64,
-        0_i64,
-        0_i64
-    )
-    .execute(&state.db)
-    .await
-    .expect("Failed to create recipient account");
+    let receiver_address = setup_account(&state, 0).await.address;
     
     // Create mint request
     let amount = 100_i64;
     
-    // Create message to sign (to + amount)
-    let message = format!(
-        "{}{}",
-        hex::encode(receiver_address),
-        amount
-    );
+    // Create payload to sign (to + amount + mint nonce)
+    let payload = SignablePayload::mint(state.chain_id, receiver_address, tokens(amount), 0);
     
-    // Sign message
-    let signature = signing_key.sign(message.as_bytes());
+    // Sign payload
+    let signature = payload.sign(&signing_key);
     
     let req = Json(MintRequest {
+        token_id: DEFAULT_TOKEN_ID,
         to: hex::encode(receiver_address),
-        amount,
-        signature: hex::encode(signature.to_bytes()),
+        amount: tokens(amount),
+        nonce: 0,
+        signature: hex::encode(signature),
     });
     
     // Execute mint
@@ -72,11 +52,11 @@ async fn test_mint() {
     
     // Verify response
     assert!(!response.0.tx_id.is_empty());
-    assert_eq!(response.0.status, "pending");
+    assert_eq!(response.0.status, "SEQUENCED");
     
     // Verify balances
     let receiver = sqlx::query!(
-        "SELECT pending_balance FROM accounts WHERE address = $1",
+        r#"SELECT pending_balance::BIGINT AS "pending_balance!" FROM accounts WHERE address = $1"#,
         receiver_address.as_slice()
     )
     .fetch_one(&state.db)
@@ -85,6 +65,16 @@ async fn test_mint() {
     
     // Verify receiver's balance is increased by amount
     assert_eq!(receiver.pending_balance, amount);
+    
+    // Mints are recorded without a sender
+    let record = sqlx::query!(
+        "SELECT from_addr FROM transactions WHERE tx_id = $1",
+        response.0.tx_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .expect("Failed to fetch mint transaction");
+    assert!(record.from_addr.is_none());
 }
 
 #[tokio::test]
@@ -94,48 +84,27 @@ async fn test_mint_invalid_signature() {
     // Setup test accounts
     let (_, _) = setup_test_accounts(&state).await;
     
-    // Generate recipient keypair
-    let mut receiver_secret = [0u8; 32];
-    OsRng.fill_bytes(&mut receiver_secret);
-    let receiver_signing_key = SigningKey::from_bytes(&receiver_secret);
-    let receiver_verifying_key = receiver_signing_key.verifying_key();
-    let receiver_address = receiver_verifying_key.to_bytes();
-
     // Create recipient account
-    sqlx::query!(
-        r#"
-        INSERT INTO accounts (address, balance, pending_balance, nonce, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        "#,
-        receiver_address.as_slice(),
-        0_i64,
-        0_i64,
-        0_i64
-    )
-    .execute(&state.db)
-    .await
-    .expect("Failed to create recipient account");
+    let receiver_address = setup_account(&state, 0).await.address;
     
     // Create mint request
     let amount = 100_i64;
     
-    // Create message to sign (to + amount)
-    let message = format!(
-        "{}{}",
-        hex::encode(receiver_address),
-        amount
-    );
+    // Create payload to sign (to + amount + mint nonce)
+    let payload = SignablePayload::mint(state.chain_id, receiver_address, tokens(amount), 0);
     
-    // Sign message with wrong key
+    // Sign payload with wrong key
     let mut wrong_secret = [0u8; 32];
     OsRng.fill_bytes(&mut wrong_secret);
     let wrong_signing_key = SigningKey::from_bytes(&wrong_secret);
-    let signature = wrong_signing_key.sign(message.as_bytes());
+    let signature = payload.sign(&wrong_signing_key);
     
     let req = Json(MintRequest {
+        token_id: DEFAULT_TOKEN_ID,
         to: hex::encode(receiver_address),
-        amount,
-        signature: hex::encode(signature.to_bytes()),
+        amount: tokens(amount),
+        nonce: 0,
+        signature: hex::encode(signature),
     });
     
     // Execute mint
@@ -144,3 +113,93 @@ async fn test_mint_invalid_signature() {
     // Verify it fails with invalid signature
     assert!(matches!(result, Err(crate::error::AppError::InvalidSignature)));
 }
+
+#[tokio::test]
+async fn test_mint_replay_rejected() {
+    let state = setup_test_state().await;
+    
+    // Setup test accounts
+    let (signing_key, _) = setup_test_accounts(&state).await;
+    
+    let mut receiver_secret = [0u8; 32];
+    OsRng.fill_bytes(&mut receiver_secret);
+    let receiver_address = SigningKey::from_bytes(&receiver_secret)
+        .verifying_key()
+        .to_bytes();
+    
+    let amount = 100_i64;
+    let payload = SignablePayload::mint(state.chain_id, receiver_address, tokens(amount), 0);
+    let signature = hex::encode(payload.sign(&signing_key));
+    
+    let req = || {
+        Json(MintRequest {
+            token_id: DEFAULT_TOKEN_ID,
+            to: hex::encode(receiver_address),
+            amount: tokens(amount),
+            nonce: 0,
+            signature: signature.clone(),
+        })
+    };
+    
+    // First submission is accepted
+    let first = mint(axum::extract::State(state.clone()), req())
+        .await
+        .expect("Failed to execute mint");
+    
+    // Replaying the same signed mint returns the original record
+    let replay = mint(axum::extract::State(state.clone()), req())
+        .await
+        .expect("Failed to replay mint");
+    assert_eq!(replay.tx_id, first.tx_id);
+    assert_eq!(first.tx_id, payload.tx_id());
+    
+    // A different mint reusing the nonce is rejected
+    let other = SignablePayload::mint(state.chain_id, receiver_address, tokens(amount + 1), 0);
+    let result = mint(
+        axum::extract::State(state.clone()),
+        Json(MintRequest {
+            token_id: DEFAULT_TOKEN_ID,
+            to: hex::encode(receiver_address),
+            amount: tokens(amount + 1),
+            nonce: 0,
+            signature: hex::encode(other.sign(&signing_key)),
+        }),
+    )
+    .await;
+    assert!(matches!(result, Err(crate::error::AppError::InvalidNonce)));
+    
+    let receiver = sqlx::query!(
+        r#"SELECT pending_balance::BIGINT AS "pending_balance!" FROM accounts WHERE address = $1"#,
+        receiver_address.as_slice()
+    )
+    .fetch_one(&state.db)
+    .await
+    .expect("Failed to fetch receiver account");
+    assert_eq!(receiver.pending_balance, amount);
+}
+
+#[tokio::test]
+async fn test_transfer_without_sender_rejected() {
+    let state = setup_test_state().await;
+    
+    let mut receiver_secret = [0u8; 32];
+    OsRng.fill_bytes(&mut receiver_secret);
+    let receiver_address = SigningKey::from_bytes(&receiver_secret)
+        .verifying_key()
+        .to_bytes();
+    
+    // A transfer without a sender must not create tokens
+    let req = Json(crate::api::transaction::TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: None,
+        to: hex::encode(receiver_address),
+        amount: Amount::new(100).into(),
+        fee: Amount::ZERO.into(),
+        nonce: 0,
+        signature: hex::encode([0u8; 64]),
+        valid_until: None,
+    });
+    
+    let result = crate::api::transaction::transfer(axum::extract::State(state.clone()), req).await;
+    assert!(matches!(result, Err(crate::error::AppError::InvalidInput(_))));
+}
@@ -1,6 +1,21 @@
 mod account_tests;
 mod transaction_tests;
 mod mint_tests;
+mod burn_tests;
+mod batch_transfer_tests;
+mod nonce_queue_tests;
+mod cancel_tests;
+mod settlement_tests;
+mod token_tests;
+mod multi_asset_tests;
+mod allowance_tests;
+mod escrow_tests;
+mod scheduler_tests;
+mod multisig_tests;
+mod compliance_tests;
+mod limits_tests;
+mod key_tests;
+mod recovery_tests;
 mod nonce_tests;
 mod websocket_tests;
 mod util;
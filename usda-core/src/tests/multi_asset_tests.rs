@@ -0,0 +1,174 @@
+use super::*;
+use crate::api::token::list;
+use crate::api::transaction::{burn, mint, transfer, BurnRequest, MintRequest, TransferRequest};
+use crate::error::AppError;
+use crate::settlement::{fail_batch, seal_batch};
+use axum::{extract::State, Json};
+use ed25519_dalek::SigningKey;
+use usda_common::{signing::DEFAULT_CHAIN_ID, SignablePayload, TokenId, TokenMetadata};
+
+/// Second token on the test ledger
+const EUR: TokenId = 1;
+
+/// State accepting the default token and EUR, each with its own issuer.
+async fn setup_multi_asset_state() -> (Arc<AppState>, SigningKey, SigningKey) {
+    let eur = TokenMetadata {
+        name: "Euro Coin".into(),
+        symbol: "EURC".into(),
+        decimals: 6,
+    };
+    let state = Arc::new(test_app_state().await.with_token(EUR, eur));
+    let usda_issuer = new_key();
+    let eur_issuer = new_key();
+    state.set_issuer_key(DEFAULT_TOKEN_ID, usda_issuer.verifying_key());
+    state.set_issuer_key(EUR, eur_issuer.verifying_key());
+    (state, usda_issuer, eur_issuer)
+}
+
+fn mint_request(issuer: &SigningKey, token_id: TokenId, to: [u8; 32], amount: i64, nonce: i64) -> MintRequest {
+    let payload = SignablePayload::mint(DEFAULT_CHAIN_ID, to, tokens(amount), nonce).with_token_id(token_id);
+    MintRequest {
+        token_id,
+        to: hex::encode(to),
+        amount: tokens(amount),
+        nonce,
+        signature: hex::encode(payload.sign(issuer)),
+    }
+}
+
+fn transfer_request(sender: &TestAccount, token_id: TokenId, to: [u8; 32], amount: i64, nonce: i64) -> TransferRequest {
+    let from = sender.address;
+    let payload = SignablePayload::transfer(DEFAULT_CHAIN_ID, from, to, tokens(amount), Amount::ZERO, nonce)
+        .with_token_id(token_id);
+    TransferRequest {
+        token_id,
+        from: Some(hex::encode(from)),
+        to: hex::encode(to),
+        amount: tokens(amount).into(),
+        fee: Amount::ZERO.into(),
+        nonce,
+        signature: hex::encode(payload.sign(&sender.key)),
+        valid_until: None,
+    }
+}
+
+async fn mint_to(state: &Arc<AppState>, issuer: &SigningKey, token_id: TokenId, to: [u8; 32], amount: i64, nonce: i64) -> String {
+    mint(State(state.clone()), Json(mint_request(issuer, token_id, to, amount, nonce)))
+        .await
+        .expect("Failed to mint")
+        .0
+        .tx_id
+}
+
+async fn send_token(state: &Arc<AppState>, sender: &TestAccount, token_id: TokenId, to: [u8; 32], amount: i64, nonce: i64) -> String {
+    transfer(State(state.clone()), Json(transfer_request(sender, token_id, to, amount, nonce)))
+        .await
+        .expect("Failed to transfer")
+        .0
+        .tx_id
+}
+
+/// `(pending_balance, nonce)` of `address` in `token_id`
+async fn pending_token(state: &AppState, address: [u8; 32], token_id: TokenId) -> (Amount, i64) {
+    let account = state
+        .get_account(&address, token_id)
+        .await
+        .unwrap()
+        .expect("Account not found");
+    (account.pending_balance, account.nonce)
+}
+
+#[tokio::test]
+async fn test_balances_and_nonces_are_per_token() {
+    let (state, usda_issuer, eur_issuer) = setup_multi_asset_state().await;
+    let alice = setup_account(&state, 0).await;
+    let alice_bytes = alice.address;
+    let bob_bytes = new_key().verifying_key().to_bytes();
+
+    mint_to(&state, &usda_issuer, DEFAULT_TOKEN_ID, alice_bytes, 1000, 0).await;
+    mint_to(&state, &eur_issuer, EUR, alice_bytes, 500, 0).await;
+
+    // Moving EUR leaves the USDA account and its nonce alone
+    send_token(&state, &alice, EUR, bob_bytes, 200, 0).await;
+    assert_eq!(pending_token(&state, alice_bytes, EUR).await, (tokens(300), 1));
+    assert_eq!(pending_token(&state, bob_bytes, EUR).await, (tokens(200), 0));
+    assert_eq!(pending_token(&state, alice_bytes, DEFAULT_TOKEN_ID).await, (tokens(1000), 0));
+    assert!(state.get_account(&bob_bytes, DEFAULT_TOKEN_ID).await.unwrap().is_none());
+
+    // A signature for one token can't move another
+    let mut req = transfer_request(&alice, DEFAULT_TOKEN_ID, bob_bytes, 100, 0);
+    req.token_id = EUR;
+    req.nonce = 1;
+    let result = transfer(State(state.clone()), Json(req)).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+
+    // Transactions record the token they moved
+    let tx_id = send_token(&state, &alice, EUR, bob_bytes, 100, 1).await;
+    let recorded = sqlx::query!("SELECT token_id FROM transactions WHERE tx_id = $1", tx_id)
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(recorded.token_id, EUR as i64);
+}
+
+#[tokio::test]
+async fn test_mint_requires_token_issuer() {
+    let (state, usda_issuer, _) = setup_multi_asset_state().await;
+    let sender = setup_account(&state, 0).await;
+    let to = new_key().verifying_key().to_bytes();
+
+    // The default token's issuer can't mint EUR
+    let result = mint(State(state.clone()), Json(mint_request(&usda_issuer, EUR, to, 100, 0))).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+
+    // Tokens the ledger doesn't know are refused outright
+    let result = mint(State(state.clone()), Json(mint_request(&usda_issuer, 7, to, 100, 0))).await;
+    assert!(matches!(result, Err(AppError::UnknownToken(7))));
+
+    let result = transfer(State(state.clone()), Json(transfer_request(&sender, 7, to, 100, 0))).await;
+    assert!(matches!(result, Err(AppError::UnknownToken(7))));
+}
+
+#[tokio::test]
+async fn test_supply_is_tracked_per_token() {
+    let (state, usda_issuer, eur_issuer) = setup_multi_asset_state().await;
+    let holder = setup_account(&state, 0).await;
+    let holder_bytes = holder.address;
+
+    // A mint whose batch fails no longer counts towards supply
+    mint_to(&state, &eur_issuer, EUR, holder_bytes, 250, 0).await;
+    let batch = seal_batch(&state, 100).await.unwrap().expect("Expected a batch");
+    fail_batch(&state, &batch.batch_id).await.expect("Failed to fail batch");
+
+    mint_to(&state, &eur_issuer, EUR, holder_bytes, 1000, 1).await;
+    mint_to(&state, &usda_issuer, DEFAULT_TOKEN_ID, holder_bytes, 40, 0).await;
+
+    let payload = SignablePayload::burn(state.chain_id, holder_bytes, tokens(300), 0, None).with_token_id(EUR);
+    let burned = burn(
+        State(state.clone()),
+        Json(BurnRequest {
+            token_id: EUR,
+            from: hex::encode(holder_bytes),
+            amount: tokens(300),
+            nonce: 0,
+            redemption_ref: None,
+            signature: hex::encode(payload.sign(&holder.key)),
+        }),
+    )
+    .await
+    .expect("Failed to burn EUR");
+    assert_eq!(burned.0.status, "SEQUENCED");
+
+    let listed = list(State(state.clone())).await.expect("Failed to list tokens").0;
+    assert_eq!(listed.len(), 2);
+
+    let usda = &listed[0];
+    assert_eq!(usda.token_id, DEFAULT_TOKEN_ID);
+    assert_eq!((usda.minted, usda.burned, usda.supply), (tokens(40), Amount::ZERO, tokens(40)));
+    assert_eq!(usda.issuer, Some(hex::encode(usda_issuer.verifying_key().to_bytes())));
+
+    let eur = &listed[1];
+    assert_eq!(eur.token_id, EUR);
+    assert_eq!(eur.metadata.symbol, "EURC");
+    assert_eq!((eur.minted, eur.burned, eur.supply), (tokens(1000), tokens(300), tokens(700)));
+}
@@ -0,0 +1,203 @@
+use super::*;
+use crate::api::multisig::{
+    create, get, update, CreateMultisigRequest, MemberSignatureInput, UpdateMultisigRequest,
+};
+use crate::api::transaction::{multisig_transfer, MultisigTransferRequest};
+use crate::error::AppError;
+use axum::{extract::{Path, State}, Json};
+use ed25519_dalek::SigningKey;
+use usda_common::{MultisigPolicy, SignablePayload, TransactionKind, TransactionStatus};
+
+fn member_hex(keys: &[&SigningKey]) -> Vec<String> {
+    keys.iter().map(|key| hex::encode(key.verifying_key().to_bytes())).collect()
+}
+
+fn sign_all(payload: &SignablePayload, signers: &[&SigningKey]) -> Vec<MemberSignatureInput> {
+    signers
+        .iter()
+        .map(|signer| MemberSignatureInput {
+            public_key: hex::encode(signer.verifying_key().to_bytes()),
+            signature: hex::encode(payload.sign(signer)),
+        })
+        .collect()
+}
+
+/// Creates the multisig account of `threshold` of `members` holding `balance`
+async fn setup_multisig(state: &Arc<AppState>, threshold: u32, members: &[&SigningKey], balance: i64) -> [u8; 32] {
+    let account = create(
+        State(state.clone()),
+        Json(CreateMultisigRequest { threshold, members: member_hex(members) }),
+    )
+    .await
+    .expect("Failed to create multisig account")
+    .0;
+    let address: [u8; 32] = hex::decode(&account.address).unwrap().try_into().unwrap();
+
+    fund(state, address, balance).await;
+
+    address
+}
+
+fn signed_transfer(
+    state: &AppState,
+    from: [u8; 32],
+    to: [u8; 32],
+    amount: i64,
+    nonce: i64,
+    signers: &[&SigningKey],
+) -> Json<MultisigTransferRequest> {
+    let payload = SignablePayload::transfer(state.chain_id, from, to, tokens(amount), Amount::ZERO, nonce);
+
+    Json(MultisigTransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: hex::encode(from),
+        to: hex::encode(to),
+        amount: tokens(amount).into(),
+        fee: Amount::ZERO.into(),
+        nonce,
+        signatures: sign_all(&payload, signers),
+        valid_until: None,
+    })
+}
+
+fn signed_update(
+    state: &AppState,
+    address: [u8; 32],
+    threshold: u32,
+    members: &[&SigningKey],
+    version: i64,
+    signers: &[&SigningKey],
+) -> Json<UpdateMultisigRequest> {
+    let policy = MultisigPolicy::new(
+        threshold,
+        members.iter().map(|key| key.verifying_key().to_bytes()).collect(),
+    )
+    .unwrap();
+    let payload = SignablePayload::multisig_update(state.chain_id, address, policy, version);
+
+    Json(UpdateMultisigRequest {
+        address: hex::encode(address),
+        threshold,
+        members: member_hex(members),
+        version,
+        signatures: sign_all(&payload, signers),
+    })
+}
+
+#[tokio::test]
+async fn test_multisig_transfer_needs_threshold() {
+    let state = setup_test_state().await;
+    let (alice, bob, carol, mallory) = (new_key(), new_key(), new_key(), new_key());
+    let wallet = setup_multisig(&state, 2, &[&alice, &bob, &carol], 1000).await;
+    let receiver = new_key().verifying_key().to_bytes();
+
+    // One member, the same member twice, or a member and an outsider fall short
+    for signers in [vec![&alice], vec![&alice, &alice], vec![&alice, &mallory]] {
+        let result = multisig_transfer(State(state.clone()), signed_transfer(&state, wallet, receiver, 100, 0, &signers)).await;
+        assert!(matches!(result, Err(AppError::InvalidSignature)));
+    }
+    assert_eq!(pending_balance(&state, wallet).await, tokens(1000));
+
+    let response = multisig_transfer(State(state.clone()), signed_transfer(&state, wallet, receiver, 100, 0, &[&carol, &alice]))
+        .await
+        .expect("Transfer signed by two members should succeed")
+        .0;
+    assert_eq!(response.status, TransactionStatus::Sequenced.to_string());
+    assert_eq!(pending_balance(&state, wallet).await, tokens(900));
+    assert_eq!(pending_balance(&state, receiver).await, tokens(100));
+
+    // Resubmitting returns the original record
+    let resubmitted = multisig_transfer(State(state.clone()), signed_transfer(&state, wallet, receiver, 100, 0, &[&carol, &alice]))
+        .await
+        .expect("Resubmitted transfer should succeed");
+    assert_eq!(resubmitted.0.tx_id, response.tx_id);
+    assert_eq!(pending_balance(&state, wallet).await, tokens(900));
+
+    let record = sqlx::query!("SELECT kind, multisig_version FROM transactions WHERE tx_id = $1", response.tx_id)
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+    assert_eq!(record.kind, TransactionKind::MultisigTransfer.to_string());
+    assert_eq!(record.multisig_version, Some(0));
+    let approvals = sqlx::query_scalar!("SELECT COUNT(*) FROM multisig_signatures WHERE tx_id = $1", response.tx_id)
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+    assert_eq!(approvals, Some(2));
+
+    // Multisig transfers aren't queued
+    let result = multisig_transfer(State(state.clone()), signed_transfer(&state, wallet, receiver, 100, 2, &[&alice, &bob])).await;
+    assert!(matches!(result, Err(AppError::InvalidNonce)));
+}
+
+#[tokio::test]
+async fn test_multisig_update_needs_current_policy() {
+    let state = setup_test_state().await;
+    let (alice, bob, carol, dave) = (new_key(), new_key(), new_key(), new_key());
+    let wallet = setup_multisig(&state, 2, &[&alice, &bob, &carol], 1000).await;
+    let receiver = new_key().verifying_key().to_bytes();
+
+    // Replace the members with carol and dave, either of whom can sign alone
+    let result = update(State(state.clone()), signed_update(&state, wallet, 1, &[&carol, &dave], 0, &[&alice])).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+    let result = update(State(state.clone()), signed_update(&state, wallet, 1, &[&carol, &dave], 1, &[&alice, &bob])).await;
+    assert!(matches!(result, Err(AppError::InvalidInput(_))));
+
+    let account = update(State(state.clone()), signed_update(&state, wallet, 1, &[&carol, &dave], 0, &[&alice, &bob]))
+        .await
+        .expect("Update approved by the current policy should succeed")
+        .0;
+    assert_eq!(account.address, hex::encode(wallet));
+    assert_eq!((account.threshold, account.version), (1, 1));
+
+    // A signed update can't be replayed against the new version
+    let result = update(State(state.clone()), signed_update(&state, wallet, 1, &[&carol, &dave], 0, &[&alice, &bob])).await;
+    assert!(matches!(result, Err(AppError::InvalidInput(_))));
+
+    let current = get(State(state.clone()), Path(hex::encode(wallet))).await.unwrap().0;
+    assert_eq!(current.members.len(), 2);
+    assert!(current.members.contains(&hex::encode(dave.verifying_key().to_bytes())));
+
+    // Creating the original policy again returns the account as it is now
+    let recreated = create(
+        State(state.clone()),
+        Json(CreateMultisigRequest { threshold: 2, members: member_hex(&[&alice, &bob, &carol]) }),
+    )
+    .await
+    .unwrap()
+    .0;
+    assert_eq!((recreated.address, recreated.version), (hex::encode(wallet), 1));
+
+    // Former members no longer count; a new one signs alone
+    let result = multisig_transfer(State(state.clone()), signed_transfer(&state, wallet, receiver, 100, 0, &[&alice, &bob])).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+    let response = multisig_transfer(State(state.clone()), signed_transfer(&state, wallet, receiver, 100, 0, &[&dave]))
+        .await
+        .expect("Transfer signed under the new policy should succeed");
+    assert_eq!(response.0.status, TransactionStatus::Sequenced.to_string());
+    assert_eq!(pending_balance(&state, receiver).await, tokens(100));
+
+    let versions = sqlx::query_scalar!("SELECT version FROM multisig_policies WHERE address = $1 ORDER BY version", wallet.as_slice())
+        .fetch_all(&state.db)
+        .await
+        .unwrap();
+    assert_eq!(versions, vec![0, 1]);
+}
+
+#[tokio::test]
+async fn test_invalid_multisig_policy_is_rejected() {
+    let state = setup_test_state().await;
+    let (alice, bob) = (new_key(), new_key());
+
+    for (threshold, members) in [(0, vec![&alice, &bob]), (3, vec![&alice, &bob]), (1, vec![&alice, &alice]), (1, vec![])] {
+        let result = create(
+            State(state.clone()),
+            Json(CreateMultisigRequest { threshold, members: member_hex(&members) }),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::InvalidInput(_))));
+    }
+
+    let result = get(State(state.clone()), Path(hex::encode([0x42u8; 32]))).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+}
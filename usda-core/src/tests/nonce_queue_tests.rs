@@ -0,0 +1,81 @@
+use super::*;
+use crate::api::transaction::{prune_queued_transfers, transfer};
+use crate::error::AppError;
+use axum::extract::State;
+use std::time::Duration;
+use usda_common::TransactionStatus;
+
+#[tokio::test]
+async fn test_future_nonce_queued_until_gap_fills() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let receiver_bytes = setup_account(&state, 0).await.address;
+    
+    // Nonces 2 and 1 arrive before 0 and are held
+    for nonce in [2, 1] {
+        let response = transfer(State(state.clone()), signed_transfer(&state, &sender, receiver_bytes, 100, 0, nonce))
+            .await
+            .expect("Failed to queue transfer");
+        assert_eq!(response.status, TransactionStatus::Received.to_string());
+    }
+    assert_eq!(pending(&state, sender_bytes).await, (tokens(1000), 0));
+    
+    // Nonce 0 fills the gap and releases the queued transfers in order
+    let response = transfer(State(state.clone()), signed_transfer(&state, &sender, receiver_bytes, 100, 0, 0))
+        .await
+        .expect("Failed to execute transfer");
+    assert_eq!(response.status, TransactionStatus::Sequenced.to_string());
+    
+    assert_eq!(pending(&state, sender_bytes).await, (tokens(700), 3));
+    assert_eq!(pending(&state, receiver_bytes).await, (tokens(300), 0));
+    
+    let queued = sqlx::query!("SELECT COUNT(*) AS count FROM queued_transfers")
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+    assert_eq!(queued.count, Some(0));
+}
+
+#[tokio::test]
+async fn test_nonce_gap_limit() {
+    let state = Arc::new(test_app_state().await.with_max_nonce_gap(2));
+    let sender = setup_account(&state, 1000).await;
+    let receiver_bytes = setup_account(&state, 0).await.address;
+    
+    let result = transfer(State(state.clone()), signed_transfer(&state, &sender, receiver_bytes, 100, 0, 3)).await;
+    assert!(matches!(result, Err(AppError::InvalidNonce)));
+    
+    // A different transfer can't take a nonce that is already queued
+    let _ = transfer(State(state.clone()), signed_transfer(&state, &sender, receiver_bytes, 100, 0, 2))
+        .await
+        .expect("Failed to queue transfer");
+    let result = transfer(State(state.clone()), signed_transfer(&state, &sender, receiver_bytes, 200, 0, 2)).await;
+    assert!(matches!(result, Err(AppError::InvalidNonce)));
+}
+
+#[tokio::test]
+async fn test_queued_transfer_times_out() {
+    let state = Arc::new(test_app_state().await.with_queue_timeout(Duration::ZERO));
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let receiver_bytes = setup_account(&state, 0).await.address;
+    
+    let response = transfer(State(state.clone()), signed_transfer(&state, &sender, receiver_bytes, 100, 0, 1))
+        .await
+        .expect("Failed to queue transfer");
+    assert_eq!(response.status, TransactionStatus::Received.to_string());
+    
+    // The expired entry is not applied once the gap fills
+    let _ = transfer(State(state.clone()), signed_transfer(&state, &sender, receiver_bytes, 100, 0, 0))
+        .await
+        .expect("Failed to execute transfer");
+    assert_eq!(pending(&state, sender_bytes).await, (tokens(900), 1));
+    
+    // Expired entries left behind are pruned
+    let _ = transfer(State(state.clone()), signed_transfer(&state, &sender, receiver_bytes, 100, 0, 3))
+        .await
+        .expect("Failed to queue transfer");
+    let pruned = prune_queued_transfers(&state.db).await.unwrap();
+    assert_eq!(pruned, 1);
+}
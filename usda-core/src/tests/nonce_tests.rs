@@ -1,10 +1,10 @@
 use axum::Json;
-use ed25519_dalek::{SigningKey, Signer};
+use ed25519_dalek::SigningKey;
 use rand::rngs::OsRng;
 use sqlx::postgres::PgPoolOptions;
 use std::sync::Arc;
 use tokio::sync::broadcast;
-use usda_common::WebSocketMessage;
+use usda_common::{Amount, SignablePayload, WebSocketMessage};
 
 use crate::{
     api::transaction::{transfer, TransferRequest},
@@ -19,10 +19,58 @@ async fn setup_test_state() -> Arc<AppState> {
         .await
         .expect("Failed to create connection pool");
 
+    sqlx::query!("DELETE FROM multisig_signatures")
+        .execute(&pool)
+        .await
+        .expect("Failed to clear multisig signatures");
     sqlx::query!("DELETE FROM transactions")
         .execute(&pool)
         .await
         .expect("Failed to clear transactions");
+    sqlx::query!("DELETE FROM standing_order_executions")
+        .execute(&pool)
+        .await
+        .expect("Failed to clear standing order executions");
+    sqlx::query!("DELETE FROM standing_orders")
+        .execute(&pool)
+        .await
+        .expect("Failed to clear standing orders");
+    sqlx::query!("DELETE FROM multisig_policies")
+        .execute(&pool)
+        .await
+        .expect("Failed to clear multisig policies");
+    sqlx::query!("DELETE FROM multisig_accounts")
+        .execute(&pool)
+        .await
+        .expect("Failed to clear multisig accounts");
+    sqlx::query!("DELETE FROM frozen_accounts")
+        .execute(&pool)
+        .await
+        .expect("Failed to clear frozen accounts");
+    sqlx::query!("DELETE FROM freeze_actions")
+        .execute(&pool)
+        .await
+        .expect("Failed to clear freeze actions");
+    sqlx::query!("DELETE FROM spending_limits")
+        .execute(&pool)
+        .await
+        .expect("Failed to clear spending limits");
+    sqlx::query!("DELETE FROM escrows")
+        .execute(&pool)
+        .await
+        .expect("Failed to clear escrows");
+    sqlx::query!("DELETE FROM allowances")
+        .execute(&pool)
+        .await
+        .expect("Failed to clear allowances");
+    sqlx::query!("DELETE FROM account_key_history")
+        .execute(&pool)
+        .await
+        .expect("Failed to clear account key history");
+    sqlx::query!("DELETE FROM account_keys")
+        .execute(&pool)
+        .await
+        .expect("Failed to clear account keys");
     sqlx::query!("DELETE FROM accounts")
         .execute(&pool)
         .await
@@ -53,7 +101,7 @@ async fn test_nonce_validation() {
     sqlx::query!(
         r#"
         INSERT INTO accounts (address, balance, pending_balance, nonce, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
+        VALUES ($1, $2::BIGINT, $3::BIGINT, $4, NOW())
         "#,
         sender_address.as_slice(),
         1000_i64,
@@ -67,7 +115,7 @@ async fn test_nonce_validation() {
     sqlx::query!(
         r#"
         INSERT INTO accounts (address, balance, pending_balance, nonce, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
+        VALUES ($1, $2::BIGINT, $3::BIGINT, $4, NOW())
         "#,
         recipient_address.as_slice(),
         0_i64,
@@ -79,78 +127,103 @@ async fn test_nonce_validation() {
     .expect("Failed to create recipient account");
 
     // Test 1: Valid nonce should succeed
-    let transfer_message = format!(
-        "{}{}{}{}",
-        hex::encode(sender_address),
-        hex::encode(recipient_address),
-        100,
-        0 // First nonce
+    let transfer_payload = SignablePayload::transfer(
+        state.chain_id,
+        sender_address,
+        recipient_address,
+        Amount::new(100),
+        Amount::ZERO,
+        0, // First nonce
     );
-    let transfer_signature = sender_signing_key.sign(transfer_message.as_bytes());
+    let transfer_signature = transfer_payload.sign(&sender_signing_key);
 
     let transfer_req = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
         from: Some(hex::encode(sender_address)),
         to: hex::encode(recipient_address),
-        amount: 100,
+        amount: Amount::new(100).into(),
+        fee: Amount::ZERO.into(),
         nonce: 0,
-        signature: hex::encode(transfer_signature.to_bytes()),
+        signature: hex::encode(transfer_signature),
+        valid_until: None,
     });
 
     let result = transfer(axum::extract::State(state.clone()), transfer_req)
         .await;
     assert!(result.is_ok(), "First transfer with nonce 0 should succeed");
 
-    // Test 2: Reusing the same nonce should fail
+    // Test 2: Reusing the same nonce for a different transfer should fail
+    let transfer_payload = SignablePayload::transfer(
+        state.chain_id,
+        sender_address,
+        recipient_address,
+        Amount::new(50),
+        Amount::ZERO,
+        0, // Reusing nonce
+    );
+    let transfer_signature = transfer_payload.sign(&sender_signing_key);
+
     let transfer_req = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
         from: Some(hex::encode(sender_address)),
         to: hex::encode(recipient_address),
-        amount: 100,
-        nonce: 0, // Reusing nonce
-        signature: hex::encode(transfer_signature.to_bytes()),
+        amount: Amount::new(50).into(),
+        fee: Amount::ZERO.into(),
+        nonce: 0,
+        signature: hex::encode(transfer_signature),
+        valid_until: None,
     });
 
     let result = transfer(axum::extract::State(state.clone()), transfer_req)
         .await;
     assert!(matches!(result, Err(AppError::InvalidNonce)), "Reused nonce should fail");
 
-    // Test 3: Skipping a nonce should fail
-    let transfer_message = format!(
-        "{}{}{}{}",
-        hex::encode(sender_address),
-        hex::encode(recipient_address),
-        100,
-        2 // Skipping nonce 1
+    // Test 3: Skipping a nonce queues the transfer
+    let transfer_payload = SignablePayload::transfer(
+        state.chain_id,
+        sender_address,
+        recipient_address,
+        Amount::new(100),
+        Amount::ZERO,
+        2, // Skipping nonce 1
     );
-    let transfer_signature = sender_signing_key.sign(transfer_message.as_bytes());
+    let transfer_signature = transfer_payload.sign(&sender_signing_key);
 
     let transfer_req = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
         from: Some(hex::encode(sender_address)),
         to: hex::encode(recipient_address),
-        amount: 100,
+        amount: Amount::new(100).into(),
+        fee: Amount::ZERO.into(),
         nonce: 2,
-        signature: hex::encode(transfer_signature.to_bytes()),
+        signature: hex::encode(transfer_signature),
+        valid_until: None,
     });
 
     let result = transfer(axum::extract::State(state.clone()), transfer_req)
         .await;
-    assert!(matches!(result, Err(AppError::InvalidNonce)), "Skipped nonce should fail");
-
-    // Test 4: Correct next nonce should succeed
-    let transfer_message = format!(
-        "{}{}{}{}",
-        hex::encode(sender_address),
-        hex::encode(recipient_address),
-        100,
-        1 // Correct next nonce
+    assert_eq!(result.map(|r| r.status.clone()).ok(), Some("QUEUED".to_string()), "Skipped nonce should be queued");
+
+    // Test 4: Correct next nonce should succeed and release the queued transfer
+    let transfer_payload = SignablePayload::transfer(
+        state.chain_id,
+        sender_address,
+        recipient_address,
+        Amount::new(100),
+        Amount::ZERO,
+        1, // Correct next nonce
     );
-    let transfer_signature = sender_signing_key.sign(transfer_message.as_bytes());
+    let transfer_signature = transfer_payload.sign(&sender_signing_key);
 
     let transfer_req = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
         from: Some(hex::encode(sender_address)),
         to: hex::encode(recipient_address),
-        amount: 100,
+        amount: Amount::new(100).into(),
+        fee: Amount::ZERO.into(),
         nonce: 1,
-        signature: hex::encode(transfer_signature.to_bytes()),
+        signature: hex::encode(transfer_signature),
+        valid_until: None,
     });
 
     let result = transfer(axum::extract::State(state.clone()), transfer_req)
@@ -167,7 +240,7 @@ async fn test_nonce_validation() {
     sqlx::query!(
         r#"
         INSERT INTO accounts (address, balance, pending_balance, nonce, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
+        VALUES ($1, $2::BIGINT, $3::BIGINT, $4, NOW())
         "#,
         other_address.as_slice(),
         1000_i64,
@@ -178,21 +251,25 @@ async fn test_nonce_validation() {
     .await
     .expect("Failed to create other account");
 
-    let transfer_message = format!(
-        "{}{}{}{}",
-        hex::encode(other_address),
-        hex::encode(recipient_address),
-        100,
-        0 // First nonce for new account
+    let transfer_payload = SignablePayload::transfer(
+        state.chain_id,
+        other_address,
+        recipient_address,
+        Amount::new(100),
+        Amount::ZERO,
+        0, // First nonce for new account
     );
-    let transfer_signature = other_signing_key.sign(transfer_message.as_bytes());
+    let transfer_signature = transfer_payload.sign(&other_signing_key);
 
     let transfer_req = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
         from: Some(hex::encode(other_address)),
         to: hex::encode(recipient_address),
-        amount: 100,
+        amount: Amount::new(100).into(),
+        fee: Amount::ZERO.into(),
         nonce: 0,
-        signature: hex::encode(transfer_signature.to_bytes()),
+        signature: hex::encode(transfer_signature),
+        valid_until: None,
     });
 
     let result = transfer(axum::extract::State(state.clone()), transfer_req)
@@ -0,0 +1,204 @@
+use super::*;
+use crate::api::account::{rotate_key, RotateKeyRequest};
+use crate::api::transaction::{mint, MintRequest};
+use crate::prover::{prove_next_batch, BatchOutcome, BatchProver, ProverError};
+use async_trait::async_trait;
+use axum::{extract::State, Json};
+use ed25519_dalek::SigningKey;
+use std::collections::{BTreeMap, BTreeSet};
+use usda_common::batch::{BatchEntry, BatchInput, BatchProof, BatchResult};
+use usda_common::{SignablePayload, TransactionStatus};
+
+/// Checks every entry the way the batch program does, and commits what it would.
+struct CheckingProver;
+
+#[async_trait]
+impl BatchProver for CheckingProver {
+    async fn prove(&self, _batch_id: &str, input: &BatchInput) -> Result<BatchProof, ProverError> {
+        let issuers: BTreeMap<_, _> = input.issuer_keys.iter().copied().collect();
+        let mut account_keys = BTreeSet::new();
+        let mut multisig_policies = BTreeSet::new();
+
+        for entry in &input.entries {
+            let payload = entry.payload(input.chain_id);
+            let signed = match entry {
+                BatchEntry::Mint(proof) => issuers
+                    .get(&proof.token_id)
+                    .is_some_and(|key| payload.verify(key, &proof.signature)),
+                BatchEntry::Clawback(proof) => issuers
+                    .get(&proof.token_id)
+                    .is_some_and(|key| payload.verify(key, &proof.signature)),
+                BatchEntry::MultisigTransfer(proof) => {
+                    multisig_policies.insert((proof.from_addr, proof.policy.digest()));
+                    proof.policy.verify(&payload, &proof.signatures)
+                }
+                BatchEntry::Transfer(proof) => {
+                    account_keys.insert((proof.from_addr, proof.public_key));
+                    payload.verify(&proof.public_key, &proof.signature)
+                }
+                BatchEntry::Burn(proof) => {
+                    account_keys.insert((proof.from_addr, proof.public_key));
+                    payload.verify(&proof.public_key, &proof.signature)
+                }
+                BatchEntry::BatchTransfer(proof) => {
+                    account_keys.insert((proof.from_addr, proof.public_key));
+                    payload.verify(&proof.public_key, &proof.signature)
+                }
+                BatchEntry::TransferFrom(proof) => {
+                    account_keys.insert((proof.spender_addr, proof.public_key));
+                    payload.verify(&proof.public_key, &proof.signature)
+                }
+                BatchEntry::EscrowLock(proof) => {
+                    account_keys.insert((proof.terms.from_addr, proof.public_key));
+                    payload.verify(&proof.public_key, &proof.signature)
+                }
+                BatchEntry::EscrowClaim(proof) => {
+                    account_keys.insert((proof.terms.to_addr, proof.public_key));
+                    payload.verify(&proof.public_key, &proof.signature)
+                }
+                BatchEntry::EscrowRefund(proof) => {
+                    account_keys.insert((proof.terms.from_addr, proof.public_key));
+                    payload.verify(&proof.public_key, &proof.signature)
+                }
+                BatchEntry::ScheduledTransfer(proof) => {
+                    account_keys.insert((proof.terms.from_addr, proof.public_key));
+                    payload.verify(&proof.public_key, &proof.signature)
+                }
+            };
+            if !signed || entry.is_expired(input.chain_id, input.batch_time) {
+                return Err(ProverError::Rejected("Invalid entry".into()));
+            }
+        }
+
+        Ok(BatchProof {
+            proof: b"proof".to_vec(),
+            vkey_hash: "0x00".into(),
+            result: BatchResult {
+                chain_id: input.chain_id,
+                batch_time: input.batch_time,
+                issuer_keys: input.issuer_keys.clone(),
+                multisig_policies: multisig_policies.into_iter().collect(),
+                account_keys: account_keys.into_iter().collect(),
+                cycles_used: 0,
+                supply: Vec::new(),
+            },
+        })
+    }
+}
+
+/// Never produces a proof; `rejected` tells whether the program refused the batch.
+struct RefusingProver {
+    rejected: bool,
+}
+
+#[async_trait]
+impl BatchProver for RefusingProver {
+    async fn prove(&self, _batch_id: &str, _input: &BatchInput) -> Result<BatchProof, ProverError> {
+        if self.rejected {
+            Err(ProverError::Rejected("Invalid entry signature".into()))
+        } else {
+            Err(ProverError::Unavailable("Prover offline".into()))
+        }
+    }
+}
+
+/// A new account holding `balance` minted by `issuer`, and the key that signs for it.
+async fn funded_account(state: &Arc<AppState>, issuer: &SigningKey, mint_nonce: i64, balance: i64) -> TestAccount {
+    let key = new_key();
+    let address = state
+        .create_account(key.verifying_key().to_bytes(), DEFAULT_TOKEN_ID)
+        .await
+        .expect("Failed to create account")
+        .address;
+    if balance > 0 {
+        let payload = SignablePayload::mint(state.chain_id, address, tokens(balance), mint_nonce);
+        let minted = mint(
+            State(state.clone()),
+            Json(MintRequest {
+                token_id: DEFAULT_TOKEN_ID,
+                to: hex::encode(address),
+                amount: tokens(balance),
+                nonce: mint_nonce,
+                signature: hex::encode(payload.sign(issuer)),
+            }),
+        )
+        .await
+        .expect("Failed to mint");
+        assert_eq!(minted.0.status, TransactionStatus::Sequenced.to_string());
+    }
+    TestAccount { key, address }
+}
+
+#[tokio::test]
+async fn test_batches_are_proven_end_to_end() {
+    let state = setup_test_state().await;
+    let issuer = new_key();
+    state.set_issuer_key(DEFAULT_TOKEN_ID, issuer.verifying_key());
+    let alice = funded_account(&state, &issuer, 0, 1000).await;
+    let alice_address = alice.address;
+    let bob_address = funded_account(&state, &issuer, 1, 0).await.address;
+
+    // Alice pays Bob, rotates her key and pays him again with the new one
+    let before = send(&state, &alice, bob_address, 100, 0, 0).await;
+    let rotated = new_key();
+    let new_public_key = rotated.verifying_key().to_bytes();
+    let payload = SignablePayload::rotate_key(state.chain_id, alice_address, new_public_key, 0);
+    let key = rotate_key(
+        State(state.clone()),
+        Json(RotateKeyRequest {
+            address: hex::encode(alice_address),
+            new_public_key: hex::encode(new_public_key),
+            version: 0,
+            signature: hex::encode(payload.sign(&alice.key)),
+        }),
+    )
+    .await
+    .expect("Failed to rotate key");
+    assert_eq!(key.0.version, 1);
+    let rotated = TestAccount { key: rotated, address: alice_address };
+    let after = send(&state, &rotated, bob_address, 50, 0, 1).await;
+
+    // The batch is sealed, proven against both of Alice's keys and settled
+    let outcome = prove_next_batch(&state, &CheckingProver, 100).await.unwrap();
+    assert!(matches!(outcome, Some(BatchOutcome::Settled(_))), "Unexpected outcome {:?}", outcome);
+    assert_eq!(balances(&state, alice_address).await, (850, 850));
+    assert_eq!(balances(&state, bob_address).await, (150, 150));
+    for tx_id in [&before, &after] {
+        assert_eq!(status_of(&state, tx_id).await, TransactionStatus::Proven.to_string());
+    }
+
+    assert_eq!(prove_next_batch(&state, &CheckingProver, 100).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_unproven_batches_are_retried_or_failed() {
+    let state = setup_test_state().await;
+    let issuer = new_key();
+    state.set_issuer_key(DEFAULT_TOKEN_ID, issuer.verifying_key());
+    let alice = funded_account(&state, &issuer, 0, 1000).await;
+    let alice_address = alice.address;
+    let bob_address = funded_account(&state, &issuer, 1, 0).await.address;
+    let first = send(&state, &alice, bob_address, 100, 0, 0).await;
+
+    // An unavailable prover leaves the batch to be retried
+    let Some(BatchOutcome::Deferred(deferred, _)) =
+        prove_next_batch(&state, &RefusingProver { rejected: false }, 100).await.unwrap()
+    else {
+        panic!("Expected the batch to be deferred");
+    };
+    assert_eq!(status_of(&state, &first).await, TransactionStatus::Batched.to_string());
+
+    // The retry proves the same batch before sealing the transactions after it
+    let second = send(&state, &alice, bob_address, 200, 0, 1).await;
+    let outcome = prove_next_batch(&state, &CheckingProver, 100).await.unwrap();
+    assert_eq!(outcome, Some(BatchOutcome::Settled(deferred)));
+    assert_eq!(status_of(&state, &first).await, TransactionStatus::Proven.to_string());
+    assert_eq!(status_of(&state, &second).await, TransactionStatus::Sequenced.to_string());
+
+    // A batch the program rejects is failed and rolled back
+    let outcome = prove_next_batch(&state, &RefusingProver { rejected: true }, 100).await.unwrap();
+    assert!(matches!(outcome, Some(BatchOutcome::Failed(_, _))), "Unexpected outcome {:?}", outcome);
+    assert_eq!(status_of(&state, &second).await, TransactionStatus::Failed.to_string());
+    assert_eq!(balances(&state, alice_address).await, (900, 900));
+    assert_eq!(balances(&state, bob_address).await, (100, 100));
+}
@@ -0,0 +1,218 @@
+use super::*;
+use crate::api::account::{get_key, rotate_key, RotateKeyRequest};
+use crate::api::multisig::MemberSignatureInput;
+use crate::api::recovery::{
+    get_recoveries, initiate, set_guardians, veto, RecoveryRequest, SetGuardiansRequest, VetoRecoveryRequest,
+};
+use crate::api::transaction::transfer;
+use crate::error::AppError;
+use crate::recovery::complete_due_recoveries;
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use ed25519_dalek::SigningKey;
+use std::time::Duration;
+use usda_common::{MultisigPolicy, RecoveryStatus, SignablePayload};
+
+fn signed_guardians(
+    state: &AppState,
+    owner: &TestAccount,
+    threshold: u32,
+    guardians: &[&SigningKey],
+    version: i64,
+) -> Json<SetGuardiansRequest> {
+    let address = owner.address;
+    let keys: Vec<[u8; 32]> = guardians.iter().map(|guardian| guardian.verifying_key().to_bytes()).collect();
+    let policy = MultisigPolicy::new(threshold, keys.clone()).unwrap();
+    let payload = SignablePayload::set_guardians(state.chain_id, address, policy, version);
+
+    Json(SetGuardiansRequest {
+        address: hex::encode(address),
+        threshold,
+        guardians: keys.iter().map(hex::encode).collect(),
+        version,
+        signature: hex::encode(payload.sign(&owner.key)),
+    })
+}
+
+fn signed_recovery(
+    state: &AppState,
+    address: [u8; 32],
+    new_key: &SigningKey,
+    version: i64,
+    signers: &[&SigningKey],
+) -> Json<RecoveryRequest> {
+    let new_key = new_key.verifying_key().to_bytes();
+    let payload = SignablePayload::recover_account(state.chain_id, address, new_key, version);
+
+    Json(RecoveryRequest {
+        address: hex::encode(address),
+        new_public_key: hex::encode(new_key),
+        version,
+        signatures: signers
+            .iter()
+            .map(|signer| MemberSignatureInput {
+                public_key: hex::encode(signer.verifying_key().to_bytes()),
+                signature: hex::encode(payload.sign(signer)),
+            })
+            .collect(),
+    })
+}
+
+fn signed_veto(state: &AppState, signer: &SigningKey, recovery_id: &str) -> Json<VetoRecoveryRequest> {
+    let recovery: [u8; 32] = hex::decode(recovery_id).unwrap().try_into().unwrap();
+
+    Json(VetoRecoveryRequest {
+        recovery_id: recovery_id.to_string(),
+        signature: hex::encode(SignablePayload::veto_recovery(state.chain_id, recovery).sign(signer)),
+    })
+}
+
+#[tokio::test]
+async fn test_guardians_recover_account_after_delay() {
+    let state = Arc::new(test_app_state().await.with_recovery_delay(Duration::ZERO));
+    let owner = setup_account(&state, 1000).await;
+    let address = owner.address;
+    let guardians = [new_key(), new_key(), new_key()];
+    let [first, second, third] = &guardians;
+    let bob = new_key().verifying_key().to_bytes();
+
+    // Only the owner sets guardians
+    let impostor = TestAccount { key: first.clone(), address };
+    let result = set_guardians(State(state.clone()), signed_guardians(&state, &impostor, 2, &[first, second, third], 0)).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+    let result = set_guardians(State(state.clone()), signed_guardians(&state, &owner, 2, &[first, second, third], 1)).await;
+    assert!(matches!(result, Err(AppError::InvalidInput(_))));
+    let set = set_guardians(State(state.clone()), signed_guardians(&state, &owner, 2, &[first, second, third], 0))
+        .await
+        .expect("Failed to set guardians")
+        .0;
+    assert_eq!((set.threshold, set.version), (2, 1));
+
+    // One guardian isn't enough
+    let recovered_key = new_key();
+    let result = initiate(State(state.clone()), signed_recovery(&state, address, &recovered_key, 0, &[first, first])).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+
+    let recovery = initiate(State(state.clone()), signed_recovery(&state, address, &recovered_key, 0, &[first, third]))
+        .await
+        .expect("Failed to initiate recovery")
+        .0;
+    assert_eq!((recovery.status, recovery.key_version), (RecoveryStatus::Pending, 0));
+
+    assert_eq!(complete_due_recoveries(&state).await.unwrap(), 1);
+    assert_eq!(complete_due_recoveries(&state).await.unwrap(), 0);
+
+    let key = get_key(State(state.clone()), Path(hex::encode(address))).await.unwrap().0;
+    assert_eq!(
+        (key.public_key, key.version),
+        (hex::encode(recovered_key.verifying_key().to_bytes()), 1)
+    );
+
+    // The lost key no longer signs for the account; the recovered one does
+    let result = transfer(State(state.clone()), signed_transfer(&state, &owner, bob, 100, 0, 0)).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+    let recovered = TestAccount { key: recovered_key.clone(), address };
+    let response = transfer(State(state.clone()), signed_transfer(&state, &recovered, bob, 100, 0, 0)).await;
+    assert!(response.is_ok(), "Transfer signed by the recovered key should succeed");
+
+    // The recovery is kept with the key it installed
+    let recoveries = get_recoveries(State(state.clone()), Path(hex::encode(address))).await.unwrap().0;
+    assert_eq!(recoveries.len(), 1);
+    assert_eq!(recoveries[0].status, RecoveryStatus::Completed);
+    assert!(recoveries[0].resolved_at.is_some());
+    let installed_by = sqlx::query_scalar!(
+        "SELECT recovery_id FROM account_key_history WHERE address = $1 AND version = 1",
+        address.as_slice()
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(installed_by, Some(recovery.recovery_id.clone()));
+    let approvals = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM account_recovery_signatures WHERE recovery_id = $1"#,
+        recovery.recovery_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(approvals, 2);
+}
+
+#[tokio::test]
+async fn test_owner_vetoes_recovery() {
+    let state = setup_test_state().await;
+    let owner = setup_account(&state, 1000).await;
+    let address = owner.address;
+    let guardian = new_key();
+
+    let set = set_guardians(State(state.clone()), signed_guardians(&state, &owner, 1, &[&guardian], 0))
+        .await
+        .expect("Failed to set guardians");
+    assert_eq!(set.0.guardians, vec![hex::encode(guardian.verifying_key().to_bytes())]);
+
+    let recovery = initiate(State(state.clone()), signed_recovery(&state, address, &guardian, 0, &[&guardian]))
+        .await
+        .expect("Failed to initiate recovery")
+        .0;
+    assert!(recovery.executable_at > recovery.requested_at);
+
+    // Not due until the delay has passed
+    assert_eq!(complete_due_recoveries(&state).await.unwrap(), 0);
+
+    // Only the account's current key can veto
+    let result = veto(State(state.clone()), signed_veto(&state, &guardian, &recovery.recovery_id)).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+    let vetoed = veto(State(state.clone()), signed_veto(&state, &owner.key, &recovery.recovery_id))
+        .await
+        .expect("Failed to veto recovery")
+        .0;
+    assert_eq!(vetoed.status, RecoveryStatus::Vetoed);
+
+    // A vetoed recovery never completes, even once due
+    sqlx::query!(
+        "UPDATE account_recoveries SET executable_at = NOW() WHERE recovery_id = $1",
+        recovery.recovery_id
+    )
+    .execute(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(complete_due_recoveries(&state).await.unwrap(), 0);
+    let key = get_key(State(state.clone()), Path(hex::encode(address))).await.unwrap().0;
+    assert_eq!((key.public_key, key.version), (hex::encode(owner.key.verifying_key().to_bytes()), 0));
+
+    // A recovery the owner's own rotation overtook is superseded
+    let recovery = initiate(State(state.clone()), signed_recovery(&state, address, &new_key(), 0, &[&guardian]))
+        .await
+        .expect("Failed to initiate recovery")
+        .0;
+    let rotated_key = new_key().verifying_key().to_bytes();
+    let payload = SignablePayload::rotate_key(state.chain_id, address, rotated_key, 0);
+    let rotated = rotate_key(
+        State(state.clone()),
+        Json(RotateKeyRequest {
+            address: hex::encode(address),
+            new_public_key: hex::encode(rotated_key),
+            version: 0,
+            signature: hex::encode(payload.sign(&owner.key)),
+        }),
+    )
+    .await
+    .expect("Failed to rotate key");
+    assert_eq!(rotated.0.version, 1);
+    sqlx::query!(
+        "UPDATE account_recoveries SET executable_at = NOW() WHERE recovery_id = $1",
+        recovery.recovery_id
+    )
+    .execute(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(complete_due_recoveries(&state).await.unwrap(), 1);
+
+    let recoveries = get_recoveries(State(state.clone()), Path(hex::encode(address))).await.unwrap().0;
+    let statuses: Vec<RecoveryStatus> = recoveries.iter().map(|recovery| recovery.status).collect();
+    assert_eq!(statuses, vec![RecoveryStatus::Superseded, RecoveryStatus::Vetoed]);
+    let key = get_key(State(state.clone()), Path(hex::encode(address))).await.unwrap().0;
+    assert_eq!((key.public_key, key.version), (hex::encode(rotated_key), 1));
+}
@@ -0,0 +1,257 @@
+use super::*;
+use crate::api::schedule::{
+    cancel, create, executions, get, CancelStandingOrderRequest, StandingOrderExecution,
+    StandingOrderRequest,
+};
+use crate::error::AppError;
+use crate::scheduler::{run_due_orders, ORDER_ACTIVE, ORDER_CANCELLED, ORDER_COMPLETED, ORDER_FAILED};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::Utc;
+use ed25519_dalek::SigningKey;
+use usda_common::{SignablePayload, TransactionKind, TransactionStatus};
+
+const HOUR: i64 = 3600;
+
+fn signed_order(
+    state: &AppState,
+    sender: &TestAccount,
+    to: [u8; 32],
+    amount: i64,
+    start: i64,
+    interval: Option<i64>,
+    max_executions: u32,
+) -> Json<StandingOrderRequest> {
+    let from = sender.address;
+    let payload = SignablePayload::standing_order(
+        state.chain_id,
+        from,
+        to,
+        tokens(amount),
+        Amount::ZERO,
+        start,
+        interval,
+        max_executions,
+        0,
+    );
+
+    Json(StandingOrderRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: hex::encode(from),
+        to: hex::encode(to),
+        amount: tokens(amount),
+        fee: Amount::ZERO,
+        start,
+        interval,
+        max_executions,
+        nonce: 0,
+        signature: hex::encode(payload.sign(&sender.key)),
+    })
+}
+
+fn signed_cancel(state: &AppState, order_id: &str, signer: &SigningKey) -> Json<CancelStandingOrderRequest> {
+    let order: [u8; 32] = hex::decode(order_id).unwrap().try_into().unwrap();
+    let payload = SignablePayload::cancel_standing_order(state.chain_id, order);
+
+    Json(CancelStandingOrderRequest {
+        order_id: order_id.to_string(),
+        signature: hex::encode(payload.sign(signer)),
+    })
+}
+
+async fn history(state: &Arc<AppState>, order_id: &str) -> Vec<StandingOrderExecution> {
+    executions(State(state.clone()), Path(order_id.to_string()))
+        .await
+        .expect("Failed to list executions")
+        .0
+}
+
+#[tokio::test]
+async fn test_recurring_order_runs_when_due() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let receiver = setup_account(&state, 0).await.address;
+
+    // Three hourly executions, the first two overdue and the last one in half an hour
+    let start = Utc::now().timestamp() - HOUR - HOUR / 2;
+    let order = create(State(state.clone()), signed_order(&state, &sender, receiver, 100, start, Some(HOUR), 3))
+        .await
+        .expect("Failed to create standing order")
+        .0;
+    assert_eq!(order.status, ORDER_ACTIVE);
+    assert_eq!(order.next_run_at.map(|at| at.timestamp()), Some(start));
+
+    // Setting up the order uses the sender's nonce; nothing moves yet
+    assert_eq!(pending(&state, sender_bytes).await, (tokens(1000), 1));
+
+    // Resubmitting returns the order instead of setting up another
+    let resubmitted = create(State(state.clone()), signed_order(&state, &sender, receiver, 100, start, Some(HOUR), 3))
+        .await
+        .expect("Resubmitted order should succeed");
+    assert_eq!(resubmitted.0.order_id, order.order_id);
+
+    // Only what's due runs, each execution at the sender's next nonce
+    assert_eq!(run_due_orders(&state).await.unwrap(), 2);
+    assert_eq!(run_due_orders(&state).await.unwrap(), 0);
+    assert_eq!(pending(&state, sender_bytes).await, (tokens(800), 3));
+    assert_eq!(pending(&state, receiver).await, (tokens(200), 0));
+
+    let executed = history(&state, &order.order_id).await;
+    assert_eq!(executed.len(), 2);
+    assert_eq!(executed[1].due_at.timestamp(), start + HOUR);
+    assert_eq!(executed[1].status, Some(TransactionStatus::Sequenced.to_string()));
+    let tx_id = executed[0].tx_id.clone().expect("Execution should have made a transfer");
+    let record = sqlx::query!("SELECT kind, nonce, order_id FROM transactions WHERE tx_id = $1", tx_id)
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+    assert_eq!(record.kind, TransactionKind::ScheduledTransfer.to_string());
+    assert_eq!(record.nonce, 1);
+    assert_eq!(record.order_id, Some(order.order_id.clone()));
+
+    let order = get(State(state.clone()), Path(order.order_id.clone()))
+        .await
+        .unwrap()
+        .0;
+    assert_eq!((order.executions, order.status.as_str()), (2, ORDER_ACTIVE));
+    assert_eq!(order.next_run_at.map(|at| at.timestamp()), Some(start + 2 * HOUR));
+}
+
+#[tokio::test]
+async fn test_failed_execution_is_recorded() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let receiver = setup_account(&state, 0).await.address;
+
+    // Two overdue executions, but the balance only covers one
+    let start = Utc::now().timestamp() - 2 * HOUR;
+    let order = create(State(state.clone()), signed_order(&state, &sender, receiver, 600, start, Some(HOUR), 2))
+        .await
+        .expect("Failed to create standing order")
+        .0;
+    assert_eq!(run_due_orders(&state).await.unwrap(), 2);
+
+    // The failed attempt is recorded without using a nonce, and the order still finishes
+    assert_eq!(pending(&state, sender_bytes).await, (tokens(400), 2));
+    let executed = history(&state, &order.order_id).await;
+    assert!(executed[0].tx_id.is_some());
+    assert_eq!(executed[1].tx_id, None);
+    assert_eq!(executed[1].error.as_deref(), Some("Insufficient balance for transaction"));
+
+    let order = get(State(state.clone()), Path(order.order_id.clone()))
+        .await
+        .unwrap()
+        .0;
+    assert_eq!((order.executions, order.status.as_str()), (2, ORDER_COMPLETED));
+    assert_eq!(order.next_run_at, None);
+}
+
+#[tokio::test]
+async fn test_cancelled_order_stops() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let other = setup_account(&state, 0).await;
+    let receiver = other.address;
+    let start = Utc::now().timestamp() - HOUR;
+
+    // Several executions need an interval to space them
+    let result = create(State(state.clone()), signed_order(&state, &sender, receiver, 100, start, None, 2)).await;
+    assert!(matches!(result, Err(AppError::InvalidInput(_))));
+
+    let order = create(State(state.clone()), signed_order(&state, &sender, receiver, 100, start, Some(HOUR), 12))
+        .await
+        .expect("Failed to create standing order")
+        .0;
+
+    // Only the sender can cancel
+    let result = cancel(State(state.clone()), signed_cancel(&state, &order.order_id, &other.key)).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+    let cancelled = cancel(State(state.clone()), signed_cancel(&state, &order.order_id, &sender.key))
+        .await
+        .expect("Failed to cancel standing order")
+        .0;
+    assert_eq!(cancelled.status, ORDER_CANCELLED);
+    assert_eq!(cancelled.next_run_at, None);
+
+    assert_eq!(run_due_orders(&state).await.unwrap(), 0);
+    assert_eq!(pending(&state, sender_bytes).await, (tokens(1000), 1));
+    assert!(history(&state, &order.order_id).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_broken_orders_dont_block_others() {
+    let state = setup_test_state().await;
+    let receiver = setup_account(&state, 0).await.address;
+    let now = Utc::now().timestamp();
+
+    // The two most overdue orders are broken: one's terms are corrupt, and the
+    // other's transfer hits a database error every time it's attempted
+    let corrupt_sender = setup_account(&state, 1000).await;
+    let corrupt = create(State(state.clone()), signed_order(&state, &corrupt_sender, receiver, 100, now - 3 * HOUR, None, 1))
+        .await
+        .expect("Failed to create standing order")
+        .0;
+    sqlx::query!("UPDATE standing_orders SET to_addr = '\\x00' WHERE order_id = $1", corrupt.order_id)
+        .execute(&state.db)
+        .await
+        .unwrap();
+
+    let failing_sender = setup_account(&state, 1000).await;
+    let failing = create(State(state.clone()), signed_order(&state, &failing_sender, receiver, 100, now - 2 * HOUR, None, 1))
+        .await
+        .expect("Failed to create standing order")
+        .0;
+    let failing_tx_id = SignablePayload::standing_order(
+        state.chain_id,
+        failing_sender.address,
+        receiver,
+        tokens(100),
+        Amount::ZERO,
+        now - 2 * HOUR,
+        None,
+        1,
+        0,
+    )
+    .leg_tx_id(0);
+    sqlx::query!(
+        r#"
+        INSERT INTO transactions (tx_id, amount, fee, nonce, signature, timestamp, status)
+        VALUES ($1, 0, 0, 0, '\x00', NOW(), 'FAILED')
+        "#,
+        failing_tx_id
+    )
+    .execute(&state.db)
+    .await
+    .unwrap();
+
+    let sender = setup_account(&state, 1000).await;
+    let order = create(State(state.clone()), signed_order(&state, &sender, receiver, 100, now - HOUR, None, 1))
+        .await
+        .expect("Failed to create standing order")
+        .0;
+
+    run_due_orders(&state).await.expect("Broken orders shouldn't stop the run");
+
+    // The order after them still runs
+    assert_eq!(pending(&state, receiver).await, (tokens(100), 0));
+    let order = get(State(state.clone()), Path(order.order_id.clone())).await.unwrap().0;
+    assert_eq!(order.status, ORDER_COMPLETED);
+
+    // The corrupt order is stopped, with the reason recorded
+    let corrupt = get(State(state.clone()), Path(corrupt.order_id.clone())).await.unwrap().0;
+    assert_eq!((corrupt.status.as_str(), corrupt.next_run_at), (ORDER_FAILED, None));
+    let executed = history(&state, &corrupt.order_id).await;
+    assert_eq!(executed.len(), 1);
+    assert!(executed[0].error.as_deref().unwrap().contains("Corrupt standing order"));
+
+    // The failing execution is recorded like any other that couldn't be applied
+    let executed = history(&state, &failing.order_id).await;
+    assert_eq!((executed.len(), executed[0].tx_id.clone()), (1, None));
+    assert!(executed[0].error.is_some());
+    assert_eq!(pending(&state, failing_sender.address).await, (tokens(1000), 1));
+}
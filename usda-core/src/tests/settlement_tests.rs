@@ -0,0 +1,337 @@
+use super::*;
+use crate::api::account::{rotate_key, RotateKeyRequest};
+use crate::api::transaction::get_transaction;
+use crate::error::AppError;
+use crate::lifecycle::can_transition;
+use crate::settlement::{fail_batch, finalize_batch, seal_batch, settle_batch};
+use axum::{extract::{Path, State}, Json};
+use chrono::Utc;
+use ed25519_dalek::SigningKey;
+use usda_common::{batch::BatchResult, SignablePayload, TransactionStatus, WebSocketMessage};
+
+/// Verifying-key hash the test batches are settled with
+const TEST_VKEY_HASH: &str = "0x00c0ffee00c0ffee00c0ffee00c0ffee00c0ffee00c0ffee00c0ffee00c0ffee";
+
+/// Public values of a proof that vouches for no signer
+fn public_values(state: &AppState) -> BatchResult {
+    BatchResult {
+        chain_id: state.chain_id,
+        batch_time: Utc::now().timestamp(),
+        issuer_keys: Vec::new(),
+        multisig_policies: Vec::new(),
+        account_keys: Vec::new(),
+        cycles_used: 0,
+        supply: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn test_proven_batch_settles_balances() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let receiver_bytes = setup_account(&state, 0).await.address;
+    
+    let tx_id = send(&state, &sender, receiver_bytes, 100, 5, 0).await;
+    
+    // Preconfirmed: only the pending view moves
+    assert_eq!(balances(&state, sender_bytes).await, (1000, 895));
+    assert_eq!(balances(&state, receiver_bytes).await, (0, 100));
+    
+    let batch = seal_batch(&state, 100)
+        .await
+        .unwrap()
+        .expect("Expected a batch");
+    assert_eq!(batch.tx_ids, vec![tx_id.clone()]);
+    assert!(seal_batch(&state, 100).await.unwrap().is_none());
+    
+    let mut ws_rx = state.ws_tx.subscribe();
+    settle_batch(&state, &batch.batch_id, b"proof", TEST_VKEY_HASH, &public_values(&state))
+        .await
+        .expect("Failed to settle batch");
+    
+    assert_eq!(balances(&state, sender_bytes).await, (895, 895));
+    assert_eq!(balances(&state, receiver_bytes).await, (100, 100));
+    assert_eq!(balances(&state, TEST_TREASURY).await, (5, 5));
+    assert_eq!(status_of(&state, &tx_id).await, TransactionStatus::Proven.to_string());
+    
+    match ws_rx.try_recv() {
+        Ok(WebSocketMessage::TransactionProven(transaction)) => assert_eq!(transaction.tx_id, tx_id),
+        other => panic!("Expected a proven event, got {:?}", other),
+    }
+    
+    // A settled batch can't be settled again
+    let result = settle_batch(&state, &batch.batch_id, b"proof", TEST_VKEY_HASH, &public_values(&state)).await;
+    assert!(matches!(result, Err(AppError::InvalidInput(_))));
+}
+
+#[tokio::test]
+async fn test_failed_batch_rolls_back() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let receiver_bytes = setup_account(&state, 0).await.address;
+    
+    let tx_id = send(&state, &sender, receiver_bytes, 100, 5, 0).await;
+    let batch = seal_batch(&state, 100).await.unwrap().unwrap();
+    
+    fail_batch(&state, &batch.batch_id)
+        .await
+        .expect("Failed to fail batch");
+    
+    assert_eq!(balances(&state, sender_bytes).await, (1000, 1000));
+    assert_eq!(balances(&state, receiver_bytes).await, (0, 0));
+    assert_eq!(balances(&state, TEST_TREASURY).await, (0, 0));
+    assert_eq!(status_of(&state, &tx_id).await, TransactionStatus::Failed.to_string());
+
+    let receipt = get_transaction(State(state.clone()), Path(tx_id)).await.unwrap().0.receipt;
+    assert_eq!(receipt.reason, Some(format!("Proof batch {} failed", batch.batch_id)));
+}
+
+#[tokio::test]
+async fn test_failed_batch_fails_spends_of_its_credits() {
+    let state = setup_test_state().await;
+    let alice = setup_account(&state, 1000).await;
+    let alice_bytes = alice.address;
+    let bob = setup_account(&state, 0).await;
+    let bob_bytes = bob.address;
+    let carol = setup_account(&state, 0).await;
+    let carol_bytes = carol.address;
+    let dave_bytes = setup_account(&state, 0).await.address;
+
+    let credit = send(&state, &alice, bob_bytes, 100, 0, 0).await;
+    let batch = seal_batch(&state, 100).await.unwrap().unwrap();
+
+    // Before the batch fails, Bob spends the credit and Carol what Bob sent her
+    let spend = send(&state, &bob, carol_bytes, 60, 0, 0).await;
+    let respend = send(&state, &carol, dave_bytes, 10, 0, 0).await;
+    let independent = send(&state, &alice, dave_bytes, 50, 0, 1).await;
+
+    fail_batch(&state, &batch.batch_id)
+        .await
+        .expect("Failed to fail batch");
+
+    // Everything built on the credit is undone with it
+    assert_eq!(balances(&state, bob_bytes).await, (0, 0));
+    assert_eq!(balances(&state, carol_bytes).await, (0, 0));
+    assert_eq!(balances(&state, dave_bytes).await, (0, 50));
+    assert_eq!(balances(&state, alice_bytes).await, (1000, 950));
+    for tx_id in [&credit, &spend, &respend] {
+        assert_eq!(status_of(&state, tx_id).await, TransactionStatus::Failed.to_string());
+    }
+    let receipt = get_transaction(State(state.clone()), Path(spend)).await.unwrap().0.receipt;
+    assert_eq!(
+        receipt.reason,
+        Some(format!("Depended on proof batch {}, which failed", batch.batch_id))
+    );
+
+    // Alice's own later transfer didn't depend on the batch
+    assert_eq!(status_of(&state, &independent).await, TransactionStatus::Sequenced.to_string());
+    let next = seal_batch(&state, 100).await.unwrap().expect("Expected a batch");
+    assert_eq!(next.tx_ids, vec![independent]);
+    settle_batch(&state, &next.batch_id, b"proof", TEST_VKEY_HASH, &public_values(&state))
+        .await
+        .expect("Failed to settle batch");
+    assert_eq!(balances(&state, dave_bytes).await, (50, 50));
+}
+
+#[tokio::test]
+async fn test_fees_settle_with_the_treasury_that_collected_them() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let receiver_bytes = setup_account(&state, 0).await.address;
+
+    let _ = send(&state, &sender, receiver_bytes, 100, 5, 0).await;
+    let first = seal_batch(&state, 100).await.unwrap().unwrap();
+    let _ = send(&state, &sender, receiver_bytes, 100, 5, 1).await;
+    let second = seal_batch(&state, 100).await.unwrap().unwrap();
+    assert_eq!(balances(&state, TEST_TREASURY).await, (0, 10));
+
+    // After a restart with another treasury, the fees still settle into the old one
+    let new_treasury = [0xdd; 32];
+    let restarted = AppState::new(state.db.clone()).with_treasury(new_treasury);
+    settle_batch(&restarted, &first.batch_id, b"proof", TEST_VKEY_HASH, &public_values(&restarted))
+        .await
+        .expect("Failed to settle batch");
+    assert_eq!(balances(&state, TEST_TREASURY).await, (5, 10));
+    assert!(restarted.get_account(&new_treasury, DEFAULT_TOKEN_ID).await.unwrap().is_none());
+
+    // And with none configured, they are still taken back from it
+    let restarted = AppState::new(state.db.clone());
+    fail_batch(&restarted, &second.batch_id)
+        .await
+        .expect("Failed to fail batch");
+    assert_eq!(balances(&state, TEST_TREASURY).await, (5, 5));
+    assert_eq!(balances(&state, sender.address).await, (895, 895));
+}
+
+#[tokio::test]
+async fn test_batches_settle_in_order() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let receiver_bytes = setup_account(&state, 0).await.address;
+    
+    let _ = send(&state, &sender, receiver_bytes, 100, 0, 0).await;
+    let first = seal_batch(&state, 100).await.unwrap().unwrap();
+    let _ = send(&state, &sender, receiver_bytes, 100, 0, 1).await;
+    let second = seal_batch(&state, 100).await.unwrap().unwrap();
+    
+    let result = settle_batch(&state, &second.batch_id, b"proof", TEST_VKEY_HASH, &public_values(&state)).await;
+    assert!(matches!(result, Err(AppError::InvalidInput(_))));
+    
+    settle_batch(&state, &first.batch_id, b"proof", TEST_VKEY_HASH, &public_values(&state)).await.unwrap();
+    settle_batch(&state, &second.batch_id, b"proof", TEST_VKEY_HASH, &public_values(&state)).await.unwrap();
+    assert_eq!(balances(&state, receiver_bytes).await, (200, 200));
+}
+
+#[tokio::test]
+async fn test_batch_follows_application_order() {
+    let state = setup_test_state().await;
+    let alice = setup_account(&state, 1000).await;
+    let bob = setup_account(&state, 0).await;
+    let bob_bytes = bob.address;
+    let carol_bytes = setup_account(&state, 0).await.address;
+
+    // Bob spends what Alice sent him, in a database transaction that started
+    // before hers committed and so carries the earlier timestamp
+    let credit = send(&state, &alice, bob_bytes, 100, 0, 0).await;
+    let spend = send(&state, &bob, carol_bytes, 100, 0, 0).await;
+    sqlx::query!(
+        "UPDATE transactions SET timestamp = timestamp - INTERVAL '1 minute' WHERE tx_id = $1",
+        spend
+    )
+    .execute(&state.db)
+    .await
+    .unwrap();
+
+    // The credit still comes first, so settling never overdraws Bob
+    let batch = seal_batch(&state, 100).await.unwrap().expect("Expected a batch");
+    assert_eq!(batch.tx_ids, vec![credit, spend]);
+    settle_batch(&state, &batch.batch_id, b"proof", TEST_VKEY_HASH, &public_values(&state))
+        .await
+        .expect("Failed to settle batch");
+    assert_eq!(balances(&state, bob_bytes).await, (0, 0));
+    assert_eq!(balances(&state, carol_bytes).await, (100, 100));
+}
+
+#[tokio::test]
+async fn test_transaction_receipt_follows_settlement() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let receiver_bytes = setup_account(&state, 0).await.address;
+
+    let tx_id = send(&state, &sender, receiver_bytes, 100, 5, 0).await;
+    let lookup = get_transaction(State(state.clone()), Path(tx_id.clone())).await.unwrap().0;
+    assert_eq!(lookup.transaction.tx_id, tx_id);
+    assert_eq!(lookup.transaction.amount, tokens(100));
+    assert_eq!(lookup.receipt.status, TransactionStatus::Sequenced);
+    assert_eq!(lookup.receipt.batch_id, None);
+
+    // Transfers waiting in the nonce queue can be looked up too
+    let queued_id = send(&state, &sender, receiver_bytes, 100, 5, 2).await;
+    let queued = get_transaction(State(state.clone()), Path(queued_id)).await.unwrap().0;
+    assert_eq!(queued.receipt.status, TransactionStatus::Received);
+    assert_eq!(queued.transaction.nonce, 2);
+
+    // Sealed but not yet proven
+    let batch = seal_batch(&state, 100).await.unwrap().expect("Expected a batch");
+    let receipt = get_transaction(State(state.clone()), Path(tx_id.clone())).await.unwrap().0.receipt;
+    assert_eq!(receipt.batch_id.as_deref(), Some(batch.batch_id.as_str()));
+    assert_eq!((receipt.proven_at, receipt.vkey_hash), (None, None));
+
+    settle_batch(&state, &batch.batch_id, b"proof", TEST_VKEY_HASH, &public_values(&state))
+        .await
+        .expect("Failed to settle batch");
+    let lookup = get_transaction(State(state.clone()), Path(tx_id.clone())).await.unwrap().0;
+    assert_eq!(lookup.transaction.status, TransactionStatus::Proven);
+    assert_eq!(lookup.receipt.status, TransactionStatus::Proven);
+    assert!(lookup.receipt.proven_at.is_some());
+    assert_eq!(lookup.receipt.vkey_hash.as_deref(), Some(TEST_VKEY_HASH));
+
+    let result = get_transaction(State(state.clone()), Path("00".repeat(32))).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+}
+
+#[tokio::test]
+async fn test_transaction_lifecycle() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let receiver_bytes = setup_account(&state, 0).await.address;
+
+    let tx_id = send(&state, &sender, receiver_bytes, 100, 0, 0).await;
+    assert_eq!(status_of(&state, &tx_id).await, TransactionStatus::Sequenced.to_string());
+
+    let batch = seal_batch(&state, 100).await.unwrap().expect("Expected a batch");
+    assert_eq!(status_of(&state, &tx_id).await, TransactionStatus::Batched.to_string());
+
+    // Only a settled batch can be finalized, and only once
+    let result = finalize_batch(&state, &batch.batch_id).await;
+    assert!(matches!(result, Err(AppError::InvalidInput(_))));
+
+    settle_batch(&state, &batch.batch_id, b"proof", TEST_VKEY_HASH, &public_values(&state)).await.unwrap();
+    assert_eq!(status_of(&state, &tx_id).await, TransactionStatus::Proven.to_string());
+
+    finalize_batch(&state, &batch.batch_id).await.expect("Failed to finalize batch");
+    let lookup = get_transaction(State(state.clone()), Path(tx_id)).await.unwrap().0;
+    assert_eq!(lookup.receipt.status, TransactionStatus::Finalized);
+    assert!(lookup.receipt.finalized_at.is_some());
+    assert_eq!(lookup.receipt.reason, None);
+
+    let result = finalize_batch(&state, &batch.batch_id).await;
+    assert!(matches!(result, Err(AppError::InvalidInput(_))));
+
+    // No status can be skipped or left once final
+    assert!(!can_transition(TransactionStatus::Sequenced, TransactionStatus::Proven));
+    assert!(!can_transition(TransactionStatus::Batched, TransactionStatus::Cancelled));
+    assert!(!can_transition(TransactionStatus::Finalized, TransactionStatus::Failed));
+    assert!(!can_transition(TransactionStatus::Cancelled, TransactionStatus::Sequenced));
+}
+
+#[tokio::test]
+async fn test_settlement_checks_proven_signers() {
+    let state = setup_test_state().await;
+    let sender = setup_account(&state, 1000).await;
+    let sender_bytes = sender.address;
+    let receiver_bytes = setup_account(&state, 0).await.address;
+
+    send(&state, &sender, receiver_bytes, 100, 0, 0).await;
+    let batch = seal_batch(&state, 100).await.unwrap().expect("Expected a batch");
+
+    // The sender's key is replaced before the batch is proven
+    let new_key = SigningKey::from_bytes(&[42u8; 32]).verifying_key().to_bytes();
+    let payload = SignablePayload::rotate_key(state.chain_id, sender_bytes, new_key, 0);
+    let rotated = rotate_key(
+        State(state.clone()),
+        Json(RotateKeyRequest {
+            address: hex::encode(sender_bytes),
+            new_public_key: hex::encode(new_key),
+            version: 0,
+            signature: hex::encode(payload.sign(&sender.key)),
+        }),
+    )
+    .await
+    .expect("Failed to rotate key");
+    assert_eq!(rotated.0.version, 1);
+
+    // Proofs checked against keys or policies the ledger doesn't know are refused
+    let stranger = SigningKey::from_bytes(&[43u8; 32]).verifying_key().to_bytes();
+    let wrong_chain = BatchResult { chain_id: state.chain_id + 1, ..public_values(&state) };
+    let wrong_key = BatchResult { account_keys: vec![(sender_bytes, stranger)], ..public_values(&state) };
+    let wrong_issuer = BatchResult { issuer_keys: vec![(DEFAULT_TOKEN_ID, stranger)], ..public_values(&state) };
+    let wrong_policy = BatchResult { multisig_policies: vec![(sender_bytes, stranger)], ..public_values(&state) };
+    for result in [wrong_chain, wrong_key, wrong_issuer, wrong_policy] {
+        let settled = settle_batch(&state, &batch.batch_id, b"proof", TEST_VKEY_HASH, &result).await;
+        assert!(matches!(settled, Err(AppError::InvalidInput(_))), "Settled with {:?}", result);
+    }
+    assert_eq!(balances(&state, receiver_bytes).await, (0, 100));
+
+    // The key the transfer was signed with was the sender's at the time
+    let proven = BatchResult {
+        account_keys: vec![(sender_bytes, sender.key.verifying_key().to_bytes())],
+        ..public_values(&state)
+    };
+    settle_batch(&state, &batch.batch_id, b"proof", TEST_VKEY_HASH, &proven)
+        .await
+        .expect("Failed to settle batch");
+    assert_eq!(balances(&state, receiver_bytes).await, (100, 100));
+}
@@ -0,0 +1,19 @@
+use super::*;
+use crate::api::token::{metadata, TokenQuery};
+use axum::extract::{Query, State};
+use usda_common::TokenMetadata;
+
+#[tokio::test]
+async fn test_token_metadata() {
+    let token = TokenMetadata {
+        name: "Test Dollar".into(),
+        symbol: "TUSD".into(),
+        decimals: 2,
+    };
+    let state = Arc::new(test_app_state().await.with_token(DEFAULT_TOKEN_ID, token.clone()));
+    
+    let response = metadata(State(state), Query(TokenQuery::default()))
+        .await
+        .expect("Failed to get token metadata");
+    assert_eq!(response.0, token);
+}
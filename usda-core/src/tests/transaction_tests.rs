@@ -1,58 +1,15 @@
 use super::*;
-use crate::api::transaction::{transfer, TransferRequest};
+use crate::api::transaction::{simulate, transfer, AmountInput, SimulateTransferRequest, TransferRequest};
 use axum::Json;
 use axum::extract::State;
 use crate::error::AppError;
-use ed25519_dalek::{Signer, SigningKey, VerifyingKey, SecretKey};
+use ed25519_dalek::{Signer, SigningKey};
+use usda_common::{FeePolicy, SignablePayload, TokenMetadata, TransactionStatus};
 use rand::{RngCore, rngs::OsRng};
 
-async fn setup_test_accounts(state: &AppState) -> (SigningKey, VerifyingKey) {
-    // Create sender and receiver keypairs
-    let mut sender_secret = [0u8; 32];
-    OsRng.fill_bytes(&mut sender_secret);
-    let sender_signing_key = SigningKey::from_bytes(&sender_secret);
-    let sender_verifying_key = sender_signing_key.verifying_key();
-    
-    let mut receiver_secret = [0u8; 32];
-    OsRng.fill_bytes(&mut receiver_secret);
-    let receiver_signing_key = SigningKey::from_bytes(&receiver_secret);
-    let receiver_verifying_key = receiver_signing_key.verifying_key();
-    
-    // Store bytes for later use
-    let sender_bytes = sender_verifying_key.to_bytes();
-    let receiver_bytes = receiver_verifying_key.to_bytes();
-    
-    // Insert sender account with balance
-    sqlx::query!(
-        r#"
-        INSERT INTO accounts (address, balance, pending_balance, nonce, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        "#,
-        sender_bytes.as_slice(),
-        1000_i64,
-        1000_i64,
-        0_i64
-    )
-    .execute(&state.db)
-    .await
-    .expect("Failed to insert sender account");
-    
-    // Insert receiver account
-    sqlx::query!(
-        r#"
-        INSERT INTO accounts (address, balance, pending_balance, nonce, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        "#,
-        receiver_bytes.as_slice(),
-        0_i64,
-        0_i64,
-        0_i64
-    )
-    .execute(&state.db)
-    .await
-    .expect("Failed to insert receiver account");
-    
-    (sender_signing_key, receiver_verifying_key)
+/// A sender holding 1000 and an empty receiver
+async fn setup_test_accounts(state: &AppState) -> (TestAccount, TestAccount) {
+    (setup_account(state, 1000).await, setup_account(state, 0).await)
 }
 
 #[tokio::test]
@@ -65,38 +22,42 @@ async fn test_transfer() {
     let mut secret_bytes = [0u8; 32];
     rng.fill_bytes(&mut secret_bytes);
     let issuer_key = SigningKey::from_bytes(&secret_bytes);
-    state.set_issuer_key(issuer_key.verifying_key());
+    state.set_issuer_key(DEFAULT_TOKEN_ID, issuer_key.verifying_key());
     
     // Create sender and receiver keypairs
-    let (sender_signing_key, receiver_verifying_key) = setup_test_accounts(&state).await;
+    let (sender_account, receiver_account) = setup_test_accounts(&state).await;
     
     // Create transfer request
     let amount = 100_i64;
     let nonce = 0_i64;
     
     // Store bytes for later use
-    let sender_bytes = sender_signing_key.verifying_key().to_bytes();
-    let receiver_bytes = receiver_verifying_key.to_bytes();
-    
-    // Create message to sign
-    let message = format!(
-        "{}{}{}{}",
-        hex::encode(sender_bytes),
-        hex::encode(receiver_bytes),
-        amount,
-        nonce
+    let sender_bytes = sender_account.address;
+    let receiver_bytes = receiver_account.address;
+    
+    // Create payload to sign
+    let fee = amount / 100; // 1% fee
+    let payload = SignablePayload::transfer(
+        state.chain_id,
+        sender_bytes,
+        receiver_bytes,
+        tokens(amount),
+        tokens(fee),
+        nonce,
     );
     
-    // Sign message
-    let signature = sender_signing_key.sign(message.as_bytes());
+    // Sign payload
+    let signature = payload.sign(&sender_account.key);
     
     let req = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
         from: Some(hex::encode(sender_bytes)),
         to: hex::encode(receiver_bytes),
-        amount,
-        fee: amount / 100, // 1% fee
+        amount: tokens(amount).into(),
+        fee: tokens(fee).into(),
         nonce,
-        signature: hex::encode(signature.to_bytes()),
+        signature: hex::encode(signature),
+        valid_until: None,
     });
     
     // Execute transfer
@@ -106,11 +67,11 @@ async fn test_transfer() {
     
     // Verify response
     assert!(!response.0.tx_id.is_empty());
-    assert_eq!(response.0.status, "pending");
+    assert_eq!(response.0.status, TransactionStatus::Sequenced.to_string());
     
     // Verify balances
     let sender = sqlx::query!(
-        "SELECT balance FROM accounts WHERE address = $1",
+        r#"SELECT pending_balance::BIGINT AS "pending_balance!" FROM accounts WHERE address = $1"#,
         &sender_bytes
     )
     .fetch_one(&state.db)
@@ -118,7 +79,7 @@ async fn test_transfer() {
     .unwrap();
 
     let receiver = sqlx::query!(
-        "SELECT balance FROM accounts WHERE address = $1",
+        r#"SELECT pending_balance::BIGINT AS "pending_balance!" FROM accounts WHERE address = $1"#,
         &receiver_bytes
     )
     .fetch_one(&state.db)
@@ -126,41 +87,44 @@ async fn test_transfer() {
     .unwrap();
 
     // Account for 1% fee
-    let fee = amount / 100;
-    assert_eq!(sender.balance, 1000 - amount - fee);  // Initial balance - amount - fee
-    assert_eq!(receiver.balance, amount);  // Received full amount
+    assert_eq!(sender.pending_balance, 1000 - amount - fee);  // Initial balance - amount - fee
+    assert_eq!(receiver.pending_balance, amount);  // Received full amount
 }
 
 #[tokio::test]
 async fn test_transfer_insufficient_balance() {
     let state = setup_test_state().await;
-    let (sender_signing_key, receiver_verifying_key) = setup_test_accounts(&state).await;
+    let (sender_account, receiver_account) = setup_test_accounts(&state).await;
     
     // Create transfer request with amount larger than balance
     let amount = 2000_i64; // Balance is only 1000
     let nonce = 0_i64;
     
     // Store bytes for later use
-    let sender_bytes = sender_signing_key.verifying_key().to_bytes();
-    let receiver_bytes = receiver_verifying_key.to_bytes();
-    
-    let message = format!(
-        "{}{}{}{}",
-        hex::encode(sender_bytes),
-        hex::encode(receiver_bytes),
-        amount,
-        nonce
+    let sender_bytes = sender_account.address;
+    let receiver_bytes = receiver_account.address;
+    
+    let fee = amount / 100; // 1% fee
+    let payload = SignablePayload::transfer(
+        state.chain_id,
+        sender_bytes,
+        receiver_bytes,
+        tokens(amount),
+        tokens(fee),
+        nonce,
     );
     
-    let signature = sender_signing_key.sign(message.as_bytes());
+    let signature = payload.sign(&sender_account.key);
     
     let req = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
         from: Some(hex::encode(sender_bytes)),
         to: hex::encode(receiver_bytes),
-        amount,
-        fee: amount / 100, // 1% fee
+        amount: tokens(amount).into(),
+        fee: tokens(fee).into(),
         nonce,
-        signature: hex::encode(signature.to_bytes()),
+        signature: hex::encode(signature),
+        valid_until: None,
     });
     
     // Execute transfer
@@ -170,6 +134,378 @@ async fn test_transfer_insufficient_balance() {
     assert!(matches!(result, Err(crate::error::AppError::InsufficientBalance)));
 }
 
+#[tokio::test]
+async fn test_transfer_invalid_signature() {
+    let state = setup_test_state().await;
+    let (sender_account, receiver_account) = setup_test_accounts(&state).await;
+    
+    let amount = 100_i64;
+    let fee = amount / 100;
+    let nonce = 0_i64;
+    
+    let sender_bytes = sender_account.address;
+    let receiver_bytes = receiver_account.address;
+    
+    let payload = SignablePayload::transfer(
+        state.chain_id,
+        sender_bytes,
+        receiver_bytes,
+        tokens(amount),
+        tokens(fee),
+        nonce,
+    );
+    
+    // Sign payload with a key that does not own the sender account
+    let mut wrong_secret = [0u8; 32];
+    OsRng.fill_bytes(&mut wrong_secret);
+    let wrong_signing_key = SigningKey::from_bytes(&wrong_secret);
+    let signature = payload.sign(&wrong_signing_key);
+    
+    let req = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: Some(hex::encode(sender_bytes)),
+        to: hex::encode(receiver_bytes),
+        amount: tokens(amount).into(),
+        fee: tokens(fee).into(),
+        nonce,
+        signature: hex::encode(signature),
+        valid_until: None,
+    });
+    
+    let result = transfer(State(state.clone()), req).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+    
+    // Sender's balance must be untouched
+    let sender = sqlx::query!(
+        r#"SELECT pending_balance::BIGINT AS "pending_balance!", nonce FROM accounts WHERE address = $1"#,
+        &sender_bytes
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(sender.pending_balance, 1000);
+    assert_eq!(sender.nonce, 0);
+}
+
+#[tokio::test]
+async fn test_transfer_fee_credited_to_treasury() {
+    let state = setup_test_state().await;
+    let (sender_account, receiver_account) = setup_test_accounts(&state).await;
+    
+    let amount = 100_i64;
+    let fee = 5_i64;
+    let nonce = 0_i64;
+    
+    let sender_bytes = sender_account.address;
+    let receiver_bytes = receiver_account.address;
+    
+    let payload = SignablePayload::transfer(
+        state.chain_id,
+        sender_bytes,
+        receiver_bytes,
+        tokens(amount),
+        tokens(fee),
+        nonce,
+    );
+    let signature = payload.sign(&sender_account.key);
+    
+    let req = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: Some(hex::encode(sender_bytes)),
+        to: hex::encode(receiver_bytes),
+        amount: tokens(amount).into(),
+        fee: tokens(fee).into(),
+        nonce,
+        signature: hex::encode(signature),
+        valid_until: None,
+    });
+    
+    let _ = transfer(State(state.clone()), req)
+        .await
+        .expect("Failed to execute transfer");
+    
+    // The fee lands in the treasury instead of disappearing
+    let treasury = sqlx::query!(
+        r#"SELECT pending_balance::BIGINT AS "pending_balance!" FROM accounts WHERE address = $1"#,
+        TEST_TREASURY.as_slice()
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(treasury.pending_balance, fee);
+    
+    // Total supply is unchanged
+    let total = sqlx::query!("SELECT SUM(pending_balance)::BIGINT AS total FROM accounts")
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+    assert_eq!(total.total, Some(1000));
+}
+
+#[tokio::test]
+async fn test_transfer_resubmission_is_idempotent() {
+    let state = setup_test_state().await;
+    let (sender_account, receiver_account) = setup_test_accounts(&state).await;
+    
+    let amount = 100_i64;
+    let sender_bytes = sender_account.address;
+    let receiver_bytes = receiver_account.address;
+    
+    let payload = SignablePayload::transfer(state.chain_id, sender_bytes, receiver_bytes, tokens(amount), Amount::ZERO, 0);
+    let signature = hex::encode(payload.sign(&sender_account.key));
+    
+    let req = || {
+        Json(TransferRequest {
+            token_id: DEFAULT_TOKEN_ID,
+            from: Some(hex::encode(sender_bytes)),
+            to: hex::encode(receiver_bytes),
+            amount: tokens(amount).into(),
+            fee: Amount::ZERO.into(),
+            nonce: 0,
+            signature: signature.clone(),
+            valid_until: None,
+        })
+    };
+    
+    let first = transfer(State(state.clone()), req())
+        .await
+        .expect("Failed to execute transfer");
+    let retry = transfer(State(state.clone()), req())
+        .await
+        .expect("Failed to resubmit transfer");
+    
+    // The ID is derived from the signed payload, so clients know it up front
+    assert_eq!(first.tx_id, payload.tx_id());
+    assert_eq!(retry.tx_id, first.tx_id);
+    assert_eq!(retry.status, first.status);
+    
+    // The sender is only debited once
+    let sender = sqlx::query!(
+        r#"SELECT pending_balance::BIGINT AS "pending_balance!", nonce FROM accounts WHERE address = $1"#,
+        sender_bytes.as_slice()
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(sender.pending_balance, 1000 - amount);
+    assert_eq!(sender.nonce, 1);
+}
+
+#[tokio::test]
+async fn test_transfer_expiry() {
+    let state = setup_test_state().await;
+    let (sender_account, receiver_account) = setup_test_accounts(&state).await;
+    
+    let amount = 100_i64;
+    let sender_bytes = sender_account.address;
+    let receiver_bytes = receiver_account.address;
+    let now = chrono::Utc::now().timestamp();
+    
+    let request = |valid_until: i64, nonce: i64| {
+        let payload = SignablePayload::transfer(state.chain_id, sender_bytes, receiver_bytes, tokens(amount), Amount::ZERO, nonce)
+            .with_valid_until(Some(valid_until));
+        Json(TransferRequest {
+            token_id: DEFAULT_TOKEN_ID,
+            from: Some(hex::encode(sender_bytes)),
+            to: hex::encode(receiver_bytes),
+            amount: tokens(amount).into(),
+            fee: Amount::ZERO.into(),
+            nonce,
+            signature: hex::encode(payload.sign(&sender_account.key)),
+            valid_until: Some(valid_until),
+        })
+    };
+    
+    // A transfer whose expiry has passed is rejected
+    let result = transfer(State(state.clone()), request(now - 60, 0)).await;
+    assert!(matches!(result, Err(AppError::TransactionExpired)));
+    
+    // An unexpired transfer is applied and its expiry recorded
+    let response = transfer(State(state.clone()), request(now + 3600, 0))
+        .await
+        .expect("Failed to execute transfer");
+    let record = sqlx::query!(
+        "SELECT valid_until FROM transactions WHERE tx_id = $1",
+        response.tx_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(record.valid_until.map(|t| t.timestamp()), Some(now + 3600));
+}
+
+#[tokio::test]
+async fn test_transfer_fee_below_policy() {
+    let state = setup_test_state().await;
+    let (sender_account, receiver_account) = setup_test_accounts(&state).await;
+    
+    // 1% fee, at least 2 and at most 50
+    let state = Arc::new(
+        AppState::new(state.db.clone())
+            .with_fee_policy(FeePolicy::Bounded { bps: 100, min: Amount::new(2), max: Amount::new(50) })
+            .with_treasury(TEST_TREASURY),
+    );
+    
+    let amount = 100_i64;
+    let fee = 1_i64;
+    let nonce = 0_i64;
+    
+    let sender_bytes = sender_account.address;
+    let receiver_bytes = receiver_account.address;
+    
+    let payload = SignablePayload::transfer(
+        state.chain_id,
+        sender_bytes,
+        receiver_bytes,
+        tokens(amount),
+        tokens(fee),
+        nonce,
+    );
+    let signature = payload.sign(&sender_account.key);
+    
+    let req = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: Some(hex::encode(sender_bytes)),
+        to: hex::encode(receiver_bytes),
+        amount: tokens(amount).into(),
+        fee: tokens(fee).into(),
+        nonce,
+        signature: hex::encode(signature),
+        valid_until: None,
+    });
+    
+    let result = transfer(State(state), req).await;
+    assert!(matches!(result, Err(AppError::InsufficientFee { required }) if required == Amount::new(2)));
+}
+
+#[tokio::test]
+async fn test_transfer_amount_overflow() {
+    let state = setup_test_state().await;
+    let (sender_account, receiver_account) = setup_test_accounts(&state).await;
+    
+    let sender_bytes = sender_account.address;
+    let receiver_bytes = receiver_account.address;
+    
+    // amount + fee doesn't fit in an Amount
+    let payload = SignablePayload::transfer(
+        state.chain_id,
+        sender_bytes,
+        receiver_bytes,
+        Amount::MAX,
+        Amount::new(1),
+        0,
+    );
+    let req = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: Some(hex::encode(sender_bytes)),
+        to: hex::encode(receiver_bytes),
+        amount: Amount::MAX.into(),
+        fee: Amount::new(1).into(),
+        nonce: 0,
+        signature: hex::encode(payload.sign(&sender_account.key)),
+        valid_until: None,
+    });
+    
+    let result = transfer(State(state.clone()), req).await;
+    assert!(matches!(result, Err(AppError::AmountOverflow)));
+    
+    let sender = sqlx::query!(
+        r#"SELECT pending_balance::BIGINT AS "pending_balance!", nonce FROM accounts WHERE address = $1"#,
+        sender_bytes.as_slice()
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(sender.pending_balance, 1000);
+    assert_eq!(sender.nonce, 0);
+}
+
+#[test]
+fn test_transfer_request_amount_encoding() {
+    // 6 decimals
+    let token = TokenMetadata::default();
+    let amounts = |amount: &str, fee: &str| {
+        let req = serde_json::from_str::<TransferRequest>(&format!(
+            r#"{{"to": "00", "amount": {}, "fee": {}, "nonce": 0, "signature": "00"}}"#,
+            amount, fee
+        ))
+        .ok()?;
+        Some((req.amount.resolve(&token).ok()?, req.fee.resolve(&token).ok()?))
+    };
+    
+    // Amounts beyond i64 and f64 precision arrive intact as decimal strings
+    assert_eq!(
+        amounts(r#""340282366920938463463374607431768211455""#, r#""0""#),
+        Some((Amount::MAX, Amount::ZERO))
+    );
+    assert_eq!(
+        serde_json::to_string(&Amount::MAX).unwrap(),
+        r#""340282366920938463463374607431768211455""#
+    );
+    
+    // Token amounts are tagged as such
+    assert_eq!(
+        amounts(r#"{"tokens": "12.345678 USDA"}"#, r#"{"tokens": "0.01"}"#),
+        Some((Amount::new(12_345_678), Amount::new(10_000)))
+    );
+    assert_eq!(amounts(r#"{"tokens": "1 USDA"}"#, "5"), Some((Amount::new(1_000_000), Amount::new(5))));
+    assert_eq!(amounts(r#"{"tokens": "12"}"#, r#""12""#), Some((Amount::new(12_000_000), Amount::new(12))));
+    
+    // Untagged strings are only ever base units
+    assert_eq!(amounts(r#""12.345678 USDA""#, r#""0""#), None);
+    assert_eq!(amounts(r#""100""#, r#""0.01""#), None);
+    
+    // Negative, over-precise and out of range amounts are rejected
+    assert_eq!(amounts(r#""100""#, "-1"), None);
+    assert_eq!(amounts(r#""100""#, r#""-1""#), None);
+    assert_eq!(amounts(r#"{"tokens": "0.0000001"}"#, r#""0""#), None);
+    assert_eq!(amounts(r#"{"tokens": "1 BTC"}"#, r#""0""#), None);
+    assert_eq!(amounts(r#""340282366920938463463374607431768211456""#, r#""0""#), None);
+}
+
+#[tokio::test]
+async fn test_transfer_with_token_amounts() {
+    let state = setup_test_state().await;
+    let (sender_account, receiver_account) = setup_test_accounts(&state).await;
+    
+    let sender_bytes = sender_account.address;
+    let receiver_bytes = receiver_account.address;
+    
+    // The signature covers base units: 0.0001 USDA is 100 with 6 decimals
+    let payload = SignablePayload::transfer(
+        state.chain_id,
+        sender_bytes,
+        receiver_bytes,
+        Amount::new(100),
+        Amount::new(1),
+        0,
+    );
+    let req = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: Some(hex::encode(sender_bytes)),
+        to: hex::encode(receiver_bytes),
+        amount: AmountInput::Tokens { tokens: "0.0001 USDA".into() },
+        fee: AmountInput::Tokens { tokens: "0.000001".into() },
+        nonce: 0,
+        signature: hex::encode(payload.sign(&sender_account.key)),
+        valid_until: None,
+    });
+    
+    let response = transfer(State(state.clone()), req)
+        .await
+        .expect("Failed to execute transfer");
+    assert_eq!(response.tx_id, payload.tx_id());
+    
+    let sender = sqlx::query!(
+        r#"SELECT pending_balance::BIGINT AS "pending_balance!" FROM accounts WHERE address = $1"#,
+        sender_bytes.as_slice()
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(sender.pending_balance, 1000 - 100 - 1);
+}
+
 #[tokio::test]
 async fn test_transfer_zero_amount() {
     let state = setup_test_state().await;
@@ -179,29 +515,38 @@ async fn test_transfer_zero_amount() {
     let mut secret_bytes = [0u8; 32];
     rng.fill_bytes(&mut secret_bytes);
     let issuer_key = SigningKey::from_bytes(&secret_bytes);
-    state.set_issuer_key(issuer_key.verifying_key());
+    state.set_issuer_key(DEFAULT_TOKEN_ID, issuer_key.verifying_key());
     
     // Create sender and receiver keypairs
-    let (sender_signing_key, receiver_verifying_key) = setup_test_accounts(&state).await;
+    let (sender_account, receiver_account) = setup_test_accounts(&state).await;
     
     // Create transfer request with zero amount
     let amount = 0;
     let nonce = 0;
     let fee = 0;
     
-    let sender_bytes = sender_signing_key.verifying_key().to_bytes();
-    let receiver_bytes = receiver_verifying_key.to_bytes();
+    let sender_bytes = sender_account.address;
+    let receiver_bytes = receiver_account.address;
     
-    let message = format!("{}:{}:{}", hex::encode(&receiver_bytes), amount, nonce);
-    let signature = sender_signing_key.sign(message.as_bytes());
+    let payload = SignablePayload::transfer(
+        state.chain_id,
+        sender_bytes,
+        receiver_bytes,
+        tokens(amount),
+        tokens(fee),
+        nonce,
+    );
+    let signature = payload.sign(&sender_account.key);
     
     let req = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
         from: Some(hex::encode(sender_bytes)),
         to: hex::encode(receiver_bytes),
-        amount,
-        fee,
+        amount: tokens(amount).into(),
+        fee: tokens(fee).into(),
         nonce,
-        signature: hex::encode(signature.to_bytes()),
+        signature: hex::encode(signature),
+        valid_until: None,
     });
     
     // Attempt transfer
@@ -222,62 +567,61 @@ async fn test_concurrent_transfers() {
     let mut secret_bytes = [0u8; 32];
     rng.fill_bytes(&mut secret_bytes);
     let issuer_key = SigningKey::from_bytes(&secret_bytes);
-    state.set_issuer_key(issuer_key.verifying_key());
-    
-    // Create sender and two receiver keypairs
-    let (sender_signing_key, receiver1_verifying_key) = setup_test_accounts(&state).await;
-    let mut receiver2_secret = [0u8; 32];
-    OsRng.fill_bytes(&mut receiver2_secret);
-    let receiver2_signing_key = SigningKey::from_bytes(&receiver2_secret);
-    let receiver2_verifying_key = receiver2_signing_key.verifying_key();
-    
-    // Create second receiver account
-    let receiver2_bytes = receiver2_verifying_key.to_bytes();
-    sqlx::query!(
-        r#"
-        INSERT INTO accounts (address, balance, pending_balance, nonce, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        "#,
-        receiver2_bytes.as_slice(),
-        0_i64,
-        0_i64,
-        0_i64
-    )
-    .execute(&state.db)
-    .await
-    .unwrap();
+    state.set_issuer_key(DEFAULT_TOKEN_ID, issuer_key.verifying_key());
+    
+    // Create a sender and two receivers
+    let (sender_account, receiver1_account) = setup_test_accounts(&state).await;
+    let receiver2_bytes = setup_account(&state, 0).await.address;
     
     // Prepare concurrent transfers
-    let sender_bytes = sender_signing_key.verifying_key().to_bytes();
-    let receiver1_bytes = receiver1_verifying_key.to_bytes();
+    let sender_bytes = sender_account.address;
+    let receiver1_bytes = receiver1_account.address;
     
     let amount = 100;
     let fee = amount / 100;
     let nonce = 0;
     
     // Create two transfer requests with same nonce
-    let message1 = format!("{}:{}:{}", hex::encode(&receiver1_bytes), amount, nonce);
-    let signature1 = sender_signing_key.sign(message1.as_bytes());
-    
-    let message2 = format!("{}:{}:{}", hex::encode(&receiver2_bytes), amount, nonce);
-    let signature2 = sender_signing_key.sign(message2.as_bytes());
+    let payload1 = SignablePayload::transfer(
+        state.chain_id,
+        sender_bytes,
+        receiver1_bytes,
+        tokens(amount),
+        tokens(fee),
+        nonce,
+    );
+    let signature1 = payload1.sign(&sender_account.key);
+    
+    let payload2 = SignablePayload::transfer(
+        state.chain_id,
+        sender_bytes,
+        receiver2_bytes,
+        tokens(amount),
+        tokens(fee),
+        nonce,
+    );
+    let signature2 = payload2.sign(&sender_account.key);
     
     let req1 = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
         from: Some(hex::encode(sender_bytes)),
         to: hex::encode(receiver1_bytes),
-        amount,
-        fee,
+        amount: tokens(amount).into(),
+        fee: tokens(fee).into(),
         nonce,
-        signature: hex::encode(signature1.to_bytes()),
+        signature: hex::encode(signature1),
+        valid_until: None,
     });
     
     let req2 = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
         from: Some(hex::encode(sender_bytes)),
         to: hex::encode(receiver2_bytes),
-        amount,
-        fee,
+        amount: tokens(amount).into(),
+        fee: tokens(fee).into(),
         nonce,
-        signature: hex::encode(signature2.to_bytes()),
+        signature: hex::encode(signature2),
+        valid_until: None,
     });
     
     // Execute transfers concurrently
@@ -294,3 +638,170 @@ async fn test_concurrent_transfers() {
         _ => panic!("Expected one success and one InvalidNonce error"),
     }
 }
+
+#[tokio::test]
+async fn test_simulate_transfer_previews_without_applying() {
+    let state = setup_test_state().await;
+    let (sender_account, receiver_account) = setup_test_accounts(&state).await;
+    let state = Arc::new(
+        AppState::new(state.db.clone())
+            .with_fee_policy(FeePolicy::Flat { fee: Amount::new(5) })
+            .with_treasury(TEST_TREASURY),
+    );
+
+    let sender_bytes = sender_account.address;
+    let receiver_bytes = receiver_account.address;
+    let req = || SimulateTransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: hex::encode(sender_bytes),
+        to: hex::encode(receiver_bytes),
+        amount: tokens(100).into(),
+        fee: None,
+        nonce: None,
+        signature: None,
+        valid_until: None,
+    };
+
+    // Unsigned previews fill in the required fee and the next nonce
+    let preview = simulate(State(state.clone()), Json(req()))
+        .await
+        .expect("Failed to simulate transfer")
+        .0;
+    assert_eq!(preview.status, TransactionStatus::Sequenced.to_string());
+    assert_eq!((preview.fee, preview.required_fee), (tokens(5), tokens(5)));
+    assert_eq!((preview.nonce, preview.expected_nonce), (0, 0));
+    assert_eq!(preview.sender_balance, tokens(895));
+    assert_eq!(preview.receiver_balance, tokens(100));
+
+    // Nothing was applied
+    let sender = sqlx::query!(
+        r#"SELECT pending_balance::BIGINT AS "pending_balance!", nonce FROM accounts WHERE address = $1"#,
+        sender_bytes.as_slice()
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!((sender.pending_balance, sender.nonce), (1000, 0));
+
+    // Signing the previewed payload gives the transfer the previewed ID
+    let payload = hex::decode(&preview.signing_payload).unwrap();
+    let signature = hex::encode(sender_account.key.sign(&payload).to_bytes());
+    let signed = simulate(
+        State(state.clone()),
+        Json(SimulateTransferRequest { signature: Some(signature.clone()), ..req() }),
+    )
+    .await
+    .expect("Signed preview should succeed");
+    assert_eq!(signed.0.tx_id, preview.tx_id);
+
+    let response = transfer(
+        State(state.clone()),
+        Json(TransferRequest {
+            token_id: DEFAULT_TOKEN_ID,
+            from: Some(hex::encode(sender_bytes)),
+            to: hex::encode(receiver_bytes),
+            amount: tokens(100).into(),
+            fee: tokens(5).into(),
+            nonce: 0,
+            signature,
+            valid_until: None,
+        }),
+    )
+    .await
+    .expect("Failed to execute transfer");
+    assert_eq!(response.0.tx_id, preview.tx_id);
+
+    // Previewing the submitted transfer again reports it as accepted
+    let resubmitted = simulate(State(state.clone()), Json(SimulateTransferRequest { nonce: Some(0), ..req() }))
+        .await
+        .expect("Preview of an accepted transfer should succeed")
+        .0;
+    assert_eq!((resubmitted.status, resubmitted.expected_nonce), (TransactionStatus::Sequenced.to_string(), 1));
+    assert_eq!(resubmitted.sender_balance, tokens(895));
+
+    // A nonce ahead of the sender's would be queued
+    let queued = simulate(State(state.clone()), Json(SimulateTransferRequest { nonce: Some(2), ..req() }))
+        .await
+        .expect("Failed to simulate queued transfer")
+        .0;
+    assert_eq!(queued.status, TransactionStatus::Received.to_string());
+    assert_eq!(queued.sender_balance, tokens(895));
+}
+
+#[tokio::test]
+async fn test_simulate_transfer_returns_submission_error() {
+    let state = setup_test_state().await;
+    let (sender_account, receiver_account) = setup_test_accounts(&state).await;
+    let sender_bytes = sender_account.address;
+    let receiver_bytes = receiver_account.address;
+
+    let req = |amount: i64| SimulateTransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: hex::encode(sender_bytes),
+        to: hex::encode(receiver_bytes),
+        amount: tokens(amount).into(),
+        fee: None,
+        nonce: None,
+        signature: None,
+        valid_until: None,
+    };
+
+    let result = simulate(State(state.clone()), Json(req(1001))).await;
+    assert!(matches!(result, Err(AppError::InsufficientBalance)));
+
+    let result = simulate(State(state.clone()), Json(SimulateTransferRequest { nonce: Some(-1), ..req(100) })).await;
+    assert!(matches!(result, Err(AppError::InvalidNonce)));
+
+    // A signature over different terms fails like it would on submission
+    let payload = SignablePayload::transfer(state.chain_id, sender_bytes, receiver_bytes, tokens(50), Amount::ZERO, 0);
+    let signature = hex::encode(payload.sign(&sender_account.key));
+    let result = simulate(State(state.clone()), Json(SimulateTransferRequest { signature: Some(signature), ..req(100) })).await;
+    assert!(matches!(result, Err(AppError::InvalidSignature)));
+
+    let result = simulate(State(state.clone()), Json(SimulateTransferRequest { from: hex::encode([0x42u8; 32]), ..req(100) })).await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+}
+
+#[tokio::test]
+async fn test_simulate_transfer_checks_request_before_sender() {
+    let state = setup_test_state().await;
+    let unknown = hex::encode([0x42u8; 32]);
+    let forged = hex::encode([0u8; 64]);
+
+    // Invalid requests from an unknown sender fail on the request, as on submission
+    for (amount, to) in [(0, hex::encode([0x43u8; 32])), (100, "not-hex".to_string()), (100, hex::encode([0x43u8; 32]))] {
+        let simulated = simulate(
+            State(state.clone()),
+            Json(SimulateTransferRequest {
+                token_id: DEFAULT_TOKEN_ID,
+                from: unknown.clone(),
+                to: to.clone(),
+                amount: tokens(amount).into(),
+                fee: Some(Amount::ZERO.into()),
+                nonce: Some(0),
+                signature: Some(forged.clone()),
+                valid_until: None,
+            }),
+        )
+        .await;
+        let submitted = transfer(
+            State(state.clone()),
+            Json(TransferRequest {
+                token_id: DEFAULT_TOKEN_ID,
+                from: Some(unknown.clone()),
+                to,
+                amount: tokens(amount).into(),
+                fee: Amount::ZERO.into(),
+                nonce: 0,
+                signature: forged.clone(),
+                valid_until: None,
+            }),
+        )
+        .await;
+        let (Err(simulated), Err(submitted)) = (simulated, submitted) else {
+            panic!("Expected both to fail");
+        };
+        assert!(!matches!(simulated, AppError::NotFound(_)), "{:?}", simulated);
+        assert_eq!(format!("{:?}", simulated), format!("{:?}", submitted));
+    }
+}
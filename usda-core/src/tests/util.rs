@@ -1,12 +1,106 @@
+use axum::{extract::State, Json};
+use ed25519_dalek::SigningKey;
+use rand::{rngs::OsRng, RngCore};
 use sqlx::PgPool;
+use std::sync::Arc;
+use usda_common::{Amount, SignablePayload, DEFAULT_TOKEN_ID};
+
+use super::{tokens, transfer, AppState, TransferRequest};
+
+/// An account opened through [`AppState::create_account`], and the key that signs for it
+#[allow(dead_code)]
+pub struct TestAccount {
+    pub key: SigningKey,
+    pub address: [u8; 32],
+}
 
 #[allow(dead_code)]
 pub async fn clear_database(pool: &PgPool) {
+    sqlx::query!("DELETE FROM multisig_signatures")
+        .execute(pool)
+        .await
+        .expect("Failed to clear multisig signatures");
+        
     sqlx::query!("DELETE FROM transactions")
         .execute(pool)
         .await
         .expect("Failed to clear transactions");
         
+    sqlx::query!("DELETE FROM queued_transfers")
+        .execute(pool)
+        .await
+        .expect("Failed to clear queued transfers");
+        
+    sqlx::query!("DELETE FROM standing_order_executions")
+        .execute(pool)
+        .await
+        .expect("Failed to clear standing order executions");
+        
+    sqlx::query!("DELETE FROM standing_orders")
+        .execute(pool)
+        .await
+        .expect("Failed to clear standing orders");
+        
+    sqlx::query!("DELETE FROM multisig_policies")
+        .execute(pool)
+        .await
+        .expect("Failed to clear multisig policies");
+        
+    sqlx::query!("DELETE FROM multisig_accounts")
+        .execute(pool)
+        .await
+        .expect("Failed to clear multisig accounts");
+        
+    sqlx::query!("DELETE FROM frozen_accounts")
+        .execute(pool)
+        .await
+        .expect("Failed to clear frozen accounts");
+        
+    sqlx::query!("DELETE FROM freeze_actions")
+        .execute(pool)
+        .await
+        .expect("Failed to clear freeze actions");
+        
+    sqlx::query!("DELETE FROM spending_limits")
+        .execute(pool)
+        .await
+        .expect("Failed to clear spending limits");
+        
+    sqlx::query!("DELETE FROM escrows")
+        .execute(pool)
+        .await
+        .expect("Failed to clear escrows");
+        
+    sqlx::query!("DELETE FROM allowances")
+        .execute(pool)
+        .await
+        .expect("Failed to clear allowances");
+        
+    sqlx::query!("DELETE FROM account_key_history")
+        .execute(pool)
+        .await
+        .expect("Failed to clear account key history");
+        
+    sqlx::query!("DELETE FROM account_recovery_signatures")
+        .execute(pool)
+        .await
+        .expect("Failed to clear account recovery signatures");
+        
+    sqlx::query!("DELETE FROM account_recoveries")
+        .execute(pool)
+        .await
+        .expect("Failed to clear account recoveries");
+        
+    sqlx::query!("DELETE FROM account_guardians")
+        .execute(pool)
+        .await
+        .expect("Failed to clear account guardians");
+        
+    sqlx::query!("DELETE FROM account_keys")
+        .execute(pool)
+        .await
+        .expect("Failed to clear account keys");
+        
     sqlx::query!("DELETE FROM accounts")
         .execute(pool)
         .await
@@ -16,6 +110,11 @@ pub async fn clear_database(pool: &PgPool) {
         .execute(pool)
         .await
         .expect("Failed to clear proof batches");
+        
+    sqlx::query!("DELETE FROM tokens")
+        .execute(pool)
+        .await
+        .expect("Failed to clear tokens");
 }
 
 #[allow(dead_code)]
@@ -29,3 +128,119 @@ pub async fn setup_test_database(pool: &PgPool) {
         .await
         .expect("Failed to run migrations");
 }
+
+#[allow(dead_code)]
+pub fn new_key() -> SigningKey {
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+    SigningKey::from_bytes(&secret)
+}
+
+/// A new account of the default token holding `balance`
+#[allow(dead_code)]
+pub async fn setup_account(state: &AppState, balance: i64) -> TestAccount {
+    let key = new_key();
+    let address = state
+        .create_account(key.verifying_key().to_bytes(), DEFAULT_TOKEN_ID)
+        .await
+        .expect("Failed to create account")
+        .address;
+    fund(state, address, balance).await;
+
+    TestAccount { key, address }
+}
+
+/// Sets the default token balance of `address`, inserting the account if it has none
+#[allow(dead_code)]
+pub async fn fund(state: &AppState, address: [u8; 32], balance: i64) {
+    sqlx::query!(
+        r#"
+        INSERT INTO accounts (address, balance, pending_balance, nonce, created_at)
+        VALUES ($1, $2::BIGINT, $3::BIGINT, 0, NOW())
+        ON CONFLICT (address, token_id) DO UPDATE
+        SET balance = EXCLUDED.balance, pending_balance = EXCLUDED.pending_balance
+        "#,
+        address.as_slice(),
+        balance,
+        balance
+    )
+    .execute(&state.db)
+    .await
+    .expect("Failed to fund account");
+}
+
+#[allow(dead_code)]
+pub fn signed_transfer(
+    state: &AppState,
+    sender: &TestAccount,
+    to: [u8; 32],
+    amount: i64,
+    fee: i64,
+    nonce: i64,
+) -> Json<TransferRequest> {
+    let from = sender.address;
+    let payload = SignablePayload::transfer(state.chain_id, from, to, tokens(amount), tokens(fee), nonce);
+
+    Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
+        from: Some(hex::encode(from)),
+        to: hex::encode(to),
+        amount: tokens(amount).into(),
+        fee: tokens(fee).into(),
+        nonce,
+        signature: hex::encode(payload.sign(&sender.key)),
+        valid_until: None,
+    })
+}
+
+/// Submits a transfer that must be accepted and returns its ID
+#[allow(dead_code)]
+pub async fn send(state: &Arc<AppState>, sender: &TestAccount, to: [u8; 32], amount: i64, fee: i64, nonce: i64) -> String {
+    transfer(State(state.clone()), signed_transfer(state, sender, to, amount, fee, nonce))
+        .await
+        .expect("Failed to execute transfer")
+        .0
+        .tx_id
+}
+
+/// `(pending_balance, nonce)` of `address` in the default token, zero if it has no account
+#[allow(dead_code)]
+pub async fn pending(state: &AppState, address: [u8; 32]) -> (Amount, i64) {
+    state
+        .get_account(&address, DEFAULT_TOKEN_ID)
+        .await
+        .unwrap()
+        .map(|account| (account.pending_balance, account.nonce))
+        .unwrap_or((Amount::ZERO, 0))
+}
+
+#[allow(dead_code)]
+pub async fn pending_balance(state: &AppState, address: [u8; 32]) -> Amount {
+    pending(state, address).await.0
+}
+
+/// `(balance, pending_balance)` of `address` in the default token
+#[allow(dead_code)]
+pub async fn balances(state: &AppState, address: [u8; 32]) -> (i64, i64) {
+    let account = sqlx::query!(
+        r#"
+        SELECT balance::BIGINT AS "balance!", pending_balance::BIGINT AS "pending_balance!"
+        FROM accounts
+        WHERE address = $1 AND token_id = 0
+        "#,
+        address.as_slice()
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    (account.balance, account.pending_balance)
+}
+
+#[allow(dead_code)]
+pub async fn status_of(state: &AppState, tx_id: &str) -> String {
+    sqlx::query!(r#"SELECT status::TEXT AS "status!" FROM transactions WHERE tx_id = $1"#, tx_id)
+        .fetch_one(&state.db)
+        .await
+        .unwrap()
+        .status
+}
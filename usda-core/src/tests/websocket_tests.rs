@@ -5,11 +5,11 @@ use axum::{
     extract::ws::{Message, WebSocket},
     Json,
 };
-use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
+use ed25519_dalek::{SigningKey, VerifyingKey};
 use futures::{SinkExt, StreamExt};
 use rand::{RngCore, rngs::OsRng};
 use tokio::sync::broadcast;
-use usda_common::WebSocketMessage;
+use usda_common::{SignablePayload, WebSocketMessage};
 
 #[tokio::test]
 async fn test_websocket_notifications() {
@@ -39,8 +39,15 @@ async fn test_websocket_notifications() {
     let fee = amount / 100;
     let nonce = 0;
     
-    let message = format!("{}:{}:{}", hex::encode(&receiver_bytes), amount, nonce);
-    let signature = sender_signing_key.sign(message.as_bytes());
+    let payload = SignablePayload::transfer(
+        state.chain_id,
+        sender_bytes,
+        receiver_bytes,
+        amount,
+        fee,
+        nonce,
+    );
+    let signature = payload.sign(&sender_signing_key);
     
     let req = Json(TransferRequest {
         from: Some(hex::encode(sender_bytes)),
@@ -48,7 +55,8 @@ async fn test_websocket_notifications() {
         amount,
         fee,
         nonce,
-        signature: hex::encode(signature.to_bytes()),
+        signature: hex::encode(signature),
+        valid_until: None,
     });
     
     // Execute transfer
@@ -1,4 +1,4 @@
-use ed25519_dalek::{Signer, SigningKey};
+use ed25519_dalek::SigningKey;
 use rand::{Rng, RngCore, rngs::OsRng};
 use sqlx::postgres::PgPoolOptions;
 use std::{
@@ -6,7 +6,7 @@ use std::{
     time::Instant,
 };
 use tokio::sync::broadcast;
-use usda_common::WebSocketMessage;
+use usda_common::{Amount, SignablePayload, WebSocketMessage};
 use usda_core::state::AppState;
 
 const NUM_USERS: usize = 10_000;  // Increased from 1000 to get more diversity in transfers
@@ -65,7 +65,7 @@ async fn create_users(state: Arc<AppState>, count: usize) -> Vec<User> {
         sqlx::query!(
             r#"
             INSERT INTO accounts (address, balance, nonce)
-            VALUES ($1, $2, 0)
+            VALUES ($1, $2::BIGINT, 0)
             "#,
             address.as_slice(),
             INITIAL_USER_BALANCE
@@ -111,7 +111,7 @@ async fn create_fee_collector(state: Arc<AppState>) -> User {
     sqlx::query!(
         r#"
         INSERT INTO accounts (address, balance, nonce)
-        VALUES ($1, $2, 0)
+        VALUES ($1, $2::BIGINT, 0)
         "#,
         address.as_slice(),
         0_i64 // Start with 0 balance, will collect fees
@@ -159,12 +159,12 @@ async fn get_total_balance(state: &AppState) -> i64 {
 }
 
 async fn get_total_pending(state: &AppState) -> i64 {
-    // Get sum of all pending transactions
+    // Get sum of all sequenced transactions
     sqlx::query!(
         r#"
         SELECT CAST(COALESCE(SUM(amount), 0) AS BIGINT) as total
         FROM transactions
-        WHERE status = 'pending'
+        WHERE status = 'SEQUENCED'
         "#
     )
     .fetch_one(&state.db)
@@ -175,12 +175,12 @@ async fn get_total_pending(state: &AppState) -> i64 {
 }
 
 async fn get_total_fees(state: &AppState) -> i64 {
-    // Get sum of all fees from confirmed transactions
+    // Get sum of all fees from settled transactions
     sqlx::query!(
         r#"
         SELECT CAST(COALESCE(SUM(fee), 0) AS BIGINT) as total
         FROM transactions
-        WHERE status = 'confirmed'
+        WHERE status IN ('PROVEN', 'FINALIZED')
         "#
     )
     .fetch_one(&state.db)
@@ -221,15 +221,15 @@ async fn perform_random_transfers(
             let fee = amount / 10; // 10% fee
 
             let nonce = from_user.nonce.fetch_add(1, Ordering::SeqCst);
-            let message = format!(
-                "{}{}{}{}{}",
-                hex::encode(from_user.address),
-                hex::encode(to_user.address),
-                amount,
-                fee,
-                nonce
+            let payload = SignablePayload::transfer(
+                state.chain_id,
+                from_user.address,
+                to_user.address,
+                Amount::new(amount as u128),
+                Amount::new(fee as u128),
+                nonce,
             );
-            let signature = from_user.signing_key.sign(message.as_bytes());
+            let signature = payload.sign(&from_user.signing_key);
 
             // Execute the query and collect the future
             let future = sqlx::query!(
@@ -242,27 +242,27 @@ async fn perform_random_transfers(
                 ),
                 sender_update AS (
                     UPDATE accounts
-                    SET balance = balance - $2,
+                    SET balance = balance - $2::BIGINT,
                         nonce = nonce + 1
                     WHERE address = $1
                       AND EXISTS (
                           SELECT 1
                           FROM sender_check
-                          WHERE balance >= $2
+                          WHERE balance >= $2::BIGINT
                       )
                     RETURNING address
                 ),
                 receiver_update AS (
                     INSERT INTO accounts (address, balance, nonce)
-                    VALUES ($3, $4, 0)
-                    ON CONFLICT (address) DO UPDATE
-                    SET balance = accounts.balance + $4
+                    VALUES ($3, $4::BIGINT, 0)
+                    ON CONFLICT (address, token_id) DO UPDATE
+                    SET balance = accounts.balance + $4::BIGINT
                     WHERE EXISTS (SELECT 1 FROM sender_update)
                     RETURNING address
                 ),
                 fee_update AS (
                     UPDATE accounts
-                    SET balance = balance + $5
+                    SET balance = balance + $5::BIGINT
                     WHERE address = $6
                       AND EXISTS (SELECT 1 FROM sender_update)
                     RETURNING address
@@ -271,7 +271,7 @@ async fn perform_random_transfers(
                     tx_id, from_addr, to_addr, amount, fee,
                     nonce, signature, timestamp, status
                 )
-                SELECT $7, $1, $3, $4, $5, $8, $9, NOW(), 'PENDING'
+                SELECT $7, $1, $3, $4::BIGINT, $5::BIGINT, $8, $9, NOW(), 'SEQUENCED'
                 WHERE EXISTS (SELECT 1 FROM sender_update)
                   AND EXISTS (SELECT 1 FROM receiver_update)
                   AND EXISTS (SELECT 1 FROM fee_update)
@@ -285,7 +285,7 @@ async fn perform_random_transfers(
                 fee_collector.address.as_slice(),
                 uuid::Uuid::new_v4().to_string(),
                 nonce,
-                signature.to_bytes().to_vec()
+                signature.to_vec()
             )
             .fetch_one(&state.db);
 
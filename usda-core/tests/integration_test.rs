@@ -1,19 +1,28 @@
 use axum::{extract::State, Json};
-use ed25519_dalek::{Signer, SigningKey};
+use ed25519_dalek::SigningKey;
 use rand::{rngs::OsRng, RngCore};
 use sqlx::postgres::PgPoolOptions;
 use std::sync::Arc;
 use tokio::sync::broadcast;
-use usda_common::WebSocketMessage;
+use usda_common::{batch::BatchResult, Amount, SignablePayload, WebSocketMessage, DEFAULT_TOKEN_ID};
 use usda_core::{
     api::{
         account::CreateAccountRequest,
         transaction::{mint, transfer, MintRequest, TransferRequest},
     },
+    settlement::{seal_batch, settle_batch},
     state::AppState,
 };
 
-async fn setup_test_state() -> Arc<AppState> {
+#[path = "../src/tests/util.rs"]
+mod util;
+
+/// Amounts are tracked as `i64` here, matching the `::BIGINT` balance reads.
+fn tokens(value: i64) -> Amount {
+    Amount::from(u64::try_from(value).expect("test amounts are non-negative"))
+}
+
+async fn setup_test_state(treasury: [u8; 32]) -> Arc<AppState> {
     // Create a connection pool
     let pool = PgPoolOptions::new()
         .max_connections(5)
@@ -22,114 +31,60 @@ async fn setup_test_state() -> Arc<AppState> {
         .expect("Failed to create connection pool");
 
     // Clear any existing data
-    sqlx::query!("DELETE FROM transactions")
-        .execute(&pool)
-        .await
-        .expect("Failed to clear transactions");
-    sqlx::query!("DELETE FROM accounts")
-        .execute(&pool)
-        .await
-        .expect("Failed to clear accounts");
+    util::clear_database(&pool).await;
 
     // Create broadcast channel for WebSocket messages
     let (_tx, _) = broadcast::channel::<WebSocketMessage>(100);
 
-    // Create app state
-    Arc::new(AppState::new(pool))
+    // Create app state; the issuer collects transfer fees
+    Arc::new(AppState::new(pool).with_treasury(treasury))
 }
 
-async fn confirm_pending_transactions(state: &AppState, issuer_address: &[u8; 32]) {
-    // Get all pending transactions
-    let pending_txs = sqlx::query!(
-        "SELECT tx_id, from_addr, to_addr, amount, fee FROM transactions WHERE status = 'PENDING'"
-    )
-    .fetch_all(&state.db)
-    .await
-    .expect("Failed to fetch pending transactions");
-
-    println!("Found {} pending transactions", pending_txs.len());
-
-    // Confirm each transaction
-    for tx in pending_txs {
-        println!("Confirming transaction {} for amount {}", tx.tx_id, tx.amount);
-
-        // Update transaction status
-        sqlx::query!(
-            "UPDATE transactions SET status = 'CONFIRMED' WHERE tx_id = $1",
-            tx.tx_id
-        )
-        .execute(&state.db)
+async fn confirm_pending_transactions(state: &AppState) {
+    // Seal everything pending into one batch and settle it as proven
+    let Some(batch) = seal_batch(state, 1000)
         .await
-        .expect("Failed to update transaction status");
-
-        // Update balances
-        if let Some(from_addr) = tx.from_addr {
-            // For transfers, subtract from sender's balance
-            sqlx::query!(
-                r#"
-                UPDATE accounts 
-                SET balance = balance - $1,
-                    pending_balance = pending_balance + $1
-                WHERE address = $2
-                "#,
-                tx.amount + tx.fee,
-                from_addr
-            )
-            .execute(&state.db)
-            .await
-            .expect("Failed to update sender balance");
-
-            // Add fee to issuer's balance
-            if tx.fee > 0 {
-                sqlx::query!(
-                    r#"
-                    UPDATE accounts 
-                    SET balance = balance + $1
-                    WHERE address = $2
-                    "#,
-                    tx.fee,
-                    issuer_address.as_slice()
-                )
-                .execute(&state.db)
-                .await
-                .expect("Failed to update issuer balance");
-            }
-        }
+        .expect("Failed to seal pending transactions")
+    else {
+        return;
+    };
+    println!(
+        "Settling batch {} with {} transactions",
+        batch.batch_id,
+        batch.tx_ids.len()
+    );
 
-        // Add to recipient's balance
-        sqlx::query!(
-            r#"
-            UPDATE accounts 
-            SET balance = balance + $1,
-                pending_balance = pending_balance - $1
-            WHERE address = $2
-            "#,
-            tx.amount,
-            tx.to_addr
-        )
-        .execute(&state.db)
+    let result = BatchResult {
+        chain_id: state.chain_id,
+        batch_time: chrono::Utc::now().timestamp(),
+        issuer_keys: Vec::new(),
+        multisig_policies: Vec::new(),
+        account_keys: Vec::new(),
+        cycles_used: 0,
+        supply: Vec::new(),
+    };
+    settle_batch(state, &batch.batch_id, &[], "", &result)
         .await
-        .expect("Failed to update recipient balance");
-    }
+        .expect("Failed to settle batch");
 }
 
 #[tokio::test]
 async fn test_payment_flow() {
-    let state = setup_test_state().await;
-
     // Generate issuer keypair
     let mut issuer_secret = [0u8; 32];
     OsRng.fill_bytes(&mut issuer_secret);
     let issuer_signing_key = SigningKey::from_bytes(&issuer_secret);
     let issuer_verifying_key = issuer_signing_key.verifying_key();
     let issuer_address = issuer_verifying_key.to_bytes();
-    state.set_issuer_key(issuer_verifying_key);
+
+    let state = setup_test_state(issuer_address).await;
+    state.set_issuer_key(DEFAULT_TOKEN_ID, issuer_verifying_key);
 
     // Create issuer account
     sqlx::query!(
         r#"
         INSERT INTO accounts (address, balance, pending_balance, nonce, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
+        VALUES ($1, $2::BIGINT, $3::BIGINT, $4, NOW())
         "#,
         issuer_address.as_slice(),
         0_i64,
@@ -156,12 +111,13 @@ async fn test_payment_flow() {
 
     // 1. Create accounts for Alice and Bob
     let _alice_req = Json(CreateAccountRequest {
+        token_id: DEFAULT_TOKEN_ID,
         public_key: alice_address,
     });
     sqlx::query!(
         r#"
         INSERT INTO accounts (address, balance, pending_balance, nonce, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
+        VALUES ($1, $2::BIGINT, $3::BIGINT, $4, NOW())
         "#,
         alice_address.as_slice(),
         0_i64,
@@ -173,12 +129,13 @@ async fn test_payment_flow() {
     .expect("Failed to create Alice's account");
 
     let _bob_req = Json(CreateAccountRequest {
+        token_id: DEFAULT_TOKEN_ID,
         public_key: bob_address,
     });
     sqlx::query!(
         r#"
         INSERT INTO accounts (address, balance, pending_balance, nonce, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
+        VALUES ($1, $2::BIGINT, $3::BIGINT, $4, NOW())
         "#,
         bob_address.as_slice(),
         0_i64,
@@ -191,13 +148,15 @@ async fn test_payment_flow() {
 
     // 2. Mint 1000 tokens to Alice's account
     let mint_amount = 1000_i64;
-    let mint_message = format!("{}{}", hex::encode(alice_address), mint_amount);
-    let mint_signature = issuer_signing_key.sign(mint_message.as_bytes());
+    let mint_payload = SignablePayload::mint(state.chain_id, alice_address, tokens(mint_amount), 0);
+    let mint_signature = mint_payload.sign(&issuer_signing_key);
 
     let mint_req = Json(MintRequest {
+        token_id: DEFAULT_TOKEN_ID,
         to: hex::encode(alice_address),
-        amount: mint_amount,
-        signature: hex::encode(mint_signature.to_bytes()),
+        amount: tokens(mint_amount),
+        nonce: 0,
+        signature: hex::encode(mint_signature),
     });
     let _ = mint(State(state.clone()), mint_req)
         .await
@@ -205,7 +164,7 @@ async fn test_payment_flow() {
 
     // Check if minting transaction was created
     let pending_txs = sqlx::query!(
-        "SELECT tx_id, from_addr, to_addr, amount, status FROM transactions"
+        r#"SELECT tx_id, from_addr, to_addr, amount, status::TEXT AS "status!" FROM transactions"#
     )
     .fetch_all(&state.db)
     .await
@@ -213,21 +172,25 @@ async fn test_payment_flow() {
     println!("All transactions: {}", pending_txs.len());
     for tx in &pending_txs {
         println!(
-            "Transaction {} from {:?} to {} for amount {} (status: {})",
+            "Transaction {} from {:?} to {:?} for amount {} (status: {})",
             tx.tx_id,
             tx.from_addr.as_ref().map(hex::encode),
-            hex::encode(&tx.to_addr),
+            tx.to_addr.as_ref().map(hex::encode),
             tx.amount,
             tx.status
         );
     }
 
     // Wait for mint transaction to be confirmed
-    confirm_pending_transactions(&state, &issuer_address).await;
+    confirm_pending_transactions(&state).await;
 
     // 3. Check Alice's balance
     let alice_balance = sqlx::query!(
-        "SELECT balance, pending_balance FROM accounts WHERE address = $1",
+        r#"
+        SELECT balance::BIGINT AS "balance!", pending_balance::BIGINT AS "pending_balance!"
+        FROM accounts
+        WHERE address = $1
+        "#,
         alice_address.as_slice()
     )
     .fetch_one(&state.db)
@@ -241,34 +204,42 @@ async fn test_payment_flow() {
 
     // 4. Transfer 500 tokens from Alice to Bob
     let transfer_amount = 500_i64;
+    let transfer_fee = transfer_amount / 100; // 1% fee
     let transfer_nonce = 0_i64;
-    let transfer_message = format!(
-        "{}{}{}{}",
-        hex::encode(alice_address),
-        hex::encode(bob_address),
-        transfer_amount,
-        transfer_nonce
+    let transfer_payload = SignablePayload::transfer(
+        state.chain_id,
+        alice_address,
+        bob_address,
+        tokens(transfer_amount),
+        tokens(transfer_fee),
+        transfer_nonce,
     );
-    let transfer_signature = alice_signing_key.sign(transfer_message.as_bytes());
+    let transfer_signature = transfer_payload.sign(&alice_signing_key);
 
     let transfer_req = Json(TransferRequest {
+        token_id: DEFAULT_TOKEN_ID,
         from: Some(hex::encode(alice_address)),
         to: hex::encode(bob_address),
-        amount: transfer_amount,
-        fee: transfer_amount / 100, // 1% fee
+        amount: tokens(transfer_amount).into(),
+        fee: tokens(transfer_fee).into(),
         nonce: transfer_nonce,
-        signature: hex::encode(transfer_signature.to_bytes()),
+        signature: hex::encode(transfer_signature),
+        valid_until: None,
     });
     let _ = transfer(State(state.clone()), transfer_req)
         .await
         .expect("Failed to transfer tokens from Alice to Bob");
 
     // Wait for transfer transaction to be confirmed
-    confirm_pending_transactions(&state, &issuer_address).await;
+    confirm_pending_transactions(&state).await;
 
     // 5. Check final balances
     let alice_final_balance = sqlx::query!(
-        "SELECT balance, pending_balance FROM accounts WHERE address = $1",
+        r#"
+        SELECT balance::BIGINT AS "balance!", pending_balance::BIGINT AS "pending_balance!"
+        FROM accounts
+        WHERE address = $1
+        "#,
         alice_address.as_slice()
     )
     .fetch_one(&state.db)
@@ -284,7 +255,11 @@ async fn test_payment_flow() {
     ); // Subtract amount + 1% fee
 
     let bob_final_balance = sqlx::query!(
-        "SELECT balance, pending_balance FROM accounts WHERE address = $1",
+        r#"
+        SELECT balance::BIGINT AS "balance!", pending_balance::BIGINT AS "pending_balance!"
+        FROM accounts
+        WHERE address = $1
+        "#,
         bob_address.as_slice()
     )
     .fetch_one(&state.db)
@@ -298,7 +273,7 @@ async fn test_payment_flow() {
 
     // Check issuer's balance (should have collected the fee)
     let issuer_final_balance = sqlx::query!(
-        "SELECT balance FROM accounts WHERE address = $1",
+        r#"SELECT balance::BIGINT AS "balance!" FROM accounts WHERE address = $1"#,
         issuer_address.as_slice()
     )
     .fetch_one(&state.db)
@@ -1,54 +1,173 @@
 #![no_main]
 sp1_zkvm::entrypoint!(main);
 
-use serde::{Deserialize, Serialize};
-use serde_arrays;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TransferProof {
-    #[serde(with = "serde_arrays")]
-    pub from_addr: [u8; 32],
-    #[serde(with = "serde_arrays")]
-    pub to_addr: [u8; 32],
-    pub amount: i64,
-    pub fee: i64,
-    pub nonce: i64,
-    #[serde(with = "serde_arrays")]
-    pub signature: [u8; 64],
-    #[serde(with = "serde_arrays")]
-    pub public_key: [u8; 32],
-}
+use std::collections::{BTreeMap, BTreeSet};
+
+use usda_common::batch::{BatchEntry, BatchResult, SupplyChange};
+use usda_common::{Amount, Escrow, MemberSignature, MultisigPolicy, TokenId};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BatchResult {
-    pub cycles_used: u64,
+/// Who must have signed an entry
+enum Signers<'a> {
+    One([u8; 32], [u8; 64]),
+    Threshold(&'a MultisigPolicy, &'a [MemberSignature]),
 }
 
 pub fn main() {
+    let chain_id = sp1_zkvm::io::read::<u64>();
+    let batch_time = sp1_zkvm::io::read::<i64>();
+    let issuer_keys = sp1_zkvm::io::read::<Vec<(TokenId, [u8; 32])>>();
     let num_txs = sp1_zkvm::io::read::<u32>();
+    let issuers: BTreeMap<TokenId, [u8; 32]> = issuer_keys.iter().copied().collect();
+    assert_eq!(issuers.len(), issuer_keys.len(), "Duplicate token issuer");
     let mut cycles_used = 0;
-    
+    let mut supply: BTreeMap<TokenId, SupplyChange> = BTreeMap::new();
+    // Escrows claimed or refunded in this batch
+    let mut settled_escrows: BTreeSet<[u8; 32]> = BTreeSet::new();
+    // Standing order executions in this batch, by order ID and index
+    let mut executions: BTreeSet<([u8; 32], u32)> = BTreeSet::new();
+    // Multisig accounts sending in this batch and the policies they signed under
+    let mut multisig_policies: BTreeSet<([u8; 32], [u8; 32])> = BTreeSet::new();
+    // Accounts signing in this batch and the keys they signed with. Addresses
+    // outlive keys, so which key was the account's is committed for the
+    // ledger to vouch for
+    let mut account_keys: BTreeSet<([u8; 32], [u8; 32])> = BTreeSet::new();
+
     for _ in 0..num_txs {
-        let proof: TransferProof = sp1_zkvm::io::read();
-        
-        // In production we would:
-        // 1. Hash the transaction data
-        // 2. Verify the signature
-        // 3. Track cycles used
-        
-        // For now just increment cycles
+        let entry: BatchEntry = sp1_zkvm::io::read();
+
+        // Work out who must have signed the entry
+        let signers = match &entry {
+            BatchEntry::Transfer(proof) => {
+                // Only a key of the sending account may sign for it
+                account_keys.insert((proof.from_addr, proof.public_key));
+                assert!(!proof.amount.is_zero(), "Transfer amount must be positive");
+                proof.amount.checked_add(proof.fee).expect("Transfer debit overflow");
+                Signers::One(proof.public_key, proof.signature)
+            }
+            BatchEntry::Mint(proof) => {
+                assert!(!proof.amount.is_zero(), "Mint amount must be positive");
+                // Each token is minted only by its own issuer
+                let issuer_key = *issuers.get(&proof.token_id).expect("Token has no issuer");
+                let change = supply_change(&mut supply, proof.token_id);
+                change.minted = change.minted.checked_add(proof.amount).expect("Minted supply overflow");
+                Signers::One(issuer_key, proof.signature)
+            }
+            BatchEntry::Burn(proof) => {
+                account_keys.insert((proof.from_addr, proof.public_key));
+                assert!(!proof.amount.is_zero(), "Burn amount must be positive");
+                let change = supply_change(&mut supply, proof.token_id);
+                change.burned = change.burned.checked_add(proof.amount).expect("Burned supply overflow");
+                Signers::One(proof.public_key, proof.signature)
+            }
+            BatchEntry::BatchTransfer(proof) => {
+                account_keys.insert((proof.from_addr, proof.public_key));
+                assert!(!proof.legs.is_empty(), "Batch transfer has no legs");
+                assert!(
+                    proof.legs.iter().all(|(_, amount)| !amount.is_zero()),
+                    "Transfer amount must be positive"
+                );
+                Amount::checked_sum(proof.legs.iter().map(|(_, amount)| *amount))
+                    .and_then(|total| total.checked_add(proof.fee))
+                    .expect("Batch transfer debit overflow");
+                Signers::One(proof.public_key, proof.signature)
+            }
+            BatchEntry::TransferFrom(proof) => {
+                // The spender signs; the owner's allowance is enforced by the ledger
+                account_keys.insert((proof.spender_addr, proof.public_key));
+                assert_ne!(proof.spender_addr, proof.owner_addr, "Owner cannot spend own allowance");
+                assert!(!proof.amount.is_zero(), "Transfer amount must be positive");
+                proof.amount.checked_add(proof.fee).expect("Transfer debit overflow");
+                Signers::One(proof.public_key, proof.signature)
+            }
+            BatchEntry::EscrowLock(proof) => {
+                account_keys.insert((proof.terms.from_addr, proof.public_key));
+                assert!(!proof.terms.amount.is_zero(), "Escrow amount must be positive");
+                proof.terms.amount.checked_add(proof.terms.fee).expect("Escrow debit overflow");
+                Signers::One(proof.public_key, proof.signature)
+            }
+            BatchEntry::EscrowClaim(proof) => {
+                // Only the recipient can claim, and only with the hashlock's preimage.
+                // The ledger checked the timeout when it accepted the claim.
+                account_keys.insert((proof.terms.to_addr, proof.public_key));
+                assert_eq!(Escrow::hashlock(&proof.preimage), proof.terms.hashlock, "Preimage does not open the hashlock");
+                assert!(settled_escrows.insert(proof.terms.escrow_id(chain_id)), "Escrow settled twice");
+                Signers::One(proof.public_key, proof.signature)
+            }
+            BatchEntry::EscrowRefund(proof) => {
+                // Only the sender can reclaim, and only once the timeout has passed
+                account_keys.insert((proof.terms.from_addr, proof.public_key));
+                assert!(batch_time >= proof.terms.timeout, "Escrow refunded before its timeout");
+                assert!(settled_escrows.insert(proof.terms.escrow_id(chain_id)), "Escrow settled twice");
+                Signers::One(proof.public_key, proof.signature)
+            }
+            BatchEntry::ScheduledTransfer(proof) => {
+                // The sender signed the order; each execution runs once, within the cap and not early
+                let terms = &proof.terms;
+                account_keys.insert((terms.from_addr, proof.public_key));
+                assert!(!terms.amount.is_zero(), "Transfer amount must be positive");
+                terms.amount.checked_add(terms.fee).expect("Transfer debit overflow");
+                assert!(proof.execution < terms.max_executions, "Standing order executed too many times");
+                let due_at = terms.due_at(proof.execution).expect("Execution time overflow");
+                assert!(batch_time >= due_at, "Standing order executed before it was due");
+                let order_id = terms.payload(chain_id).digest();
+                assert!(executions.insert((order_id, proof.execution)), "Standing order executed twice");
+                Signers::One(proof.public_key, proof.signature)
+            }
+            BatchEntry::MultisigTransfer(proof) => {
+                // Enough members of the account's policy signed; which policy
+                // was in force is committed for the ledger to vouch for
+                assert!(!proof.amount.is_zero(), "Transfer amount must be positive");
+                proof.amount.checked_add(proof.fee).expect("Transfer debit overflow");
+                multisig_policies.insert((proof.from_addr, proof.policy.digest()));
+                Signers::Threshold(&proof.policy, &proof.signatures)
+            }
+            BatchEntry::Clawback(proof) => {
+                // Only the token's issuer can seize funds; supply is unchanged
+                assert!(!proof.amount.is_zero(), "Clawback amount must be positive");
+                let issuer_key = *issuers.get(&proof.token_id).expect("Token has no issuer");
+                Signers::One(issuer_key, proof.signature)
+            }
+        };
+
+        // Verify the signature over the same canonical payload the API checks
+        let payload = entry.payload(chain_id);
+        let authorized = match signers {
+            Signers::One(signer, signature) => payload.verify(&signer, &signature),
+            Signers::Threshold(policy, signatures) => policy.verify(&payload, signatures),
+        };
+        assert!(authorized, "Invalid entry signature");
+        // A transfer sequenced in time stays valid however long it waits for a batch
+        let sequenced_at = entry.sequenced_at().unwrap_or(batch_time);
+        assert!(sequenced_at <= batch_time, "Entry sequenced after the batch");
+        assert!(!payload.is_expired(sequenced_at), "Entry expired before it was sequenced");
+
         cycles_used += 1000;
     }
-    
-    let result = BatchResult { cycles_used };
+
+    let result = BatchResult {
+        chain_id,
+        batch_time,
+        issuer_keys,
+        multisig_policies: multisig_policies.into_iter().collect(),
+        account_keys: account_keys.into_iter().collect(),
+        cycles_used,
+        supply: supply.into_values().collect(),
+    };
     let bytes = bincode::serialize(&result).unwrap();
     sp1_zkvm::io::commit_slice(&bytes);
 }
 
+fn supply_change(supply: &mut BTreeMap<TokenId, SupplyChange>, token_id: TokenId) -> &mut SupplyChange {
+    supply.entry(token_id).or_insert_with(|| SupplyChange {
+        token_id,
+        ..SupplyChange::default()
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
     #[test]
     fn test_batch_verification() {
         // Tests will be moved to the script crate
@@ -1,34 +1,25 @@
 use clap::Parser;
-use sp1_sdk::{SP1Stdin, ProverClient};
-use serde::{Serialize, Deserialize};
+use ed25519_dalek::SigningKey;
+use sp1_sdk::{HashableKey, SP1Stdin, ProverClient};
 use bincode;
 use std::path::PathBuf;
 use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use usda_common::{
+    batch::{
+        BatchEntry, BatchInput, BatchProof, BatchResult, BurnProof, ClawbackProof, EscrowClaimProof, EscrowLockProof, EscrowTerms,
+        MintProof, MultisigTransferProof, ScheduledTransferProof, StandingOrderTerms,
+        TransferProof, PROVER_REJECTED_EXIT_CODE,
+    },
+    signing::DEFAULT_CHAIN_ID,
+    account_address, Amount, Escrow, MemberSignature, MultisigPolicy, SignablePayload, TokenId,
+    DEFAULT_TOKEN_ID,
+};
 
 const PROVING_KEY_DIR: &str = "proving_keys";
 const PROVING_KEY_FILE: &str = "usda_program.key";
 const VERIFYING_KEY_FILE: &str = "usda_program.vk";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TransferProof {
-    #[serde(with = "serde_arrays")]
-    pub from_addr: [u8; 32],
-    #[serde(with = "serde_arrays")]
-    pub to_addr: [u8; 32],
-    pub amount: i64,
-    pub fee: i64,
-    pub nonce: i64,
-    #[serde(with = "serde_arrays")]
-    pub signature: [u8; 64],
-    #[serde(with = "serde_arrays")]
-    pub public_key: [u8; 32],
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BatchResult {
-    pub cycles_used: u64,
-}
-
 #[derive(Parser, Debug)]
 struct Args {
     /// Execute without proof generation
@@ -38,6 +29,200 @@ struct Args {
     /// Generate proof
     #[arg(long)]
     prove: bool,
+    
+    /// Deployment ID the batch entries are signed for
+    #[arg(long, default_value_t = DEFAULT_CHAIN_ID)]
+    chain_id: u64,
+    
+    /// Batch to prove instead of the demo batch, as a bincode `BatchInput`
+    /// from the ledger
+    #[arg(long)]
+    input: Option<PathBuf>,
+    
+    /// Where to write the bincode `BatchProof` of the proven batch
+    #[arg(long, requires = "prove")]
+    output: Option<PathBuf>,
+}
+
+/// Address of the account created with `signing_key`'s public key.
+fn address(signing_key: &SigningKey) -> [u8; 32] {
+    account_address(&signing_key.verifying_key().to_bytes())
+}
+
+fn signed_transfer(
+    chain_id: u64,
+    token_id: TokenId,
+    signing_key: &SigningKey,
+    to_addr: [u8; 32],
+    amount: Amount,
+    fee: Amount,
+    nonce: i64,
+    valid_until: Option<i64>,
+    sequenced_at: Option<i64>,
+) -> BatchEntry {
+    let from_addr = address(signing_key);
+    let signature = SignablePayload::transfer(chain_id, from_addr, to_addr, amount, fee, nonce)
+        .with_valid_until(valid_until)
+        .with_token_id(token_id)
+        .sign(signing_key);
+    
+    BatchEntry::Transfer(TransferProof {
+        token_id,
+        from_addr,
+        to_addr,
+        amount,
+        fee,
+        nonce,
+        valid_until,
+        sequenced_at,
+        signature,
+        public_key: signing_key.verifying_key().to_bytes(),
+    })
+}
+
+fn signed_mint(
+    chain_id: u64,
+    token_id: TokenId,
+    issuer_key: &SigningKey,
+    to_addr: [u8; 32],
+    amount: Amount,
+    nonce: i64,
+) -> BatchEntry {
+    let signature = SignablePayload::mint(chain_id, to_addr, amount, nonce)
+        .with_token_id(token_id)
+        .sign(issuer_key);
+    
+    BatchEntry::Mint(MintProof {
+        token_id,
+        to_addr,
+        amount,
+        nonce,
+        signature,
+    })
+}
+
+fn signed_burn(
+    chain_id: u64,
+    token_id: TokenId,
+    signing_key: &SigningKey,
+    amount: Amount,
+    nonce: i64,
+    redemption_ref: Option<String>,
+) -> BatchEntry {
+    let from_addr = address(signing_key);
+    let signature =
+        SignablePayload::burn(chain_id, from_addr, amount, nonce, redemption_ref.clone())
+            .with_token_id(token_id)
+            .sign(signing_key);
+    
+    BatchEntry::Burn(BurnProof {
+        token_id,
+        from_addr,
+        amount,
+        nonce,
+        redemption_ref,
+        signature,
+        public_key: signing_key.verifying_key().to_bytes(),
+    })
+}
+
+fn signed_clawback(
+    chain_id: u64,
+    token_id: TokenId,
+    issuer_key: &SigningKey,
+    from_addr: [u8; 32],
+    to_addr: [u8; 32],
+    amount: Amount,
+    nonce: i64,
+) -> BatchEntry {
+    let signature = SignablePayload::clawback(chain_id, from_addr, to_addr, amount, nonce)
+        .with_token_id(token_id)
+        .sign(issuer_key);
+
+    BatchEntry::Clawback(ClawbackProof {
+        token_id,
+        from_addr,
+        to_addr,
+        amount,
+        nonce,
+        signature,
+    })
+}
+
+fn signed_escrow_lock(chain_id: u64, terms: EscrowTerms, signing_key: &SigningKey) -> BatchEntry {
+    let signature = terms.lock_payload(chain_id).sign(signing_key);
+    
+    BatchEntry::EscrowLock(EscrowLockProof {
+        public_key: signing_key.verifying_key().to_bytes(),
+        terms,
+        signature,
+    })
+}
+
+fn signed_escrow_claim(
+    chain_id: u64,
+    terms: EscrowTerms,
+    preimage: [u8; 32],
+    signing_key: &SigningKey,
+) -> BatchEntry {
+    let signature = SignablePayload::escrow_claim(chain_id, terms.escrow_id(chain_id), preimage)
+        .with_token_id(terms.token_id)
+        .sign(signing_key);
+    
+    BatchEntry::EscrowClaim(EscrowClaimProof {
+        public_key: signing_key.verifying_key().to_bytes(),
+        terms,
+        preimage,
+        signature,
+    })
+}
+
+fn signed_scheduled_transfer(
+    chain_id: u64,
+    terms: StandingOrderTerms,
+    execution: u32,
+    signing_key: &SigningKey,
+) -> BatchEntry {
+    let signature = terms.payload(chain_id).sign(signing_key);
+
+    BatchEntry::ScheduledTransfer(ScheduledTransferProof {
+        public_key: signing_key.verifying_key().to_bytes(),
+        terms,
+        execution,
+        signature,
+    })
+}
+
+fn signed_multisig_transfer(
+    chain_id: u64,
+    policy: MultisigPolicy,
+    to_addr: [u8; 32],
+    amount: Amount,
+    nonce: i64,
+    signers: &[&SigningKey],
+) -> BatchEntry {
+    let from_addr = policy.digest();
+    let payload = SignablePayload::transfer(chain_id, from_addr, to_addr, amount, Amount::ZERO, nonce);
+    let signatures = signers
+        .iter()
+        .map(|signer| MemberSignature {
+            public_key: signer.verifying_key().to_bytes(),
+            signature: payload.sign(signer),
+        })
+        .collect();
+
+    BatchEntry::MultisigTransfer(MultisigTransferProof {
+        token_id: DEFAULT_TOKEN_ID,
+        from_addr,
+        to_addr,
+        amount,
+        fee: Amount::ZERO,
+        nonce,
+        valid_until: None,
+        sequenced_at: None,
+        policy,
+        signatures,
+    })
 }
 
 fn get_key_paths() -> (PathBuf, PathBuf) {
@@ -59,6 +244,159 @@ fn ensure_proving_key_dir() -> std::io::Result<()> {
     fs::create_dir_all(path)
 }
 
+/// A demo batch of every kind of entry, signed by fixed test keys.
+fn demo_batch(chain_id: u64) -> BatchInput {
+    let issuer = SigningKey::from_bytes(&[9u8; 32]);
+    // A second token on the same ledger, with its own issuer
+    let eur_token: TokenId = 1;
+    let eur_issuer = SigningKey::from_bytes(&[10u8; 32]);
+    let alice = SigningKey::from_bytes(&[1u8; 32]);
+    let bob = SigningKey::from_bytes(&[3u8; 32]);
+    let batch_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System clock before Unix epoch")
+        .as_secs() as i64;
+    // Alice locks funds for Bob, who claims them with the preimage
+    let preimage = [7u8; 32];
+    let escrow = EscrowTerms {
+        token_id: DEFAULT_TOKEN_ID,
+        from_addr: address(&alice),
+        to_addr: address(&bob),
+        amount: Amount::new(50),
+        fee: Amount::ZERO,
+        hashlock: Escrow::hashlock(&preimage),
+        timeout: batch_time + 3600,
+        nonce: 1,
+    };
+    // Bob's monthly standing order to Alice, whose first execution is due
+    let subscription = StandingOrderTerms {
+        token_id: DEFAULT_TOKEN_ID,
+        from_addr: address(&bob),
+        to_addr: address(&alice),
+        amount: Amount::new(5),
+        fee: Amount::ZERO,
+        start: batch_time - 60,
+        interval: Some(30 * 24 * 3600),
+        max_executions: 12,
+        nonce: 1,
+    };
+    // A 2-of-3 treasury wallet shared by Alice, Bob and Carol
+    let carol = SigningKey::from_bytes(&[5u8; 32]);
+    let treasury = MultisigPolicy::new(
+        2,
+        vec![
+            alice.verifying_key().to_bytes(),
+            bob.verifying_key().to_bytes(),
+            carol.verifying_key().to_bytes(),
+        ],
+    )
+    .expect("valid multisig policy");
+    let proofs = vec![
+        signed_mint(
+            chain_id,
+            DEFAULT_TOKEN_ID,
+            &issuer,
+            address(&alice),
+            Amount::new(1000),
+            0,
+        ),
+        signed_mint(
+            chain_id,
+            eur_token,
+            &eur_issuer,
+            address(&bob),
+            Amount::new(500),
+            0,
+        ),
+        signed_transfer(
+            chain_id,
+            DEFAULT_TOKEN_ID,
+            &alice,
+            address(&bob),
+            Amount::new(100),
+            Amount::new(10),
+            0,
+            Some(batch_time + 3600),
+            Some(batch_time),
+        ),
+        signed_burn(chain_id, DEFAULT_TOKEN_ID, &bob, Amount::new(50), 0, Some("wire-0001".into())),
+        signed_escrow_lock(chain_id, escrow.clone(), &alice),
+        signed_escrow_claim(chain_id, escrow, preimage, &bob),
+        signed_scheduled_transfer(chain_id, subscription, 0, &bob),
+        signed_multisig_transfer(
+            chain_id,
+            treasury,
+            address(&bob),
+            Amount::new(25),
+            0,
+            &[&alice, &carol],
+        ),
+        // The EUR issuer seizes part of Bob's balance into its own account
+        signed_clawback(
+            chain_id,
+            eur_token,
+            &eur_issuer,
+            address(&bob),
+            address(&eur_issuer),
+            Amount::new(100),
+            0,
+        ),
+        signed_transfer(
+            chain_id,
+            eur_token,
+            &bob,
+            address(&alice),
+            Amount::new(200),
+            Amount::ZERO,
+            0,
+            None,
+            Some(batch_time),
+        ),
+        // Sequenced before its expiry, which passed while it waited for the batch
+        signed_transfer(
+            chain_id,
+            DEFAULT_TOKEN_ID,
+            &alice,
+            address(&bob),
+            Amount::new(20),
+            Amount::ZERO,
+            2,
+            Some(batch_time - 30),
+            Some(batch_time - 60),
+        ),
+        // Signed long ago and never sequenced; dropped below
+        signed_transfer(
+            chain_id,
+            DEFAULT_TOKEN_ID,
+            &bob,
+            address(&alice),
+            Amount::new(10),
+            Amount::new(1),
+            1,
+            Some(0),
+            None,
+        ),
+    ];
+    
+    // Drop entries that expired before they could be sequenced instead of failing the proof
+    let (proofs, expired): (Vec<_>, Vec<_>) = proofs
+        .into_iter()
+        .partition(|entry| !entry.is_expired(chain_id, batch_time));
+    if !expired.is_empty() {
+        println!("Dropped {} expired entries", expired.len());
+    }
+    
+    BatchInput {
+        chain_id,
+        batch_time,
+        issuer_keys: vec![
+            (DEFAULT_TOKEN_ID, issuer.verifying_key().to_bytes()),
+            (eur_token, eur_issuer.verifying_key().to_bytes()),
+        ],
+        entries: proofs,
+    }
+}
+
 fn main() {
     // Setup the logger
     sp1_sdk::utils::setup_logger();
@@ -71,44 +409,37 @@ fn main() {
         std::process::exit(1);
     }
     
-    // Setup test proofs
-    let proofs = vec![
-        TransferProof {
-            from_addr: [1u8; 32],
-            to_addr: [2u8; 32],
-            amount: 100,
-            fee: 10,
-            nonce: 0,
-            signature: [0u8; 64],
-            public_key: [1u8; 32],
-        },
-        TransferProof {
-            from_addr: [3u8; 32],
-            to_addr: [4u8; 32],
-            amount: 200,
-            fee: 20,
-            nonce: 1,
-            signature: [0u8; 64],
-            public_key: [3u8; 32],
-        },
-    ];
+    // Prove the ledger's batch, or the demo batch
+    let input: BatchInput = match &args.input {
+        Some(path) => {
+            let bytes = fs::read(path).expect("Failed to read batch input");
+            bincode::deserialize(&bytes).expect("Failed to decode batch input")
+        }
+        None => demo_batch(args.chain_id),
+    };
     
     // Setup the prover client
     let client = ProverClient::new();
     
     // Setup inputs
     let mut stdin = SP1Stdin::new();
-    stdin.write(&(proofs.len() as u32));
+    stdin.write(&input.chain_id);
+    stdin.write(&input.batch_time);
+    stdin.write(&input.issuer_keys);
+    stdin.write(&(input.entries.len() as u32));
     
-    for proof in proofs {
-        stdin.write(&proof);
+    for entry in &input.entries {
+        stdin.write(entry);
     }
     
     let elf = include_bytes!(env!("SP1_ELF_usda-program"));
     
     if args.execute {
         // Execute the program
-        let (output, report) = client.execute(elf, stdin).run().unwrap();
+        let (output, report) = client
+            .execute(elf, stdin)
+            .run()
+            .unwrap_or_else(|e| reject(e));
         println!("Program executed successfully.");
         
         // Read the output
@@ -116,6 +447,11 @@ fn main() {
         println!("Result: {:?}", result);
         println!("Number of cycles: {}", report.total_instruction_count());
     } else if args.prove {
+        // A batch the program rejects can't be proven; find out before proving
+        if let Err(e) = client.execute(elf, stdin.clone()).run() {
+            reject(e);
+        }
+        
         // Ensure proving key directory exists
         ensure_proving_key_dir().expect("Failed to create proving key directory");
         
@@ -147,5 +483,24 @@ fn main() {
         // Verify the proof
         client.verify(&proof, &vk).expect("Failed to verify proof");
         println!("Successfully verified proof!");
+        
+        // Hand the proof and its public values back to the ledger
+        if let Some(path) = &args.output {
+            let result = bincode::deserialize::<BatchResult>(proof.public_values.as_slice())
+                .expect("Failed to decode public values");
+            let batch_proof = BatchProof {
+                proof: bincode::serialize(&proof).expect("Failed to serialize proof"),
+                vkey_hash: vk.bytes32(),
+                result,
+            };
+            let bytes = bincode::serialize(&batch_proof).expect("Failed to serialize batch proof");
+            fs::write(path, bytes).expect("Failed to write batch proof");
+        }
     }
 }
+
+/// Exits with the status that tells the ledger to fail the batch.
+fn reject(error: impl std::fmt::Display) -> ! {
+    eprintln!("Batch rejected: {}", error);
+    std::process::exit(PROVER_REJECTED_EXIT_CODE)
+}